@@ -0,0 +1,1102 @@
+//! Chunking of source code at symbol boundaries.
+//!
+//! The language of a file is recognised by its extension, or set for code read from memory.
+//! Each line is scanned for strings and comments to track brace depth, and definitions of
+//! functions, classes, impls and similar symbols are found with per-language patterns: at a brace
+//! depth for C-like languages and at an indentation for Python and Ruby. Every symbol becomes a
+//! chunk together with the comments, attributes and decorators right above it. A symbol larger
+//! than the chunk size is split at its members, and whatever is still too large is split between
+//! lines. Code between symbols, such as imports, forms chunks of its own.
+
+use crate::processor::{BytesProcessor, Document, FileProcessor};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use text_splitter::{ChunkConfig, ChunkConfigError};
+
+/// The extensions of the source files recognised by the [CodeProcessor].
+pub const CODE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "pyi", "js", "jsx", "mjs", "cjs", "ts", "tsx", "mts", "cts", "go", "java", "c",
+    "h", "cpp", "cc", "cxx", "hpp", "hh", "hxx", "cs", "rb", "php", "kt", "kts", "swift", "scala",
+    "sh", "bash", "zsh",
+];
+
+/// Words that precede a parenthesis without starting a definition.
+const KEYWORDS: &[&str] = &[
+    "await",
+    "case",
+    "catch",
+    "delete",
+    "do",
+    "else",
+    "for",
+    "foreach",
+    "if",
+    "lock",
+    "new",
+    "return",
+    "sizeof",
+    "switch",
+    "synchronized",
+    "throw",
+    "typeof",
+    "using",
+    "while",
+    "yield",
+];
+
+/// A programming language recognised by the [CodeProcessor].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+    Java,
+    C,
+    Cpp,
+    CSharp,
+    Ruby,
+    Php,
+    Kotlin,
+    Swift,
+    Scala,
+    Shell,
+}
+
+/// The lexical syntax of a language, as far as it matters to find blocks.
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// String delimiters, longest first, and whether backslash escapes apply inside them.
+    quotes: &'static [(&'static str, bool)],
+    /// Whether strings in single-character quotes may span lines.
+    multiline_strings: bool,
+    /// Whether `'` starts a character literal, or a lifetime or label, rather than a string.
+    char_literals: bool,
+    /// Whether `r"..."` and `r#"..."#` are raw strings.
+    raw_strings: bool,
+    /// Whether a line comment only starts at the beginning of a word, as `#` in shell scripts.
+    word_comments: bool,
+    /// Whether blocks are delimited by indentation rather than braces.
+    indentation: bool,
+    /// The markers of the lines above a definition that belong to it.
+    prefixes: &'static [&'static str],
+}
+
+const C_COMMENT: Option<(&str, &str)> = Some(("/*", "*/"));
+const C_PREFIXES: &[&str] = &["//", "/*", "*"];
+const ANNOTATED_PREFIXES: &[&str] = &["//", "/*", "*", "@"];
+
+impl Language {
+    /// Returns the language of a file extension, ignoring case.
+    pub fn from_extension(extension: &str) -> Option<Language> {
+        let language = match extension.to_lowercase().as_str() {
+            "rs" => Language::Rust,
+            "py" | "pyi" => Language::Python,
+            "js" | "jsx" | "mjs" | "cjs" => Language::JavaScript,
+            "ts" | "tsx" | "mts" | "cts" => Language::TypeScript,
+            "go" => Language::Go,
+            "java" => Language::Java,
+            "c" | "h" => Language::C,
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => Language::Cpp,
+            "cs" => Language::CSharp,
+            "rb" => Language::Ruby,
+            "php" => Language::Php,
+            "kt" | "kts" => Language::Kotlin,
+            "swift" => Language::Swift,
+            "scala" => Language::Scala,
+            "sh" | "bash" | "zsh" => Language::Shell,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    /// Returns the language of a file from its extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Language> {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Language::from_extension)
+    }
+
+    /// The name of the language as recorded in the chunk metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::Rust => "rust",
+            Language::Python => "python",
+            Language::JavaScript => "javascript",
+            Language::TypeScript => "typescript",
+            Language::Go => "go",
+            Language::Java => "java",
+            Language::C => "c",
+            Language::Cpp => "cpp",
+            Language::CSharp => "csharp",
+            Language::Ruby => "ruby",
+            Language::Php => "php",
+            Language::Kotlin => "kotlin",
+            Language::Swift => "swift",
+            Language::Scala => "scala",
+            Language::Shell => "shell",
+        }
+    }
+
+    /// The separator between the name of a symbol and the names of its members.
+    fn separator(&self) -> &'static str {
+        match self {
+            Language::Rust | Language::Cpp | Language::Php => "::",
+            _ => ".",
+        }
+    }
+
+    fn syntax(&self) -> Syntax {
+        let c_like = Syntax {
+            line_comments: &["//"],
+            block_comment: C_COMMENT,
+            quotes: &[("\"", true)],
+            multiline_strings: false,
+            char_literals: true,
+            raw_strings: false,
+            word_comments: false,
+            indentation: false,
+            prefixes: C_PREFIXES,
+        };
+        match self {
+            Language::Rust => Syntax {
+                multiline_strings: true,
+                raw_strings: true,
+                prefixes: &["//", "/*", "*", "#["],
+                ..c_like
+            },
+            Language::Python => Syntax {
+                line_comments: &["#"],
+                block_comment: None,
+                quotes: &[("\"\"\"", true), ("'''", true), ("\"", true), ("'", true)],
+                char_literals: false,
+                indentation: true,
+                prefixes: &["#", "@"],
+                ..c_like
+            },
+            Language::Ruby => Syntax {
+                line_comments: &["#"],
+                block_comment: None,
+                quotes: &[("\"", true), ("'", true)],
+                char_literals: false,
+                indentation: true,
+                prefixes: &["#"],
+                ..c_like
+            },
+            Language::JavaScript | Language::TypeScript => Syntax {
+                quotes: &[("\"", true), ("'", true), ("`", true)],
+                char_literals: false,
+                prefixes: ANNOTATED_PREFIXES,
+                ..c_like
+            },
+            Language::Go => Syntax {
+                quotes: &[("\"", true), ("`", false)],
+                ..c_like
+            },
+            Language::Java | Language::Kotlin | Language::Scala | Language::Swift => Syntax {
+                quotes: &[("\"\"\"", true), ("\"", true)],
+                prefixes: ANNOTATED_PREFIXES,
+                ..c_like
+            },
+            Language::CSharp => Syntax {
+                quotes: &[("\"\"\"", true), ("\"", true)],
+                prefixes: &["//", "/*", "*", "["],
+                ..c_like
+            },
+            Language::C => c_like,
+            Language::Cpp => Syntax {
+                prefixes: &["//", "/*", "*", "template"],
+                ..c_like
+            },
+            Language::Php => Syntax {
+                line_comments: &["//", "#"],
+                quotes: &[("\"", true), ("'", true)],
+                char_literals: false,
+                prefixes: &["//", "/*", "*", "#"],
+                ..c_like
+            },
+            Language::Shell => Syntax {
+                line_comments: &["#"],
+                block_comment: None,
+                quotes: &[("\"", true), ("'", false)],
+                multiline_strings: true,
+                char_literals: false,
+                word_comments: true,
+                prefixes: &["#"],
+                ..c_like
+            },
+        }
+    }
+
+    /// Returns the patterns of the lines that start a definition, with the name of the symbol in
+    /// the `name` group. The first matching pattern wins.
+    fn definitions(&self) -> Vec<Regex> {
+        const C_FUNCTION: &str =
+            r"^\s*(?:[\w:<>,\[\]*&~?.]+\s+)*[*&]*(?P<name>[A-Za-z_~][\w:~]*)\s*\([^;]*$";
+        const C_TYPE: &str =
+            r"^\s*(?:typedef\s+)?(?:struct|union|enum)\s+(?P<name>\w+)\s*(?:\{.*)?$";
+        let patterns: &[&str] = match self {
+            Language::Rust => &[
+                r#"^\s*(?:pub(?:\s*\([^)]*\))?\s+)?(?:(?:default|const|async|unsafe|extern(?:\s+"[^"]*")?)\s+)*(?:fn|struct|enum|union|trait|mod)\s+(?P<name>[A-Za-z_]\w*)"#,
+                r"^\s*macro_rules!\s*(?P<name>\w+)",
+                r"^\s*(?:unsafe\s+)?impl\b(?P<impl>.*)$",
+            ],
+            Language::Python => &[
+                r"^\s*(?:async\s+)?def\s+(?P<name>\w+)",
+                r"^\s*class\s+(?P<name>\w+)",
+            ],
+            Language::Ruby => &[
+                r"^\s*def\s+(?P<name>(?:self\.)?[\w?!=]+)",
+                r"^\s*(?:class|module)\s+(?P<name>[\w:]+)",
+            ],
+            Language::JavaScript | Language::TypeScript => &[
+                r"^\s*(?:export\s+)?(?:default\s+)?(?:declare\s+)?(?:abstract\s+)?(?:async\s+)?(?:function\s*\*?|class|interface|enum|namespace)\s*(?P<name>[\w$]+)",
+                r"^\s*(?:export\s+)?(?:const|let|var)\s+(?P<name>[\w$]+)\s*(?::[^=]*)?=\s*(?:async\s+)?(?:function\b|(?:\([^)]*\)|[\w$]+)\s*(?::[^=]*)?=>)",
+                r"^\s*(?:(?:public|private|protected|static|async|readonly|abstract|override|get|set)\s+)*\*?(?P<name>#?[\w$]+)\s*(?:<[^>]*>)?\s*\([^;]*$",
+            ],
+            Language::Go => &[
+                r"^\s*func\s*(?:\(\s*(?:\w+\s+)?\*?\s*(?P<receiver>\w+)[^)]*\)\s*)?(?P<name>\w+)",
+                r"^\s*type\s+(?P<name>\w+)\s+(?:struct|interface)\b",
+            ],
+            Language::Java => &[
+                r"^\s*(?:(?:public|private|protected|static|final|abstract|sealed|non-sealed|strictfp)\s+)*(?:class|interface|enum|record|@interface)\s+(?P<name>\w+)",
+                C_FUNCTION,
+            ],
+            Language::CSharp => &[
+                r"^\s*(?:(?:public|private|protected|internal|static|sealed|abstract|partial|readonly|unsafe|new|ref|file)\s+)*(?:class|struct|interface|enum|record(?:\s+(?:class|struct))?|namespace)\s+(?P<name>[\w.]+)",
+                C_FUNCTION,
+            ],
+            Language::C => &[C_TYPE, C_FUNCTION],
+            Language::Cpp => &[
+                r"^\s*(?:template\s*<.*>\s*)?(?:class|struct|union|namespace)\s+(?:\w+\s+)*?(?P<name>[\w:]+)\s*(?:final\s*)?(?::[^;]*)?(?:\{.*)?$",
+                C_TYPE,
+                C_FUNCTION,
+            ],
+            Language::Php => &[
+                r"^\s*(?:(?:public|private|protected|static|abstract|final|readonly)\s+)*(?:function|class|interface|trait|enum)\s+&?(?P<name>\w+)",
+            ],
+            Language::Kotlin => &[
+                r"^\s*(?:(?:public|private|protected|internal|open|abstract|sealed|data|enum|annotation|inner|override|suspend|inline|operator|infix|tailrec|external|value|companion)\s+)*(?:fun|class|interface|object)\s+(?:<[^>]*>\s*)?(?:[\w<>?, ]+\.)?(?P<name>\w+)",
+            ],
+            Language::Swift => &[
+                r"^\s*(?:@\w+\s+)*(?:(?:public|private|fileprivate|internal|open|final|static|class|override|mutating|nonmutating|convenience|required|indirect)\s+)*(?:func|class|struct|enum|protocol|extension|actor)\s+(?P<name>[\w.]+)",
+            ],
+            Language::Scala => &[
+                r"^\s*(?:(?:private|protected|final|sealed|abstract|implicit|override|lazy|case)\s+)*(?:def|class|object|trait|enum)\s+(?P<name>\w+)",
+            ],
+            Language::Shell => &[
+                r"^\s*function\s+(?P<name>[\w.:-]+)",
+                r"^\s*(?P<name>[\w.:-]+)\s*\(\s*\)",
+            ],
+        };
+        patterns
+            .iter()
+            .map(|pattern| Regex::new(pattern).unwrap())
+            .collect()
+    }
+}
+
+/// A struct for processing source code files.
+///
+/// Every chunk carries the `language` and the `start_line` and `end_line` it spans, starting at
+/// 1, in [Document::chunk_metadata]. Chunks of a symbol also carry its `symbol` name, which is
+/// qualified by the enclosing symbol for members, as in `Server::start`.
+pub struct CodeProcessor {
+    chunk_size: usize,
+    overlap: usize,
+    language: Option<Language>,
+}
+
+impl CodeProcessor {
+    pub fn new(chunk_size: usize, overlap: usize) -> Result<CodeProcessor, ChunkConfigError> {
+        ChunkConfig::new(chunk_size).with_overlap(overlap)?;
+        Ok(CodeProcessor {
+            chunk_size,
+            overlap,
+            language: None,
+        })
+    }
+
+    /// Sets the language of the code instead of recognising it by the extension of the file.
+    /// Code read from bytes has no extension, so it needs a language.
+    pub fn with_language(mut self, language: Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    /// Splits source code of a language into chunks at symbol boundaries.
+    pub fn process_code(&self, code: &str, language: Language) -> Document {
+        let syntax = language.syntax();
+        let splitter = Splitter {
+            lines: scan(code, &syntax),
+            language,
+            definitions: language.definitions(),
+            syntax,
+            chunk_size: self.chunk_size,
+        };
+        let mut segments = Vec::new();
+        if !splitter.lines.is_empty() {
+            splitter.split(0, splitter.lines.len() - 1, 0, None, &mut segments);
+        }
+
+        let mut document = Document::default();
+        for segment in segments {
+            for (start, end, text) in splitter.pieces(&segment, self.overlap) {
+                let mut metadata = HashMap::from([
+                    ("language".to_string(), language.name().to_string()),
+                    ("start_line".to_string(), (start + 1).to_string()),
+                    ("end_line".to_string(), (end + 1).to_string()),
+                ]);
+                if let Some(symbol) = &segment.symbol {
+                    metadata.insert("symbol".to_string(), symbol.clone());
+                }
+                document.chunks.push(text);
+                document.chunk_metadata.push(metadata);
+            }
+        }
+        document
+    }
+}
+
+impl FileProcessor for CodeProcessor {
+    fn process_file(&self, path: impl AsRef<Path>) -> Result<Document> {
+        let language = self
+            .language
+            .or_else(|| Language::from_path(&path))
+            .ok_or_else(|| {
+                anyhow!(
+                    "{} is not a recognised source file",
+                    path.as_ref().display()
+                )
+            })?;
+        let bytes = std::fs::read(path)?;
+        Ok(self.process_code(&String::from_utf8_lossy(&bytes), language))
+    }
+}
+
+impl BytesProcessor for CodeProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> Result<Document> {
+        let language = self
+            .language
+            .ok_or_else(|| anyhow!("the language of the code is not set"))?;
+        Ok(self.process_code(&String::from_utf8_lossy(bytes), language))
+    }
+}
+
+/// A line of code with the lexical state at its start.
+struct Line<'a> {
+    text: &'a str,
+    /// Whether the line starts inside a block comment or a string.
+    in_literal: bool,
+    /// Brace depth at the start of the line.
+    depth: usize,
+    /// Highest brace depth reached on the line.
+    max_depth: usize,
+    /// Brace depth at the end of the line.
+    end_depth: usize,
+    /// Depth of parentheses and brackets at the start of the line.
+    brackets: usize,
+    /// The last character of the line outside comments.
+    last_code: Option<char>,
+}
+
+impl Line<'_> {
+    fn is_blank(&self) -> bool {
+        self.text.trim().is_empty()
+    }
+
+    fn indent(&self) -> usize {
+        self.text.len() - self.text.trim_start().len()
+    }
+}
+
+enum State {
+    Code,
+    BlockComment,
+    Str {
+        close: String,
+        escapes: bool,
+        multiline: bool,
+    },
+}
+
+/// Splits code into lines and tracks strings, comments, braces and brackets across them.
+fn scan<'a>(code: &'a str, syntax: &Syntax) -> Vec<Line<'a>> {
+    let starts_at = |chars: &[char], i: usize, token: &str| {
+        token
+            .chars()
+            .enumerate()
+            .all(|(j, c)| chars.get(i + j) == Some(&c))
+    };
+    let is_word = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric() || *c == '_');
+
+    let mut state = State::Code;
+    let mut depth = 0usize;
+    let mut brackets = 0usize;
+    let mut lines = Vec::new();
+    for text in code.lines() {
+        let mut line = Line {
+            text,
+            in_literal: !matches!(state, State::Code),
+            depth,
+            max_depth: depth,
+            end_depth: depth,
+            brackets,
+            last_code: None,
+        };
+        let chars = text.chars().collect::<Vec<_>>();
+        let mut i = 0;
+        while i < chars.len() {
+            match &state {
+                State::BlockComment => {
+                    let (_, end) = syntax.block_comment.unwrap();
+                    if starts_at(&chars, i, end) {
+                        i += end.len();
+                        state = State::Code;
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::Str { close, escapes, .. } => {
+                    if *escapes && chars[i] == '\\' {
+                        i += 2;
+                    } else if starts_at(&chars, i, close) {
+                        i += close.chars().count();
+                        state = State::Code;
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::Code => {
+                    let c = chars[i];
+                    let previous = i.checked_sub(1).and_then(|j| chars.get(j));
+                    if syntax.line_comments.iter().any(|comment| {
+                        starts_at(&chars, i, comment)
+                            && !(syntax.word_comments
+                                && previous.is_some_and(|c| !c.is_whitespace()))
+                    }) {
+                        break;
+                    }
+                    if let Some((start, _)) = syntax.block_comment {
+                        if starts_at(&chars, i, start) {
+                            i += start.len();
+                            state = State::BlockComment;
+                            continue;
+                        }
+                    }
+                    if !c.is_whitespace() {
+                        line.last_code = Some(c);
+                    }
+                    if syntax.raw_strings && c == 'r' && !is_word(previous)
+                        || syntax.raw_strings
+                            && c == 'b'
+                            && chars.get(i + 1) == Some(&'r')
+                            && !is_word(previous)
+                    {
+                        let start = if c == 'b' { i + 2 } else { i + 1 };
+                        let hashes = chars[start..].iter().take_while(|&&c| c == '#').count();
+                        if chars.get(start + hashes) == Some(&'"') {
+                            i = start + hashes + 1;
+                            state = State::Str {
+                                close: format!("\"{}", "#".repeat(hashes)),
+                                escapes: false,
+                                multiline: true,
+                            };
+                            continue;
+                        }
+                    }
+                    if let Some((quote, escapes)) = syntax
+                        .quotes
+                        .iter()
+                        .find(|(quote, _)| starts_at(&chars, i, quote))
+                    {
+                        i += quote.len();
+                        state = State::Str {
+                            close: quote.to_string(),
+                            escapes: *escapes,
+                            multiline: quote.len() > 1 || *quote == "`" || syntax.multiline_strings,
+                        };
+                        continue;
+                    }
+                    if syntax.char_literals && c == '\'' {
+                        // A character literal, or else a lifetime or label.
+                        i += if chars.get(i + 1) == Some(&'\\') {
+                            chars[i + 2..]
+                                .iter()
+                                .skip(1)
+                                .position(|&c| c == '\'')
+                                .map_or(1, |position| position + 4)
+                        } else if chars.get(i + 2) == Some(&'\'') {
+                            3
+                        } else {
+                            1
+                        };
+                        continue;
+                    }
+                    match c {
+                        '{' => {
+                            depth += 1;
+                            line.max_depth = line.max_depth.max(depth);
+                        }
+                        '}' => depth = depth.saturating_sub(1),
+                        '(' | '[' => brackets += 1,
+                        ')' | ']' => brackets = brackets.saturating_sub(1),
+                        _ => {}
+                    }
+                    i += 1;
+                }
+            }
+        }
+        if let State::Str {
+            multiline: false, ..
+        } = state
+        {
+            state = State::Code;
+        }
+        line.end_depth = depth;
+        lines.push(line);
+    }
+    lines
+}
+
+/// A range of lines, inclusive, that forms one or more chunks.
+struct Segment {
+    symbol: Option<String>,
+    start: usize,
+    end: usize,
+}
+
+/// A symbol found on a line, with the name its members are qualified with.
+struct Definition {
+    symbol: String,
+    scope: String,
+}
+
+struct Splitter<'a> {
+    lines: Vec<Line<'a>>,
+    language: Language,
+    syntax: Syntax,
+    definitions: Vec<Regex>,
+    chunk_size: usize,
+}
+
+impl Splitter<'_> {
+    /// Splits the lines from `start` to `end` into segments at the definitions found at `level`,
+    /// a brace depth or an indentation. Code between definitions belongs to the `parent`.
+    fn split(
+        &self,
+        start: usize,
+        end: usize,
+        level: usize,
+        parent: Option<&Definition>,
+        segments: &mut Vec<Segment>,
+    ) {
+        let mut glue_start = start;
+        let mut i = start;
+        while i <= end {
+            let Some(definition) = self.definition(i, level, parent) else {
+                i += 1;
+                continue;
+            };
+            let Some(unit_end) = self.block_end(i, level, end) else {
+                i += 1;
+                continue;
+            };
+            let unit_start = self.prefix_start(i, glue_start);
+            if unit_start > glue_start {
+                self.push_glue(glue_start, unit_start - 1, parent, segments);
+            }
+            if self.size(unit_start, unit_end) <= self.chunk_size {
+                segments.push(Segment {
+                    symbol: Some(definition.symbol),
+                    start: unit_start,
+                    end: unit_end,
+                });
+            } else if let Some(inner) = self.inner_level(i, level, unit_end) {
+                self.split(unit_start, unit_end, inner, Some(&definition), segments);
+            } else {
+                segments.push(Segment {
+                    symbol: Some(definition.symbol),
+                    start: unit_start,
+                    end: unit_end,
+                });
+            }
+            i = unit_end + 1;
+            glue_start = i;
+        }
+        if glue_start <= end {
+            self.push_glue(glue_start, end, parent, segments);
+        }
+    }
+
+    /// Returns the definition that starts on a line at a level.
+    fn definition(
+        &self,
+        i: usize,
+        level: usize,
+        parent: Option<&Definition>,
+    ) -> Option<Definition> {
+        let line = &self.lines[i];
+        let at_level = match self.syntax.indentation {
+            true => line.depth + line.brackets == 0 && line.indent() == level,
+            false => line.depth == level,
+        };
+        if line.in_literal || !at_level {
+            return None;
+        }
+        let first_word = line.text.split_whitespace().next()?;
+        if KEYWORDS.contains(&first_word.trim_end_matches('(')) {
+            return None;
+        }
+        let captures = self
+            .definitions
+            .iter()
+            .find_map(|pattern| pattern.captures(line.text))?;
+        let (symbol, scope) = if let Some(header) = captures.name("impl") {
+            impl_name(header.as_str())?
+        } else {
+            let name = captures.name("name")?.as_str();
+            if KEYWORDS.contains(&name) {
+                return None;
+            }
+            let name = match captures.name("receiver") {
+                Some(receiver) => format!("{}.{}", receiver.as_str(), name),
+                None => name.to_string(),
+            };
+            (name.clone(), name)
+        };
+        let separator = self.language.separator();
+        Some(match parent {
+            Some(parent) => Definition {
+                symbol: format!("{}{}{}", parent.scope, separator, symbol),
+                scope: format!("{}{}{}", parent.scope, separator, scope),
+            },
+            None => Definition { symbol, scope },
+        })
+    }
+
+    /// Returns the last line of the block of a definition, or None for a declaration without a
+    /// body.
+    fn block_end(&self, start: usize, level: usize, last: usize) -> Option<usize> {
+        if self.syntax.indentation {
+            let mut end = start;
+            for j in start + 1..=last {
+                let line = &self.lines[j];
+                if line.is_blank() {
+                    continue;
+                }
+                if line.in_literal || line.depth + line.brackets > 0 || line.indent() > level {
+                    end = j;
+                    continue;
+                }
+                let text = line.text.trim();
+                if self
+                    .syntax
+                    .line_comments
+                    .iter()
+                    .any(|comment| text.starts_with(comment))
+                {
+                    continue;
+                }
+                // Ruby closes its blocks with `end` at the indentation of the definition.
+                if self.language == Language::Ruby && text.split_whitespace().next() == Some("end")
+                {
+                    end = j;
+                }
+                break;
+            }
+            return Some(end);
+        }
+
+        let mut opened = false;
+        for j in start..=last {
+            let line = &self.lines[j];
+            opened |= line.max_depth > level;
+            if opened && line.end_depth <= level {
+                return Some(j);
+            }
+            if !opened
+                && (line.last_code == Some(';')
+                    || j == last
+                    || self.lines[j + 1].is_blank()
+                    || self.definition(j + 1, level, None).is_some())
+            {
+                return None;
+            }
+        }
+        Some(last)
+    }
+
+    /// Returns the level of the members of a definition.
+    fn inner_level(&self, start: usize, level: usize, end: usize) -> Option<usize> {
+        if !self.syntax.indentation {
+            return Some(level + 1);
+        }
+        self.lines[start + 1..=end]
+            .iter()
+            .find(|line| {
+                !line.is_blank()
+                    && !line.in_literal
+                    && line.depth + line.brackets == 0
+                    && line.indent() > level
+            })
+            .map(Line::indent)
+    }
+
+    /// Returns the first of the comment, attribute and decorator lines right above a definition.
+    fn prefix_start(&self, definition: usize, glue_start: usize) -> usize {
+        let mut start = definition;
+        while start > glue_start {
+            let line = &self.lines[start - 1];
+            let text = line.text.trim();
+            let attached = !text.is_empty()
+                && (line.in_literal
+                    || self
+                        .syntax
+                        .prefixes
+                        .iter()
+                        .any(|prefix| text.starts_with(prefix)));
+            if !attached {
+                break;
+            }
+            start -= 1;
+        }
+        start
+    }
+
+    /// Adds the lines between definitions, trimmed of blank lines, unless they hold no words,
+    /// like a closing brace.
+    fn push_glue(
+        &self,
+        mut start: usize,
+        mut end: usize,
+        parent: Option<&Definition>,
+        segments: &mut Vec<Segment>,
+    ) {
+        while start <= end && self.lines[start].is_blank() {
+            start += 1;
+        }
+        while end > start && self.lines[end].is_blank() {
+            end -= 1;
+        }
+        let has_words = start <= end
+            && self.lines[start..=end]
+                .iter()
+                .any(|line| line.text.chars().any(char::is_alphanumeric));
+        if has_words {
+            segments.push(Segment {
+                symbol: parent.map(|parent| parent.symbol.clone()),
+                start,
+                end,
+            });
+        }
+    }
+
+    fn size(&self, start: usize, end: usize) -> usize {
+        self.lines[start..=end]
+            .iter()
+            .map(|line| line.text.chars().count() + 1)
+            .sum::<usize>()
+            - 1
+    }
+
+    /// Returns the chunks of a segment with their first and last lines. A segment larger than
+    /// the chunk size is split between lines, the chunks overlapping by whole lines, and a line
+    /// larger than the chunk size is split between characters.
+    fn pieces(&self, segment: &Segment, overlap: usize) -> Vec<(usize, usize, String)> {
+        let text = |start: usize, end: usize| {
+            self.lines[start..=end]
+                .iter()
+                .map(|line| line.text)
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        let length = |i: usize| self.lines[i].text.chars().count();
+
+        let mut pieces = Vec::new();
+        let mut start = segment.start;
+        loop {
+            let mut end = start;
+            let mut size = length(start);
+            while end < segment.end && size + 1 + length(end + 1) <= self.chunk_size {
+                end += 1;
+                size += 1 + length(end);
+            }
+            if size > self.chunk_size {
+                let chars = self.lines[start].text.chars().collect::<Vec<_>>();
+                for part in chars.chunks(self.chunk_size) {
+                    pieces.push((start, start, String::from_iter(part)));
+                }
+            } else {
+                pieces.push((start, end, text(start, end)));
+            }
+            if end >= segment.end {
+                break;
+            }
+            let mut next = end + 1;
+            let mut overlap_size = 0;
+            while next > start + 1 && overlap_size + length(next - 1) < overlap {
+                next -= 1;
+                overlap_size += length(next) + 1;
+            }
+            start = next;
+        }
+        pieces
+    }
+}
+
+/// Returns the name of a Rust impl block from the rest of its first line, as in
+/// `Display for Server`, and the type its members are qualified with, as in `Server`.
+fn impl_name(header: &str) -> Option<(String, String)> {
+    let mut header = header.trim();
+    if header.starts_with('<') {
+        let mut depth = 0;
+        let end = header.char_indices().find_map(|(i, c)| {
+            match c {
+                '<' => depth += 1,
+                '>' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(i)
+        })?;
+        header = header[end + 1..].trim_start();
+    }
+    let header = header
+        .split(" where")
+        .next()?
+        .trim_end_matches('{')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if header.is_empty() {
+        return None;
+    }
+    let self_type = header.rsplit(" for ").next().unwrap_or(&header);
+    let scope = self_type
+        .split('<')
+        .next()
+        .unwrap_or(self_type)
+        .rsplit("::")
+        .next()
+        .unwrap_or(self_type)
+        .trim_start_matches('&')
+        .to_string();
+    Some((header, scope))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbols(document: &Document) -> Vec<(Option<&str>, &str, &str)> {
+        document
+            .chunk_metadata
+            .iter()
+            .map(|metadata| {
+                (
+                    metadata.get("symbol").map(String::as_str),
+                    metadata["start_line"].as_str(),
+                    metadata["end_line"].as_str(),
+                )
+            })
+            .collect()
+    }
+
+    const RUST: &str = r##"use std::fmt;
+
+/// A server.
+#[derive(Debug)]
+pub struct Server<'a> {
+    name: &'a str,
+}
+
+impl<'a> fmt::Display for Server<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{{ {} }}", self.name)
+    }
+}
+
+pub(crate) fn start() {
+    let brace = '{';
+    let raw = r#"}"#;
+    // }
+}
+"##;
+
+    #[test]
+    fn test_rust() {
+        let document = CodeProcessor::new(1000, 0)
+            .unwrap()
+            .process_code(RUST, Language::Rust);
+        assert_eq!(
+            symbols(&document),
+            vec![
+                (None, "1", "1"),
+                (Some("Server"), "3", "7"),
+                (Some("fmt::Display for Server<'a>"), "9", "13"),
+                (Some("start"), "15", "19"),
+            ]
+        );
+        assert!(document.chunks[1].starts_with("/// A server.\n#[derive(Debug)]"));
+        assert_eq!(document.chunk_metadata[0]["language"], "rust");
+    }
+
+    #[test]
+    fn test_rust_members() {
+        // The impl block is too large for one chunk and is split at its methods.
+        let document = CodeProcessor::new(130, 0)
+            .unwrap()
+            .process_code(RUST, Language::Rust);
+        assert_eq!(
+            symbols(&document)[2..4],
+            vec![
+                (Some("fmt::Display for Server<'a>"), "9", "9"),
+                (Some("Server::fmt"), "10", "12"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python() {
+        let code = r#"import os
+
+
+@dataclass
+class Config:
+    """A configuration.
+
+def not_a_function():
+    """
+
+    path: str
+
+    def load(
+        self,
+        name: str,
+    ) -> "Config":
+        return self
+
+    # The default file.
+    def default(self):
+        return os.path.join(self.path, "config.toml")
+
+
+async def main():
+    pass
+"#;
+        let processor = CodeProcessor::new(1000, 0).unwrap();
+        let document = processor.process_code(code, Language::Python);
+        assert_eq!(
+            symbols(&document),
+            vec![
+                (None, "1", "1"),
+                (Some("Config"), "4", "21"),
+                (Some("main"), "24", "25")
+            ]
+        );
+
+        let document = CodeProcessor::new(150, 0)
+            .unwrap()
+            .process_code(code, Language::Python);
+        assert_eq!(
+            symbols(&document)[1..],
+            vec![
+                (Some("Config"), "4", "11"),
+                (Some("Config.load"), "13", "17"),
+                (Some("Config.default"), "19", "21"),
+                (Some("main"), "24", "25"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_other_languages() {
+        let go = "package main\n\nfunc (s *Server) Start() error {\n\treturn nil\n}\n\ntype Server struct {\n\tname string\n}\n";
+        let document = CodeProcessor::new(1000, 0)
+            .unwrap()
+            .process_code(go, Language::Go);
+        assert_eq!(
+            symbols(&document),
+            vec![
+                (None, "1", "1"),
+                (Some("Server.Start"), "3", "5"),
+                (Some("Server"), "7", "9")
+            ]
+        );
+
+        let typescript = "export const add = (a: number, b: number): number => {\n  return a + b;\n};\n\nexport class Stack<T> {\n  private items: T[] = [];\n\n  push(item: T): void {\n    this.items.push(item);\n  }\n}\n";
+        let document = CodeProcessor::new(1000, 0)
+            .unwrap()
+            .process_code(typescript, Language::TypeScript);
+        assert_eq!(
+            symbols(&document),
+            vec![(Some("add"), "1", "3"), (Some("Stack"), "5", "11")]
+        );
+
+        let c = "#include <stdio.h>\n\nint add(int a, int b);\n\nstatic const char *name(void)\n{\n    return \"}\";\n}\n";
+        let document = CodeProcessor::new(1000, 0)
+            .unwrap()
+            .process_code(c, Language::C);
+        assert_eq!(
+            symbols(&document),
+            vec![(None, "1", "3"), (Some("name"), "5", "8")]
+        );
+
+        let ruby = "class Greeter\n  def hello\n    puts 'hi'\n  end\nend\n";
+        let document = CodeProcessor::new(1000, 0)
+            .unwrap()
+            .process_code(ruby, Language::Ruby);
+        assert_eq!(symbols(&document), vec![(Some("Greeter"), "1", "5")]);
+    }
+
+    #[test]
+    fn test_long_function() {
+        let body = (0..20)
+            .map(|i| format!("    let x{} = {};", i, i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let code = format!("fn long() {{\n{}\n}}", body);
+        let document = CodeProcessor::new(100, 20)
+            .unwrap()
+            .process_code(&code, Language::Rust);
+        assert!(document.chunks.len() > 1);
+        assert!(document.chunks.iter().all(|chunk| chunk.len() <= 100));
+        assert!(document
+            .chunk_metadata
+            .iter()
+            .all(|metadata| metadata["symbol"] == "long"));
+        // Consecutive chunks share a line.
+        let first_end = document.chunk_metadata[0]["end_line"]
+            .parse::<usize>()
+            .unwrap();
+        let second_start = document.chunk_metadata[1]["start_line"]
+            .parse::<usize>()
+            .unwrap();
+        assert_eq!(second_start, first_end);
+    }
+
+    #[test]
+    fn test_process_file() {
+        let dir = tempdir::TempDir::new("code").unwrap();
+        let path = dir.path().join("main.py");
+        std::fs::write(&path, "def main():\n    pass\n").unwrap();
+        let processor = CodeProcessor::new(1000, 0).unwrap();
+        let document = processor.process_file(&path).unwrap();
+        assert_eq!(document.chunks, vec!["def main():\n    pass"]);
+        assert_eq!(document.chunk_metadata[0]["language"], "python");
+
+        assert!(processor.process_file("../test_files/test.txt").is_err());
+
+        assert!(processor.process_bytes(b"def main(): pass").is_err());
+        let document = processor
+            .with_language(Language::Ruby)
+            .process_bytes(b"def main\nend\n")
+            .unwrap();
+        assert_eq!(document.chunk_metadata[0]["language"], "ruby");
+    }
+}
@@ -1,6 +1,10 @@
-use crate::markdown_processor::MarkdownProcessor;
-use crate::processor::{Document, DocumentProcessor, FileProcessor};
-use docx_parser::MarkdownDocument;
+use crate::markdown_processor::{markdown_table, MarkdownProcessor};
+use crate::processor::{BytesProcessor, Document, DocumentProcessor, FileProcessor};
+use anyhow::anyhow;
+use docx_rust::document::{BodyContent, Paragraph, TableCellContent, TableRowContent};
+use docx_rust::{Docx, DocxFile};
+use std::collections::HashMap;
+use std::io::Cursor;
 use std::path::Path;
 use text_splitter::ChunkConfigError;
 
@@ -16,17 +20,108 @@ impl DocxProcessor {
     }
 }
 
+/// Files are read and converted like bytes, so that both give the same chunks.
 impl FileProcessor for DocxProcessor {
     fn process_file(&self, path: impl AsRef<Path>) -> anyhow::Result<Document> {
-        let docs = MarkdownDocument::from_file(path);
-        let markdown = docs.to_markdown(false);
-        self.markdown_processor.process_document(&markdown)
+        let bytes = std::fs::read(path)?;
+        self.process_bytes(&bytes)
     }
 }
 
+/// The bytes are parsed in memory, and malformed documents are reported as errors.
+impl BytesProcessor for DocxProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> anyhow::Result<Document> {
+        let file = DocxFile::from_reader(Cursor::new(bytes))
+            .map_err(|e| anyhow!("Error reading DOCX: {:?}", e))?;
+        let docx = file
+            .parse()
+            .map_err(|e| anyhow!("Error parsing DOCX: {:?}", e))?;
+        self.markdown_processor
+            .process_document(&to_markdown(&docx))
+    }
+}
+
+/// Converts a parsed document to Markdown: the title, paragraphs, headings from the outline
+/// level of the paragraph or its style, and tables.
+fn to_markdown(docx: &Docx) -> String {
+    let outline_levels: HashMap<&str, isize> = docx
+        .styles
+        .styles
+        .iter()
+        .filter_map(|style| {
+            let level = style.paragraph.as_ref()?.outline_lvl.as_ref()?.value;
+            Some((style.style_id.as_ref(), level))
+        })
+        .collect();
+    let paragraph_to_markdown = |paragraph: &Paragraph| {
+        let text = paragraph.text();
+        let level = paragraph.property.as_ref().and_then(|property| {
+            property
+                .outline_lvl
+                .as_ref()
+                .map(|level| level.value)
+                .or_else(|| {
+                    let style_id = property.style_id.as_ref()?;
+                    outline_levels.get(style_id.value.as_ref()).copied()
+                })
+        });
+        match level {
+            Some(level) if !text.is_empty() => {
+                format!("{} {}", "#".repeat(level.clamp(0, 5) as usize + 1), text)
+            }
+            _ => text,
+        }
+    };
+
+    let mut blocks = Vec::new();
+    if let Some(title) = docx.core.as_ref().and_then(|core| core.title.as_ref()) {
+        if !title.is_empty() {
+            blocks.push(format!("# {}", title));
+        }
+    }
+    for content in &docx.document.body.content {
+        match content {
+            BodyContent::Paragraph(paragraph) => {
+                let markdown = paragraph_to_markdown(paragraph);
+                if !markdown.is_empty() {
+                    blocks.push(markdown);
+                }
+            }
+            BodyContent::Table(table) => {
+                let rows = table
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        row.cells
+                            .iter()
+                            .filter_map(|cell| match cell {
+                                TableRowContent::TableCell(cell) => Some(
+                                    cell.content
+                                        .iter()
+                                        .map(|TableCellContent::Paragraph(paragraph)| {
+                                            paragraph_to_markdown(paragraph)
+                                        })
+                                        .collect::<Vec<_>>()
+                                        .join("<br/>"),
+                                ),
+                                _ => None,
+                            })
+                            .collect()
+                    })
+                    .collect();
+                blocks.extend(markdown_table(rows));
+            }
+            _ => (),
+        }
+    }
+    blocks.join("\n\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use docx_rust::document::{Table, TableRow};
+
     #[test]
     fn test_extract_text() {
         let txt_file = "../test_files/test.docx";
@@ -36,16 +131,60 @@ mod tests {
         assert!(text
             .chunks
             .contains(&"This is a docx file test".to_string()));
+
+        let bytes = std::fs::read(txt_file).unwrap();
+        let from_bytes = processor.process_bytes(&bytes).unwrap();
+        assert_eq!(from_bytes.chunks, text.chunks);
+    }
+
+    #[test]
+    fn test_table_cells_with_pipes() {
+        let mut bytes = Vec::new();
+        let mut docx = Docx::default();
+        docx.document.push(
+            Table::default()
+                .push_row(
+                    TableRow::default()
+                        .push_cell(Paragraph::default().push_text("a | b"))
+                        .push_cell(Paragraph::default().push_text("c")),
+                )
+                .push_row(TableRow::default().push_cell(Paragraph::default().push_text("d"))),
+        );
+        docx.write(Cursor::new(&mut bytes)).unwrap();
+
+        let processor = DocxProcessor::new(128, 0).unwrap();
+        let document = processor.process_bytes(&bytes).unwrap();
+        assert_eq!(
+            document.chunks,
+            ["| a \\| b | c |\n| --- | --- |\n| d |  |"]
+        );
+    }
+
+    #[test]
+    fn test_process_bytes_malformed() {
+        let processor = DocxProcessor::new(128, 0).unwrap();
+        assert!(processor.process_bytes(b"not a docx file").is_err());
+
+        let mut zip = Vec::new();
+        let mut writer = zip::ZipWriter::new(Cursor::new(&mut zip));
+        writer
+            .start_file(
+                "word/document.xml",
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+        std::io::Write::write_all(&mut writer, b"<w:document>").unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+        assert!(processor.process_bytes(&zip).is_err());
     }
 
     // Returns an error if the file path is invalid.
     #[test]
-    #[should_panic(
-        expected = "Error processing file: IO(Os { code: 2, kind: NotFound, message: \"No such file or directory\" })"
-    )]
     fn test_extract_text_invalid_file_path() {
         let invalid_file_path = "this_file_definitely_does_not_exist.docx";
         let processor = DocxProcessor::new(128, 0).unwrap();
-        processor.process_file(invalid_file_path).unwrap();
+        let error = processor.process_file(invalid_file_path).unwrap_err();
+        assert!(error.to_string().contains("No such file or directory"));
     }
 }
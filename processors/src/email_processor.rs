@@ -0,0 +1,660 @@
+//! Email ingestion for RFC 822 messages (`.eml` files) and mbox archives.
+//!
+//! Messages are parsed as MIME: encoded headers, multipart bodies, base64 and quoted-printable
+//! transfer encodings and the charsets of the parts are decoded. The HTML body of a message is
+//! preferred over its plain text alternative and converted to Markdown by the [HtmlProcessor].
+//! Attachments, including attached messages, are handed to an attachment processor that routes
+//! them by their format, so their chunks can be linked back to the message they came from.
+
+use crate::html_processor::HtmlProcessor;
+use crate::markdown_processor::MarkdownProcessor;
+use crate::processor::{BytesProcessor, Document, DocumentProcessor, FileProcessor};
+use anyhow::Result;
+use base64::engine::general_purpose::{GeneralPurpose, GeneralPurposeConfig};
+use base64::engine::DecodePaddingMode;
+use base64::Engine;
+use std::collections::HashMap;
+use std::path::Path;
+use text_splitter::ChunkConfigError;
+
+/// Decodes base64 with or without padding, as found in mail.
+const BASE64: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+/// Processes the content of an attachment, given with its file name as a format hint.
+pub type AttachmentProcessor = Box<dyn Fn(&[u8], &str) -> Result<Document>>;
+
+/// A file attached to a message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attachment {
+    pub file_name: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+/// A parsed message with its decoded headers, bodies and attachments.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Email {
+    /// The Message-ID without its angle brackets.
+    pub message_id: Option<String>,
+    pub subject: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub date: Option<String>,
+    pub text_body: Option<String>,
+    pub html_body: Option<String>,
+    pub attachments: Vec<Attachment>,
+}
+
+impl Email {
+    /// Parses a message. Parsing is lenient: malformed parts are read as well as possible.
+    pub fn parse(message: &[u8]) -> Email {
+        let (headers, _) = split_headers(message);
+        let header = |name: &str| header(&headers, name).map(decode_words);
+        let mut email = Email {
+            message_id: header("Message-ID")
+                .map(|id| id.trim().trim_matches(['<', '>']).to_string())
+                .filter(|id| !id.is_empty()),
+            subject: header("Subject"),
+            from: header("From"),
+            to: header("To"),
+            date: header("Date"),
+            ..Default::default()
+        };
+        email.add_part(message);
+        email
+    }
+
+    /// Returns the `message_id`, `subject`, `from`, `to` and `date` of the message that are set.
+    pub fn metadata(&self) -> HashMap<String, String> {
+        [
+            ("message_id", &self.message_id),
+            ("subject", &self.subject),
+            ("from", &self.from),
+            ("to", &self.to),
+            ("date", &self.date),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.to_string(), value.clone()?)))
+        .collect()
+    }
+
+    fn add_part(&mut self, part: &[u8]) {
+        let (headers, body) = split_headers(part);
+        let (mime_type, parameters) = header(&headers, "Content-Type")
+            .map(parse_parameters)
+            .unwrap_or_else(|| ("text/plain".to_string(), HashMap::new()));
+        let (disposition, disposition_parameters) = header(&headers, "Content-Disposition")
+            .map(parse_parameters)
+            .unwrap_or_default();
+        let file_name = disposition_parameters
+            .get("filename")
+            .or_else(|| parameters.get("name"))
+            .map(|name| decode_words(name));
+
+        if mime_type.starts_with("multipart/") {
+            if let Some(boundary) = parameters.get("boundary") {
+                for part in split_multipart(body, boundary) {
+                    self.add_part(part);
+                }
+            }
+            return;
+        }
+
+        let data = decode_transfer_encoding(header(&headers, "Content-Transfer-Encoding"), body);
+        let is_text = mime_type == "text/plain" || mime_type == "text/html";
+        if mime_type == "message/rfc822" || disposition == "attachment" || !is_text {
+            // Inline parts without a name, such as embedded images, are not attachments.
+            let file_name = match (file_name, mime_type.as_str()) {
+                (Some(file_name), _) => file_name,
+                (None, "message/rfc822") => "message.eml".to_string(),
+                (None, _) if disposition == "attachment" => "attachment".to_string(),
+                (None, _) => return,
+            };
+            self.attachments.push(Attachment {
+                file_name,
+                content_type: mime_type,
+                data,
+            });
+            return;
+        }
+
+        let text = decode_charset(&data, parameters.get("charset").map(String::as_str));
+        let body = match mime_type.as_str() {
+            "text/html" => &mut self.html_body,
+            _ => &mut self.text_body,
+        };
+        match body {
+            Some(body) => {
+                body.push_str("\n\n");
+                body.push_str(&text);
+            }
+            None => *body = Some(text),
+        }
+    }
+}
+
+/// A struct for processing EML and MBOX files.
+///
+/// The chunks of a message carry its `message_id`, `subject`, `from`, `to` and `date` in
+/// [Document::chunk_metadata]. The chunks of its attachments carry the same headers, the
+/// metadata of the attachment processor, the `attachment_name` and the `parent_message_id`.
+pub struct EmailProcessor {
+    markdown_processor: MarkdownProcessor,
+    html_processor: HtmlProcessor,
+    attachment_processor: Option<AttachmentProcessor>,
+}
+
+impl EmailProcessor {
+    pub fn new(chunk_size: usize, overlap: usize) -> Result<EmailProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        let html_processor = HtmlProcessor::new(chunk_size, overlap)?;
+        Ok(EmailProcessor {
+            markdown_processor,
+            html_processor,
+            attachment_processor: None,
+        })
+    }
+
+    /// Sets the processor of attachments. Attachments are skipped without one, and attachments
+    /// it returns an error for, such as unsupported file types, are skipped as well.
+    pub fn with_attachment_processor(
+        mut self,
+        attachment_processor: impl Fn(&[u8], &str) -> Result<Document> + 'static,
+    ) -> Self {
+        self.attachment_processor = Some(Box::new(attachment_processor));
+        self
+    }
+
+    /// Reads the messages of an mbox archive, which is a file with the `mbox` extension or that
+    /// starts with a `From ` line, or the single message of any other file.
+    pub fn read_messages(path: impl AsRef<Path>) -> Result<Vec<Email>> {
+        let bytes = std::fs::read(&path)?;
+        let is_mbox = path
+            .as_ref()
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("mbox"));
+        Ok(Self::parse_messages(&bytes, is_mbox))
+    }
+
+    /// Parses the messages of an mbox archive that starts with a `From ` line, or the single
+    /// message of any other content.
+    pub fn messages_from_bytes(bytes: &[u8]) -> Vec<Email> {
+        Self::parse_messages(bytes, false)
+    }
+
+    fn parse_messages(bytes: &[u8], is_mbox: bool) -> Vec<Email> {
+        match is_mbox || bytes.starts_with(b"From ") {
+            true => split_mbox(bytes)
+                .iter()
+                .map(|message| Email::parse(message))
+                .collect(),
+            false => vec![Email::parse(bytes)],
+        }
+    }
+
+    fn process_email(&self, email: &Email, document: &mut Document) -> Result<()> {
+        let metadata = email.metadata();
+        let body = match (&email.html_body, &email.text_body) {
+            (Some(html), _) => self.html_processor.process_document(html)?,
+            (None, Some(text)) => self.markdown_processor.process_document(text)?,
+            (None, None) => Document::default(),
+        };
+        document
+            .chunk_metadata
+            .extend(vec![metadata.clone(); body.chunks.len()]);
+        document.chunks.extend(body.chunks);
+
+        let Some(attachment_processor) = &self.attachment_processor else {
+            return Ok(());
+        };
+        for attachment in &email.attachments {
+            let file_name = sanitize_file_name(&attachment.file_name);
+            let Ok(attached) = attachment_processor(&attachment.data, &file_name) else {
+                continue;
+            };
+            for (i, chunk) in attached.chunks.into_iter().enumerate() {
+                let mut chunk_metadata = metadata.clone();
+                chunk_metadata.remove("message_id");
+                if let Some(attached_metadata) = attached.chunk_metadata.get(i) {
+                    chunk_metadata.extend(attached_metadata.clone());
+                }
+                chunk_metadata.insert("attachment_name".to_string(), attachment.file_name.clone());
+                if let Some(message_id) = &email.message_id {
+                    chunk_metadata.insert("parent_message_id".to_string(), message_id.clone());
+                }
+                document.chunks.push(chunk);
+                document.chunk_metadata.push(chunk_metadata);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FileProcessor for EmailProcessor {
+    fn process_file(&self, path: impl AsRef<Path>) -> Result<Document> {
+        let mut document = Document::default();
+        for email in Self::read_messages(path)? {
+            self.process_email(&email, &mut document)?;
+        }
+        Ok(document)
+    }
+}
+
+impl BytesProcessor for EmailProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> Result<Document> {
+        let mut document = Document::default();
+        for email in Self::messages_from_bytes(bytes) {
+            self.process_email(&email, &mut document)?;
+        }
+        Ok(document)
+    }
+}
+
+/// Splits an mbox archive into messages. A message starts with a `From ` line at the start of
+/// the archive or after an empty line, and `>From ` lines are unquoted.
+pub fn split_mbox(archive: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut message: Option<Vec<u8>> = None;
+    let mut previous_empty = true;
+    for line in archive.split_inclusive(|&byte| byte == b'\n') {
+        let content = line.trim_ascii_end();
+        if previous_empty && content.starts_with(b"From ") {
+            messages.extend(message.replace(Vec::new()));
+            previous_empty = false;
+            continue;
+        }
+        previous_empty = content.is_empty();
+        if let Some(message) = message.as_mut() {
+            let quoted = line.iter().take_while(|&&byte| byte == b'>').count();
+            match quoted > 0 && line[quoted..].starts_with(b"From ") {
+                true => message.extend_from_slice(&line[1..]),
+                false => message.extend_from_slice(line),
+            }
+        }
+    }
+    messages.extend(message);
+    messages
+}
+
+/// Splits a message or part into its unfolded headers and its body.
+fn split_headers(part: &[u8]) -> (Vec<(String, String)>, &[u8]) {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    let mut offset = 0;
+    for line in part.split_inclusive(|&byte| byte == b'\n') {
+        offset += line.len();
+        let line = String::from_utf8_lossy(line.trim_ascii_end());
+        if line.is_empty() {
+            return (headers, &part[offset..]);
+        }
+        match (line.starts_with([' ', '\t']), headers.last_mut()) {
+            (true, Some((_, value))) => {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            _ => {
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.push((name.trim().to_string(), value.trim().to_string()));
+                }
+            }
+        }
+    }
+    (headers, &[])
+}
+
+fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Parses a header value such as `text/plain; charset="utf-8"` into its lowercase value and its
+/// parameters, decoding the `name*=charset''value` form of RFC 2231.
+fn parse_parameters(value: &str) -> (String, HashMap<String, String>) {
+    let mut segments = split_unquoted(value, ';').into_iter();
+    let value = segments.next().unwrap_or_default().trim().to_lowercase();
+    let mut parameters = HashMap::new();
+    for segment in segments {
+        let Some((name, value)) = segment.split_once('=') else {
+            continue;
+        };
+        let name = name.trim().to_lowercase();
+        let value = value.trim().trim_matches('"').replace("\\\"", "\"");
+        match name.strip_suffix('*') {
+            Some(name) => {
+                let (charset, encoded) = match value.splitn(3, '\'').collect::<Vec<_>>()[..] {
+                    [charset, _, encoded] => (Some(charset), encoded),
+                    _ => (None, value.as_str()),
+                };
+                let decoded = decode_charset(&percent_decode(encoded), charset);
+                parameters.insert(name.to_string(), decoded);
+            }
+            None => {
+                parameters.insert(name, value);
+            }
+        }
+    }
+    (value, parameters)
+}
+
+/// Splits at `separator` outside of double quotes.
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let (mut start, mut quoted) = (0, false);
+    for (i, c) in value.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                segments.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    segments.push(&value[start..]);
+    segments
+}
+
+/// Returns the parts of a multipart body, without the preamble and epilogue.
+fn split_multipart<'a>(body: &'a [u8], boundary: &str) -> Vec<&'a [u8]> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+    for line in body.split_inclusive(|&byte| byte == b'\n') {
+        let line_start = offset;
+        offset += line.len();
+        if !line.starts_with(&delimiter) {
+            continue;
+        }
+        if let Some(start) = start {
+            // The line break before a delimiter belongs to the delimiter.
+            let end = match body[..line_start].strip_suffix(b"\r\n") {
+                Some(part) => part.len(),
+                None => line_start.saturating_sub(1),
+            };
+            parts.push(&body[start..end.max(start)]);
+        }
+        if line[delimiter.len()..].starts_with(b"--") {
+            return parts;
+        }
+        start = Some(offset);
+    }
+    if let Some(start) = start {
+        parts.push(&body[start..]);
+    }
+    parts
+}
+
+fn decode_transfer_encoding(encoding: Option<&str>, body: &[u8]) -> Vec<u8> {
+    match encoding
+        .map(|encoding| encoding.trim().to_lowercase())
+        .as_deref()
+    {
+        Some("base64") => {
+            let encoded = body
+                .iter()
+                .copied()
+                .filter(|byte| !byte.is_ascii_whitespace())
+                .collect::<Vec<_>>();
+            BASE64.decode(encoded).unwrap_or_default()
+        }
+        Some("quoted-printable") => decode_quoted_printable(body, false),
+        _ => body.to_vec(),
+    }
+}
+
+/// Decodes quoted-printable text. In encoded words, underscores stand for spaces.
+fn decode_quoted_printable(encoded: &[u8], encoded_word: bool) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        match encoded[i] {
+            b'=' => {
+                let rest = &encoded[i + 1..];
+                let hex = rest
+                    .get(..2)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                if let Some(byte) = hex {
+                    decoded.push(byte);
+                    i += 3;
+                } else if rest.starts_with(b"\r\n") {
+                    i += 3;
+                } else if rest.starts_with(b"\n") {
+                    i += 2;
+                } else {
+                    decoded.push(b'=');
+                    i += 1;
+                }
+            }
+            b'_' if encoded_word => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+fn decode_charset(bytes: &[u8], charset: Option<&str>) -> String {
+    let encoding = charset
+        .and_then(|charset| encoding_rs::Encoding::for_label(charset.trim().as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    encoding.decode(bytes).0.into_owned()
+}
+
+/// Decodes the encoded words of RFC 2047, such as `=?utf-8?q?caf=C3=A9?=`, in a header value.
+/// White space between two encoded words is dropped.
+fn decode_words(value: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = value;
+    let mut after_word = false;
+    while let Some(start) = rest.find("=?") {
+        let (before, candidate) = rest.split_at(start);
+        match decode_word(candidate) {
+            Some((word, length)) => {
+                if !(after_word && before.trim().is_empty()) {
+                    decoded.push_str(before);
+                }
+                decoded.push_str(&word);
+                rest = &candidate[length..];
+                after_word = true;
+            }
+            None => {
+                decoded.push_str(before);
+                decoded.push_str("=?");
+                rest = &candidate[2..];
+                after_word = false;
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Decodes the encoded word at the start of `word` and returns it with its length.
+fn decode_word(word: &str) -> Option<(String, usize)> {
+    let mut fields = word[2..].splitn(3, '?');
+    let (charset, encoding, rest) = (fields.next()?, fields.next()?, fields.next()?);
+    let text = &rest[..rest.find("?=")?];
+    let bytes = match encoding {
+        "B" | "b" => BASE64.decode(text).ok()?,
+        "Q" | "q" => decode_quoted_printable(text.as_bytes(), true),
+        _ => return None,
+    };
+    let length = "=?".len() + charset.len() + encoding.len() + text.len() + "???=".len();
+    // The charset may carry a language, as in `utf-8*en`.
+    let charset = charset.split('*').next();
+    Some((decode_charset(&bytes, charset), length))
+}
+
+fn percent_decode(encoded: &str) -> Vec<u8> {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = encoded
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Keeps the last path component of an attachment name, so it names a file and not a path.
+fn sanitize_file_name(file_name: &str) -> String {
+    let name = file_name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>();
+    match name.trim_matches('.').is_empty() {
+        true => "attachment".to_string(),
+        false => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::txt_processor::TxtProcessor;
+
+    const MESSAGE: &str = "Message-ID: <lease-1@example.com>\r\n\
+From: =?utf-8?q?Ren=C3=A9e?= <renee@example.com>\r\n\
+To: legal@example.com\r\n\
+Subject: =?utf-8?B?TGVhc2U=?= =?utf-8?q?_renewal?=\r\n\
+Date: Mon, 5 Feb 2024 10:00:00 +0100\r\n\
+MIME-Version: 1.0\r\n\
+Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+\r\n\
+This is a multi-part message.\r\n\
+--outer\r\n\
+Content-Type: multipart/alternative; boundary=\"inner\"\r\n\
+\r\n\
+--inner\r\n\
+Content-Type: text/plain; charset=utf-8\r\n\
+\r\n\
+Please renew the lease.\r\n\
+--inner\r\n\
+Content-Type: text/html; charset=iso-8859-1\r\n\
+Content-Transfer-Encoding: quoted-printable\r\n\
+\r\n\
+<p>Please renew the <b>lease</b> for the caf=E9 by =\r\n\
+March.</p>\r\n\
+--inner--\r\n\
+--outer\r\n\
+Content-Type: text/plain; name=\"terms.txt\"\r\n\
+Content-Disposition: attachment; filename*=utf-8''terms%20v2.txt\r\n\
+Content-Transfer-Encoding: base64\r\n\
+\r\n\
+VGhlIHJlbnQgaXMgZHVlIG1vbnRobHku\r\n\
+--outer\r\n\
+Content-Type: image/png\r\n\
+Content-Disposition: inline\r\n\
+\r\n\
+iVBORw0KGgo=\r\n\
+--outer--\r\n";
+
+    fn processor() -> EmailProcessor {
+        EmailProcessor::new(1000, 0)
+            .unwrap()
+            .with_attachment_processor(|bytes, _| TxtProcessor::new(1000, 0)?.process_bytes(bytes))
+    }
+
+    #[test]
+    fn test_parse() {
+        let email = Email::parse(MESSAGE.as_bytes());
+        assert_eq!(email.message_id.as_deref(), Some("lease-1@example.com"));
+        assert_eq!(email.subject.as_deref(), Some("Lease renewal"));
+        assert_eq!(
+            email.from.as_deref(),
+            Some("Ren\u{e9}e <renee@example.com>")
+        );
+        assert_eq!(email.text_body.as_deref(), Some("Please renew the lease."));
+        assert_eq!(
+            email.html_body.as_deref(),
+            Some("<p>Please renew the <b>lease</b> for the caf\u{e9} by March.</p>")
+        );
+        // The inline image without a name is not an attachment.
+        assert_eq!(email.attachments.len(), 1);
+        assert_eq!(email.attachments[0].file_name, "terms v2.txt");
+        assert_eq!(email.attachments[0].data, b"The rent is due monthly.");
+    }
+
+    #[test]
+    fn test_process_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lease.eml");
+        std::fs::write(&path, MESSAGE).unwrap();
+        let document = processor().process_file(&path).unwrap();
+
+        assert_eq!(
+            document.chunks,
+            [
+                "Please renew the **lease** for the caf\u{e9} by March.",
+                "The rent is due monthly."
+            ]
+        );
+        let body = &document.chunk_metadata[0];
+        assert_eq!(body["message_id"], "lease-1@example.com");
+        assert_eq!(body["date"], "Mon, 5 Feb 2024 10:00:00 +0100");
+        let attachment = &document.chunk_metadata[1];
+        assert_eq!(attachment["parent_message_id"], "lease-1@example.com");
+        assert_eq!(attachment["attachment_name"], "terms v2.txt");
+        assert_eq!(attachment["subject"], "Lease renewal");
+        assert!(!attachment.contains_key("message_id"));
+    }
+
+    #[test]
+    fn test_split_mbox() {
+        let mbox = format!(
+            "From renee@example.com Mon Feb  5 10:00:00 2024\n{}\n\
+             From legal@example.com Tue Feb  6 09:00:00 2024\n\
+             Subject: Re: Lease\n\nAgreed.\n>From now on, monthly.\n",
+            MESSAGE.replace("\r\n", "\n")
+        );
+        let messages = split_mbox(mbox.as_bytes());
+        assert_eq!(messages.len(), 2);
+        let reply = Email::parse(&messages[1]);
+        assert_eq!(reply.subject.as_deref(), Some("Re: Lease"));
+        assert_eq!(
+            reply.text_body.as_deref(),
+            Some("Agreed.\nFrom now on, monthly.\n")
+        );
+        assert_eq!(Email::parse(&messages[0]).attachments.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_words() {
+        assert_eq!(
+            decode_words("=?ISO-8859-1?Q?caf=E9?= au lait"),
+            "caf\u{e9} au lait"
+        );
+        assert_eq!(
+            decode_words("=?utf-8?x?abc?= plain"),
+            "=?utf-8?x?abc?= plain"
+        );
+        assert_eq!(sanitize_file_name("../../etc/passwd"), "passwd");
+        assert_eq!(sanitize_file_name(".."), "attachment");
+    }
+}
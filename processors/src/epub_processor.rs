@@ -0,0 +1,344 @@
+//! Text extraction for EPUB books.
+//!
+//! An EPUB file is a zip package whose package document (the OPF file named by
+//! `META-INF/container.xml`) lists the XHTML chapters in reading order in its spine. Every
+//! chapter is converted to Markdown and chunked by the [HtmlProcessor], so chunks never span two
+//! chapters. Chapter titles are taken from the table of contents (the navigation document of
+//! EPUB 3 or the NCX of EPUB 2), or else from the first heading or the title of the chapter.
+
+use crate::html_processor::HtmlProcessor;
+use crate::processor::{BytesProcessor, Document, DocumentProcessor, FileProcessor};
+use crate::xml::{parse_zip_entry, resolve_target, Element};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use text_splitter::ChunkConfigError;
+use zip::ZipArchive;
+
+/// A chapter of a book in reading order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: Option<String>,
+    /// The XHTML content of the chapter.
+    pub content: String,
+}
+
+/// A struct for processing EPUB files.
+///
+/// Every chunk carries the `chapter_number` of its chapter in reading order, starting at 1, and
+/// the `chapter_title` when the chapter has one, in [Document::chunk_metadata].
+pub struct EpubProcessor {
+    html_processor: HtmlProcessor,
+}
+
+impl EpubProcessor {
+    pub fn new(chunk_size: usize, overlap: usize) -> Result<EpubProcessor, ChunkConfigError> {
+        let html_processor = HtmlProcessor::new(chunk_size, overlap)?;
+        Ok(EpubProcessor { html_processor })
+    }
+
+    /// Returns the chapters of a book in the order of its spine.
+    pub fn read_chapters(path: impl AsRef<Path>) -> Result<Vec<Chapter>> {
+        Self::chapters_from_reader(File::open(path)?)
+    }
+
+    /// Returns the chapters of a book read from a reader in the order of its spine.
+    pub fn chapters_from_reader(reader: impl Read + Seek) -> Result<Vec<Chapter>> {
+        let mut archive = ZipArchive::new(reader)?;
+        let container = parse_zip_entry(&mut archive, "META-INF/container.xml")?;
+        let package_path = container
+            .find_all("rootfile")
+            .into_iter()
+            .find_map(|rootfile| rootfile.attribute("full-path"))
+            .ok_or_else(|| anyhow!("the EPUB container names no package document"))?
+            .to_string();
+        let package = parse_zip_entry(&mut archive, &package_path)?;
+        let package_directory = package_path.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+        // Manifest items by id, with their paths inside the package.
+        let manifest = package
+            .find_all("item")
+            .into_iter()
+            .filter_map(|item| {
+                let path =
+                    resolve_target(package_directory, &percent_decode(item.attribute("href")?));
+                Some((item.attribute("id")?, (path, item)))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let nav = manifest.values().find(|(_, item)| {
+            item.attribute("properties")
+                .is_some_and(|properties| properties.split_whitespace().any(|p| p == "nav"))
+        });
+        let ncx = package
+            .find_all("spine")
+            .first()
+            .and_then(|spine| spine.attribute("toc"))
+            .and_then(|id| manifest.get(id));
+        let titles = match (nav, ncx) {
+            (Some((path, _)), _) => toc_titles(&mut archive, path, "a", "href")?,
+            (None, Some((path, _))) => toc_titles(&mut archive, path, "content", "src")?,
+            (None, None) => HashMap::new(),
+        };
+
+        let mut chapters = Vec::new();
+        for itemref in package.find_all("itemref") {
+            let Some((path, _)) = itemref.attribute("idref").and_then(|id| manifest.get(id)) else {
+                continue;
+            };
+            let mut content = String::new();
+            archive
+                .by_name(path)
+                .map_err(|e| anyhow!("{}: {}", path, e))?
+                .read_to_string(&mut content)?;
+            let title = titles
+                .get(path.as_str())
+                .cloned()
+                .or_else(|| chapter_heading(&content));
+            chapters.push(Chapter { title, content });
+        }
+        Ok(chapters)
+    }
+}
+
+impl EpubProcessor {
+    fn process_chapters(&self, chapters: Vec<Chapter>) -> Result<Document> {
+        let mut document = Document::default();
+        for (chapter, chapter_number) in chapters.into_iter().zip(1..) {
+            let chunks = self
+                .html_processor
+                .process_document(&without_head(&chapter.content))?
+                .chunks;
+            let mut metadata = HashMap::new();
+            metadata.insert("chapter_number".to_string(), chapter_number.to_string());
+            if let Some(title) = chapter.title {
+                metadata.insert("chapter_title".to_string(), title);
+            }
+            document.chunk_metadata.extend(vec![metadata; chunks.len()]);
+            document.chunks.extend(chunks);
+        }
+        Ok(document)
+    }
+}
+
+impl FileProcessor for EpubProcessor {
+    fn process_file(&self, path: impl AsRef<Path>) -> Result<Document> {
+        self.process_chapters(Self::read_chapters(path)?)
+    }
+}
+
+impl BytesProcessor for EpubProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> Result<Document> {
+        self.process_chapters(Self::chapters_from_reader(Cursor::new(bytes))?)
+    }
+}
+
+/// Reads the table of contents in `path` and returns the first title of every chapter, keyed by
+/// the path of the chapter. Entries are the `link` elements with their target in `attribute`:
+/// the `a` elements of a navigation document or the `content` elements of an NCX.
+fn toc_titles<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    path: &str,
+    link: &str,
+    attribute: &str,
+) -> Result<HashMap<String, String>> {
+    let toc = parse_zip_entry(archive, path)?;
+    let directory = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+    let mut titles = HashMap::new();
+    let entries = match link {
+        // The label of an NCX entry is a sibling of its content element.
+        "content" => toc
+            .find_all("navPoint")
+            .into_iter()
+            .flat_map(|point| {
+                let label = point.child("navLabel").map(Element::text);
+                point.child("content").zip(label)
+            })
+            .collect::<Vec<_>>(),
+        _ => toc
+            .find_all(link)
+            .into_iter()
+            .map(|anchor| (anchor, anchor.text()))
+            .collect(),
+    };
+    for (element, label) in entries {
+        let Some(target) = element.attribute(attribute) else {
+            continue;
+        };
+        let target = target.split('#').next().unwrap_or_default();
+        let title = label.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !target.is_empty() && !title.is_empty() {
+            titles
+                .entry(resolve_target(directory, &percent_decode(target)))
+                .or_insert(title);
+        }
+    }
+    Ok(titles)
+}
+
+/// Returns the first heading of a chapter, or its title if it has no heading.
+fn chapter_heading(content: &str) -> Option<String> {
+    let document = Element::parse(content).ok()?;
+    ["h1", "h2", "h3", "title"]
+        .iter()
+        .filter_map(|name| {
+            document
+                .find_all(name)
+                .first()
+                .map(|element| element.text())
+        })
+        .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+        .find(|text| !text.is_empty())
+}
+
+/// Removes the head of a chapter, whose title would otherwise be converted with the body.
+fn without_head(content: &str) -> String {
+    let lowercase = content.to_ascii_lowercase();
+    // `<header>` elements of the body also start with `<head`.
+    let start = lowercase.match_indices("<head").map(|(i, _)| i).find(|i| {
+        lowercase
+            .as_bytes()
+            .get(i + "<head".len())
+            .is_some_and(|c| *c == b'>' || c.is_ascii_whitespace())
+    });
+    match (start, lowercase.find("</head>")) {
+        (Some(start), Some(end)) if start < end => {
+            format!("{}{}", &content[..start], &content[end + "</head>".len()..])
+        }
+        _ => content.to_string(),
+    }
+}
+
+/// Decodes the percent-encoded bytes of a URL path.
+fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = path
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match byte {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn chapter(title: &str, body: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?><html xmlns="http://www.w3.org/1999/xhtml"><head><title>{}</title></head><body>{}</body></html>"#,
+            title, body
+        )
+    }
+
+    /// Writes a book with a navigation document, or with an NCX if `ncx` is set.
+    fn write_book(dir: &Path, ncx: bool) -> std::path::PathBuf {
+        let path = dir.join("book.epub");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        let mut add = |name: &str, content: &str| {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        };
+        add("mimetype", "application/epub+zip");
+        add(
+            "META-INF/container.xml",
+            r#"<container xmlns="urn:oasis:names:tc:opendocument:xmlns:container" version="1.0"><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#,
+        );
+        let (toc_item, spine) = match ncx {
+            true => (
+                r#"<item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>"#,
+                r#"<spine toc="ncx">"#,
+            ),
+            false => (
+                r#"<item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>"#,
+                "<spine>",
+            ),
+        };
+        add(
+            "OEBPS/content.opf",
+            &format!(
+                r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0"><manifest>{}<item id="c2" href="text/chapter%202.xhtml" media-type="application/xhtml+xml"/><item id="c1" href="text/chapter1.xhtml" media-type="application/xhtml+xml"/></manifest>{}<itemref idref="c1"/><itemref idref="c2"/></spine></package>"#,
+                toc_item, spine
+            ),
+        );
+        add(
+            "OEBPS/nav.xhtml",
+            r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><body><nav epub:type="toc"><ol><li><a href="text/chapter1.xhtml#start">The   Contract</a></li></ol></nav></body></html>"#,
+        );
+        add(
+            "OEBPS/toc.ncx",
+            r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/"><navMap><navPoint id="p1"><navLabel><text>Terms</text></navLabel><content src="text/chapter%202.xhtml"/></navPoint></navMap></ncx>"#,
+        );
+        add(
+            "OEBPS/text/chapter1.xhtml",
+            &chapter(
+                "Chapter 1",
+                "<header><h1>The Contract</h1></header><p>The parties agree.</p>",
+            ),
+        );
+        add(
+            "OEBPS/text/chapter 2.xhtml",
+            &chapter(
+                "Chapter 2",
+                "<h2>Payment terms</h2><p>Invoices are due in 30 days.</p>",
+            ),
+        );
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_read_chapters() {
+        let dir = TempDir::new("epub").unwrap();
+        let chapters = EpubProcessor::read_chapters(write_book(dir.path(), false)).unwrap();
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title.as_deref(), Some("The Contract"));
+        // The second chapter is missing from the navigation document and falls back to its
+        // heading.
+        assert_eq!(chapters[1].title.as_deref(), Some("Payment terms"));
+        assert!(chapters[1].content.contains("Invoices are due"));
+
+        let chapters = EpubProcessor::read_chapters(write_book(dir.path(), true)).unwrap();
+        assert_eq!(chapters[1].title.as_deref(), Some("Terms"));
+    }
+
+    #[test]
+    fn test_process_file() {
+        let dir = TempDir::new("epub").unwrap();
+        let path = write_book(dir.path(), false);
+        let document = EpubProcessor::new(1000, 0)
+            .unwrap()
+            .process_file(&path)
+            .unwrap();
+
+        assert_eq!(document.chunks.len(), 2);
+        assert_eq!(document.chunks[0], "# The Contract\n\nThe parties agree.");
+        assert_eq!(document.chunk_metadata[1]["chapter_number"], "2");
+        assert_eq!(document.chunk_metadata[1]["chapter_title"], "Payment terms");
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("chapter%202.xhtml"), "chapter 2.xhtml");
+        assert_eq!(percent_decode("caf%C3%A9%2"), "caf\u{e9}%2");
+    }
+}
@@ -0,0 +1,229 @@
+//! Text extraction for Jupyter notebooks (IPYNB).
+//!
+//! A notebook is a JSON document with a list of cells. Markdown cells are kept as they are, code
+//! cells become fenced code blocks in the language of the kernel and, when enabled, the text
+//! outputs of a code cell follow it in a fenced block of their own. Chunks never span two cells.
+
+use crate::markdown_processor::MarkdownProcessor;
+use crate::processor::{Document, DocumentProcessor};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use text_splitter::ChunkConfigError;
+
+/// A cell of a notebook.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cell {
+    /// `markdown`, `code` or `raw`.
+    pub cell_type: String,
+    pub source: String,
+    /// The text outputs of a code cell: streams, plain text results and errors.
+    pub outputs: Vec<String>,
+}
+
+/// The cells of a notebook and the language of its kernel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notebook {
+    pub language: String,
+    pub cells: Vec<Cell>,
+}
+
+impl Notebook {
+    /// Parses a notebook in the nbformat 4 JSON format.
+    pub fn parse(content: &str) -> Result<Notebook> {
+        let notebook: Value = serde_json::from_str(content)?;
+        let language = [
+            "/metadata/kernelspec/language",
+            "/metadata/language_info/name",
+        ]
+        .iter()
+        .find_map(|pointer| notebook.pointer(pointer).and_then(Value::as_str))
+        .unwrap_or("python")
+        .to_string();
+        let cells = notebook
+            .get("cells")
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("the notebook has no cells"))?
+            .iter()
+            .map(|cell| Cell {
+                cell_type: cell
+                    .get("cell_type")
+                    .and_then(Value::as_str)
+                    .unwrap_or("raw")
+                    .to_string(),
+                source: cell.get("source").map(multiline_text).unwrap_or_default(),
+                outputs: cell
+                    .get("outputs")
+                    .and_then(Value::as_array)
+                    .map(|outputs| outputs.iter().filter_map(output_text).collect())
+                    .unwrap_or_default(),
+            })
+            .collect();
+        Ok(Notebook { language, cells })
+    }
+}
+
+/// A struct for processing Jupyter notebooks.
+///
+/// Every chunk carries the `cell_index` of the cell it comes from, starting at 0, and its
+/// `cell_type` in [Document::chunk_metadata].
+pub struct IpynbProcessor {
+    markdown_processor: MarkdownProcessor,
+    outputs: bool,
+}
+
+impl IpynbProcessor {
+    pub fn new(chunk_size: usize, overlap: usize) -> Result<IpynbProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        Ok(IpynbProcessor {
+            markdown_processor,
+            outputs: false,
+        })
+    }
+
+    /// Includes the text outputs of code cells after their source. Defaults to false.
+    pub fn with_outputs(mut self, outputs: bool) -> Self {
+        self.outputs = outputs;
+        self
+    }
+
+    /// Returns the Markdown of a cell, which is empty for a cell without text.
+    fn cell_to_markdown(&self, cell: &Cell, language: &str) -> String {
+        let source = cell.source.trim_end();
+        if cell.cell_type != "code" {
+            return source.to_string();
+        }
+        let mut blocks = Vec::new();
+        if !source.trim().is_empty() {
+            blocks.push(format!("```{}\n{}\n```", language, source));
+        }
+        if self.outputs {
+            for output in &cell.outputs {
+                if !output.trim().is_empty() {
+                    blocks.push(format!("```output\n{}\n```", output.trim_end()));
+                }
+            }
+        }
+        blocks.join("\n\n")
+    }
+}
+
+impl DocumentProcessor for IpynbProcessor {
+    fn process_document(&self, content: &str) -> Result<Document> {
+        let notebook = Notebook::parse(content)?;
+        let mut document = Document::default();
+        for (index, cell) in notebook.cells.iter().enumerate() {
+            let markdown = self.cell_to_markdown(cell, &notebook.language);
+            if markdown.trim().is_empty() {
+                continue;
+            }
+            for chunk in self.markdown_processor.process_document(&markdown)?.chunks {
+                document.chunks.push(chunk);
+                document.chunk_metadata.push(HashMap::from([
+                    ("cell_index".to_string(), index.to_string()),
+                    ("cell_type".to_string(), cell.cell_type.clone()),
+                ]));
+            }
+        }
+        Ok(document)
+    }
+}
+
+/// Returns the text of a field that is either a string or a list of lines.
+fn multiline_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Returns the text of an output, if it has any.
+fn output_text(output: &Value) -> Option<String> {
+    match output.get("output_type")?.as_str()? {
+        "stream" => output.get("text").map(multiline_text),
+        "execute_result" | "display_data" => {
+            output.pointer("/data/text~1plain").map(multiline_text)
+        }
+        "error" => Some(format!(
+            "{}: {}",
+            output.get("ename")?.as_str()?,
+            output.get("evalue")?.as_str()?
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::FileProcessor;
+
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {"cell_type": "markdown", "metadata": {}, "source": ["# Analysis\n", "\n", "Load the data."]},
+  {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": "import pandas as pd\ndf = pd.read_csv('data.csv')\ndf.shape",
+   "outputs": [
+    {"output_type": "stream", "name": "stdout", "text": ["loading\n"]},
+    {"output_type": "execute_result", "execution_count": 1, "metadata": {}, "data": {"text/plain": ["(10, 3)"], "image/png": "iVBOR"}}
+   ]},
+  {"cell_type": "code", "execution_count": null, "metadata": {}, "source": [], "outputs": []},
+  {"cell_type": "code", "execution_count": 2, "metadata": {}, "source": "1 / 0",
+   "outputs": [{"output_type": "error", "ename": "ZeroDivisionError", "evalue": "division by zero", "traceback": []}]}
+ ],
+ "metadata": {"kernelspec": {"display_name": "Python 3", "language": "python", "name": "python3"}},
+ "nbformat": 4,
+ "nbformat_minor": 5
+}"##;
+
+    #[test]
+    fn test_parse() {
+        let notebook = Notebook::parse(NOTEBOOK).unwrap();
+        assert_eq!(notebook.language, "python");
+        assert_eq!(notebook.cells.len(), 4);
+        assert_eq!(notebook.cells[0].source, "# Analysis\n\nLoad the data.");
+        assert_eq!(notebook.cells[1].outputs, vec!["loading\n", "(10, 3)"]);
+        assert_eq!(
+            notebook.cells[3].outputs,
+            vec!["ZeroDivisionError: division by zero"]
+        );
+
+        assert!(Notebook::parse("{}").is_err());
+    }
+
+    #[test]
+    fn test_process_document() {
+        let processor = IpynbProcessor::new(1000, 0).unwrap();
+        let document = processor.process_document(NOTEBOOK).unwrap();
+        assert_eq!(document.chunks.len(), 3);
+        assert_eq!(
+            document.chunks[1],
+            "```python\nimport pandas as pd\ndf = pd.read_csv('data.csv')\ndf.shape\n```"
+        );
+        let cells = document
+            .chunk_metadata
+            .iter()
+            .map(|metadata| {
+                (
+                    metadata["cell_index"].as_str(),
+                    metadata["cell_type"].as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(cells, vec![("0", "markdown"), ("1", "code"), ("3", "code")]);
+    }
+
+    #[test]
+    fn test_process_file_with_outputs() {
+        let dir = tempdir::TempDir::new("ipynb").unwrap();
+        let path = dir.path().join("analysis.ipynb");
+        std::fs::write(&path, NOTEBOOK).unwrap();
+        let document = IpynbProcessor::new(1000, 0)
+            .unwrap()
+            .with_outputs(true)
+            .process_file(&path)
+            .unwrap();
+        assert!(document.chunks[1].ends_with("```output\nloading\n```\n\n```output\n(10, 3)\n```"));
+        assert!(document.chunks[2].contains("ZeroDivisionError: division by zero"));
+    }
+}
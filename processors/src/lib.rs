@@ -15,3 +15,39 @@ pub mod html_processor;
 
 /// This module contains the file processor for DOCX files.
 pub mod docx_processor;
+
+/// This module contains the file processor for PPTX files.
+pub mod pptx_processor;
+
+/// This module contains the file processor for CSV, TSV and XLSX files.
+pub mod tabular_processor;
+
+/// This module contains the file processor for EPUB files.
+pub mod epub_processor;
+
+/// This module contains the file processor for ODT files.
+pub mod odt_processor;
+
+/// This module contains the file processor for RTF files.
+pub mod rtf_processor;
+
+/// This module contains the file processor for EML and MBOX files.
+pub mod email_processor;
+
+/// This module contains the file processor for JSON, JSONL and XML files.
+pub mod structured_processor;
+
+/// This module contains the file processor for Jupyter notebooks.
+pub mod ipynb_processor;
+
+/// This module contains the file processor for LaTeX files.
+pub mod tex_processor;
+
+/// This module contains the file processor for source code files.
+pub mod code_processor;
+
+/// This module contains the registry that detects the format of a file.
+pub mod registry;
+
+/// This module contains a minimal XML tree for the processors of XML based formats.
+pub(crate) mod xml;
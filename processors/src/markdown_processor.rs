@@ -21,6 +21,25 @@ impl DocumentProcessor for MarkdownProcessor {
             .chunks(content)
             .map(|x| x.to_string())
             .collect();
-        Ok(Document { chunks })
+        Ok(Document::new(chunks))
     }
 }
+
+/// Renders rows of cells as a Markdown table with the first row as the header. Rows are padded
+/// to the longest row, and pipes in cells are escaped. Returns None for a table without cells.
+pub(crate) fn markdown_table(rows: Vec<Vec<String>>) -> Option<String> {
+    let columns = rows.iter().map(Vec::len).max().filter(|&n| n > 0)?;
+    let mut lines = Vec::with_capacity(rows.len() + 1);
+    for (i, mut row) in rows.into_iter().enumerate() {
+        row.resize(columns, String::new());
+        let cells = row
+            .iter()
+            .map(|cell| cell.replace('|', "\\|"))
+            .collect::<Vec<_>>();
+        lines.push(format!("| {} |", cells.join(" | ")));
+        if i == 0 {
+            lines.push(format!("|{}", " --- |".repeat(columns)));
+        }
+    }
+    Some(lines.join("\n"))
+}
@@ -0,0 +1,243 @@
+//! Text extraction for OpenDocument text (ODT) files.
+//!
+//! An ODT file is a zip package whose `content.xml` holds the body of the document. Headings,
+//! paragraphs, nested lists and tables of the body are converted to Markdown for the
+//! [MarkdownProcessor]. Notes, annotations and the generated table of contents are left out.
+
+use crate::markdown_processor::{markdown_table, MarkdownProcessor};
+use crate::processor::{BytesProcessor, Document, DocumentProcessor, FileProcessor};
+use crate::xml::{parse_zip_entry, Element, Node};
+use anyhow::{anyhow, Result};
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use text_splitter::ChunkConfigError;
+use zip::ZipArchive;
+
+/// A struct for processing ODT files.
+pub struct OdtProcessor {
+    markdown_processor: MarkdownProcessor,
+}
+
+impl OdtProcessor {
+    pub fn new(chunk_size: usize, overlap: usize) -> Result<OdtProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        Ok(OdtProcessor { markdown_processor })
+    }
+
+    /// Returns the body of a document as Markdown.
+    pub fn to_markdown(path: impl AsRef<Path>) -> Result<String> {
+        Self::markdown_from_reader(File::open(path)?)
+    }
+
+    /// Returns the body of a document read from a reader as Markdown.
+    pub fn markdown_from_reader(reader: impl Read + Seek) -> Result<String> {
+        let mut archive = ZipArchive::new(reader)?;
+        let content = parse_zip_entry(&mut archive, "content.xml")?;
+        let body = content
+            .child("body")
+            .and_then(|body| body.child("text"))
+            .ok_or_else(|| anyhow!("the document has no text body"))?;
+        let mut blocks = Vec::new();
+        body_to_markdown(body, &mut blocks);
+        Ok(blocks.join("\n\n"))
+    }
+}
+
+impl FileProcessor for OdtProcessor {
+    fn process_file(&self, path: impl AsRef<Path>) -> Result<Document> {
+        let markdown = Self::to_markdown(path)?;
+        self.markdown_processor.process_document(&markdown)
+    }
+}
+
+impl BytesProcessor for OdtProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> Result<Document> {
+        let markdown = Self::markdown_from_reader(Cursor::new(bytes))?;
+        self.markdown_processor.process_document(&markdown)
+    }
+}
+
+/// Appends the Markdown blocks of the children of a body or section in document order.
+fn body_to_markdown(element: &Element, blocks: &mut Vec<String>) {
+    for child in element.elements() {
+        match child.name.as_str() {
+            "h" => {
+                let level = child
+                    .attribute("text:outline-level")
+                    .and_then(|level| level.parse::<usize>().ok())
+                    .unwrap_or(1)
+                    .clamp(1, 6);
+                let text = inline_text(child);
+                if !text.is_empty() {
+                    blocks.push(format!("{} {}", "#".repeat(level), text));
+                }
+            }
+            "p" => {
+                let text = inline_text(child);
+                if !text.is_empty() {
+                    blocks.push(text);
+                }
+            }
+            "list" => {
+                let mut lines = Vec::new();
+                list_to_markdown(child, 0, &mut lines);
+                if !lines.is_empty() {
+                    blocks.push(lines.join("\n"));
+                }
+            }
+            "table" => {
+                if let Some(table) = table_to_markdown(child) {
+                    blocks.push(table);
+                }
+            }
+            "table-of-content" | "tracked-changes" => {}
+            _ => body_to_markdown(child, blocks),
+        }
+    }
+}
+
+fn list_to_markdown(list: &Element, level: usize, lines: &mut Vec<String>) {
+    for item in list.elements() {
+        for child in item.elements() {
+            match child.name.as_str() {
+                "list" => list_to_markdown(child, level + 1, lines),
+                _ => {
+                    let text = inline_text(child);
+                    if !text.is_empty() {
+                        lines.push(format!("{}- {}", "  ".repeat(level), text));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn table_to_markdown(table: &Element) -> Option<String> {
+    let rows = table
+        .find_all("table-row")
+        .into_iter()
+        .map(|row| {
+            row.elements()
+                .filter(|cell| cell.name == "table-cell" || cell.name == "covered-table-cell")
+                .map(|cell| {
+                    cell.elements()
+                        .map(inline_text)
+                        .filter(|text| !text.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    markdown_table(rows)
+}
+
+/// Returns the text of a paragraph or heading. Runs of white space collapse into one space, as
+/// in ODF, while `text:s`, `text:tab` and `text:line-break` keep their spacing.
+fn inline_text(element: &Element) -> String {
+    fn collect(element: &Element, text: &mut String) {
+        for node in &element.children {
+            match node {
+                Node::Text(t) => {
+                    for c in t.chars() {
+                        match c.is_whitespace() {
+                            true if text.ends_with(' ') => {}
+                            true => text.push(' '),
+                            false => text.push(c),
+                        }
+                    }
+                }
+                Node::Element(child) => match child.name.as_str() {
+                    "s" => {
+                        let count = child
+                            .attribute("text:c")
+                            .and_then(|count| count.parse().ok())
+                            .unwrap_or(1);
+                        text.push_str(&"\u{a0}".repeat(count));
+                    }
+                    "tab" => text.push('\t'),
+                    "line-break" => text.push('\n'),
+                    "note" | "annotation" | "annotation-end" => {}
+                    _ => collect(child, text),
+                },
+            }
+        }
+    }
+    let mut text = String::new();
+    collect(element, &mut text);
+    text.replace('\u{a0}', " ").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    const CONTENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0">
+  <office:body>
+    <office:text>
+      <text:sequence-decls/>
+      <text:h text:outline-level="1">Lease   agreement</text:h>
+      <text:p>The <text:span>tenant</text:span> pays<text:s text:c="2"/>rent.<text:note><text:note-body><text:p>A note.</text:p></text:note-body></text:note></text:p>
+      <text:section>
+        <text:h text:outline-level="2">Obligations</text:h>
+        <text:list>
+          <text:list-item><text:p>Keep the flat clean</text:p>
+            <text:list><text:list-item><text:p>Weekly</text:p></text:list-item></text:list>
+          </text:list-item>
+          <text:list-item><text:p>Pay on time</text:p></text:list-item>
+        </text:list>
+      </text:section>
+      <table:table>
+        <table:table-header-rows><table:table-row><table:table-cell><text:p>Item</text:p></table:table-cell><table:table-cell><text:p>Cost</text:p></table:table-cell></table:table-row></table:table-header-rows>
+        <table:table-row><table:table-cell><text:p>Rent</text:p></table:table-cell><table:table-cell><text:p>900</text:p></table:table-cell></table:table-row>
+      </table:table>
+      <text:p/>
+    </office:text>
+  </office:body>
+</office:document-content>"#;
+
+    fn write_document(dir: &Path) -> std::path::PathBuf {
+        let path = dir.join("lease.odt");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        zip.start_file("mimetype", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(b"application/vnd.oasis.opendocument.text")
+            .unwrap();
+        zip.start_file("content.xml", SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(CONTENT.as_bytes()).unwrap();
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let dir = TempDir::new("odt").unwrap();
+        let markdown = OdtProcessor::to_markdown(write_document(dir.path())).unwrap();
+        assert_eq!(
+            markdown,
+            "# Lease agreement\n\n\
+             The tenant pays  rent.\n\n\
+             ## Obligations\n\n\
+             - Keep the flat clean\n  - Weekly\n- Pay on time\n\n\
+             | Item | Cost |\n| --- | --- |\n| Rent | 900 |"
+        );
+    }
+
+    #[test]
+    fn test_process_file() {
+        let dir = TempDir::new("odt").unwrap();
+        let processor = OdtProcessor::new(1000, 0).unwrap();
+        let document = processor.process_file(write_document(dir.path())).unwrap();
+        assert_eq!(document.chunks.len(), 1);
+        assert!(document.chunks[0].contains("Pay on time"));
+
+        assert!(processor.process_file("../test_files/test.docx").is_err());
+    }
+}
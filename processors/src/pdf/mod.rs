@@ -1,3 +1,4 @@
 pub mod tesseract;
 
+pub mod pdf_images;
 pub mod pdf_processor;
@@ -0,0 +1,387 @@
+use std::path::Path;
+
+use anyhow::Error;
+use image::{DynamicImage, GrayImage, ImageFormat, RgbImage};
+use pdf_extract::{Document, Object, Stream};
+
+/// An image embedded in a PDF, together with the page it appears on.
+pub struct PdfPageImage {
+    /// The 1-based number of the page the image is drawn on.
+    pub page_number: u32,
+    /// The position of the image among the images of its page.
+    pub image_index: usize,
+    pub image: DynamicImage,
+}
+
+/// Extracts the raster images embedded in a PDF.
+///
+/// JPEG (`DCTDecode`) images and uncompressed or Flate/LZW compressed 8-bit Gray, RGB and CMYK
+/// images are supported, including ICC-based ones, as well as Indexed images of 1 to 8 bits per
+/// sample; images in other encodings are skipped. Images whose width or height is
+/// below `min_size` pixels, such as icons and bullets, are skipped as well.
+pub fn extract_images_from_pdf<T: AsRef<Path>>(
+    file_path: &T,
+    min_size: u32,
+) -> Result<Vec<PdfPageImage>, Error> {
+    images_of_document(Document::load(file_path)?, min_size)
+}
+
+/// Same as [extract_images_from_pdf], for a PDF held in memory.
+pub fn extract_images_from_pdf_bytes(
+    bytes: &[u8],
+    min_size: u32,
+) -> Result<Vec<PdfPageImage>, Error> {
+    images_of_document(Document::load_mem(bytes)?, min_size)
+}
+
+fn images_of_document(document: Document, min_size: u32) -> Result<Vec<PdfPageImage>, Error> {
+    let mut images = Vec::new();
+    for (page_number, page_id) in document.get_pages() {
+        // Pages without an XObject resource dictionary have no images.
+        let Ok(page_images) = document.get_page_images(page_id) else {
+            continue;
+        };
+        let page_images = page_images
+            .iter()
+            .filter(|image| image.width >= min_size as i64 && image.height >= min_size as i64)
+            .filter_map(|image| {
+                let stream = document.get_object(image.id).ok()?.as_stream().ok()?;
+                decode_image(&document, stream)
+            });
+        images.extend(
+            page_images
+                .enumerate()
+                .map(|(image_index, image)| PdfPageImage {
+                    page_number,
+                    image_index,
+                    image,
+                }),
+        );
+    }
+    Ok(images)
+}
+
+/// A color space whose samples can be converted to an image.
+#[derive(Debug, PartialEq)]
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    /// Samples are indices into a palette of colors in the base color space.
+    Indexed {
+        base: Box<ColorSpace>,
+        palette: Vec<u8>,
+    },
+}
+
+impl ColorSpace {
+    fn parse(document: &Document, object: &Object) -> Option<ColorSpace> {
+        match resolve(document, object)? {
+            Object::Name(name) => Self::from_family(name),
+            Object::Array(array) => match array.first()?.as_name().ok()? {
+                b"ICCBased" => {
+                    let profile = resolve(document, array.get(1)?)?.as_stream().ok()?;
+                    match profile.dict.get(b"N").ok()?.as_i64().ok()? {
+                        1 => Some(ColorSpace::Gray),
+                        3 => Some(ColorSpace::Rgb),
+                        4 => Some(ColorSpace::Cmyk),
+                        _ => None,
+                    }
+                }
+                b"Indexed" | b"I" => {
+                    let base = ColorSpace::parse(document, array.get(1)?)?;
+                    if matches!(base, ColorSpace::Indexed { .. }) {
+                        return None;
+                    }
+                    let palette = match resolve(document, array.get(3)?)? {
+                        Object::String(bytes, _) => bytes.clone(),
+                        Object::Stream(stream) => stream.get_plain_content().ok()?,
+                        _ => return None,
+                    };
+                    Some(ColorSpace::Indexed {
+                        base: Box::new(base),
+                        palette,
+                    })
+                }
+                // Calibrated color spaces are arrays of their family and its parameters.
+                family => Self::from_family(family),
+            },
+            _ => None,
+        }
+    }
+
+    fn from_family(name: &[u8]) -> Option<ColorSpace> {
+        match name {
+            b"DeviceGray" | b"CalGray" | b"G" => Some(ColorSpace::Gray),
+            b"DeviceRGB" | b"CalRGB" | b"RGB" => Some(ColorSpace::Rgb),
+            b"DeviceCMYK" | b"CMYK" => Some(ColorSpace::Cmyk),
+            _ => None,
+        }
+    }
+
+    fn components(&self) -> usize {
+        match self {
+            ColorSpace::Gray | ColorSpace::Indexed { .. } => 1,
+            ColorSpace::Rgb => 3,
+            ColorSpace::Cmyk => 4,
+        }
+    }
+}
+
+fn resolve<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Object> {
+    match object {
+        Object::Reference(id) => document.get_object(*id).ok(),
+        object => Some(object),
+    }
+}
+
+fn decode_image(document: &Document, stream: &Stream) -> Option<DynamicImage> {
+    let dict = &stream.dict;
+    let filters = stream.filters().unwrap_or_default();
+    if filters.last() == Some(&&b"DCTDecode"[..]) {
+        return match filters.len() {
+            1 => image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg).ok(),
+            _ => None,
+        };
+    }
+
+    let width = dict.get(b"Width").ok()?.as_i64().ok()? as u32;
+    let height = dict.get(b"Height").ok()?.as_i64().ok()? as u32;
+    let bits_per_component = dict
+        .get(b"BitsPerComponent")
+        .and_then(Object::as_i64)
+        .unwrap_or(8);
+    let color_space = ColorSpace::parse(document, dict.get(b"ColorSpace").ok()?)?;
+
+    let samples = stream.get_plain_content().ok()?;
+    let (color_space, samples) = match color_space {
+        ColorSpace::Indexed { base, palette } => {
+            if !matches!(bits_per_component, 1 | 2 | 4 | 8) {
+                return None;
+            }
+            let n = base.components();
+            let colors = unpack_samples(&samples, width, bits_per_component as u32)
+                .into_iter()
+                .flat_map(|index| {
+                    let start = index as usize * n;
+                    // Indices beyond the palette are clamped to its last color.
+                    let start = start.min(palette.len().saturating_sub(n));
+                    palette
+                        .get(start..start + n)
+                        .unwrap_or(&[0; 4][..n])
+                        .to_vec()
+                })
+                .collect();
+            (*base, colors)
+        }
+        color_space if bits_per_component == 8 => (color_space, samples),
+        _ => return None,
+    };
+    match color_space {
+        ColorSpace::Gray => {
+            GrayImage::from_raw(width, height, samples).map(DynamicImage::ImageLuma8)
+        }
+        ColorSpace::Rgb => RgbImage::from_raw(width, height, samples).map(DynamicImage::ImageRgb8),
+        ColorSpace::Cmyk => {
+            let rgb = samples
+                .chunks_exact(4)
+                .flat_map(|cmyk| {
+                    let k = 255 - cmyk[3] as u32;
+                    cmyk[..3]
+                        .iter()
+                        .map(move |&c| ((255 - c as u32) * k / 255) as u8)
+                })
+                .collect();
+            RgbImage::from_raw(width, height, rgb).map(DynamicImage::ImageRgb8)
+        }
+        ColorSpace::Indexed { .. } => None,
+    }
+}
+
+/// Splits rows of samples of fewer than 8 bits into one byte per sample. Every row starts on a
+/// byte boundary.
+fn unpack_samples(samples: &[u8], width: u32, bits_per_component: u32) -> Vec<u8> {
+    if bits_per_component == 8 {
+        return samples.to_vec();
+    }
+    let row_bytes = (width * bits_per_component).div_ceil(8) as usize;
+    let mask = (1u8 << bits_per_component) - 1;
+    samples
+        .chunks(row_bytes.max(1))
+        .flat_map(|row| {
+            (0..width).filter_map(move |x| {
+                let bit = x * bits_per_component;
+                let byte = row.get((bit / 8) as usize)?;
+                Some((byte >> (8 - bits_per_component - bit % 8)) & mask)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pdf_extract::{Dictionary, Object};
+    use std::io::Cursor;
+    use tempdir::TempDir;
+
+    fn image_stream(dict: Vec<(&str, Object)>, content: Vec<u8>) -> Stream {
+        let mut dictionary = Dictionary::new();
+        dictionary.set("Type", Object::Name(b"XObject".to_vec()));
+        dictionary.set("Subtype", Object::Name(b"Image".to_vec()));
+        for (key, value) in dict {
+            dictionary.set(key, value);
+        }
+        Stream::new(dictionary, content)
+    }
+
+    #[test]
+    fn test_extract_images_from_pdf() {
+        let mut jpeg = Vec::new();
+        DynamicImage::new_rgb8(40, 30)
+            .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+            .unwrap();
+
+        let mut document = Document::with_version("1.5");
+        let jpeg_id = document.add_object(image_stream(
+            vec![
+                ("Width", Object::Integer(40)),
+                ("Height", Object::Integer(30)),
+                ("ColorSpace", Object::Name(b"DeviceRGB".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+                ("Filter", Object::Name(b"DCTDecode".to_vec())),
+            ],
+            jpeg,
+        ));
+        let mut raw = image_stream(
+            vec![
+                ("Width", Object::Integer(64)),
+                ("Height", Object::Integer(48)),
+                ("ColorSpace", Object::Name(b"DeviceGray".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+            ],
+            vec![128; 64 * 48],
+        );
+        raw.compress().unwrap();
+        let raw_id = document.add_object(raw);
+        let icon_id = document.add_object(image_stream(
+            vec![
+                ("Width", Object::Integer(4)),
+                ("Height", Object::Integer(4)),
+                ("ColorSpace", Object::Name(b"DeviceGray".to_vec())),
+                ("BitsPerComponent", Object::Integer(8)),
+            ],
+            vec![0; 16],
+        ));
+
+        let pages_id = document.new_object_id();
+        let page = |document: &mut Document, xobjects: Vec<(&str, Object)>| {
+            let mut xobject = Dictionary::new();
+            for (name, id) in xobjects {
+                xobject.set(name, id);
+            }
+            let mut resources = Dictionary::new();
+            resources.set("XObject", Object::Dictionary(xobject));
+            let mut page = Dictionary::new();
+            page.set("Type", Object::Name(b"Page".to_vec()));
+            page.set("Parent", Object::Reference(pages_id));
+            page.set("Resources", Object::Dictionary(resources));
+            document.add_object(Object::Dictionary(page))
+        };
+        let first = page(&mut document, vec![("Im1", Object::Reference(jpeg_id))]);
+        let second = page(
+            &mut document,
+            vec![
+                ("Im1", Object::Reference(icon_id)),
+                ("Im2", Object::Reference(raw_id)),
+            ],
+        );
+
+        let mut pages = Dictionary::new();
+        pages.set("Type", Object::Name(b"Pages".to_vec()));
+        pages.set(
+            "Kids",
+            Object::Array(vec![Object::Reference(first), Object::Reference(second)]),
+        );
+        pages.set("Count", Object::Integer(2));
+        document.objects.insert(pages_id, Object::Dictionary(pages));
+        let mut catalog = Dictionary::new();
+        catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+        catalog.set("Pages", Object::Reference(pages_id));
+        let catalog_id = document.add_object(Object::Dictionary(catalog));
+        document.trailer.set("Root", Object::Reference(catalog_id));
+
+        let temp_dir = TempDir::new("pdf_images").unwrap();
+        let pdf_file = temp_dir.path().join("images.pdf");
+        document.save(&pdf_file).unwrap();
+
+        let images = extract_images_from_pdf(&pdf_file, 16).unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].page_number, 1);
+        assert_eq!(
+            (images[0].image.width(), images[0].image.height()),
+            (40, 30)
+        );
+        assert_eq!(images[1].page_number, 2);
+        assert_eq!(images[1].image_index, 0);
+        assert_eq!(
+            (images[1].image.width(), images[1].image.height()),
+            (64, 48)
+        );
+
+        let from_bytes =
+            extract_images_from_pdf_bytes(&std::fs::read(&pdf_file).unwrap(), 16).unwrap();
+        assert_eq!(from_bytes.len(), 2);
+        assert_eq!(from_bytes[1].page_number, 2);
+    }
+
+    #[test]
+    fn test_decode_image_color_spaces() {
+        let mut document = Document::with_version("1.5");
+        let mut profile = Dictionary::new();
+        profile.set("N", Object::Integer(3));
+        let profile_id = document.add_object(Stream::new(profile, Vec::new()));
+
+        let icc = image_stream(
+            vec![
+                ("Width", Object::Integer(2)),
+                ("Height", Object::Integer(1)),
+                (
+                    "ColorSpace",
+                    Object::Array(vec![
+                        Object::Name(b"ICCBased".to_vec()),
+                        Object::Reference(profile_id),
+                    ]),
+                ),
+                ("BitsPerComponent", Object::Integer(8)),
+            ],
+            vec![255, 0, 0, 0, 0, 255],
+        );
+        let image = decode_image(&document, &icc).unwrap().into_rgb8();
+        assert_eq!(image.into_raw(), vec![255, 0, 0, 0, 0, 255]);
+
+        // A 1-bit image of 3 x 2 pixels indexing a palette of two RGB colors.
+        let indexed = image_stream(
+            vec![
+                ("Width", Object::Integer(3)),
+                ("Height", Object::Integer(2)),
+                (
+                    "ColorSpace",
+                    Object::Array(vec![
+                        Object::Name(b"Indexed".to_vec()),
+                        Object::Name(b"DeviceRGB".to_vec()),
+                        Object::Integer(1),
+                        Object::string_literal(vec![10, 20, 30, 200, 210, 220]),
+                    ]),
+                ),
+                ("BitsPerComponent", Object::Integer(1)),
+            ],
+            vec![0b1010_0000, 0b0100_0000],
+        );
+        let image = decode_image(&document, &indexed).unwrap().into_rgb8();
+        assert_eq!(
+            image.pixels().map(|pixel| pixel.0[0]).collect::<Vec<_>>(),
+            vec![200, 10, 200, 10, 200, 10]
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use crate::markdown_processor::MarkdownProcessor;
 use crate::pdf::tesseract::input::{Args, Image};
-use crate::processor::{Document, DocumentProcessor, FileProcessor};
+use crate::processor::{BytesProcessor, Document, DocumentProcessor, FileProcessor};
 use anyhow::Error;
 use image::DynamicImage;
 use pdf2image::{Pages, RenderOptionsBuilder, PDF};
@@ -40,6 +40,45 @@ impl PdfProcessor {
     }
 }
 
+impl PdfProcessor {
+    /// Extracts and chunks the text of each page separately. The returned documents are in page
+    /// order, so the document at index `i` holds the chunks of page `i + 1`. With OCR, the text of
+    /// every rendered page is recognized instead of read from its text layer.
+    pub fn process_pages(&self, path: impl AsRef<Path>) -> anyhow::Result<Vec<Document>> {
+        let pages = if self.ocr_config.use_ocr {
+            let tesseract_path = self.ocr_config.tesseract_path.as_deref();
+            pdf_pages_with_ocr(PDF::from_file(&path)?, tesseract_path)?
+        } else {
+            match self.backend {
+                PdfBackend::LoPdf => pdf_extract::extract_text_by_pages(path.as_ref())
+                    .map_err(|e| anyhow::anyhow!(e))?,
+            }
+        };
+        self.process_page_texts(&pages)
+    }
+
+    /// Same as [PdfProcessor::process_pages], for a PDF held in memory.
+    pub fn process_pages_from_bytes(&self, bytes: &[u8]) -> anyhow::Result<Vec<Document>> {
+        let pages = if self.ocr_config.use_ocr {
+            let tesseract_path = self.ocr_config.tesseract_path.as_deref();
+            pdf_pages_with_ocr(PDF::from_bytes(bytes.to_vec())?, tesseract_path)?
+        } else {
+            match self.backend {
+                PdfBackend::LoPdf => pdf_extract::extract_text_from_mem_by_pages(bytes)
+                    .map_err(|e| anyhow::anyhow!(e))?,
+            }
+        };
+        self.process_page_texts(&pages)
+    }
+
+    fn process_page_texts(&self, pages: &[String]) -> anyhow::Result<Vec<Document>> {
+        pages
+            .iter()
+            .map(|page| self.markdown_processor.process_document(page))
+            .collect()
+    }
+}
+
 impl FileProcessor for PdfProcessor {
     fn process_file(&self, path: impl AsRef<Path>) -> anyhow::Result<Document> {
         let content = if self.ocr_config.use_ocr {
@@ -57,8 +96,24 @@ impl FileProcessor for PdfProcessor {
     }
 }
 
-fn get_images_from_pdf<T: AsRef<Path>>(file_path: &T) -> Result<Vec<DynamicImage>, Error> {
-    let pdf = PDF::from_file(file_path)?;
+impl BytesProcessor for PdfProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> anyhow::Result<Document> {
+        let content = if self.ocr_config.use_ocr {
+            let tesseract_path = self.ocr_config.tesseract_path.as_deref();
+            pdf_to_text_with_ocr(PDF::from_bytes(bytes.to_vec())?, tesseract_path)?
+        } else {
+            match self.backend {
+                PdfBackend::LoPdf => {
+                    pdf_extract::extract_text_from_mem(bytes).map_err(|e| anyhow::anyhow!(e))?
+                }
+            }
+        };
+
+        self.markdown_processor.process_document(&content)
+    }
+}
+
+fn get_images_from_pdf(pdf: PDF) -> Result<Vec<DynamicImage>, Error> {
     let page_count = pdf.page_count();
     let pages = pdf.render(
         Pages::Range(1..=page_count),
@@ -77,21 +132,31 @@ fn extract_text_with_ocr<T: AsRef<Path>>(
     file_path: &T,
     tesseract_path: Option<&str>,
 ) -> Result<String, Error> {
-    let images = get_images_from_pdf(file_path)?;
-    let texts: Result<Vec<String>, Error> = images
+    pdf_to_text_with_ocr(PDF::from_file(file_path)?, tesseract_path)
+}
+
+fn pdf_to_text_with_ocr(pdf: PDF, tesseract_path: Option<&str>) -> Result<String, Error> {
+    let pages = pdf_pages_with_ocr(pdf, tesseract_path)?;
+    Ok(pages
+        .into_iter()
+        .filter(|page| !page.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n"))
+}
+
+/// Recognizes the text of every page, without empty lines.
+fn pdf_pages_with_ocr(pdf: PDF, tesseract_path: Option<&str>) -> Result<Vec<String>, Error> {
+    get_images_from_pdf(pdf)?
         .iter()
-        .map(|image| extract_text_from_image(image, &Args::default().with_path(tesseract_path)))
-        .collect();
-
-    // Join the texts and clean up empty lines
-    let text = texts?.join("\n");
-    let cleaned_text = text
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .collect::<Vec<&str>>()
-        .join("\n");
-
-    Ok(cleaned_text)
+        .map(|image| {
+            let text = extract_text_from_image(image, &Args::default().with_path(tesseract_path))?;
+            Ok(text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<&str>>()
+                .join("\n"))
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -122,6 +187,37 @@ mod tests {
         assert_eq!(text.chunks.len(), 4271);
     }
 
+    #[test]
+    fn test_process_pages() {
+        let processor = PdfProcessor::new(
+            128,
+            0,
+            OcrConfig {
+                use_ocr: false,
+                tesseract_path: None,
+            },
+            PdfBackend::LoPdf,
+        )
+        .unwrap();
+
+        let pdf_file = "../test_files/test.pdf";
+        let pages = processor.process_pages(pdf_file).unwrap();
+        let page_count = pdf_extract::Document::load(pdf_file)
+            .unwrap()
+            .get_pages()
+            .len();
+        assert_eq!(pages.len(), page_count);
+        assert!(pages.iter().any(|page| !page.chunks.is_empty()));
+
+        let from_bytes = processor
+            .process_pages_from_bytes(&std::fs::read(pdf_file).unwrap())
+            .unwrap();
+        let chunks = |pages: &[Document]| -> Vec<Vec<String>> {
+            pages.iter().map(|page| page.chunks.clone()).collect()
+        };
+        assert_eq!(chunks(&from_bytes), chunks(&pages));
+    }
+
     #[test]
     fn test_extract_text_with_ocr() {
         let pdf_file = "../test_files/test.pdf";
@@ -0,0 +1,409 @@
+//! Text extraction for PowerPoint (PPTX) presentations.
+//!
+//! A PPTX file is a zip package of XML parts. Slides are read in the order of the slide list of
+//! `ppt/presentation.xml` and converted to Markdown one by one: the title becomes a heading, body
+//! placeholders become lists, text boxes become paragraphs, tables become Markdown tables and the
+//! speaker notes follow under a "Notes" heading. Chunks never span two slides.
+
+use crate::markdown_processor::{markdown_table, MarkdownProcessor};
+use crate::processor::{BytesProcessor, Document, DocumentProcessor, FileProcessor};
+use crate::xml::{parse_zip_entry, read_relationships, Element};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use text_splitter::ChunkConfigError;
+use zip::ZipArchive;
+
+const SLIDE_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide";
+const NOTES_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/notesSlide";
+
+/// A struct for processing PPTX files.
+///
+/// Every chunk carries the `slide_number` it comes from, starting at 1, in
+/// [Document::chunk_metadata].
+pub struct PptxProcessor {
+    markdown_processor: MarkdownProcessor,
+}
+
+impl PptxProcessor {
+    pub fn new(chunk_size: usize, overlap: usize) -> Result<PptxProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        Ok(PptxProcessor { markdown_processor })
+    }
+
+    /// Returns the Markdown of each slide of a presentation in slide order.
+    pub fn slides_to_markdown(path: impl AsRef<Path>) -> Result<Vec<String>> {
+        Self::slides_from_reader(File::open(path)?)
+    }
+
+    /// Returns the Markdown of each slide of a presentation read from a reader.
+    pub fn slides_from_reader(reader: impl Read + Seek) -> Result<Vec<String>> {
+        let mut archive = ZipArchive::new(reader)?;
+        let presentation = parse_zip_entry(&mut archive, "ppt/presentation.xml")?;
+        let relationships = read_relationships(&mut archive, "ppt/presentation.xml")?;
+
+        let mut slides = Vec::new();
+        for slide_id in presentation.find_all("sldId") {
+            let Some(slide_part) = slide_id
+                .attribute("r:id")
+                .and_then(|id| relationships.get(id))
+                .filter(|relationship| relationship.kind == SLIDE_RELATIONSHIP)
+                .map(|relationship| relationship.target.clone())
+            else {
+                continue;
+            };
+            let slide = parse_zip_entry(&mut archive, &slide_part)?;
+            let notes_part = read_relationships(&mut archive, &slide_part)?
+                .into_values()
+                .find(|relationship| relationship.kind == NOTES_RELATIONSHIP);
+            let notes = match notes_part {
+                Some(relationship) => Some(parse_zip_entry(&mut archive, &relationship.target)?),
+                None => None,
+            };
+            slides.push(slide_to_markdown(&slide, notes.as_ref()));
+        }
+        Ok(slides)
+    }
+}
+
+impl PptxProcessor {
+    fn process_slides(&self, slides: Vec<String>) -> Result<Document> {
+        let mut document = Document::default();
+        for (markdown, slide_number) in slides.iter().zip(1..) {
+            let slide = self.markdown_processor.process_document(markdown)?;
+            for chunk in slide.chunks {
+                document.chunks.push(chunk);
+                document.chunk_metadata.push(HashMap::from([(
+                    "slide_number".to_string(),
+                    slide_number.to_string(),
+                )]));
+            }
+        }
+        Ok(document)
+    }
+}
+
+impl FileProcessor for PptxProcessor {
+    fn process_file(&self, path: impl AsRef<Path>) -> Result<Document> {
+        self.process_slides(Self::slides_to_markdown(path)?)
+    }
+}
+
+impl BytesProcessor for PptxProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> Result<Document> {
+        self.process_slides(Self::slides_from_reader(Cursor::new(bytes))?)
+    }
+}
+
+fn slide_to_markdown(slide: &Element, notes: Option<&Element>) -> String {
+    let mut blocks = Vec::new();
+    if let Some(tree) = slide.find_all("spTree").first() {
+        shapes_to_markdown(tree, &mut blocks);
+    }
+
+    // Only the body placeholder of a notes slide holds the notes; the others hold the slide
+    // image and number.
+    let notes = notes
+        .and_then(|notes| notes.find_all("spTree").first().copied())
+        .map(|tree| {
+            tree.find_all("sp")
+                .into_iter()
+                .filter(|shape| placeholder_type(shape) == Some("body"))
+                .flat_map(paragraphs)
+                .map(|(_, text)| text)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    if !notes.is_empty() {
+        blocks.push("## Notes".to_string());
+        blocks.extend(notes);
+    }
+    blocks.join("\n\n")
+}
+
+/// Appends the Markdown of the shapes of a shape tree or group in their order on the slide.
+fn shapes_to_markdown(tree: &Element, blocks: &mut Vec<String>) {
+    for shape in tree.elements() {
+        match shape.name.as_str() {
+            "sp" => {
+                let paragraphs = paragraphs(shape);
+                if paragraphs.is_empty() {
+                    continue;
+                }
+                match placeholder_type(shape) {
+                    Some("title" | "ctrTitle") => {
+                        let title = paragraphs.into_iter().map(|(_, text)| text);
+                        blocks.push(format!("# {}", title.collect::<Vec<_>>().join(" ")));
+                    }
+                    Some("dt" | "ftr" | "hdr" | "sldNum") => {}
+                    Some("subTitle") | None => {
+                        blocks.extend(paragraphs.into_iter().map(|(_, text)| text));
+                    }
+                    Some(_) => blocks.push(
+                        paragraphs
+                            .into_iter()
+                            .map(|(level, text)| format!("{}- {}", "  ".repeat(level), text))
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    ),
+                }
+            }
+            "grpSp" => shapes_to_markdown(shape, blocks),
+            "graphicFrame" => {
+                for table in shape.find_all("tbl") {
+                    if let Some(table) = table_to_markdown(table) {
+                        blocks.push(table);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Returns the placeholder type of a shape, `None` for shapes that are not placeholders. A
+/// placeholder without a type is a body placeholder.
+fn placeholder_type(shape: &Element) -> Option<&str> {
+    let placeholder = shape.child("nvSpPr")?.child("nvPr")?.child("ph")?;
+    Some(placeholder.attribute("type").unwrap_or("body"))
+}
+
+/// Returns the non-empty paragraphs of a shape or table cell with their indentation level.
+fn paragraphs(element: &Element) -> Vec<(usize, String)> {
+    element
+        .find_all("p")
+        .into_iter()
+        .filter_map(|paragraph| {
+            let text = paragraph_text(paragraph);
+            let text = text.trim();
+            if text.is_empty() {
+                return None;
+            }
+            let level = paragraph
+                .child("pPr")
+                .and_then(|properties| properties.attribute("lvl"))
+                .and_then(|level| level.parse().ok())
+                .unwrap_or(0);
+            Some((level, text.to_string()))
+        })
+        .collect()
+}
+
+/// Returns the text of the runs and fields of a paragraph, with line breaks as spaces.
+fn paragraph_text(element: &Element) -> String {
+    let mut text = String::new();
+    for child in element.elements() {
+        match child.name.as_str() {
+            "t" => text.push_str(&child.text()),
+            "br" => text.push(' '),
+            _ => text.push_str(&paragraph_text(child)),
+        }
+    }
+    text
+}
+
+/// Converts a table to Markdown with its first row as the header.
+fn table_to_markdown(table: &Element) -> Option<String> {
+    let rows = table
+        .elements()
+        .filter(|row| row.name == "tr")
+        .map(|row| {
+            row.elements()
+                .filter(|cell| cell.name == "tc")
+                .map(|cell| {
+                    paragraphs(cell)
+                        .into_iter()
+                        .map(|(_, text)| text)
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    markdown_table(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    const NAMESPACES: &str = r#"xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main""#;
+
+    fn shape(placeholder: Option<&str>, paragraphs: &[(usize, &str)]) -> String {
+        let placeholder = match placeholder {
+            Some("") => "<p:ph idx=\"1\"/>".to_string(),
+            Some(kind) => format!("<p:ph type=\"{}\"/>", kind),
+            None => String::new(),
+        };
+        let paragraphs = paragraphs
+            .iter()
+            .map(|(level, text)| {
+                format!(
+                    "<a:p><a:pPr lvl=\"{}\"/><a:r><a:t>{}</a:t></a:r></a:p>",
+                    level, text
+                )
+            })
+            .collect::<String>();
+        format!(
+            "<p:sp><p:nvSpPr><p:cNvPr id=\"2\" name=\"Shape\"/><p:cNvSpPr/><p:nvPr>{}</p:nvPr></p:nvSpPr><p:txBody><a:bodyPr/>{}</p:txBody></p:sp>",
+            placeholder, paragraphs
+        )
+    }
+
+    fn table(rows: &[&[&str]]) -> String {
+        let rows = rows
+            .iter()
+            .map(|row| {
+                let cells = row
+                    .iter()
+                    .map(|cell| {
+                        format!(
+                            "<a:tc><a:txBody><a:p><a:r><a:t>{}</a:t></a:r></a:p></a:txBody></a:tc>",
+                            cell
+                        )
+                    })
+                    .collect::<String>();
+                format!("<a:tr h=\"370840\">{}</a:tr>", cells)
+            })
+            .collect::<String>();
+        format!(
+            "<p:graphicFrame><a:graphic><a:graphicData><a:tbl>{}</a:tbl></a:graphicData></a:graphic></p:graphicFrame>",
+            rows
+        )
+    }
+
+    fn slide(shapes: &[String]) -> String {
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?><p:sld {}><p:cSld><p:spTree>{}</p:spTree></p:cSld></p:sld>",
+            NAMESPACES,
+            shapes.concat()
+        )
+    }
+
+    fn rels_xml(targets: &[(&str, &str, &str)]) -> String {
+        let relationships = targets
+            .iter()
+            .map(|(id, kind, target)| {
+                format!(
+                    "<Relationship Id=\"{}\" Type=\"{}\" Target=\"{}\"/>",
+                    id, kind, target
+                )
+            })
+            .collect::<String>();
+        format!(
+            "<?xml version=\"1.0\"?><Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">{}</Relationships>",
+            relationships
+        )
+    }
+
+    /// Writes a presentation whose slide list puts `slide2.xml` before `slide1.xml`.
+    fn write_presentation(dir: &Path) -> std::path::PathBuf {
+        let path = dir.join("deck.pptx");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        let mut add = |name: &str, content: String| {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        };
+
+        add(
+            "ppt/presentation.xml",
+            format!(
+                "<p:presentation {}><p:sldIdLst><p:sldId id=\"256\" r:id=\"rId3\"/><p:sldId id=\"257\" r:id=\"rId2\"/></p:sldIdLst></p:presentation>",
+                NAMESPACES
+            ),
+        );
+        add(
+            "ppt/_rels/presentation.xml.rels",
+            rels_xml(&[
+                ("rId2", SLIDE_RELATIONSHIP, "slides/slide1.xml"),
+                ("rId3", SLIDE_RELATIONSHIP, "/ppt/slides/slide2.xml"),
+            ]),
+        );
+        add(
+            "ppt/slides/slide2.xml",
+            slide(&[
+                shape(Some("ctrTitle"), &[(0, "Quarterly review")]),
+                shape(Some("subTitle"), &[(0, "Sales &amp; marketing")]),
+                shape(Some("sldNum"), &[(0, "1")]),
+            ]),
+        );
+        add(
+            "ppt/slides/slide1.xml",
+            slide(&[
+                shape(Some("title"), &[(0, "Results")]),
+                shape(Some(""), &[(0, "Revenue grew"), (1, "Mostly in Europe")]),
+                table(&[&["Region", "Revenue"], &["Europe", "12 | 14"], &["Asia"]]),
+            ]),
+        );
+        add(
+            "ppt/slides/_rels/slide1.xml.rels",
+            rels_xml(&[("rId1", NOTES_RELATIONSHIP, "../notesSlides/notesSlide1.xml")]),
+        );
+        add(
+            "ppt/notesSlides/notesSlide1.xml",
+            slide(&[
+                shape(Some("sldImg"), &[]),
+                shape(Some("body"), &[(0, "Mention the new office.")]),
+                shape(Some("sldNum"), &[(0, "2")]),
+            ]),
+        );
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_slides_to_markdown() {
+        let dir = TempDir::new("pptx").unwrap();
+        let path = write_presentation(dir.path());
+        let slides = PptxProcessor::slides_to_markdown(&path).unwrap();
+
+        assert_eq!(slides.len(), 2);
+        assert_eq!(slides[0], "# Quarterly review\n\nSales & marketing");
+        assert_eq!(
+            slides[1],
+            "# Results\n\n- Revenue grew\n  - Mostly in Europe\n\n\
+             | Region | Revenue |\n| --- | --- |\n| Europe | 12 \\| 14 |\n| Asia |  |\n\n\
+             ## Notes\n\nMention the new office."
+        );
+    }
+
+    #[test]
+    fn test_process_file() {
+        let dir = TempDir::new("pptx").unwrap();
+        let path = write_presentation(dir.path());
+        let document = PptxProcessor::new(1000, 0)
+            .unwrap()
+            .process_file(&path)
+            .unwrap();
+
+        assert_eq!(document.chunks.len(), 2);
+        assert_eq!(document.chunk_metadata.len(), 2);
+        assert!(document.chunks[1].contains("Mention the new office."));
+        assert_eq!(document.chunk_metadata[0]["slide_number"], "1");
+        assert_eq!(document.chunk_metadata[1]["slide_number"], "2");
+
+        let bytes = std::fs::read(&path).unwrap();
+        let from_bytes = PptxProcessor::new(1000, 0)
+            .unwrap()
+            .process_bytes(&bytes)
+            .unwrap();
+        assert_eq!(from_bytes.chunks, document.chunks);
+    }
+
+    #[test]
+    fn test_process_file_err() {
+        let processor = PptxProcessor::new(128, 0).unwrap();
+        assert!(processor.process_file("../test_files/test.docx").is_err());
+        assert!(processor
+            .process_file("this_file_does_not_exist.pptx")
+            .is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{collections::HashMap, io::Read, path::Path};
 
 pub trait DocumentProcessor {
     fn process_document(&self, content: &str) -> anyhow::Result<Document>;
@@ -8,6 +8,19 @@ pub trait FileProcessor {
     fn process_file(&self, path: impl AsRef<Path>) -> anyhow::Result<Document>;
 }
 
+/// Processes a document held in memory, such as an upload or an object read from a bucket,
+/// without writing it to a file.
+pub trait BytesProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> anyhow::Result<Document>;
+
+    /// Reads a document to the end and processes it.
+    fn process_reader(&self, mut reader: impl Read) -> anyhow::Result<Document> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        self.process_bytes(&bytes)
+    }
+}
+
 pub trait UrlProcessor {
     fn process_url(&self, url: &str) -> anyhow::Result<Document>;
 }
@@ -20,6 +33,12 @@ impl<T: DocumentProcessor> FileProcessor for T {
     }
 }
 
+impl<T: DocumentProcessor> BytesProcessor for T {
+    fn process_bytes(&self, bytes: &[u8]) -> anyhow::Result<Document> {
+        self.process_document(&String::from_utf8_lossy(bytes))
+    }
+}
+
 impl<T: DocumentProcessor> UrlProcessor for T {
     fn process_url(&self, url: &str) -> anyhow::Result<Document> {
         let content = reqwest::blocking::get(url)?.text()?;
@@ -27,6 +46,20 @@ impl<T: DocumentProcessor> UrlProcessor for T {
     }
 }
 
+#[derive(Debug, Clone, Default)]
 pub struct Document {
     pub chunks: Vec<String>,
+    /// Metadata of each chunk in the order of `chunks`, for instance the slide a chunk comes
+    /// from. Empty when the processor records no metadata per chunk.
+    pub chunk_metadata: Vec<HashMap<String, String>>,
+}
+
+impl Document {
+    /// Creates a document from chunks without metadata.
+    pub fn new(chunks: Vec<String>) -> Self {
+        Self {
+            chunks,
+            chunk_metadata: Vec::new(),
+        }
+    }
 }
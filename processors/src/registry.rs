@@ -0,0 +1,600 @@
+//! Detection of the format of a file and the processors of custom formats.
+//!
+//! A file is recognized by its content first: the magic bytes at its start, the entries of a ZIP
+//! container or the prolog of a markup document give its MIME type, and the MIME type gives the
+//! format. Files without a signature, such as Markdown, CSV or source code, are recognized by
+//! their extension. Formats can be added or replaced, and a [FileProcessor] can be registered for
+//! a new format or instead of a built-in processor.
+
+use crate::code_processor::CODE_EXTENSIONS;
+use crate::processor::{BytesProcessor, Document, FileProcessor};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use std::sync::Arc;
+use zip::ZipArchive;
+
+/// The number of bytes read from the start of a file to detect its format.
+const HEAD_SIZE: u64 = 8192;
+
+/// Headers of which a message must have at least one, besides `From`, to be taken for an email.
+const EMAIL_HEADERS: &[&str] = &[
+    "message-id",
+    "received",
+    "mime-version",
+    "return-path",
+    "delivered-to",
+];
+
+/// A file format and how to recognize it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFormat {
+    /// The name the format is registered under, such as `pdf`.
+    pub name: String,
+    /// The extensions of the format without the dot, matched case-insensitively.
+    pub extensions: Vec<String>,
+    /// The MIME types of the format.
+    pub mime_types: Vec<String>,
+    /// The bytes files of the format start with.
+    pub signatures: Vec<Vec<u8>>,
+    /// Whether the format is text. A file that contains NUL bytes is not taken for a text format
+    /// by its extension.
+    pub text: bool,
+}
+
+impl FileFormat {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            extensions: Vec::new(),
+            mime_types: Vec::new(),
+            signatures: Vec::new(),
+            text: false,
+        }
+    }
+
+    pub fn with_extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions = extensions
+            .iter()
+            .map(|extension| extension.to_string())
+            .collect();
+        self
+    }
+
+    pub fn with_mime_types(mut self, mime_types: &[&str]) -> Self {
+        self.mime_types = mime_types.iter().map(|mime| mime.to_string()).collect();
+        self
+    }
+
+    /// Adds bytes that files of the format start with.
+    pub fn with_signature(mut self, signature: &[u8]) -> Self {
+        self.signatures.push(signature.to_vec());
+        self
+    }
+
+    pub fn with_text(mut self, text: bool) -> Self {
+        self.text = text;
+        self
+    }
+}
+
+type ProcessFile = dyn Fn(&Path) -> Result<Document> + Send + Sync;
+type ProcessBytes = dyn Fn(&[u8]) -> Result<Document> + Send + Sync;
+
+/// A processor registered for a format, which processes both files and bytes.
+pub struct RegisteredProcessor {
+    file: Box<ProcessFile>,
+    bytes: Box<ProcessBytes>,
+}
+
+impl FileProcessor for RegisteredProcessor {
+    fn process_file(&self, path: impl AsRef<Path>) -> Result<Document> {
+        (self.file)(path.as_ref())
+    }
+}
+
+impl BytesProcessor for RegisteredProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> Result<Document> {
+        (self.bytes)(bytes)
+    }
+}
+
+/// The formats that can be detected, and the processors registered for them.
+///
+/// [ProcessorRegistry::new] knows the formats of the built-in processors, which are registered
+/// without a processor since they are configured by the caller. Formats added later take
+/// precedence over earlier ones when several match a file.
+///
+/// ```
+/// use processors_rs::processor::{Document, DocumentProcessor};
+/// use processors_rs::registry::{FileFormat, ProcessorRegistry};
+///
+/// struct OrgProcessor;
+///
+/// impl DocumentProcessor for OrgProcessor {
+///     fn process_document(&self, content: &str) -> anyhow::Result<Document> {
+///         Ok(Document::new(vec![content.to_string()]))
+///     }
+/// }
+///
+/// let registry = ProcessorRegistry::new().with_processor(
+///     FileFormat::new("org").with_extensions(&["org"]).with_text(true),
+///     OrgProcessor,
+/// );
+/// assert!(registry.processor("org").is_some());
+/// assert!(registry.extensions().contains(&"org"));
+/// ```
+pub struct ProcessorRegistry {
+    formats: Vec<FileFormat>,
+    processors: HashMap<String, RegisteredProcessor>,
+}
+
+impl Default for ProcessorRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ProcessorRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcessorRegistry")
+            .field("formats", &self.formats)
+            .field("processors", &self.processors.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ProcessorRegistry {
+    /// Creates a registry with the formats of the built-in processors.
+    pub fn new() -> Self {
+        let format = |name: &str, extensions: &[&str], mime_types: &[&str], text: bool| {
+            FileFormat::new(name)
+                .with_extensions(extensions)
+                .with_mime_types(mime_types)
+                .with_text(text)
+        };
+        let formats = vec![
+            format("pdf", &["pdf"], &["application/pdf"], false),
+            format("md", &["md"], &["text/markdown"], true),
+            format("txt", &["txt"], &["text/plain"], true),
+            format(
+                "docx",
+                &["docx"],
+                &["application/vnd.openxmlformats-officedocument.wordprocessingml.document"],
+                false,
+            ),
+            format(
+                "pptx",
+                &["pptx"],
+                &["application/vnd.openxmlformats-officedocument.presentationml.presentation"],
+                false,
+            ),
+            format(
+                "xlsx",
+                &["xlsx"],
+                &["application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"],
+                false,
+            ),
+            format("epub", &["epub"], &["application/epub+zip"], false),
+            format(
+                "odt",
+                &["odt"],
+                &["application/vnd.oasis.opendocument.text"],
+                false,
+            ),
+            format("rtf", &["rtf"], &["application/rtf", "text/rtf"], true),
+            format("tex", &["tex"], &["application/x-tex", "text/x-tex"], true),
+            format("ipynb", &["ipynb"], &["application/x-ipynb+json"], true),
+            format("eml", &["eml"], &["message/rfc822"], true),
+            format("mbox", &["mbox"], &["application/mbox"], true),
+            format("csv", &["csv"], &["text/csv"], true),
+            format("tsv", &["tsv"], &["text/tab-separated-values"], true),
+            format("json", &["json"], &["application/json"], true),
+            format(
+                "jsonl",
+                &["jsonl", "ndjson"],
+                &["application/jsonl", "application/x-ndjson"],
+                true,
+            ),
+            format("xml", &["xml"], &["application/xml", "text/xml"], true),
+            format("html", &["html"], &["text/html"], true),
+            format("code", CODE_EXTENSIONS, &[], true),
+        ];
+        Self {
+            formats,
+            processors: HashMap::new(),
+        }
+    }
+
+    /// Adds a format, or replaces the format of the same name.
+    pub fn with_format(mut self, format: FileFormat) -> Self {
+        self.formats
+            .retain(|registered| registered.name != format.name);
+        self.formats.push(format);
+        self
+    }
+
+    /// Adds a format with the processor of its files. A processor registered under the name of
+    /// a built-in format is used instead of the built-in processor.
+    pub fn with_processor(
+        mut self,
+        format: FileFormat,
+        processor: impl FileProcessor + BytesProcessor + Send + Sync + 'static,
+    ) -> Self {
+        let file_processor = Arc::new(processor);
+        let bytes_processor = file_processor.clone();
+        self.processors.insert(
+            format.name.clone(),
+            RegisteredProcessor {
+                file: Box::new(move |path| file_processor.process_file(path)),
+                bytes: Box::new(move |bytes| bytes_processor.process_bytes(bytes)),
+            },
+        );
+        self.with_format(format)
+    }
+
+    pub fn formats(&self) -> &[FileFormat] {
+        &self.formats
+    }
+
+    pub fn format(&self, name: &str) -> Option<&FileFormat> {
+        self.formats.iter().find(|format| format.name == name)
+    }
+
+    /// Returns the processor registered for a format, if any.
+    pub fn processor(&self, name: &str) -> Option<&RegisteredProcessor> {
+        self.processors.get(name)
+    }
+
+    /// Returns the extensions of all formats.
+    pub fn extensions(&self) -> Vec<&str> {
+        self.formats
+            .iter()
+            .flat_map(|format| format.extensions.iter().map(String::as_str))
+            .collect()
+    }
+
+    /// Returns the format of a MIME type. Parameters such as `charset` are ignored.
+    pub fn format_for_mime_type(&self, mime_type: &str) -> Option<&FileFormat> {
+        let mime_type = mime_type.split(';').next().unwrap_or_default().trim();
+        self.formats.iter().rev().find(|format| {
+            format
+                .mime_types
+                .iter()
+                .any(|registered| registered.eq_ignore_ascii_case(mime_type))
+        })
+    }
+
+    pub fn format_for_extension(&self, extension: &str) -> Option<&FileFormat> {
+        self.formats.iter().rev().find(|format| {
+            format
+                .extensions
+                .iter()
+                .any(|registered| registered.eq_ignore_ascii_case(extension))
+        })
+    }
+
+    /// Detects the format of a file. Returns None when the file is of no registered format.
+    pub fn detect(&self, path: impl AsRef<Path>) -> Result<Option<&FileFormat>> {
+        let path = path.as_ref();
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        self.detect_reader(File::open(path)?, file_name)
+    }
+
+    /// Detects the format of a file held in memory, falling back to the extension of the file
+    /// name when the content has no signature.
+    pub fn detect_bytes(&self, bytes: &[u8], file_name: Option<&str>) -> Option<&FileFormat> {
+        // Reading from memory cannot fail.
+        self.detect_reader(Cursor::new(bytes), file_name)
+            .ok()
+            .flatten()
+    }
+
+    /// Detects the format of the content of a reader, falling back to the extension of the file
+    /// name when the content has no signature.
+    pub fn detect_reader(
+        &self,
+        mut reader: impl Read + Seek,
+        file_name: Option<&str>,
+    ) -> Result<Option<&FileFormat>> {
+        let mut head = Vec::new();
+        (&mut reader).take(HEAD_SIZE).read_to_end(&mut head)?;
+
+        let signed = self.formats.iter().rev().find(|format| {
+            format
+                .signatures
+                .iter()
+                .any(|signature| !signature.is_empty() && head.starts_with(signature))
+        });
+        if signed.is_some() {
+            return Ok(signed);
+        }
+        let mime_type = sniff_mime_type(&head, reader);
+        if let Some(format) = mime_type
+            .as_deref()
+            .and_then(|mime| self.format_for_mime_type(mime))
+        {
+            return Ok(Some(format));
+        }
+
+        // A file is not taken by its extension for a format its content contradicts: a binary
+        // file for a text format, or a file of another MIME type for a format that has them.
+        let binary = head.contains(&0);
+        Ok(file_name
+            .and_then(|name| Path::new(name).extension())
+            .and_then(|extension| extension.to_str())
+            .and_then(|extension| self.format_for_extension(extension))
+            .filter(|format| !(format.text && binary))
+            .filter(|format| mime_type.is_none() || format.mime_types.is_empty()))
+    }
+}
+
+/// Returns the MIME type of a file from its first bytes, reading the entries of ZIP containers.
+fn sniff_mime_type(head: &[u8], reader: impl Read + Seek) -> Option<String> {
+    if head.starts_with(b"%PDF-") {
+        return Some("application/pdf".to_string());
+    }
+    if head.starts_with(b"PK\x03\x04") {
+        return Some(zip_mime_type(reader).unwrap_or_else(|| "application/zip".to_string()));
+    }
+    if head.starts_with(b"{\\rtf") {
+        return Some("application/rtf".to_string());
+    }
+
+    let text = String::from_utf8_lossy(head);
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    if text.starts_with('<') {
+        return markup_mime_type(text).map(str::to_string);
+    }
+    let notebook = text
+        .strip_prefix('{')
+        .is_some_and(|rest| rest.trim_start().starts_with("\"cells\""))
+        && text.contains("\"cell_type\"");
+    if notebook {
+        return Some("application/x-ipynb+json".to_string());
+    }
+    if text.starts_with("From ") && text.lines().nth(1).and_then(header_name).is_some() {
+        return Some("application/mbox".to_string());
+    }
+    is_email(text).then(|| "message/rfc822".to_string())
+}
+
+/// Returns the MIME type of an OpenDocument or EPUB container from its `mimetype` entry, or of an
+/// Office Open XML document from its main part.
+fn zip_mime_type(reader: impl Read + Seek) -> Option<String> {
+    let mut archive = ZipArchive::new(reader).ok()?;
+    if let Ok(entry) = archive.by_name("mimetype") {
+        let mut mime_type = String::new();
+        entry.take(256).read_to_string(&mut mime_type).ok()?;
+        return Some(mime_type.trim().to_string());
+    }
+    [
+        (
+            "word/document.xml",
+            "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        ),
+        (
+            "ppt/presentation.xml",
+            "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        ),
+        (
+            "xl/workbook.xml",
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        ),
+    ]
+    .into_iter()
+    .find(|(part, _)| archive.index_for_name(part).is_some())
+    .map(|(_, mime_type)| mime_type.to_string())
+}
+
+/// Returns the MIME type of an HTML document, or of an XML document with a declaration. Other
+/// markup, such as a Markdown file that starts with an HTML tag, has no MIME type.
+fn markup_mime_type(text: &str) -> Option<&'static str> {
+    let mut rest = text;
+    let mut xml = false;
+    loop {
+        rest = rest.trim_start();
+        if let Some(declaration) = rest.strip_prefix("<?xml") {
+            xml = true;
+            match declaration.split_once("?>") {
+                Some((_, after)) => rest = after,
+                None => break,
+            }
+        } else if let Some(comment) = rest.strip_prefix("<!--") {
+            match comment.split_once("-->") {
+                Some((_, after)) => rest = after,
+                None => break,
+            }
+        } else if let Some(doctype) = rest.strip_prefix("<!") {
+            if starts_with_ignore_case(doctype, "doctype html") {
+                return Some("text/html");
+            }
+            match doctype.split_once('>') {
+                Some((_, after)) => rest = after,
+                None => break,
+            }
+        } else {
+            break;
+        }
+    }
+    match starts_with_ignore_case(rest, "<html") {
+        true => Some("text/html"),
+        false => xml.then_some("application/xml"),
+    }
+}
+
+/// Whether a text starts with the header block of an email.
+fn is_email(text: &str) -> bool {
+    let mut names = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            continue;
+        }
+        match header_name(line) {
+            Some(name) => names.push(name.to_ascii_lowercase()),
+            None => return false,
+        }
+    }
+    names.iter().any(|name| name == "from")
+        && names
+            .iter()
+            .any(|name| EMAIL_HEADERS.contains(&name.as_str()))
+}
+
+/// Returns the name of a `Name: value` header line.
+fn header_name(line: &str) -> Option<&str> {
+    let (name, _) = line.split_once(':')?;
+    let valid = !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-');
+    valid.then_some(name)
+}
+
+fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::DocumentProcessor;
+    use std::io::{Cursor, Write};
+    use tempdir::TempDir;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn detect(content: &[u8], file_name: &str) -> Option<String> {
+        ProcessorRegistry::new()
+            .detect_bytes(content, Some(file_name))
+            .map(|format| format.name.clone())
+    }
+
+    fn zip(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_detect_by_content() {
+        assert_eq!(detect(b"%PDF-1.7\n", "report.txt").as_deref(), Some("pdf"));
+        assert_eq!(
+            detect(b"{\\rtf1\\ansi Hello}", "notes").as_deref(),
+            Some("rtf")
+        );
+        assert_eq!(
+            detect(b"\xef\xbb\xbf<!DOCTYPE html><html></html>", "page").as_deref(),
+            Some("html")
+        );
+        assert_eq!(
+            detect(b"<?xml version=\"1.0\"?>\n<!-- feed -->\n<rss/>", "feed").as_deref(),
+            Some("xml")
+        );
+        assert_eq!(
+            detect(b"<?xml version=\"1.0\"?><html xmlns=\"x\"/>", "page.xml").as_deref(),
+            Some("html")
+        );
+        assert_eq!(
+            detect(b"{\n \"cells\": [{\"cell_type\": \"code\"}]}", "a.json").as_deref(),
+            Some("ipynb")
+        );
+        assert_eq!(
+            detect(b"From alice@example.com Mon Jan 1 00:00:00 2024\nFrom: Alice <alice@example.com>\n\nHi", "archive").as_deref(),
+            Some("mbox")
+        );
+        assert_eq!(
+            detect(
+                b"Received: from mail\nFrom: Alice\nSubject: Hi\n  there\n\nBody",
+                "message"
+            )
+            .as_deref(),
+            Some("eml")
+        );
+    }
+
+    #[test]
+    fn test_detect_zip_containers() {
+        let docx = zip(&[("[Content_Types].xml", ""), ("word/document.xml", "")]);
+        assert_eq!(detect(&docx, "letter.zip").as_deref(), Some("docx"));
+        let xlsx = zip(&[("xl/workbook.xml", "")]);
+        assert_eq!(detect(&xlsx, "data.csv").as_deref(), Some("xlsx"));
+        let epub = zip(&[("mimetype", "application/epub+zip")]);
+        assert_eq!(detect(&epub, "book").as_deref(), Some("epub"));
+        let ods = zip(&[("mimetype", "application/vnd.oasis.opendocument.spreadsheet")]);
+        assert_eq!(detect(&ods, "sheet.odt"), None);
+    }
+
+    #[test]
+    fn test_detect_by_extension() {
+        assert_eq!(detect(b"# Title", "README.MD").as_deref(), Some("md"));
+        assert_eq!(
+            detect(b"<p align=\"center\">Logo</p>", "README.md").as_deref(),
+            Some("md")
+        );
+        assert_eq!(
+            detect(b"From: Alice\n\nHi", "letter.txt").as_deref(),
+            Some("txt")
+        );
+        assert_eq!(detect(b"fn main() {}", "main.rs").as_deref(), Some("code"));
+        assert_eq!(detect(b"", "empty.pdf").as_deref(), Some("pdf"));
+        assert_eq!(detect(b"\x89PNG\r\n\x1a\n\0\0", "image.txt"), None);
+        assert_eq!(detect(b"plain text", "LICENSE"), None);
+        assert_eq!(detect(b"plain text", "archive.lock"), None);
+    }
+
+    #[test]
+    fn test_with_processor() {
+        struct Upper;
+
+        impl DocumentProcessor for Upper {
+            fn process_document(&self, content: &str) -> Result<Document> {
+                Ok(Document::new(vec![content.to_uppercase()]))
+            }
+        }
+
+        let registry = ProcessorRegistry::new()
+            .with_processor(
+                FileFormat::new("note")
+                    .with_extensions(&["note"])
+                    .with_signature(b"NOTE:"),
+                Upper,
+            )
+            .with_format(FileFormat::new("md").with_extensions(&["md", "markdown"]));
+
+        let dir = TempDir::new("registry").unwrap();
+        let path = dir.path().join("todo.txt");
+        std::fs::write(&path, "NOTE: buy milk").unwrap();
+        let format = registry.detect(&path).unwrap().unwrap();
+        assert_eq!(format.name, "note");
+        let processor = registry.processor(&format.name).unwrap();
+        let document = processor.process_file(&path).unwrap();
+        assert_eq!(document.chunks, vec!["NOTE: BUY MILK"]);
+        let document = processor.process_bytes(b"NOTE: call").unwrap();
+        assert_eq!(document.chunks, vec!["NOTE: CALL"]);
+
+        assert_eq!(
+            registry.format_for_extension("markdown").unwrap().name,
+            "md"
+        );
+        assert_eq!(registry.format_for_mime_type("text/markdown"), None);
+        assert_eq!(
+            registry
+                .format_for_mime_type("text/HTML; charset=utf-8")
+                .unwrap()
+                .name,
+            "html"
+        );
+        assert!(registry.processor("pdf").is_none());
+    }
+}
@@ -0,0 +1,357 @@
+//! Text extraction for Rich Text Format (RTF) files.
+//!
+//! The control words that carry text structure are interpreted: paragraphs, line breaks, tabs,
+//! outline levels as headings, list markers as bullets and table cells and rows as Markdown
+//! tables. Font, color and style tables, pictures, objects, headers, footers, field instructions
+//! and ignorable destinations are skipped. Hex escapes are decoded as Windows-1252.
+
+use crate::markdown_processor::{markdown_table, MarkdownProcessor};
+use crate::processor::{Document, DocumentProcessor};
+use anyhow::{bail, Result};
+use text_splitter::ChunkConfigError;
+
+/// Destinations whose content is not part of the text of the document.
+const SKIPPED_DESTINATIONS: &[&str] = &[
+    "author",
+    "buptim",
+    "colortbl",
+    "comment",
+    "creatim",
+    "datastore",
+    "doccomm",
+    "fldinst",
+    "fonttbl",
+    "footer",
+    "footerf",
+    "footerl",
+    "footerr",
+    "footnote",
+    "header",
+    "headerf",
+    "headerl",
+    "headerr",
+    "info",
+    "keywords",
+    "listoverridetable",
+    "listtable",
+    "object",
+    "operator",
+    "pict",
+    "printim",
+    "revtim",
+    "rsidtbl",
+    "stylesheet",
+    "subject",
+    "themedata",
+    "title",
+];
+
+/// The characters of the bytes 0x80 to 0x9F of Windows-1252, which differ from Latin-1.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+/// A struct for processing RTF files.
+pub struct RtfProcessor {
+    markdown_processor: MarkdownProcessor,
+}
+
+impl RtfProcessor {
+    pub fn new(chunk_size: usize, overlap: usize) -> Result<RtfProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        Ok(RtfProcessor { markdown_processor })
+    }
+}
+
+impl DocumentProcessor for RtfProcessor {
+    fn process_document(&self, content: &str) -> Result<Document> {
+        let markdown = rtf_to_markdown(content)?;
+        self.markdown_processor.process_document(&markdown)
+    }
+}
+
+/// The state of a group that is restored when the group ends.
+#[derive(Clone, Copy)]
+struct GroupState {
+    skip: bool,
+    /// Number of characters that replace a `\u` character for readers without Unicode.
+    unicode_skip: usize,
+}
+
+#[derive(Default)]
+struct Writer {
+    blocks: Vec<String>,
+    paragraph: String,
+    heading_level: Option<usize>,
+    bullet: bool,
+    in_table: bool,
+    cells: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Writer {
+    fn end_paragraph(&mut self) {
+        let text = std::mem::take(&mut self.paragraph);
+        let text = text.trim();
+        if self.in_table {
+            // A paragraph inside a cell continues the cell.
+            self.paragraph = format!("{} ", text);
+            return;
+        }
+        self.end_table();
+        if text.is_empty() {
+            return;
+        }
+        let block = match (self.heading_level, self.bullet) {
+            (Some(level), _) => format!("{} {}", "#".repeat(level.min(5) + 1), text),
+            (None, true) => format!("- {}", text),
+            (None, false) => text.to_string(),
+        };
+        // Consecutive list items form one list.
+        match self.bullet && self.heading_level.is_none() {
+            true if self
+                .blocks
+                .last()
+                .is_some_and(|last| last.starts_with("- ")) =>
+            {
+                let last = self.blocks.last_mut().unwrap();
+                last.push('\n');
+                last.push_str(&block);
+            }
+            _ => self.blocks.push(block),
+        }
+        self.bullet = false;
+    }
+
+    fn end_cell(&mut self) {
+        let text = std::mem::take(&mut self.paragraph);
+        self.cells
+            .push(text.split_whitespace().collect::<Vec<_>>().join(" "));
+    }
+
+    fn end_row(&mut self) {
+        let cells = std::mem::take(&mut self.cells);
+        self.rows.push(cells);
+        self.paragraph.clear();
+    }
+
+    fn end_table(&mut self) {
+        let rows = std::mem::take(&mut self.rows);
+        if let Some(table) = markdown_table(rows) {
+            self.blocks.push(table);
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.in_table = false;
+        self.end_paragraph();
+        self.end_table();
+        self.blocks.join("\n\n")
+    }
+}
+
+/// Converts an RTF document to Markdown.
+pub fn rtf_to_markdown(rtf: &str) -> Result<String> {
+    if !rtf.trim_start().starts_with("{\\rtf") {
+        bail!("the document is not an RTF document");
+    }
+    let chars = rtf.chars().collect::<Vec<_>>();
+    let mut writer = Writer::default();
+    let mut state = GroupState {
+        skip: false,
+        unicode_skip: 1,
+    };
+    let mut stack = Vec::new();
+    // Characters still to skip after a `\u` character.
+    let mut pending_skip = 0;
+    // Whether the next control word is the first of its group.
+    let mut group_start = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        i += 1;
+        match c {
+            '{' => {
+                stack.push(state);
+                group_start = true;
+                pending_skip = 0;
+            }
+            '}' => {
+                state = stack.pop().unwrap_or(state);
+                group_start = false;
+                pending_skip = 0;
+            }
+            '\\' => {
+                let Some(&next) = chars.get(i) else {
+                    break;
+                };
+                let starts_group = std::mem::take(&mut group_start);
+                if !next.is_ascii_alphabetic() {
+                    i += 1;
+                    match next {
+                        '*' if starts_group => state.skip = true,
+                        '\'' => {
+                            let hex = chars.get(i..i + 2).map(String::from_iter);
+                            i += 2;
+                            if let Some(byte) =
+                                hex.and_then(|hex| u8::from_str_radix(&hex, 16).ok())
+                            {
+                                push_char(
+                                    &mut writer,
+                                    &state,
+                                    &mut pending_skip,
+                                    decode_byte(byte),
+                                );
+                            }
+                        }
+                        '~' => push_char(&mut writer, &state, &mut pending_skip, ' '),
+                        '_' => push_char(&mut writer, &state, &mut pending_skip, '-'),
+                        '\n' | '\r' if !state.skip => writer.end_paragraph(),
+                        '\\' | '{' | '}' => push_char(&mut writer, &state, &mut pending_skip, next),
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_alphabetic() {
+                    i += 1;
+                }
+                let word = String::from_iter(&chars[start..i]);
+                let number_start = i;
+                if i < chars.len() && (chars[i] == '-' || chars[i].is_ascii_digit()) {
+                    i += 1;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+                let parameter = String::from_iter(&chars[number_start..i])
+                    .parse::<i32>()
+                    .ok();
+                if chars.get(i) == Some(&' ') {
+                    i += 1;
+                }
+
+                if starts_group && SKIPPED_DESTINATIONS.contains(&word.as_str()) {
+                    state.skip = true;
+                    continue;
+                }
+                if state.skip {
+                    continue;
+                }
+                match word.as_str() {
+                    "par" | "sect" | "page" => writer.end_paragraph(),
+                    "line" => writer.paragraph.push('\n'),
+                    "tab" => push_char(&mut writer, &state, &mut pending_skip, '\t'),
+                    "emdash" => push_char(&mut writer, &state, &mut pending_skip, '\u{2014}'),
+                    "endash" => push_char(&mut writer, &state, &mut pending_skip, '\u{2013}'),
+                    "bullet" => push_char(&mut writer, &state, &mut pending_skip, '\u{2022}'),
+                    "lquote" => push_char(&mut writer, &state, &mut pending_skip, '\u{2018}'),
+                    "rquote" => push_char(&mut writer, &state, &mut pending_skip, '\u{2019}'),
+                    "ldblquote" => push_char(&mut writer, &state, &mut pending_skip, '\u{201c}'),
+                    "rdblquote" => push_char(&mut writer, &state, &mut pending_skip, '\u{201d}'),
+                    "u" => {
+                        if let Some(code) = parameter {
+                            // Code points above 32767 are written as negative numbers.
+                            let code = if code < 0 { code + 65536 } else { code };
+                            let c = char::from_u32(code as u32).unwrap_or('\u{fffd}');
+                            push_char(&mut writer, &state, &mut pending_skip, c);
+                            pending_skip = state.unicode_skip;
+                        }
+                    }
+                    "uc" => state.unicode_skip = parameter.unwrap_or(1).max(0) as usize,
+                    "pard" => {
+                        writer.heading_level = None;
+                        writer.in_table = false;
+                    }
+                    "outlinelevel" => {
+                        writer.heading_level = parameter.map(|level| level.max(0) as usize);
+                    }
+                    "intbl" => writer.in_table = true,
+                    "cell" => writer.end_cell(),
+                    "row" => writer.end_row(),
+                    // List markers are replaced by a Markdown bullet.
+                    "listtext" | "pntext" if starts_group => {
+                        writer.bullet = true;
+                        state.skip = true;
+                    }
+                    _ => {}
+                }
+            }
+            '\n' | '\r' => {}
+            c => {
+                group_start = false;
+                push_char(&mut writer, &state, &mut pending_skip, c);
+            }
+        }
+    }
+    Ok(writer.finish())
+}
+
+/// Appends a character to the paragraph unless the group is skipped or the character replaces a
+/// `\u` character.
+fn push_char(writer: &mut Writer, state: &GroupState, pending_skip: &mut usize, c: char) {
+    if *pending_skip > 0 {
+        *pending_skip -= 1;
+    } else if !state.skip {
+        writer.paragraph.push(c);
+    }
+}
+
+fn decode_byte(byte: u8) -> char {
+    match byte {
+        0x80..=0x9f => WINDOWS_1252[byte as usize - 0x80],
+        byte => byte as char,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::FileProcessor;
+
+    const RTF: &str = r#"{\rtf1\ansi\ansicpg1252\deff0{\fonttbl{\f0\fswiss Helvetica;}}{\colortbl;\red0\green0\blue0;}
+{\*\generator Riched20 10.0;}{\info{\title Lease}{\author Legal}}
+\pard\outlinelevel0\b Lease agreement\b0\par
+\pard The tenant\rquote s rent is \'80 900\u8364?.\line Due monthly.\par
+{\listtext\'b7\tab}Keep the flat clean\par
+{\listtext\'b7\tab}Pay on time\par
+\pard\intbl Item\cell Cost\cell\row
+\pard\intbl Rent\cell 900\cell\row
+\pard Signed {\field{\*\fldinst HYPERLINK "x"}{\fldrslt here}}.\par
+}"#;
+
+    #[test]
+    fn test_rtf_to_markdown() {
+        assert_eq!(
+            rtf_to_markdown(RTF).unwrap(),
+            "# Lease agreement\n\n\
+             The tenant\u{2019}s rent is \u{20ac} 900\u{20ac}.\nDue monthly.\n\n\
+             - Keep the flat clean\n- Pay on time\n\n\
+             | Item | Cost |\n| --- | --- |\n| Rent | 900 |\n\n\
+             Signed here."
+        );
+    }
+
+    #[test]
+    fn test_rtf_to_markdown_err() {
+        assert!(rtf_to_markdown("plain text").is_err());
+    }
+
+    #[test]
+    fn test_process_file() {
+        let dir = tempdir::TempDir::new("rtf").unwrap();
+        let path = dir.path().join("lease.rtf");
+        std::fs::write(&path, RTF).unwrap();
+        let document = RtfProcessor::new(1000, 0)
+            .unwrap()
+            .process_file(&path)
+            .unwrap();
+        assert_eq!(document.chunks.len(), 1);
+        assert!(document.chunks[0].starts_with("# Lease agreement"));
+    }
+}
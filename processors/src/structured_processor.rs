@@ -0,0 +1,743 @@
+//! Record-level processing of JSON, JSONL and XML files.
+//!
+//! A file is read as a list of records: the lines of a JSONL file, the elements of a JSON array
+//! or the XML elements matching a path. Every record becomes one chunk that lists its fields as
+//! `field: value` lines, nested fields being flattened into dotted names such as
+//! `specs.weight`. Fields are selected with JSON pointers for JSON and with a subset of XPath for
+//! XML, both for the text of the chunks and for their metadata.
+
+use crate::processor::{BytesProcessor, Document, FileProcessor};
+use crate::xml::{Element, Node};
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Configuration of the [StructuredProcessor].
+///
+/// Paths are JSON pointers, such as `/data/items` or `/specs/weight`, for JSON and JSONL files
+/// and XPaths for XML files. The supported XPaths are made of element names or `*` separated by
+/// `/` or `//`, optionally with a `[@attribute]` or `[@attribute='value']` predicate, and may end
+/// with `@attribute` or `text()`.
+///
+/// # Example
+///
+/// ```rust
+/// use processors_rs::structured_processor::StructuredConfig;
+///
+/// let config = StructuredConfig::default()
+///     .with_records("//product")
+///     .with_text_fields(&["name", "description", "specs/@weight"])
+///     .with_metadata_fields(&["@sku"]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StructuredConfig {
+    /// Path of the records. For JSON, a pointer to an array whose elements are the records or to
+    /// a single record; for XML, an XPath matching the record elements. Defaults to None, which
+    /// reads the root array or object of a JSON file and the children of the root element of an
+    /// XML file. The lines of a JSONL file are always the records.
+    pub records: Option<String>,
+    /// Paths of the fields rendered into the text of a record, relative to the record. Defaults
+    /// to none, which renders every field.
+    pub text_fields: Vec<String>,
+    /// Paths of the fields whose values are added to the metadata of the chunks, relative to the
+    /// record. Fields missing from a record are skipped. Defaults to none.
+    pub metadata_fields: Vec<String>,
+}
+
+impl StructuredConfig {
+    pub fn with_records(mut self, records: &str) -> Self {
+        self.records = Some(records.to_string());
+        self
+    }
+
+    pub fn with_text_fields(mut self, fields: &[&str]) -> Self {
+        self.text_fields = fields.iter().map(|field| field.to_string()).collect();
+        self
+    }
+
+    pub fn with_metadata_fields(mut self, fields: &[&str]) -> Self {
+        self.metadata_fields = fields.iter().map(|field| field.to_string()).collect();
+        self
+    }
+}
+
+/// A record flattened into named fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Record {
+    /// The text fields of the record in order, as pairs of dotted names and values. The name is
+    /// empty for a record that is a single value.
+    pub fields: Vec<(String, String)>,
+    /// The values of the metadata fields by name.
+    pub metadata: HashMap<String, String>,
+}
+
+impl Record {
+    /// Renders the fields as `field: value` lines.
+    pub fn text(&self) -> String {
+        self.fields
+            .iter()
+            .map(|(name, value)| match name.is_empty() {
+                true => value.clone(),
+                false => format!("{}: {}", name, value),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// A struct for processing JSON, JSONL and XML files.
+///
+/// Every chunk carries the `record_index` of its record, starting at 0, and the values of the
+/// [StructuredConfig::metadata_fields] in [Document::chunk_metadata]. Metadata fields are named
+/// like the flattened fields, e.g. `specs.weight` for `/specs/weight` or `specs/weight`.
+pub struct StructuredProcessor {
+    config: StructuredConfig,
+}
+
+impl StructuredProcessor {
+    pub fn new(config: StructuredConfig) -> StructuredProcessor {
+        StructuredProcessor { config }
+    }
+
+    /// Reads the records of a file by its extension: `jsonl` and `ndjson` files line by line,
+    /// `xml` files and any other file that starts with a tag as XML, `json` files as JSON and
+    /// the rest as JSON or else line by line.
+    pub fn read_records(&self, path: impl AsRef<Path>) -> Result<Vec<Record>> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        self.parse_records(&std::fs::read(path)?, &extension)
+    }
+
+    /// Reads the records of a document held in memory: as XML when it starts with a tag, and as
+    /// JSON or else line by line otherwise.
+    pub fn read_records_from_bytes(&self, bytes: &[u8]) -> Result<Vec<Record>> {
+        self.parse_records(bytes, "")
+    }
+
+    fn parse_records(&self, bytes: &[u8], extension: &str) -> Result<Vec<Record>> {
+        let content = String::from_utf8_lossy(bytes);
+        let content = content.trim_start_matches('\u{feff}');
+        match extension {
+            "jsonl" | "ndjson" => self.read_json_lines(content),
+            _ if extension == "xml" || content.trim_start().starts_with('<') => {
+                self.read_xml(content)
+            }
+            "json" => self.read_json(content),
+            _ => self
+                .read_json(content)
+                .or_else(|error| self.read_json_lines(content).map_err(|_| error)),
+        }
+    }
+
+    fn read_json(&self, content: &str) -> Result<Vec<Record>> {
+        let document: Value = serde_json::from_str(content)?;
+        let records = match &self.config.records {
+            Some(pointer) => document
+                .pointer(pointer)
+                .ok_or_else(|| anyhow!("the JSON document has no value at `{}`", pointer))?,
+            None => &document,
+        };
+        Ok(match records {
+            Value::Array(records) => records
+                .iter()
+                .map(|record| self.json_record(record))
+                .collect(),
+            record => vec![self.json_record(record)],
+        })
+    }
+
+    fn read_json_lines(&self, content: &str) -> Result<Vec<Record>> {
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let record = serde_json::from_str(line)
+                    .map_err(|error| anyhow!("invalid JSON on line {}: {}", i + 1, error))?;
+                Ok(self.json_record(&record))
+            })
+            .collect()
+    }
+
+    fn json_record(&self, record: &Value) -> Record {
+        let mut fields = Vec::new();
+        if self.config.text_fields.is_empty() {
+            flatten_json(record, String::new(), &mut fields);
+        }
+        for pointer in &self.config.text_fields {
+            if let Some(value) = record.pointer(pointer) {
+                flatten_json(value, pointer_name(pointer), &mut fields);
+            }
+        }
+        let metadata = self
+            .config
+            .metadata_fields
+            .iter()
+            .filter_map(|pointer| {
+                let value = match record.pointer(pointer)? {
+                    Value::Null => return None,
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                Some((pointer_name(pointer), value))
+            })
+            .collect();
+        Record { fields, metadata }
+    }
+
+    fn read_xml(&self, content: &str) -> Result<Vec<Record>> {
+        let root = Element::parse(content)?;
+        let document = Element {
+            children: vec![Node::Element(root)],
+            ..Default::default()
+        };
+        let records = match &self.config.records {
+            Some(path) => match XPath::parse(path)?.select(&document) {
+                Selected::Elements(elements) => elements,
+                _ => bail!("the XPath `{}` does not select elements", path),
+            },
+            None => match document.elements().next() {
+                Some(root) => root.elements().collect(),
+                None => Vec::new(),
+            },
+        };
+
+        let text_fields = parse_xpaths(&self.config.text_fields)?;
+        let metadata_fields = parse_xpaths(&self.config.metadata_fields)?;
+        Ok(records
+            .into_iter()
+            .map(|record| {
+                let mut fields = Vec::new();
+                if text_fields.is_empty() {
+                    flatten_xml(record, String::new(), &mut fields);
+                }
+                for (name, xpath) in &text_fields {
+                    match xpath.select(record) {
+                        Selected::Elements(elements) => {
+                            for element in elements {
+                                flatten_xml(element, name.clone(), &mut fields);
+                            }
+                        }
+                        Selected::Values(values) => {
+                            fields.extend(values.into_iter().map(|value| (name.clone(), value)));
+                        }
+                    }
+                }
+                let metadata = metadata_fields
+                    .iter()
+                    .filter_map(|(name, xpath)| {
+                        let values = match xpath.select(record) {
+                            Selected::Elements(elements) => {
+                                elements.into_iter().map(element_text).collect()
+                            }
+                            Selected::Values(values) => values,
+                        };
+                        let values = values
+                            .into_iter()
+                            .filter(|value| !value.is_empty())
+                            .collect::<Vec<_>>();
+                        (!values.is_empty()).then(|| (name.clone(), values.join("\n")))
+                    })
+                    .collect();
+                Record { fields, metadata }
+            })
+            .collect())
+    }
+}
+
+impl StructuredProcessor {
+    fn process_records(&self, records: Vec<Record>) -> Document {
+        let mut document = Document::default();
+        for (index, record) in records.into_iter().enumerate() {
+            let text = record.text();
+            if text.trim().is_empty() {
+                continue;
+            }
+            let mut metadata = record.metadata;
+            metadata.insert("record_index".to_string(), index.to_string());
+            document.chunks.push(text);
+            document.chunk_metadata.push(metadata);
+        }
+        document
+    }
+}
+
+impl FileProcessor for StructuredProcessor {
+    fn process_file(&self, path: impl AsRef<Path>) -> Result<Document> {
+        Ok(self.process_records(self.read_records(path)?))
+    }
+}
+
+impl BytesProcessor for StructuredProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> Result<Document> {
+        Ok(self.process_records(self.read_records_from_bytes(bytes)?))
+    }
+}
+
+/// Joins the name of a field to the name of its parent with a dot.
+fn join_name(parent: &str, name: &str) -> String {
+    match (parent.is_empty(), name.is_empty()) {
+        (true, _) => name.to_string(),
+        (_, true) => parent.to_string(),
+        _ => format!("{}.{}", parent, name),
+    }
+}
+
+/// Returns the dotted name of a JSON pointer, e.g. `specs.weight` for `/specs/weight`.
+fn pointer_name(pointer: &str) -> String {
+    pointer
+        .split('/')
+        .filter(|token| !token.is_empty())
+        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Flattens a JSON value into named fields. Objects are flattened in key order, arrays of
+/// values are joined with commas and arrays of objects are flattened by index.
+fn flatten_json(value: &Value, name: String, fields: &mut Vec<(String, String)>) {
+    match value {
+        Value::Null => {}
+        Value::Object(object) => {
+            for (key, value) in object {
+                flatten_json(value, join_name(&name, key), fields);
+            }
+        }
+        Value::Array(values)
+            if values
+                .iter()
+                .all(|value| !value.is_object() && !value.is_array()) =>
+        {
+            let values = values
+                .iter()
+                .filter(|value| !value.is_null())
+                .map(|value| match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+                .collect::<Vec<_>>();
+            if !values.is_empty() {
+                fields.push((name, values.join(", ")));
+            }
+        }
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                flatten_json(value, join_name(&name, &i.to_string()), fields);
+            }
+        }
+        Value::String(value) => fields.push((name, value.clone())),
+        value => fields.push((name, value.to_string())),
+    }
+}
+
+/// Returns the text of an element and its descendants with white space collapsed.
+fn element_text(element: &Element) -> String {
+    element
+        .text()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Flattens an XML element into named fields: its attributes, its own text and its child
+/// elements in document order. Namespace declarations are left out.
+fn flatten_xml(element: &Element, name: String, fields: &mut Vec<(String, String)>) {
+    for (attribute, value) in &element.attributes {
+        if attribute != "xmlns" && !attribute.starts_with("xmlns:") {
+            fields.push((join_name(&name, attribute), value.clone()));
+        }
+    }
+    let text = element
+        .children
+        .iter()
+        .filter_map(|node| match node {
+            Node::Text(text) => Some(text.as_str()),
+            Node::Element(_) => None,
+        })
+        .collect::<String>();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !text.is_empty() {
+        fields.push((name.clone(), text));
+    }
+    for child in element.elements() {
+        flatten_xml(child, join_name(&name, &child.name), fields);
+    }
+}
+
+/// Parses XPaths and names them like the flattened fields, e.g. `specs.weight` for
+/// `specs/@weight`.
+fn parse_xpaths(paths: &[String]) -> Result<Vec<(String, XPath)>> {
+    paths
+        .iter()
+        .map(|path| {
+            let name = path
+                .split('/')
+                .map(|step| step.trim_start_matches('@'))
+                .filter(|step| !step.is_empty() && *step != "." && *step != "text()")
+                .map(|step| step.split('[').next().unwrap_or(step))
+                .collect::<Vec<_>>()
+                .join(".");
+            Ok((name, XPath::parse(path)?))
+        })
+        .collect()
+}
+
+/// A step of an XPath.
+struct Step {
+    /// Whether the step searches all descendants rather than the children.
+    descendant: bool,
+    /// A local element name, `*` for any element or `.` for the context element.
+    name: String,
+    /// An attribute the element must have, and its value.
+    predicate: Option<(String, Option<String>)>,
+}
+
+enum Selection {
+    Elements,
+    Attribute(String),
+    Text,
+}
+
+/// A path in the subset of XPath described in [StructuredConfig].
+struct XPath {
+    steps: Vec<Step>,
+    selection: Selection,
+}
+
+/// The result of an [XPath].
+enum Selected<'a> {
+    Elements(Vec<&'a Element>),
+    Values(Vec<String>),
+}
+
+impl XPath {
+    fn parse(path: &str) -> Result<XPath> {
+        let invalid = || anyhow!("unsupported XPath `{}`", path);
+        let mut steps = Vec::new();
+        let mut selection = Selection::Elements;
+        let mut rest = path.trim();
+        // A relative path starts from the context element, an absolute one from the document.
+        let mut descendant = false;
+        if let Some(stripped) = rest.strip_prefix("//") {
+            descendant = true;
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix('/') {
+            rest = stripped;
+        }
+        for (i, step) in split_steps(rest).into_iter().enumerate() {
+            if i > 0 && step.is_empty() {
+                descendant = true;
+                continue;
+            }
+            if !matches!(selection, Selection::Elements) {
+                return Err(invalid());
+            }
+            if let Some(attribute) = step.strip_prefix('@') {
+                selection = Selection::Attribute(attribute.to_string());
+                continue;
+            }
+            if step == "text()" {
+                selection = Selection::Text;
+                continue;
+            }
+            let (name, predicate) = match step.split_once('[') {
+                Some((name, predicate)) => {
+                    let predicate = predicate
+                        .strip_suffix(']')
+                        .and_then(|predicate| predicate.trim().strip_prefix('@'))
+                        .ok_or_else(invalid)?;
+                    let predicate = match predicate.split_once('=') {
+                        Some((attribute, value)) => {
+                            let value = value.trim();
+                            let value = value
+                                .strip_prefix('\'')
+                                .and_then(|value| value.strip_suffix('\''))
+                                .or_else(|| {
+                                    value
+                                        .strip_prefix('"')
+                                        .and_then(|value| value.strip_suffix('"'))
+                                })
+                                .ok_or_else(invalid)?;
+                            (attribute.trim().to_string(), Some(value.to_string()))
+                        }
+                        None => (predicate.trim().to_string(), None),
+                    };
+                    (name, Some(predicate))
+                }
+                None => (step, None),
+            };
+            let name = name.rsplit(':').next().unwrap_or(name);
+            let valid_name = name == "*"
+                || name == "."
+                || !name.is_empty()
+                    && name
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'));
+            if !valid_name {
+                return Err(invalid());
+            }
+            steps.push(Step {
+                descendant: std::mem::take(&mut descendant),
+                name: name.to_string(),
+                predicate,
+            });
+        }
+        Ok(XPath { steps, selection })
+    }
+
+    fn select<'a>(&self, context: &'a Element) -> Selected<'a> {
+        let mut elements = vec![context];
+        for step in &self.steps {
+            let mut next = Vec::new();
+            for element in elements {
+                if step.name == "." {
+                    next.push(element);
+                } else if step.descendant {
+                    descendants(element, &mut next);
+                } else {
+                    next.extend(element.elements());
+                }
+            }
+            next.retain(|element| step.matches(element));
+            elements = next;
+        }
+        match &self.selection {
+            Selection::Elements => Selected::Elements(elements),
+            Selection::Attribute(attribute) => Selected::Values(
+                elements
+                    .iter()
+                    .filter_map(|element| element.attribute(attribute))
+                    .map(str::to_string)
+                    .collect(),
+            ),
+            Selection::Text => Selected::Values(
+                elements
+                    .iter()
+                    .map(|element| {
+                        element
+                            .children
+                            .iter()
+                            .filter_map(|node| match node {
+                                Node::Text(text) => Some(text.trim()),
+                                Node::Element(_) => None,
+                            })
+                            .collect::<String>()
+                    })
+                    .filter(|text| !text.is_empty())
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Step {
+    fn matches(&self, element: &Element) -> bool {
+        let name_matches = matches!(self.name.as_str(), "*" | ".") || element.name == self.name;
+        let predicate_matches = match &self.predicate {
+            None => true,
+            Some((attribute, None)) => element.attribute(attribute).is_some(),
+            Some((attribute, Some(value))) => element.attribute(attribute) == Some(value.as_str()),
+        };
+        name_matches && predicate_matches
+    }
+}
+
+/// Splits an XPath at the slashes outside predicates.
+fn split_steps(path: &str) -> Vec<&str> {
+    let mut steps = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in path.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            '/' if depth == 0 => {
+                steps.push(path[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    steps.push(path[start..].trim());
+    steps
+}
+
+/// Adds the descendants of an element in document order.
+fn descendants<'a>(element: &'a Element, found: &mut Vec<&'a Element>) {
+    for child in element.elements() {
+        found.push(child);
+        descendants(child, found);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    const CATALOG: &str = r#"{
+  "data": {
+    "products": [
+      {"sku": "A-1", "name": "Widget", "tags": ["tools", "metal"], "specs": {"weight": 2.5, "color": null}},
+      {"sku": "B-2", "name": "Gadget", "variants": [{"color": "red"}, {"color": "blue"}]}
+    ]
+  }
+}"#;
+
+    const XML: &str = r#"<?xml version="1.0"?>
+<catalog xmlns:c="urn:catalog">
+  <c:product sku="A-1">
+    <name>Widget</name>
+    <description>A  small
+      widget.</description>
+    <specs weight="2.5" />
+  </c:product>
+  <c:product sku="B-2" discontinued="true">
+    <name>Gadget</name>
+  </c:product>
+</catalog>"#;
+
+    fn write(dir: &TempDir, name: &str, content: &str) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_json() {
+        let dir = TempDir::new("structured").unwrap();
+        let path = write(&dir, "catalog.json", CATALOG);
+
+        let processor = StructuredProcessor::new(
+            StructuredConfig::default()
+                .with_records("/data/products")
+                .with_metadata_fields(&["/sku", "/specs/weight", "/missing"]),
+        );
+        let document = processor.process_file(&path).unwrap();
+        assert_eq!(
+            document.chunks,
+            vec![
+                "name: Widget\nsku: A-1\nspecs.weight: 2.5\ntags: tools, metal",
+                "name: Gadget\nsku: B-2\nvariants.0.color: red\nvariants.1.color: blue",
+            ]
+        );
+        assert_eq!(
+            document.chunk_metadata[0],
+            HashMap::from([
+                ("record_index".to_string(), "0".to_string()),
+                ("sku".to_string(), "A-1".to_string()),
+                ("specs.weight".to_string(), "2.5".to_string()),
+            ])
+        );
+
+        let processor = StructuredProcessor::new(
+            StructuredConfig::default()
+                .with_records("/data/products")
+                .with_text_fields(&["/name", "/specs"]),
+        );
+        let document = processor.process_file(&path).unwrap();
+        assert_eq!(
+            document.chunks,
+            vec!["name: Widget\nspecs.weight: 2.5", "name: Gadget"]
+        );
+
+        let processor =
+            StructuredProcessor::new(StructuredConfig::default().with_records("/missing"));
+        assert!(processor.process_file(&path).is_err());
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let dir = TempDir::new("structured").unwrap();
+        let path = write(
+            &dir,
+            "events.jsonl",
+            "{\"id\": 1, \"message\": \"started\"}\n\n\"plain\"\n",
+        );
+        let processor = StructuredProcessor::new(StructuredConfig::default());
+        let document = processor.process_file(&path).unwrap();
+        assert_eq!(document.chunks, vec!["id: 1\nmessage: started", "plain"]);
+        assert_eq!(document.chunk_metadata[1]["record_index"], "1");
+
+        let path = write(&dir, "broken.jsonl", "{}\n{\n");
+        let error = processor.process_file(&path).unwrap_err();
+        assert!(error.to_string().contains("line 2"));
+    }
+
+    #[test]
+    fn test_xml() {
+        let dir = TempDir::new("structured").unwrap();
+        let path = write(&dir, "catalog.xml", XML);
+
+        let processor = StructuredProcessor::new(StructuredConfig::default());
+        let document = processor.process_file(&path).unwrap();
+        assert_eq!(
+            document.chunks,
+            vec![
+                "sku: A-1\nname: Widget\ndescription: A small widget.\nspecs.weight: 2.5",
+                "sku: B-2\ndiscontinued: true\nname: Gadget",
+            ]
+        );
+
+        let processor = StructuredProcessor::new(
+            StructuredConfig::default()
+                .with_records("//product[@discontinued='true']")
+                .with_text_fields(&["name/text()"])
+                .with_metadata_fields(&["@sku"]),
+        );
+        let document = processor.process_file(&path).unwrap();
+        assert_eq!(document.chunks, vec!["name: Gadget"]);
+        assert_eq!(document.chunk_metadata[0]["sku"], "B-2");
+
+        let processor = StructuredProcessor::new(
+            StructuredConfig::default()
+                .with_records("/catalog/c:product")
+                .with_text_fields(&["description", "specs/@weight"]),
+        );
+        let document = processor.process_file(&path).unwrap();
+        assert_eq!(
+            document.chunks,
+            vec!["description: A small widget.\nspecs.weight: 2.5"]
+        );
+    }
+
+    #[test]
+    fn test_process_bytes() {
+        let processor = StructuredProcessor::new(StructuredConfig::default());
+        let document = processor.process_bytes(XML.as_bytes()).unwrap();
+        assert_eq!(document.chunks.len(), 2);
+        assert!(document.chunks[1].starts_with("sku: B-2"));
+
+        let document = processor
+            .process_bytes(b"{\"id\": 1}\n{\"id\": 2}\n")
+            .unwrap();
+        assert_eq!(document.chunks, vec!["id: 1", "id: 2"]);
+
+        let error = processor.process_bytes(b"{\"id\": 1,").unwrap_err();
+        assert!(error.to_string().contains("EOF"));
+    }
+
+    #[test]
+    fn test_xpath() {
+        let root =
+            Element::parse("<a><b id='1'><c>x</c></b><b><c>y</c><d><c>z</c></d></b></a>").unwrap();
+        let texts = |path: &str| match XPath::parse(path).unwrap().select(&root) {
+            Selected::Elements(elements) => elements.into_iter().map(element_text).collect(),
+            Selected::Values(values) => values,
+        };
+        assert_eq!(texts("b/c"), vec!["x", "y"]);
+        assert_eq!(texts("//c"), vec!["x", "y", "z"]);
+        assert_eq!(texts("b//c"), vec!["x", "y", "z"]);
+        assert_eq!(texts("b[@id]/c"), vec!["x"]);
+        assert_eq!(texts("*/@id"), vec!["1"]);
+        assert_eq!(texts("./b/d/c/text()"), vec!["z"]);
+
+        assert!(XPath::parse("b/@id/c").is_err());
+        assert!(XPath::parse("b[1]").is_err());
+    }
+}
@@ -0,0 +1,877 @@
+//! Row-level processing of CSV, TSV and XLSX files.
+//!
+//! Tables are read row by row, with the first row as the header, and every row (or group of
+//! rows) becomes one chunk rendered through a template such as `"{name}: {description}"`. The
+//! chunks carry the sheet name, the row index and the values of selected columns as metadata.
+//! Workbooks are read sheet by sheet, and cells with a date or time number format are written
+//! as `YYYY-MM-DD`, `HH:MM:SS` or `YYYY-MM-DD HH:MM:SS`.
+
+use crate::processor::{BytesProcessor, Document, FileProcessor};
+use crate::xml::{parse_zip_entry, read_relationships, Element};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// The bytes a ZIP container, such as an XLSX workbook, starts with.
+const ZIP_SIGNATURE: &[u8] = b"PK\x03\x04";
+
+const WORKSHEET_RELATIONSHIP: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet";
+
+/// The number of rows and columns of an Excel worksheet. Cells referenced past them are skipped.
+const MAX_ROWS: usize = 1_048_576;
+const MAX_COLUMNS: usize = 16_384;
+
+/// Configuration of the [TabularProcessor].
+///
+/// # Example
+///
+/// ```rust
+/// use processors_rs::tabular_processor::TabularConfig;
+///
+/// let config = TabularConfig::default()
+///     .with_template("{name}: {description}")
+///     .with_metadata_columns(&["id"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TabularConfig {
+    /// Template each row is rendered through. Column names between braces are replaced by the
+    /// values of the row, `{{` and `}}` stand for literal braces. Defaults to None, which renders
+    /// every non-empty column as a `column: value` line.
+    pub template: Option<String>,
+    /// Number of rows in a chunk. Defaults to 1.
+    pub rows_per_chunk: usize,
+    /// Columns whose values are added to the metadata of the chunks. Columns missing from a
+    /// table are skipped. Defaults to none.
+    pub metadata_columns: Vec<String>,
+    /// Sheets of a workbook to read. Defaults to None, which reads all sheets.
+    pub sheets: Option<Vec<String>>,
+}
+
+impl Default for TabularConfig {
+    fn default() -> Self {
+        Self {
+            template: None,
+            rows_per_chunk: 1,
+            metadata_columns: Vec::new(),
+            sheets: None,
+        }
+    }
+}
+
+impl TabularConfig {
+    pub fn with_template(mut self, template: &str) -> Self {
+        self.template = Some(template.to_string());
+        self
+    }
+
+    pub fn with_rows_per_chunk(mut self, rows_per_chunk: usize) -> Self {
+        self.rows_per_chunk = rows_per_chunk.max(1);
+        self
+    }
+
+    pub fn with_metadata_columns(mut self, columns: &[&str]) -> Self {
+        self.metadata_columns = columns.iter().map(|column| column.to_string()).collect();
+        self
+    }
+
+    pub fn with_sheets(mut self, sheets: &[&str]) -> Self {
+        self.sheets = Some(sheets.iter().map(|sheet| sheet.to_string()).collect());
+        self
+    }
+}
+
+/// A table read from a CSV or TSV file or from a sheet of a workbook.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// Name of the sheet, None for CSV and TSV files.
+    pub name: Option<String>,
+    pub header: Vec<String>,
+    /// The rows below the header, padded to the length of the header. Empty rows are left out.
+    pub rows: Vec<Vec<String>>,
+    /// The index of each row below the header, counted from 0 including the empty rows.
+    pub row_indices: Vec<usize>,
+}
+
+impl Table {
+    fn new(name: Option<String>, rows: Vec<Vec<String>>) -> Self {
+        let mut rows = rows
+            .into_iter()
+            .enumerate()
+            .filter(|(_, row)| row.iter().any(|cell| !cell.trim().is_empty()));
+        let Some((header_index, header)) = rows.next() else {
+            return Self {
+                name,
+                header: Vec::new(),
+                rows: Vec::new(),
+                row_indices: Vec::new(),
+            };
+        };
+        let header = header
+            .into_iter()
+            .enumerate()
+            .map(|(i, column)| match column.trim() {
+                "" => format!("column_{}", i + 1),
+                column => column.to_string(),
+            })
+            .collect::<Vec<_>>();
+        let (row_indices, rows) = rows
+            .map(|(index, mut row)| {
+                row.resize(header.len(), String::new());
+                (index - header_index - 1, row)
+            })
+            .unzip();
+        Self {
+            name,
+            header,
+            rows,
+            row_indices,
+        }
+    }
+}
+
+/// A struct for processing CSV, TSV and XLSX files.
+///
+/// Every chunk carries the `row_index` of its first row, counted from 0 below the header and
+/// including empty rows, and the values of the [TabularConfig::metadata_columns] in
+/// [Document::chunk_metadata]. The chunks of a workbook also carry their `sheet_name`. When a
+/// chunk holds several rows, the `row_count` is added and the values of the metadata columns are
+/// joined with newlines.
+pub struct TabularProcessor {
+    config: TabularConfig,
+}
+
+impl TabularProcessor {
+    pub fn new(config: TabularConfig) -> TabularProcessor {
+        TabularProcessor { config }
+    }
+
+    /// Reads the tables of a file: one for CSV and TSV files, one per selected sheet for XLSX
+    /// files. A ZIP container is read as a workbook whatever its extension.
+    pub fn read_tables(&self, path: impl AsRef<Path>) -> Result<Vec<Table>> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        if extension == "xlsx" || is_zip(path)? {
+            return read_workbook(File::open(path)?, self.config.sheets.as_deref());
+        }
+        let delimiter = match extension.as_str() {
+            "tsv" => Some('\t'),
+            "csv" => Some(','),
+            _ => None,
+        };
+        Ok(vec![read_delimited(&std::fs::read(path)?, delimiter)])
+    }
+
+    /// Reads the tables of a file held in memory. A ZIP container is read as a workbook, and
+    /// other content as CSV or TSV depending on the delimiter of its first line.
+    pub fn read_tables_from_bytes(&self, bytes: &[u8]) -> Result<Vec<Table>> {
+        match bytes.starts_with(ZIP_SIGNATURE) {
+            true => read_workbook(Cursor::new(bytes), self.config.sheets.as_deref()),
+            false => Ok(vec![read_delimited(bytes, None)]),
+        }
+    }
+
+    /// Renders the rows of a table through the template, one string per row.
+    pub fn render_rows(&self, table: &Table) -> Result<Vec<String>> {
+        match &self.config.template {
+            Some(template) => {
+                let template = Template::parse(template, &table.header)?;
+                Ok(table.rows.iter().map(|row| template.render(row)).collect())
+            }
+            None => Ok(table
+                .rows
+                .iter()
+                .map(|row| {
+                    table
+                        .header
+                        .iter()
+                        .zip(row)
+                        .filter(|(_, value)| !value.trim().is_empty())
+                        .map(|(column, value)| format!("{}: {}", column, value.trim()))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                })
+                .collect()),
+        }
+    }
+}
+
+impl TabularProcessor {
+    fn process_tables(&self, tables: Vec<Table>) -> Result<Document> {
+        let mut document = Document::default();
+        for table in tables {
+            let rendered = self.render_rows(&table)?;
+            let metadata_columns = self
+                .config
+                .metadata_columns
+                .iter()
+                .filter_map(|column| {
+                    let index = table.header.iter().position(|name| name == column)?;
+                    Some((column, index))
+                })
+                .collect::<Vec<_>>();
+
+            let rows_per_chunk = self.config.rows_per_chunk.max(1);
+            for (chunk_index, rows) in table.rows.chunks(rows_per_chunk).enumerate() {
+                let first_row = chunk_index * rows_per_chunk;
+                let mut metadata = HashMap::new();
+                if let Some(name) = &table.name {
+                    metadata.insert("sheet_name".to_string(), name.clone());
+                }
+                metadata.insert(
+                    "row_index".to_string(),
+                    table.row_indices[first_row].to_string(),
+                );
+                if rows.len() > 1 {
+                    metadata.insert("row_count".to_string(), rows.len().to_string());
+                }
+                for (column, index) in &metadata_columns {
+                    let values = rows.iter().map(|row| row[*index].trim());
+                    metadata.insert(column.to_string(), values.collect::<Vec<_>>().join("\n"));
+                }
+
+                let chunk = rendered[first_row..first_row + rows.len()].join("\n\n");
+                if chunk.trim().is_empty() {
+                    continue;
+                }
+                document.chunks.push(chunk);
+                document.chunk_metadata.push(metadata);
+            }
+        }
+        Ok(document)
+    }
+}
+
+impl FileProcessor for TabularProcessor {
+    fn process_file(&self, path: impl AsRef<Path>) -> Result<Document> {
+        self.process_tables(self.read_tables(path)?)
+    }
+}
+
+impl BytesProcessor for TabularProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> Result<Document> {
+        self.process_tables(self.read_tables_from_bytes(bytes)?)
+    }
+}
+
+enum TemplatePart {
+    Text(String),
+    Column(usize),
+}
+
+/// A row template whose columns are resolved against the header of a table.
+struct Template(Vec<TemplatePart>);
+
+impl Template {
+    fn parse(template: &str, header: &[String]) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let name = chars.by_ref().take_while(|&c| c != '}').collect::<String>();
+                    let Some(index) = header.iter().position(|column| *column == name.trim())
+                    else {
+                        bail!(
+                            "the template column `{}` is not in the header: {}",
+                            name,
+                            header.join(", ")
+                        );
+                    };
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    parts.push(TemplatePart::Column(index));
+                }
+                c => text.push(c),
+            }
+        }
+        parts.push(TemplatePart::Text(text));
+        Ok(Self(parts))
+    }
+
+    fn render(&self, row: &[String]) -> String {
+        self.0
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.as_str(),
+                TemplatePart::Column(index) => row[*index].trim(),
+            })
+            .collect()
+    }
+}
+
+/// Whether a file starts with the signature of a ZIP container.
+fn is_zip(path: &Path) -> Result<bool> {
+    let mut signature = [0; 4];
+    let read = File::open(path)?.read_exact(&mut signature).is_ok();
+    Ok(read && signature == ZIP_SIGNATURE)
+}
+
+/// Reads delimited text. Without a delimiter, the first line is split at tabs when it has more
+/// tabs than commas, and at commas otherwise.
+fn read_delimited(bytes: &[u8], delimiter: Option<char>) -> Table {
+    let text = String::from_utf8_lossy(bytes);
+    let text = text.trim_start_matches('\u{feff}');
+    let delimiter = delimiter.unwrap_or_else(|| {
+        let first_line = text.lines().next().unwrap_or_default();
+        match first_line.matches('\t').count() > first_line.matches(',').count() {
+            true => '\t',
+            false => ',',
+        }
+    });
+    Table::new(None, parse_delimited(text, delimiter))
+}
+
+/// Splits delimited text into rows of cells. Cells may be quoted with double quotes, in which
+/// case they can hold delimiters, line breaks and doubled quotes.
+fn parse_delimited(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                cell.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if cell.is_empty() => quoted = true,
+            (c, false) if c == delimiter => row.push(std::mem::take(&mut cell)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n' | '\r', false) => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Reads the sheets of a workbook in the order of the workbook, keeping only `sheets` if given.
+fn read_workbook<R: Read + Seek>(reader: R, sheets: Option<&[String]>) -> Result<Vec<Table>> {
+    let mut archive = ZipArchive::new(reader)?;
+    let workbook = parse_zip_entry(&mut archive, "xl/workbook.xml")?;
+    let relationships = read_relationships(&mut archive, "xl/workbook.xml")?;
+    let shared_strings = match archive.index_for_name("xl/sharedStrings.xml") {
+        Some(_) => parse_zip_entry(&mut archive, "xl/sharedStrings.xml")?
+            .find_all("si")
+            .into_iter()
+            .map(|item| {
+                item.find_all("t")
+                    .into_iter()
+                    .map(Element::text)
+                    .collect::<String>()
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    let date1904 = workbook
+        .find_all("workbookPr")
+        .first()
+        .and_then(|properties| properties.attribute("date1904"))
+        .is_some_and(|date1904| date1904 == "1" || date1904 == "true");
+    let cell_formats = match archive.index_for_name("xl/styles.xml") {
+        Some(_) => CellFormats::parse(&parse_zip_entry(&mut archive, "xl/styles.xml")?, date1904),
+        None => CellFormats {
+            styles: Vec::new(),
+            date1904,
+        },
+    };
+
+    let mut tables = Vec::new();
+    for sheet in workbook.find_all("sheet") {
+        let name = sheet.attribute("name").unwrap_or_default().to_string();
+        if sheets.is_some_and(|sheets| !sheets.contains(&name)) {
+            continue;
+        }
+        let Some(relationship) = sheet
+            .attribute("r:id")
+            .and_then(|id| relationships.get(id))
+            .filter(|relationship| relationship.kind == WORKSHEET_RELATIONSHIP)
+        else {
+            continue;
+        };
+        let worksheet = parse_zip_entry(&mut archive, &relationship.target)?;
+        tables.push(Table::new(
+            Some(name),
+            worksheet_rows(&worksheet, &shared_strings, &cell_formats),
+        ));
+    }
+    Ok(tables)
+}
+
+/// Returns the cells of a worksheet as rows of text. Cells and rows left out of the sheet are
+/// filled with empty strings, and cells past the bounds of a worksheet are skipped.
+fn worksheet_rows(
+    worksheet: &Element,
+    shared_strings: &[String],
+    cell_formats: &CellFormats,
+) -> Vec<Vec<String>> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    for row in worksheet.find_all("row") {
+        let row_index = row
+            .attribute("r")
+            .and_then(|r| r.parse::<usize>().ok())
+            .map(|r| r.saturating_sub(1))
+            .unwrap_or(rows.len());
+        if row_index >= MAX_ROWS {
+            continue;
+        }
+        if rows.len() <= row_index {
+            rows.resize(row_index + 1, Vec::new());
+        }
+        let cells = &mut rows[row_index];
+        for cell in row.elements().filter(|cell| cell.name == "c") {
+            let column = cell.attribute("r").map(column_index).unwrap_or(cells.len());
+            if column >= MAX_COLUMNS {
+                continue;
+            }
+            let value = cell.child("v").map(Element::text).unwrap_or_default();
+            let value = match cell.attribute("t") {
+                Some("s") => value
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| shared_strings.get(index).cloned())
+                    .unwrap_or_default(),
+                Some("inlineStr") => cell.find_all("t").into_iter().map(Element::text).collect(),
+                Some("b") => match value.trim() {
+                    "1" => "TRUE".to_string(),
+                    _ => "FALSE".to_string(),
+                },
+                None | Some("n") => cell_formats.format_number(cell.attribute("s"), value),
+                _ => value,
+            };
+            if cells.len() <= column {
+                cells.resize(column + 1, String::new());
+            }
+            cells[column] = value;
+        }
+    }
+    rows
+}
+
+/// Returns the index of the column of a cell reference such as `AB12`, counted from 0.
+fn column_index(reference: &str) -> usize {
+    reference
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .fold(0usize, |index, c| {
+            index
+                .saturating_mul(26)
+                .saturating_add(c.to_ascii_uppercase() as usize - 'A' as usize + 1)
+        })
+        .saturating_sub(1)
+}
+
+/// How a number with a date or time format is written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateFormat {
+    Date,
+    Time,
+    DateTime,
+}
+
+/// The number formats of the cell styles of a workbook, to write dates and times, which
+/// workbooks store as serial numbers of days, as text.
+struct CellFormats {
+    /// The date format of each cell style, indexed by the `s` attribute of cells.
+    styles: Vec<Option<DateFormat>>,
+    /// Whether serial numbers count days from 1904-01-01 instead of 1899-12-31.
+    date1904: bool,
+}
+
+impl CellFormats {
+    fn parse(styles: &Element, date1904: bool) -> Self {
+        let custom_formats = styles
+            .find_all("numFmt")
+            .into_iter()
+            .filter_map(|format| {
+                Some((
+                    format.attribute("numFmtId")?,
+                    format.attribute("formatCode")?,
+                ))
+            })
+            .collect::<HashMap<_, _>>();
+        let styles = styles
+            .find_all("cellXfs")
+            .first()
+            .map(|cell_styles| {
+                cell_styles
+                    .elements()
+                    .filter(|style| style.name == "xf")
+                    .map(|style| {
+                        let id = style.attribute("numFmtId").unwrap_or("0");
+                        match custom_formats.get(id) {
+                            Some(code) => date_format_of_code(code),
+                            None => builtin_date_format(id.parse().unwrap_or(0)),
+                        }
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { styles, date1904 }
+    }
+
+    /// Writes a number as a date or time if the cell style `style` has a date format.
+    fn format_number(&self, style: Option<&str>, value: String) -> String {
+        let format = style
+            .and_then(|style| style.parse::<usize>().ok())
+            .and_then(|style| self.styles.get(style).copied().flatten());
+        let serial = value.trim().parse::<f64>().ok();
+        match (format, serial) {
+            (Some(format), Some(serial)) => {
+                format_serial(serial, format, self.date1904).unwrap_or(value)
+            }
+            _ => value,
+        }
+    }
+}
+
+/// The date format of a built-in number format id.
+fn builtin_date_format(id: u32) -> Option<DateFormat> {
+    match id {
+        14..=17 | 27..=31 | 36 | 50..=58 => Some(DateFormat::Date),
+        18..=21 | 32..=35 | 45..=47 => Some(DateFormat::Time),
+        22 => Some(DateFormat::DateTime),
+        _ => None,
+    }
+}
+
+/// The date format of a custom number format such as `yyyy-mm-dd` or `h:mm AM/PM`, from the
+/// date and time tokens of its first section outside quoted text, escapes and brackets.
+fn date_format_of_code(code: &str) -> Option<DateFormat> {
+    let mut tokens = String::new();
+    let mut chars = code.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ';' => break,
+            '"' => chars.by_ref().take_while(|&c| c != '"').for_each(drop),
+            '[' => chars.by_ref().take_while(|&c| c != ']').for_each(drop),
+            '\\' | '_' | '*' => {
+                chars.next();
+            }
+            c => tokens.push(c.to_ascii_lowercase()),
+        }
+    }
+    let has_time = tokens.contains(['h', 's']);
+    let has_date = tokens.contains(['y', 'd']) || (tokens.contains('m') && !has_time);
+    match (has_date, has_time) {
+        (true, true) => Some(DateFormat::DateTime),
+        (true, false) => Some(DateFormat::Date),
+        (false, true) => Some(DateFormat::Time),
+        (false, false) => None,
+    }
+}
+
+/// Writes a serial number of days as a date and time, or None if it is out of range.
+fn format_serial(serial: f64, format: DateFormat, date1904: bool) -> Option<String> {
+    // The last day that workbooks can hold, 9999-12-31.
+    if !(0.0..2_958_466.0).contains(&serial) {
+        return None;
+    }
+    let mut days = serial.trunc() as i64;
+    let mut seconds = (serial.fract() * 86_400.0).round() as i64;
+    if seconds == 86_400 {
+        days += 1;
+        seconds = 0;
+    }
+    // Days since 1970-01-01. The 1900 date system counts 1900-02-29, which does not exist.
+    let days = match (date1904, days) {
+        (true, days) => days - 24_107,
+        (false, days) if days < 60 => days - 25_568,
+        (false, days) => days - 25_569,
+    };
+    let (year, month, day) = civil_from_days(days);
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    let time = format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    Some(match format {
+        DateFormat::Date => date,
+        DateFormat::Time => time,
+        DateFormat::DateTime => format!("{} {}", date, time),
+    })
+}
+
+/// Converts days since 1970-01-01 to a year, month and day of the proleptic Gregorian calendar.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempdir::TempDir;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    const CSV: &str = "\u{feff}id,name,description\r\n\
+        1,Widget,\"A small, useful part\"\r\n\
+        2,Gadget,\"Says \"\"hello\"\"\nwhen pressed\"\r\n\
+        ,,\r\n\
+        3,Gizmo,\r\n";
+
+    fn write_workbook(dir: &Path) -> std::path::PathBuf {
+        let path = dir.join("book.xlsx");
+        let mut zip = ZipWriter::new(File::create(&path).unwrap());
+        let mut add = |name: &str, content: &str| {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        };
+        add(
+            "xl/workbook.xml",
+            r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Parts" sheetId="1" r:id="rId1"/><sheet name="Notes" sheetId="2" r:id="rId2"/></sheets></workbook>"#,
+        );
+        add(
+            "xl/_rels/workbook.xml.rels",
+            &format!(
+                r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="{0}" Target="worksheets/sheet1.xml"/><Relationship Id="rId2" Type="{0}" Target="/xl/worksheets/sheet2.xml"/></Relationships>"#,
+                WORKSHEET_RELATIONSHIP
+            ),
+        );
+        add(
+            "xl/sharedStrings.xml",
+            r#"<sst><si><t>name</t></si><si><t>price</t></si><si><r><t>Wid</t></r><r><t>get</t></r></si><si><t>in stock</t></si></sst>"#,
+        );
+        add(
+            "xl/worksheets/sheet1.xml",
+            r#"<worksheet><sheetData>
+                <row r="1"><c r="A1" t="s"><v>0</v></c><c r="B1" t="s"><v>1</v></c><c r="C1" t="s"><v>3</v></c></row>
+                <row r="3"><c r="A3" t="s"><v>2</v></c><c r="B3"><v>9.5</v></c><c r="C3" t="b"><v>1</v></c></row>
+                <row r="4"><c r="A4" t="inlineStr"><is><t>Gadget</t></is></c><c r="C4" t="b"><v>0</v></c></row>
+            </sheetData></worksheet>"#,
+        );
+        add(
+            "xl/worksheets/sheet2.xml",
+            r#"<worksheet><sheetData><row r="1"><c r="A1" t="inlineStr"><is><t>note</t></is></c></row><row r="2"><c r="A2" t="inlineStr"><is><t>Check the prices</t></is></c></row></sheetData></worksheet>"#,
+        );
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_delimited() {
+        let rows = parse_delimited(CSV.trim_start_matches('\u{feff}'), ',');
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[1], ["1", "Widget", "A small, useful part"]);
+        assert_eq!(rows[2][2], "Says \"hello\"\nwhen pressed");
+        assert_eq!(rows[4], ["3", "Gizmo", ""]);
+        assert_eq!(
+            parse_delimited("a\tb\nc\td", '\t'),
+            [["a", "b"], ["c", "d"]]
+        );
+    }
+
+    #[test]
+    fn test_process_csv() {
+        let dir = TempDir::new("tabular").unwrap();
+        let path = dir.path().join("parts.csv");
+        std::fs::write(&path, CSV).unwrap();
+
+        let config = TabularConfig::default()
+            .with_template("{name}: {description} {{{id}}}")
+            .with_metadata_columns(&["id", "missing"]);
+        let document = TabularProcessor::new(config).process_file(&path).unwrap();
+        assert_eq!(
+            document.chunks,
+            [
+                "Widget: A small, useful part {1}",
+                "Gadget: Says \"hello\"\nwhen pressed {2}",
+                "Gizmo:  {3}",
+            ]
+        );
+        assert_eq!(document.chunk_metadata[1]["row_index"], "1");
+        assert_eq!(document.chunk_metadata[1]["id"], "2");
+        assert!(!document.chunk_metadata[1].contains_key("missing"));
+        assert!(!document.chunk_metadata[1].contains_key("sheet_name"));
+        // The empty row before Gizmo still counts.
+        assert_eq!(document.chunk_metadata[2]["row_index"], "3");
+    }
+
+    #[test]
+    fn test_process_csv_rows_per_chunk() {
+        let dir = TempDir::new("tabular").unwrap();
+        let path = dir.path().join("parts.tsv");
+        std::fs::write(&path, "id\tname\n1\tWidget\n2\tGadget\n3\tGizmo\n").unwrap();
+
+        let config = TabularConfig::default()
+            .with_rows_per_chunk(2)
+            .with_metadata_columns(&["id"]);
+        let document = TabularProcessor::new(config).process_file(&path).unwrap();
+        assert_eq!(
+            document.chunks,
+            [
+                "id: 1\nname: Widget\n\nid: 2\nname: Gadget",
+                "id: 3\nname: Gizmo"
+            ]
+        );
+        assert_eq!(document.chunk_metadata[0]["id"], "1\n2");
+        assert_eq!(document.chunk_metadata[0]["row_count"], "2");
+        assert_eq!(document.chunk_metadata[1]["row_index"], "2");
+        assert!(!document.chunk_metadata[1].contains_key("row_count"));
+    }
+
+    #[test]
+    fn test_unknown_template_column() {
+        let dir = TempDir::new("tabular").unwrap();
+        let path = dir.path().join("parts.csv");
+        std::fs::write(&path, CSV).unwrap();
+
+        let config = TabularConfig::default().with_template("{title}");
+        let error = TabularProcessor::new(config)
+            .process_file(&path)
+            .unwrap_err();
+        assert!(error.to_string().contains("`title`"));
+    }
+
+    #[test]
+    fn test_process_xlsx() {
+        let dir = TempDir::new("tabular").unwrap();
+        let path = write_workbook(dir.path());
+
+        let processor = TabularProcessor::new(TabularConfig::default());
+        let tables = processor.read_tables(&path).unwrap();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[0].header, ["name", "price", "in stock"]);
+        assert_eq!(
+            tables[0].rows,
+            [["Widget", "9.5", "TRUE"], ["Gadget", "", "FALSE"]]
+        );
+        assert_eq!(tables[0].row_indices, [1, 2]);
+
+        let config = TabularConfig::default()
+            .with_template("{note}")
+            .with_sheets(&["Notes"]);
+        let document = TabularProcessor::new(config).process_file(&path).unwrap();
+        assert_eq!(document.chunks, ["Check the prices"]);
+        assert_eq!(document.chunk_metadata[0]["sheet_name"], "Notes");
+        assert_eq!(document.chunk_metadata[0]["row_index"], "0");
+    }
+
+    #[test]
+    fn test_process_bytes() {
+        let processor = TabularProcessor::new(TabularConfig::default());
+        let document = processor
+            .process_bytes(b"name\tnote\nWidget\tsmall, useful\n")
+            .unwrap();
+        assert_eq!(document.chunks, ["name: Widget\nnote: small, useful"]);
+
+        let dir = TempDir::new("tabular").unwrap();
+        let bytes = std::fs::read(write_workbook(dir.path())).unwrap();
+        let tables = processor.read_tables_from_bytes(&bytes).unwrap();
+        assert_eq!(tables.len(), 2);
+        assert_eq!(tables[1].name.as_deref(), Some("Notes"));
+    }
+
+    #[test]
+    fn test_xlsx_dates_and_bounds() {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut add = |name: &str, content: &str| {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        };
+        add(
+            "xl/workbook.xml",
+            r#"<workbook xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets><sheet name="Orders" sheetId="1" r:id="rId1"/></sheets></workbook>"#,
+        );
+        add(
+            "xl/_rels/workbook.xml.rels",
+            &format!(
+                r#"<Relationships><Relationship Id="rId1" Type="{}" Target="worksheets/sheet1.xml"/></Relationships>"#,
+                WORKSHEET_RELATIONSHIP
+            ),
+        );
+        add(
+            "xl/styles.xml",
+            r#"<styleSheet><numFmts><numFmt numFmtId="164" formatCode="yyyy\-mm\-dd\ hh:mm"/><numFmt numFmtId="165" formatCode="&quot;day&quot; 0.00"/></numFmts><cellStyleXfs><xf numFmtId="14"/></cellStyleXfs><cellXfs><xf numFmtId="0"/><xf numFmtId="14"/><xf numFmtId="164"/><xf numFmtId="20"/><xf numFmtId="165"/></cellXfs></styleSheet>"#,
+        );
+        add(
+            "xl/worksheets/sheet1.xml",
+            r#"<worksheet><sheetData>
+                <row r="1"><c r="A1" t="inlineStr"><is><t>date</t></is></c><c r="B1" t="inlineStr"><is><t>placed</t></is></c><c r="C1" t="inlineStr"><is><t>time</t></is></c><c r="D1" t="inlineStr"><is><t>count</t></is></c></row>
+                <row r="2"><c r="A2" s="1"><v>45000</v></c><c r="B2" s="2"><v>45000.75</v></c><c r="C2" s="3"><v>0.5</v></c><c r="D2" s="4"><v>3</v></c><c r="XFE2"><v>1</v></c></row>
+                <row r="3"><c r="A3" s="1"><v>59</v></c><c r="B3" s="2"><v>61</v></c><c r="D3" s="0"><v>7</v></c></row>
+                <row r="1048576000"><c r="A1048576000"><v>1</v></c></row>
+            </sheetData></worksheet>"#,
+        );
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let processor = TabularProcessor::new(TabularConfig::default());
+        let tables = processor.read_tables_from_bytes(&bytes).unwrap();
+        assert_eq!(tables[0].header, ["date", "placed", "time", "count"]);
+        assert_eq!(
+            tables[0].rows,
+            [
+                ["2023-03-15", "2023-03-15 18:00:00", "12:00:00", "3"],
+                ["1900-02-28", "1900-03-01 00:00:00", "", "7"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_serial() {
+        assert_eq!(
+            format_serial(0.0, DateFormat::Date, true).as_deref(),
+            Some("1904-01-01")
+        );
+        assert_eq!(
+            format_serial(1.0, DateFormat::Date, false).as_deref(),
+            Some("1900-01-01")
+        );
+        assert_eq!(
+            format_serial(2_958_465.0, DateFormat::Date, false).as_deref(),
+            Some("9999-12-31")
+        );
+        assert_eq!(format_serial(-1.0, DateFormat::Date, false), None);
+        assert_eq!(
+            date_format_of_code("[$-409]h:mm AM/PM;@"),
+            Some(DateFormat::Time)
+        );
+        assert_eq!(date_format_of_code("#,##0.00 \"dm\""), None);
+    }
+
+    #[test]
+    fn test_column_index() {
+        assert_eq!(column_index("A1"), 0);
+        assert_eq!(column_index("Z9"), 25);
+        assert_eq!(column_index("AB12"), 27);
+        assert_eq!(column_index("ZZZZZZZZZZZZZZZZ1"), usize::MAX - 1);
+    }
+}
@@ -0,0 +1,896 @@
+//! Text extraction for LaTeX (TEX) files.
+//!
+//! Comments are stripped and `\input` and `\include` are replaced by the files they name, which
+//! are looked up relative to the including file and then to the main file. Only files inside the
+//! directory of the main file are included, each of them once. The body of the document is
+//! converted to Markdown: sectioning commands become headings, lists, quotes,
+//! verbatim blocks and tables keep their structure and math is kept as LaTeX source. Other
+//! commands are dropped and the text of their arguments is kept, except for commands without
+//! text such as `\label` or `\includegraphics`.
+
+use crate::markdown_processor::{markdown_table, MarkdownProcessor};
+use crate::processor::{BytesProcessor, Document, DocumentProcessor, FileProcessor};
+use anyhow::Result;
+use regex::{Captures, Regex};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use text_splitter::ChunkConfigError;
+
+static INCLUDE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\\(?:input|include)\s*\{([^}]*)\}").unwrap());
+
+/// Sectioning commands and the level of their headings.
+const HEADINGS: &[(&str, usize)] = &[
+    ("part", 1),
+    ("chapter", 1),
+    ("section", 2),
+    ("subsection", 3),
+    ("subsubsection", 4),
+    ("paragraph", 5),
+    ("subparagraph", 6),
+];
+
+/// Commands whose arguments hold no text of the document.
+const DROPPED_COMMANDS: &[&str] = &[
+    "addbibresource",
+    "addtocounter",
+    "affiliation",
+    "author",
+    "bibliography",
+    "bibliographystyle",
+    "color",
+    "date",
+    "DeclareMathOperator",
+    "definecolor",
+    "documentclass",
+    "geometry",
+    "graphicspath",
+    "hspace",
+    "hypersetup",
+    "includegraphics",
+    "index",
+    "input",
+    "include",
+    "label",
+    "newcommand",
+    "newenvironment",
+    "newtheorem",
+    "nocite",
+    "pagestyle",
+    "providecommand",
+    "renewcommand",
+    "renewenvironment",
+    "setcounter",
+    "setlength",
+    "thanks",
+    "thispagestyle",
+    "title",
+    "usepackage",
+    "vspace",
+];
+
+/// Environments whose content is not text of the document.
+const DROPPED_ENVIRONMENTS: &[&str] = &["comment", "tikzpicture", "titlepage"];
+
+/// Environments whose content is copied as a code block.
+const VERBATIM_ENVIRONMENTS: &[&str] = &["verbatim", "Verbatim", "lstlisting", "minted"];
+
+/// Environments whose content is copied as a display math block.
+const MATH_ENVIRONMENTS: &[&str] = &[
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "displaymath",
+    "eqnarray",
+    "eqnarray*",
+    "equation",
+    "equation*",
+    "flalign",
+    "flalign*",
+    "gather",
+    "gather*",
+    "math",
+    "multline",
+    "multline*",
+];
+
+/// A struct for processing LaTeX files.
+pub struct TexProcessor {
+    markdown_processor: MarkdownProcessor,
+}
+
+impl TexProcessor {
+    pub fn new(chunk_size: usize, overlap: usize) -> Result<TexProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        Ok(TexProcessor { markdown_processor })
+    }
+
+    /// Returns the text of a LaTeX file and of the files it includes as Markdown.
+    pub fn to_markdown(path: impl AsRef<Path>) -> Result<String> {
+        let path = path.as_ref();
+        let tex = String::from_utf8_lossy(&std::fs::read(path)?).into_owned();
+        let path = path.canonicalize()?;
+        let root = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+        let mut visited = HashSet::from([path]);
+        let tex = resolve_includes(&strip_comments(&tex), &root, &root, &mut visited);
+        Ok(tex_to_markdown(&tex))
+    }
+}
+
+impl FileProcessor for TexProcessor {
+    fn process_file(&self, path: impl AsRef<Path>) -> Result<Document> {
+        let markdown = Self::to_markdown(path)?;
+        self.markdown_processor.process_document(&markdown)
+    }
+}
+
+/// Files named by `\input` and `\include` cannot be looked up without the directory of the file
+/// and are left out.
+impl BytesProcessor for TexProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> Result<Document> {
+        let tex = strip_comments(&String::from_utf8_lossy(bytes));
+        let markdown = tex_to_markdown(&tex);
+        self.markdown_processor.process_document(&markdown)
+    }
+}
+
+/// Converts LaTeX source without comments to Markdown. Only the body of the document is
+/// converted when there is one, preceded by its title.
+pub fn tex_to_markdown(tex: &str) -> String {
+    let title = Regex::new(r"\\title\s*(?:\[[^\]]*\])?\s*\{")
+        .unwrap()
+        .find(tex)
+        .and_then(|title| {
+            let mut parser = Parser::new(&tex[title.end() - 1..]);
+            parser.group()
+        })
+        .map(|title| {
+            render(&title)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|title| !title.is_empty());
+    let body = match (tex.find("\\begin{document}"), tex.find("\\end{document}")) {
+        (Some(start), Some(end)) if start < end => &tex[start + "\\begin{document}".len()..end],
+        (Some(start), None) => &tex[start + "\\begin{document}".len()..],
+        _ => tex,
+    };
+
+    let mut markdown = String::new();
+    if let Some(title) = title {
+        markdown.push_str(&format!("# {}\n\n", title));
+    }
+    markdown.push_str(&render(body));
+    normalize(&markdown)
+}
+
+/// Removes comments, keeping the percent signs of `\%` and of verbatim environments. As in
+/// TeX, a comment also removes the line break and the indentation of the next line.
+fn strip_comments(tex: &str) -> String {
+    let verbatim =
+        Regex::new(r"^\\begin\{(verbatim|Verbatim|lstlisting|minted|comment)\}").unwrap();
+    let mut output = String::with_capacity(tex.len());
+    let mut rest = tex;
+    while let Some(c) = rest.chars().next() {
+        match c {
+            '\\' => {
+                if let Some(name) = verbatim
+                    .captures(rest)
+                    .map(|captures| captures[1].to_string())
+                {
+                    let end = format!("\\end{{{}}}", name);
+                    let length = rest.find(&end).map_or(rest.len(), |i| i + end.len());
+                    output.push_str(&rest[..length]);
+                    rest = &rest[length..];
+                    continue;
+                }
+                let length = rest[1..].chars().next().map_or(1, |c| 1 + c.len_utf8());
+                output.push_str(&rest[..length]);
+                rest = &rest[length..];
+            }
+            '%' => {
+                rest = match rest.find('\n') {
+                    Some(end) => rest[end + 1..].trim_start_matches([' ', '\t']),
+                    None => "",
+                };
+            }
+            c => {
+                output.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    output
+}
+
+/// Replaces `\input` and `\include` with the files they name, themselves without comments and
+/// with their includes resolved. Files that cannot be read, are outside of `root` or were already
+/// included are left out. `dir` and `root` are canonical paths.
+fn resolve_includes(tex: &str, dir: &Path, root: &Path, visited: &mut HashSet<PathBuf>) -> String {
+    INCLUDE
+        .replace_all(tex, |captures: &Captures| {
+            let name = captures[1].trim();
+            let candidates = [dir.join(name), root.join(name)]
+                .into_iter()
+                .flat_map(|path| {
+                    let mut with_extension = path.clone().into_os_string();
+                    with_extension.push(".tex");
+                    [path, PathBuf::from(with_extension)]
+                });
+            let included = candidates
+                .filter(|path| path.is_file())
+                .filter_map(|path| path.canonicalize().ok())
+                .find(|path| path.starts_with(root));
+            let Some(path) = included else {
+                return String::new();
+            };
+            if !visited.insert(path.clone()) {
+                return String::new();
+            }
+            match std::fs::read(&path) {
+                Ok(bytes) => {
+                    let tex = strip_comments(&String::from_utf8_lossy(&bytes));
+                    let dir = path.parent().unwrap_or(root);
+                    format!("\n{}\n", resolve_includes(&tex, dir, root, visited))
+                }
+                Err(_) => String::new(),
+            }
+        })
+        .into_owned()
+}
+
+/// Collapses runs of blank lines and removes trailing white space.
+fn normalize(markdown: &str) -> String {
+    let mut output = String::new();
+    let mut blank_lines = 0;
+    for line in markdown.lines() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+        if !output.is_empty() {
+            output.push_str(if blank_lines > 0 { "\n\n" } else { "\n" });
+        }
+        output.push_str(line);
+        blank_lines = 0;
+    }
+    output
+}
+
+/// A cursor over LaTeX source.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(tex: &str) -> Parser {
+        Parser {
+            chars: tex.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c))
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Reads the content of a balanced pair of delimiters that starts at the cursor, after
+    /// optional white space.
+    fn delimited(&mut self, open: char, close: char) -> Option<String> {
+        let start = self.pos;
+        self.skip_spaces();
+        if self.peek() != Some(open) {
+            self.pos = start;
+            return None;
+        }
+        self.pos += 1;
+        let content_start = self.pos;
+        let mut depth = 1;
+        while let Some(c) = self.peek() {
+            match c {
+                '\\' => self.pos += 1,
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let content = String::from_iter(&self.chars[content_start..self.pos]);
+                        self.pos += 1;
+                        return Some(content);
+                    }
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        Some(String::from_iter(&self.chars[content_start..]))
+    }
+
+    /// Reads a `{...}` argument.
+    fn group(&mut self) -> Option<String> {
+        self.delimited('{', '}')
+    }
+
+    /// Reads a `[...]` argument.
+    fn optional(&mut self) -> Option<String> {
+        self.delimited('[', ']')
+    }
+
+    /// Skips every argument that follows a command.
+    fn skip_arguments(&mut self) {
+        while self.optional().is_some() || self.group().is_some() {}
+    }
+
+    /// Reads the name of a command after its backslash.
+    fn command_name(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            if let Some(c) = self.peek() {
+                self.pos += 1;
+                return c.to_string();
+            }
+        }
+        let name = String::from_iter(&self.chars[start..self.pos]);
+        if self.peek() == Some('*') {
+            self.pos += 1;
+            return name + "*";
+        }
+        name
+    }
+
+    /// Reads the body of an environment up to its `\end`, which may be nested.
+    fn environment_body(&mut self, name: &str) -> String {
+        let begin = format!("\\begin{{{}}}", name);
+        let end = format!("\\end{{{}}}", name);
+        let start = self.pos;
+        let mut depth = 1;
+        while self.pos < self.chars.len() {
+            if self.starts_with(&begin) {
+                depth += 1;
+                self.pos += begin.chars().count();
+            } else if self.starts_with(&end) {
+                depth -= 1;
+                if depth == 0 {
+                    let body = String::from_iter(&self.chars[start..self.pos]);
+                    self.pos += end.chars().count();
+                    return body;
+                }
+                self.pos += end.chars().count();
+            } else {
+                self.pos += 1;
+            }
+        }
+        String::from_iter(&self.chars[start..])
+    }
+
+    /// Reads math up to a closing delimiter.
+    fn math(&mut self, close: &str) -> String {
+        let start = self.pos;
+        while self.pos < self.chars.len() && !self.starts_with(close) {
+            if self.peek() == Some('\\') {
+                self.pos += 1;
+            }
+            self.pos += 1;
+        }
+        let math = String::from_iter(&self.chars[start..self.pos.min(self.chars.len())]);
+        self.pos = (self.pos + close.chars().count()).min(self.chars.len());
+        math
+    }
+}
+
+/// Converts LaTeX source to Markdown.
+fn render(tex: &str) -> String {
+    let mut parser = Parser::new(tex);
+    let mut output = String::new();
+    while let Some(c) = parser.peek() {
+        parser.pos += 1;
+        match c {
+            '\\' => render_command(&mut parser, &mut output),
+            '{' | '}' => {}
+            '~' => output.push(' '),
+            '&' => output.push(' '),
+            '$' if parser.peek() == Some('$') => {
+                parser.pos += 1;
+                let math = parser.math("$$");
+                output.push_str(&format!("\n\n$$\n{}\n$$\n\n", math.trim()));
+            }
+            '$' => {
+                let math = parser.math("$");
+                output.push_str(&format!("${}$", math));
+            }
+            '`' if parser.peek() == Some('`') => {
+                parser.pos += 1;
+                output.push('"');
+            }
+            '\'' if parser.peek() == Some('\'') => {
+                parser.pos += 1;
+                output.push('"');
+            }
+            '-' if parser.starts_with("--") => {
+                parser.pos += 2;
+                output.push('\u{2014}');
+            }
+            '-' if parser.peek() == Some('-') => {
+                parser.pos += 1;
+                output.push('\u{2013}');
+            }
+            '\n' => {
+                // The indentation of the source would make code blocks in Markdown.
+                output.push('\n');
+                while parser.peek().is_some_and(|c| c == ' ' || c == '\t') {
+                    parser.pos += 1;
+                }
+            }
+            c => output.push(c),
+        }
+    }
+    output
+}
+
+fn render_command(parser: &mut Parser, output: &mut String) {
+    let name = parser.command_name();
+    let accent = match name.as_str() {
+        "'" => Some('\u{301}'),
+        "`" => Some('\u{300}'),
+        "^" => Some('\u{302}'),
+        "\"" => Some('\u{308}'),
+        "~" => Some('\u{303}'),
+        "=" => Some('\u{304}'),
+        "." => Some('\u{307}'),
+        "c" => Some('\u{327}'),
+        "v" => Some('\u{30c}'),
+        "u" => Some('\u{306}'),
+        "H" => Some('\u{30b}'),
+        _ => None,
+    };
+    if let Some(accent) = accent {
+        let letter = parser.group().or_else(|| {
+            let c = parser.peek()?;
+            parser.pos += 1;
+            Some(c.to_string())
+        });
+        if let Some(letter) = letter {
+            output.push_str(&render(&letter));
+            output.push(accent);
+        }
+        return;
+    }
+
+    let base = name.trim_end_matches('*');
+    if let Some((_, level)) = HEADINGS.iter().find(|(heading, _)| *heading == base) {
+        parser.optional();
+        let title = parser.group().unwrap_or_default();
+        let title = render(&title)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        output.push_str(&format!("\n\n{} {}\n\n", "#".repeat(*level), title));
+        return;
+    }
+    if DROPPED_COMMANDS.contains(&base) {
+        parser.skip_arguments();
+        return;
+    }
+
+    let text_argument = |parser: &mut Parser| render(&parser.group().unwrap_or_default());
+    match name.as_str() {
+        "\\" => {
+            parser.optional();
+            output.push('\n');
+        }
+        "&" | "%" | "$" | "#" | "_" | "{" | "}" => output.push_str(&name),
+        "," | ";" | ":" | " " | "\n" => output.push(' '),
+        "[" => {
+            let math = parser.math("\\]");
+            output.push_str(&format!("\n\n$$\n{}\n$$\n\n", math.trim()));
+        }
+        "(" => {
+            let math = parser.math("\\)");
+            output.push_str(&format!("${}$", math));
+        }
+        "begin" => {
+            let environment = parser.group().unwrap_or_default();
+            render_environment(parser, &environment, output);
+        }
+        "end" => {
+            parser.group();
+        }
+        "textbf" => output.push_str(&format!("**{}**", text_argument(parser).trim())),
+        "emph" | "textit" | "textsl" => {
+            output.push_str(&format!("*{}*", text_argument(parser).trim()))
+        }
+        "texttt" => output.push_str(&format!("`{}`", text_argument(parser).trim())),
+        "verb" | "verb*" => {
+            if let Some(delimiter) = parser.peek() {
+                parser.pos += 1;
+                let code = parser.math(&delimiter.to_string());
+                output.push_str(&format!("`{}`", code));
+            }
+        }
+        "url" => output.push_str(&parser.group().unwrap_or_default()),
+        "href" => {
+            let url = parser.group().unwrap_or_default();
+            let text = text_argument(parser);
+            output.push_str(&format!("[{}]({})", text.trim(), url));
+        }
+        "footnote" => {
+            parser.optional();
+            output.push_str(&format!(" ({})", text_argument(parser).trim()));
+        }
+        "cite" | "citep" | "citet" | "autocite" | "parencite" | "textcite" | "ref" | "eqref"
+        | "autoref" | "cref" | "Cref" | "pageref" => {
+            parser.optional();
+            parser.optional();
+            output.push_str(&format!("[{}]", parser.group().unwrap_or_default()));
+        }
+        "caption" => {
+            parser.optional();
+            output.push_str(&format!("\n\n{}\n\n", text_argument(parser).trim()));
+        }
+        "multicolumn" => {
+            parser.group();
+            parser.group();
+            output.push_str(&text_argument(parser));
+        }
+        "multirow" => {
+            parser.group();
+            parser.optional();
+            parser.group();
+            output.push_str(&text_argument(parser));
+        }
+        "item" => {
+            let label = parser.optional();
+            output.push_str("\n- ");
+            if let Some(label) = label {
+                output.push_str(&format!("**{}** ", render(&label).trim()));
+            }
+        }
+        "bibitem" => {
+            parser.optional();
+            let key = parser.group().unwrap_or_default();
+            output.push_str(&format!("\n- [{}] ", key));
+        }
+        "maketitle" | "tableofcontents" | "newpage" | "clearpage" | "noindent" | "centering" => {}
+        "ldots" | "dots" => output.push('\u{2026}'),
+        "LaTeX" => output.push_str("LaTeX"),
+        "TeX" => output.push_str("TeX"),
+        "today" => {}
+        _ => {
+            // An unknown command keeps the text of its arguments.
+            while parser.optional().is_some() {}
+            while let Some(argument) = parser.group() {
+                output.push_str(&render(&argument));
+            }
+        }
+    }
+}
+
+fn render_environment(parser: &mut Parser, environment: &str, output: &mut String) {
+    let body = parser.environment_body(environment);
+    let mut body_parser = Parser::new(&body);
+    let base = environment.trim_end_matches('*');
+    let block = match base {
+        "itemize" | "enumerate" | "description" => {
+            body_parser.optional();
+            render_list(
+                &String::from_iter(&body_parser.chars[body_parser.pos..]),
+                base,
+            )
+        }
+        "tabular" | "tabularx" | "longtable" | "tabulary" => {
+            if base != "tabular" {
+                body_parser.group();
+            }
+            body_parser.optional();
+            body_parser.group();
+            render_table(&String::from_iter(&body_parser.chars[body_parser.pos..]))
+        }
+        "quote" | "quotation" | "verse" => normalize(&render(&body))
+            .lines()
+            .map(|line| format!("> {}", line).trim_end().to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "abstract" => format!("## Abstract\n\n{}", render(&body)),
+        "thebibliography" => {
+            body_parser.group();
+            format!(
+                "## References\n\n{}",
+                render(&String::from_iter(&body_parser.chars[body_parser.pos..]))
+            )
+        }
+        "minipage" | "multicols" | "wrapfigure" => {
+            body_parser.skip_arguments();
+            render(&String::from_iter(&body_parser.chars[body_parser.pos..]))
+        }
+        _ if VERBATIM_ENVIRONMENTS.contains(&environment) => {
+            body_parser.optional();
+            let language = match environment {
+                "minted" => body_parser.group().unwrap_or_default(),
+                _ => String::new(),
+            };
+            let code = String::from_iter(&body_parser.chars[body_parser.pos..]);
+            format!("```{}\n{}\n```", language, code.trim_matches('\n'))
+        }
+        _ if MATH_ENVIRONMENTS.contains(&environment) => format!("$$\n{}\n$$", body.trim()),
+        _ if DROPPED_ENVIRONMENTS.contains(&environment) => String::new(),
+        _ => {
+            // Other environments, such as figures or theorems, keep their text.
+            while body_parser.optional().is_some() {}
+            render(&String::from_iter(&body_parser.chars[body_parser.pos..]))
+        }
+    };
+    output.push_str(&format!("\n\n{}\n\n", block));
+}
+
+/// Renders the items of a list. The lines of an item after its first are indented to nest
+/// them under it.
+fn render_list(body: &str, kind: &str) -> String {
+    let item = Regex::new(r"^\\item(?:[^a-zA-Z]|$)").unwrap();
+    let mut items = Vec::new();
+    let mut parser = Parser::new(body);
+    let mut depth = 0usize;
+    let mut current: Option<usize> = None;
+    while parser.pos < parser.chars.len() {
+        let rest =
+            String::from_iter(&parser.chars[parser.pos..(parser.pos + 6).min(parser.chars.len())]);
+        if depth == 0 && item.is_match(&rest) {
+            if let Some(start) = current {
+                items.push(String::from_iter(&parser.chars[start..parser.pos]));
+            }
+            parser.pos += "\\item".len();
+            current = Some(parser.pos);
+            continue;
+        }
+        if parser.starts_with("\\begin{") {
+            depth += 1;
+        } else if parser.starts_with("\\end{") {
+            depth = depth.saturating_sub(1);
+        }
+        match parser.peek() {
+            Some('\\') => parser.pos += 2,
+            Some('{') => {
+                depth += 1;
+                parser.pos += 1;
+            }
+            Some('}') => {
+                depth = depth.saturating_sub(1);
+                parser.pos += 1;
+            }
+            _ => parser.pos += 1,
+        }
+    }
+    if let Some(start) = current {
+        items.push(String::from_iter(&parser.chars[start..]));
+    }
+
+    let mut lines = Vec::new();
+    for (i, item) in items.iter().enumerate() {
+        let mut item_parser = Parser::new(item);
+        let label = item_parser.optional();
+        let mut text = normalize(&render(&String::from_iter(
+            &item_parser.chars[item_parser.pos..],
+        )))
+        .trim_start()
+        .to_string();
+        if let Some(label) = label {
+            let label = render(&label).trim().to_string();
+            text = match kind {
+                "description" => format!("**{}** {}", label, text),
+                _ => format!("{} {}", label, text),
+            };
+        }
+        let marker = match kind {
+            "enumerate" => format!("{}.", i + 1),
+            _ => "-".to_string(),
+        };
+        let indent = " ".repeat(marker.len() + 1);
+        for (j, line) in text.lines().enumerate() {
+            match (j, line.is_empty()) {
+                (0, _) => lines.push(format!("{} {}", marker, line)),
+                (_, true) => lines.push(String::new()),
+                (_, false) => lines.push(format!("{}{}", indent, line)),
+            }
+        }
+        if text.is_empty() {
+            lines.push(marker);
+        }
+    }
+    lines.join("\n")
+}
+
+/// Renders the body of a tabular environment as a Markdown table.
+fn render_table(body: &str) -> String {
+    let rules = Regex::new(r"\\(?:hline|toprule|midrule|bottomrule|endhead|endfirsthead|endfoot|endlastfoot)\b|\\cline\s*\{[^}]*\}|\\cmidrule\s*(?:\([^)]*\))?\s*\{[^}]*\}").unwrap();
+    let body = rules.replace_all(body, "");
+    let rows = split_top_level(&body, "\\\\")
+        .iter()
+        .map(|row| {
+            split_top_level(row, "&")
+                .iter()
+                .map(|cell| {
+                    render(cell)
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+        })
+        .filter(|row| row.iter().any(|cell| !cell.is_empty()))
+        .collect::<Vec<_>>();
+    markdown_table(rows).unwrap_or_default()
+}
+
+/// Splits LaTeX source at a separator outside groups, ignoring escaped separators.
+fn split_top_level(tex: &str, separator: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut parser = Parser::new(tex);
+    let mut depth = 0usize;
+    let mut start = 0;
+    while parser.pos < parser.chars.len() {
+        if depth == 0 && parser.starts_with(separator) {
+            parts.push(String::from_iter(&parser.chars[start..parser.pos]));
+            parser.pos += separator.chars().count();
+            start = parser.pos;
+            continue;
+        }
+        match parser.peek() {
+            Some('\\') => parser.pos += 2,
+            Some('{') => {
+                depth += 1;
+                parser.pos += 1;
+            }
+            Some('}') => {
+                depth = depth.saturating_sub(1);
+                parser.pos += 1;
+            }
+            _ => parser.pos += 1,
+        }
+    }
+    parts.push(String::from_iter(
+        &parser.chars[start.min(parser.chars.len())..],
+    ));
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    const PAPER: &str = r#"\documentclass{article}
+\usepackage[utf8]{inputenc}
+\title{Attention \\ Is All You Need}
+\author{A. Vaswani}
+\begin{document}
+\maketitle
+\begin{abstract}
+  We propose the \textbf{Transformer}.% a new model
+\end{abstract}
+
+\section{Introduction}\label{sec:intro}
+Recurrent models~\cite{rnn} are slow---see Section~\ref{sec:model}.
+Costs 50\% less, like $O(n^2)$.
+\begin{itemize}
+  \item Attention
+  \item Positional encodings
+  \begin{enumerate}
+    \item Sine
+    \item Cosine
+  \end{enumerate}
+\end{itemize}
+
+\subsection*{Results}
+\begin{tabular}{lr}
+\hline
+Model & BLEU \\
+\hline
+Transformer & 28.4 \\
+\end{tabular}
+\begin{equation}
+  E = mc^2
+\end{equation}
+\end{document}
+"#;
+
+    #[test]
+    fn test_tex_to_markdown() {
+        assert_eq!(
+            tex_to_markdown(&strip_comments(PAPER)),
+            "# Attention Is All You Need\n\n\
+             ## Abstract\n\n\
+             We propose the **Transformer**.\n\n\
+             ## Introduction\n\n\
+             Recurrent models [rnn] are slow\u{2014}see Section [sec:model].\n\
+             Costs 50% less, like $O(n^2)$.\n\n\
+             - Attention\n\
+             - Positional encodings\n\n  1. Sine\n  2. Cosine\n\n\
+             ### Results\n\n\
+             | Model | BLEU |\n| --- | --- |\n| Transformer | 28.4 |\n\n\
+             $$\nE = mc^2\n$$"
+        );
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        assert_eq!(
+            strip_comments("a 5\\% b % comment\n  c\n\\begin{verbatim}\n% kept\n\\end{verbatim}"),
+            "a 5\\% b c\n\\begin{verbatim}\n% kept\n\\end{verbatim}"
+        );
+    }
+
+    #[test]
+    fn test_process_file() {
+        let dir = TempDir::new("tex").unwrap();
+        std::fs::create_dir(dir.path().join("sections")).unwrap();
+        std::fs::write(
+            dir.path().join("main.tex"),
+            "\\begin{document}\n\\input{sections/intro}\n% \\input{missing}\n\\include{sections/missing}\n\\end{document}",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("sections/intro.tex"),
+            "\\section{Intro}\nText.\n\\input{details.tex}",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("sections/details.tex"),
+            "\\subsection{Details}\nMore. % comment",
+        )
+        .unwrap();
+
+        assert_eq!(
+            TexProcessor::to_markdown(dir.path().join("main.tex")).unwrap(),
+            "## Intro\n\nText.\n\n### Details\n\nMore."
+        );
+        let document = TexProcessor::new(1000, 0)
+            .unwrap()
+            .process_file(dir.path().join("main.tex"))
+            .unwrap();
+        assert_eq!(document.chunks.len(), 1);
+    }
+
+    #[test]
+    fn test_resolve_includes_outside_root_and_cycles() {
+        let dir = TempDir::new("tex").unwrap();
+        let outside = dir.path().join("secret.tex");
+        std::fs::write(&outside, "Secret.").unwrap();
+        std::fs::create_dir(dir.path().join("paper")).unwrap();
+        std::fs::write(
+            dir.path().join("paper/main.tex"),
+            format!(
+                "\\input{{../secret}}\n\\input{{{}}}\nMain.\n\\input{{loop}}\n\\input{{main}}",
+                outside.display()
+            ),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("paper/loop.tex"),
+            "Loop.\n\\input{loop}\\input{loop}\\input{main}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            TexProcessor::to_markdown(dir.path().join("paper/main.tex")).unwrap(),
+            "Main.\n\nLoop."
+        );
+    }
+}
@@ -0,0 +1,298 @@
+//! A minimal XML tree used by the processors of XML based formats.
+//!
+//! Documents are parsed with `xmlparser` into [Element]s that keep the order of their children.
+//! Elements are matched by their local name, attributes by their qualified name, e.g. `r:id`.
+//! DTDs, comments and processing instructions are skipped. The parts of zip packages and the
+//! relationships between the parts of Office Open XML packages are read with the same tree.
+
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+
+use anyhow::{anyhow, bail, Result};
+use xmlparser::{ElementEnd, Token, Tokenizer};
+use zip::ZipArchive;
+
+/// A child of an [Element].
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct Element {
+    pub prefix: String,
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+impl Element {
+    /// Parses a document and returns its root element.
+    pub fn parse(xml: &str) -> Result<Element> {
+        let mut stack: Vec<Element> = Vec::new();
+        let mut root = None;
+        for token in Tokenizer::from(xml) {
+            match token? {
+                Token::ElementStart { prefix, local, .. } => stack.push(Element {
+                    prefix: prefix.to_string(),
+                    name: local.to_string(),
+                    ..Default::default()
+                }),
+                Token::Attribute {
+                    prefix,
+                    local,
+                    value,
+                    ..
+                } => {
+                    let name = match prefix.is_empty() {
+                        true => local.to_string(),
+                        false => format!("{}:{}", prefix, local),
+                    };
+                    if let Some(element) = stack.last_mut() {
+                        element.attributes.push((name, unescape(&value)));
+                    }
+                }
+                Token::ElementEnd { end, .. } => {
+                    if let ElementEnd::Open = end {
+                        continue;
+                    }
+                    let element = stack.pop().ok_or_else(|| anyhow!("unbalanced XML"))?;
+                    if let ElementEnd::Close(prefix, local) = end {
+                        if prefix.as_str() != element.prefix || local.as_str() != element.name {
+                            bail!("unexpected closing tag </{}>", local);
+                        }
+                    }
+                    match stack.last_mut() {
+                        Some(parent) => parent.children.push(Node::Element(element)),
+                        None => root = Some(element),
+                    }
+                }
+                Token::Text { text } => {
+                    if let Some(element) = stack.last_mut() {
+                        element.children.push(Node::Text(unescape(&text)));
+                    }
+                }
+                Token::Cdata { text, .. } => {
+                    if let Some(element) = stack.last_mut() {
+                        element.children.push(Node::Text(text.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !stack.is_empty() {
+            bail!("unclosed XML element <{}>", stack[stack.len() - 1].name);
+        }
+        root.ok_or_else(|| anyhow!("the XML document has no root element"))
+    }
+
+    /// Returns the value of the attribute with the qualified `name`.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the child elements.
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// Returns the first child element named `name`.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    /// Returns the elements named `name` below this element in document order. The descendants
+    /// of a match are not searched.
+    pub fn find_all<'a>(&'a self, name: &str) -> Vec<&'a Element> {
+        let mut found = Vec::new();
+        for element in self.elements() {
+            match element.name == name {
+                true => found.push(element),
+                false => found.extend(element.find_all(name)),
+            }
+        }
+        found
+    }
+
+    /// Returns the concatenated text of this element and its descendants.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for node in &self.children {
+            match node {
+                Node::Element(element) => text.push_str(&element.text()),
+                Node::Text(t) => text.push_str(t),
+            }
+        }
+        text
+    }
+}
+
+/// Parses the XML part `name` of a zip package such as a PPTX file.
+pub(crate) fn parse_zip_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Element> {
+    let mut xml = String::new();
+    archive
+        .by_name(name)
+        .map_err(|e| anyhow!("{}: {}", name, e))?
+        .read_to_string(&mut xml)?;
+    Element::parse(&xml).map_err(|e| anyhow!("{}: {}", name, e))
+}
+
+/// A relationship of a part of an Office Open XML package, such as a slide of a presentation.
+pub(crate) struct Relationship {
+    pub kind: String,
+    /// Path of the target part inside the package.
+    pub target: String,
+}
+
+/// Reads the relationships of `part` from its `_rels` part, keyed by their id. External targets
+/// are left out.
+pub(crate) fn read_relationships<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    part: &str,
+) -> Result<HashMap<String, Relationship>> {
+    let (directory, file_name) = part.rsplit_once('/').unwrap_or(("", part));
+    let rels_part = format!("{}/_rels/{}.rels", directory, file_name);
+    if archive.index_for_name(&rels_part).is_none() {
+        return Ok(HashMap::new());
+    }
+    let rels = parse_zip_entry(archive, &rels_part)?;
+    Ok(rels
+        .find_all("Relationship")
+        .into_iter()
+        .filter(|relationship| relationship.attribute("TargetMode") != Some("External"))
+        .filter_map(|relationship| {
+            let id = relationship.attribute("Id")?;
+            let target = relationship.attribute("Target")?;
+            Some((
+                id.to_string(),
+                Relationship {
+                    kind: relationship
+                        .attribute("Type")
+                        .unwrap_or_default()
+                        .to_string(),
+                    target: resolve_target(directory, target),
+                },
+            ))
+        })
+        .collect())
+}
+
+/// Resolves a relationship target relative to `directory` to a path inside the package.
+pub(crate) fn resolve_target(directory: &str, target: &str) -> String {
+    let mut segments = match target.starts_with('/') {
+        true => Vec::new(),
+        false => directory
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>(),
+    };
+    for segment in target.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Replaces the predefined entities and character references of XML.
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let character = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse().ok()))
+                .flatten()
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                unescaped.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let xml = r#"<?xml version="1.0"?>
+            <p:root xmlns:p="urn:p" xmlns:r="urn:r">
+                <p:item id="1" r:id="rId2">Fish &amp; chips &#233;<b>!</b></p:item>
+                <p:item id="2"/><![CDATA[<raw>]]>
+            </p:root>"#;
+        let root = Element::parse(xml).unwrap();
+        assert_eq!((root.prefix.as_str(), root.name.as_str()), ("p", "root"));
+
+        let items = root.find_all("item");
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].attribute("id"), Some("1"));
+        assert_eq!(items[0].attribute("r:id"), Some("rId2"));
+        assert_eq!(items[0].text(), "Fish & chips \u{e9}!");
+        assert!(items[1].children.is_empty());
+        assert!(root.text().contains("<raw>"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Element::parse("<a><b></a>").is_err());
+        assert!(Element::parse("<a>").is_err());
+        assert!(Element::parse("").is_err());
+    }
+
+    #[test]
+    fn test_resolve_target() {
+        assert_eq!(
+            resolve_target("ppt/slides", "../notesSlides/notesSlide1.xml"),
+            "ppt/notesSlides/notesSlide1.xml"
+        );
+        assert_eq!(
+            resolve_target("ppt", "/ppt/slides/slide1.xml"),
+            "ppt/slides/slide1.xml"
+        );
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("a &lt; b &gt; c"), "a < b > c");
+        assert_eq!(unescape("&#x41;&#66;"), "AB");
+        assert_eq!(unescape("AT&T &unknown;"), "AT&T &unknown;");
+    }
+}
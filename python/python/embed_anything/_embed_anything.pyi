@@ -304,6 +304,28 @@ def embed_audio_file(
 
     """
 
+def embed_audio_directory(
+    directory: str,
+    audio_decoder: AudioDecoderModel,
+    embedder: EmbeddingModel,
+    config: TextEmbedConfig | None = None,
+    adapter: Adapter | None = None,
+) -> list[EmbedData] | None:
+    """
+    Transcribes and embeds the audio files in the given directory, including the audio tracks of
+    mp4, mkv and webm videos.
+
+    Args:
+        directory: The path to the directory containing the audio files to embed.
+        audio_decoder: The audio decoder model to use.
+        embedder: The embedding model to use.
+        config: The configuration for the embedding model.
+        adapter: The adapter to use for storing the embeddings in a vector database.
+
+    Returns:
+        A list of EmbedData objects, or None if an adapter is used.
+    """
+
 class EmbedData:
     """Represents the data of an embedded file.
 
@@ -761,6 +783,26 @@ class EmbeddingModel:
             A list of EmbedData objects.
         """
 
+    def embed_audio_directory(
+        self,
+        directory: str,
+        audio_decoder: AudioDecoderModel,
+        config: TextEmbedConfig | None = None,
+        adapter: Adapter | None = None,
+    ) -> list[EmbedData] | None:
+        """
+        Transcribes and embeds the audio files in the given directory.
+
+        Args:
+            directory: The path to the directory containing the audio files to embed.
+            audio_decoder: The audio decoder for the audio files.
+            config: The configuration for the embedding.
+            adapter: The adapter to use for storing the embeddings in a vector database.
+
+        Returns:
+            A list of EmbedData objects, or None if an adapter is used.
+        """
+
     def embed_query(
         self,
         query: list[str],
@@ -896,6 +938,7 @@ class WhichModel(Enum):
     SparseBert = ("SparseBert",)
     ModernBert = ("ModernBert",)
     Qwen3 = ("Qwen3",)
+    Clap = ("Clap",)
 
 class ONNXModel(Enum):
     """
@@ -1,6 +1,6 @@
 pub mod config;
 pub mod models;
-use embed_anything::embeddings::embed::{TextEmbedder, VisionEmbedder};
+use embed_anything::embeddings::embed::{AudioEmbedder, TextEmbedder, VisionEmbedder};
 use embed_anything::{
     self,
     config::TextEmbedConfig,
@@ -45,6 +45,17 @@ impl EmbedData {
                         .unwrap()
                         .into()
                 }
+                // Compressed embeddings are decompressed.
+                embedding => PyList::new(
+                    py,
+                    embedding
+                        .to_multi_vector()
+                        .unwrap_or_default()
+                        .iter()
+                        .map(|inner| PyList::new(py, inner).unwrap()),
+                )
+                .unwrap()
+                .into(),
             }
         })
     }
@@ -99,6 +110,7 @@ pub enum WhichModel {
     Jina,
     ModernBert,
     Colpali,
+    Clap,
 }
 
 #[pyclass(eq, eq_int)]
@@ -282,6 +294,18 @@ impl EmbeddingModel {
                     inner: Arc::new(model),
                 })
             }
+            WhichModel::Clap => {
+                let model_id = model_id.unwrap_or("laion/clap-htsat-unfused");
+                let model = Embedder::Audio(Box::new(AudioEmbedder::Clap(Box::new(
+                    embed_anything::embeddings::local::clap::ClapEmbedder::new(
+                        model_id, revision, token,
+                    )
+                    .map_err(|e| PyValueError::new_err(e.to_string()))?,
+                ))));
+                Ok(EmbeddingModel {
+                    inner: Arc::new(model),
+                })
+            }
 
             _ => panic!("Invalid model"),
         }
@@ -493,6 +517,17 @@ impl EmbeddingModel {
     ) -> PyResult<Option<Vec<EmbedData>>> {
         embed_audio_file(audio_file, audio_decoder, self, config)
     }
+
+    #[pyo3(signature = (directory, audio_decoder, config=None, adapter=None))]
+    pub fn embed_audio_directory(
+        &self,
+        directory: PathBuf,
+        audio_decoder: &mut AudioDecoderModel,
+        config: Option<&config::TextEmbedConfig>,
+        adapter: Option<PyObject>,
+    ) -> PyResult<Option<Vec<EmbedData>>> {
+        embed_audio_directory(directory, audio_decoder, self, config, adapter)
+    }
 }
 
 #[pyclass]
@@ -712,6 +747,62 @@ pub fn embed_audio_file(
     Ok(data)
 }
 
+#[pyfunction]
+#[pyo3(signature = (directory, audio_decoder, embedder, config=None, adapter = None))]
+pub fn embed_audio_directory(
+    directory: PathBuf,
+    audio_decoder: &mut AudioDecoderModel,
+    embedder: &EmbeddingModel,
+    config: Option<&config::TextEmbedConfig>,
+    adapter: Option<PyObject>,
+) -> PyResult<Option<Vec<EmbedData>>> {
+    let config = config.map(|c| &c.inner);
+    let embedding_model = &embedder.inner;
+    let audio_decoder = &mut audio_decoder.inner;
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+    let adapter = match adapter {
+        Some(adapter) => {
+            let callback = move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
+                Python::with_gil(|py| {
+                    let upsert_fn = adapter.getattr(py, "upsert").unwrap();
+                    let converted_data = data
+                        .into_iter()
+                        .map(|data| EmbedData { inner: data })
+                        .collect::<Vec<EmbedData>>();
+                    upsert_fn
+                        .call1(py, (converted_data,))
+                        .map_err(|e| PyValueError::new_err(e.to_string()))
+                        .unwrap();
+                });
+            };
+            Some(callback)
+        }
+        None => None,
+    };
+
+    let data = rt.block_on(async {
+        embed_anything::embed_audio_directory(
+            directory,
+            audio_decoder,
+            embedding_model,
+            config,
+            adapter.map(|f| {
+                Box::new(f)
+                    as Box<
+                        dyn FnMut(Vec<embed_anything::embeddings::embed::EmbedData>) + Send + Sync,
+                    >
+            }),
+        )
+        .await
+        .map_err(|e| PyValueError::new_err(e.to_string()))
+    })?;
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect::<Vec<_>>()
+    }))
+}
+
 #[pyfunction]
 #[pyo3(signature = (directory, embedder, extensions=None, config=None, adapter = None))]
 pub fn embed_directory(
@@ -888,6 +979,7 @@ fn _embed_anything(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(embed_query, m)?)?;
     m.add_function(wrap_pyfunction!(embed_webpage, m)?)?;
     m.add_function(wrap_pyfunction!(embed_audio_file, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_audio_directory, m)?)?;
     m.add_class::<ColpaliModel>()?;
     m.add_class::<ColbertModel>()?;
     m.add_class::<EmbeddingModel>()?;
@@ -364,6 +364,7 @@ mod tests {
                 tesseract_path: None,
             },
             Some(PdfBackend::LoPdf),
+            None,
         )
         .unwrap();
         let chunker = StatisticalChunker {
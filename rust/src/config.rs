@@ -4,8 +4,14 @@
 //! including chunking strategies, batch sizes, and splitting methods.
 
 use processors_rs::pdf::pdf_processor::PdfBackend;
+use processors_rs::registry::ProcessorRegistry;
+use processors_rs::structured_processor::StructuredConfig;
+use processors_rs::tabular_processor::TabularConfig;
 
+use crate::embeddings::compression::MultiVectorCompression;
 use crate::embeddings::embed::Embedder;
+use crate::file_processor::audio::export::TranscriptFormat;
+use std::ops::RangeInclusive;
 use std::sync::Arc;
 
 /// Configuration for text embedding.
@@ -59,6 +65,35 @@ pub struct TextEmbedConfig {
     pub late_chunking: Option<bool>,
     /// When embedding a PDF, controls which backend is used to extract text. Defaults to [PdfBackend::LoPdf]
     pub pdf_backend: PdfBackend,
+    /// When embedding a PDF with a vision embedder, controls how the pages are rendered to images.
+    /// See [PdfRenderConfig].
+    pub pdf_render_config: PdfRenderConfig,
+    /// When embedding a PDF, also embed the images inside it with a vision embedder. See
+    /// [PdfImageConfig]. Defaults to None, which embeds the text only.
+    pub pdf_image_config: Option<PdfImageConfig>,
+    /// When embedding audio, controls how the transcript is grouped into chunks before it is
+    /// embedded. Defaults to [AudioChunking::Segment].
+    pub audio_chunking: AudioChunking,
+    /// When embedding audio, the transcript is also saved next to the audio file in each of these
+    /// formats, as `talk.mp3.srt` for `talk.mp3`. Defaults to none.
+    pub transcript_formats: Vec<TranscriptFormat>,
+    /// When embedding a CSV, TSV or XLSX file, controls how rows are rendered and grouped into
+    /// chunks. See [TabularConfig]. Defaults to one chunk per row listing every column.
+    pub tabular_config: TabularConfig,
+    /// When embedding a JSON, JSONL or XML file, controls which records and fields are embedded
+    /// and which fields are added to the metadata. See [StructuredConfig]. Defaults to one chunk
+    /// per record listing every field.
+    pub structured_config: StructuredConfig,
+    /// When embedding a Jupyter notebook, controls whether the text outputs of code cells are
+    /// embedded with them. Defaults to false.
+    pub notebook_outputs: bool,
+    /// Detects the format of files to pick the processor that extracts their text, and holds the
+    /// processors of custom formats. See [ProcessorRegistry]. Defaults to the built-in formats.
+    pub registry: Arc<ProcessorRegistry>,
+    /// Compresses the multi-vector embeddings of documents, such as those of ColBERT and ColPali,
+    /// as they are embedded. Dense embeddings are left as they are. See
+    /// [MultiVectorCompression]. Defaults to None.
+    pub multi_vector_compression: Option<MultiVectorCompression>,
 }
 
 impl Default for TextEmbedConfig {
@@ -73,6 +108,15 @@ impl Default for TextEmbedConfig {
             use_ocr: None,
             tesseract_path: None,
             pdf_backend: PdfBackend::LoPdf,
+            pdf_render_config: PdfRenderConfig::default(),
+            pdf_image_config: None,
+            audio_chunking: AudioChunking::Segment,
+            transcript_formats: Vec::new(),
+            tabular_config: TabularConfig::default(),
+            structured_config: StructuredConfig::default(),
+            notebook_outputs: false,
+            registry: Arc::new(ProcessorRegistry::new()),
+            multi_vector_compression: None,
         }
     }
 }
@@ -145,11 +189,157 @@ impl TextEmbedConfig {
         self
     }
 
+    /// Set how PDF pages are rendered when embedding a PDF with a vision embedder.
+    pub fn with_pdf_render_config(mut self, pdf_render_config: PdfRenderConfig) -> Self {
+        self.pdf_render_config = pdf_render_config;
+        self
+    }
+
+    /// Embed the images inside PDFs next to their text. See [PdfImageConfig].
+    pub fn with_pdf_images(mut self, pdf_image_config: PdfImageConfig) -> Self {
+        self.pdf_image_config = Some(pdf_image_config);
+        self
+    }
+
+    /// Set how audio transcripts are grouped into chunks. See [AudioChunking].
+    pub fn with_audio_chunking(mut self, audio_chunking: AudioChunking) -> Self {
+        self.audio_chunking = audio_chunking;
+        self
+    }
+
+    /// Save the transcripts of embedded audio files next to them, for example as subtitles.
+    pub fn with_transcript_export(mut self, formats: &[TranscriptFormat]) -> Self {
+        self.transcript_formats = formats.to_vec();
+        self
+    }
+
+    /// Set how the rows of CSV, TSV and XLSX files are rendered and grouped into chunks.
+    pub fn with_tabular_config(mut self, tabular_config: TabularConfig) -> Self {
+        self.tabular_config = tabular_config;
+        self
+    }
+
+    /// Set which records and fields of JSON, JSONL and XML files are embedded.
+    pub fn with_structured_config(mut self, structured_config: StructuredConfig) -> Self {
+        self.structured_config = structured_config;
+        self
+    }
+
+    /// Embed the text outputs of the code cells of Jupyter notebooks along with their source.
+    pub fn with_notebook_outputs(mut self, notebook_outputs: bool) -> Self {
+        self.notebook_outputs = notebook_outputs;
+        self
+    }
+
+    /// Set the registry that detects file formats, for instance one with processors for custom
+    /// formats registered with [ProcessorRegistry::with_processor].
+    pub fn with_registry(mut self, registry: ProcessorRegistry) -> Self {
+        self.registry = Arc::new(registry);
+        self
+    }
+
+    /// Compress the multi-vector embeddings of documents. See [MultiVectorCompression].
+    pub fn with_multi_vector_compression(mut self, compression: MultiVectorCompression) -> Self {
+        self.multi_vector_compression = Some(compression);
+        self
+    }
+
     pub fn build(self) -> TextEmbedConfig {
         self
     }
 }
 
+/// Controls how PDF pages are rendered to images for vision embedders such as ColPali.
+///
+/// # Example
+///
+/// ```rust
+/// use embed_anything::config::PdfRenderConfig;
+/// let config = PdfRenderConfig::default().with_dpi(200).with_pages(2..=5);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PdfRenderConfig {
+    /// The resolution in dots per inch at which pages are rendered. Defaults to 150.
+    pub dpi: u32,
+    /// The 1-based, inclusive range of pages to render. Pages past the end of the document are
+    /// ignored. Defaults to None, which renders every page.
+    pub pages: Option<RangeInclusive<u32>>,
+}
+
+impl Default for PdfRenderConfig {
+    fn default() -> Self {
+        Self {
+            dpi: 150,
+            pages: None,
+        }
+    }
+}
+
+impl PdfRenderConfig {
+    pub fn new(dpi: Option<u32>, pages: Option<RangeInclusive<u32>>) -> Self {
+        Self {
+            dpi: dpi.unwrap_or(150),
+            pages,
+        }
+    }
+
+    pub fn with_dpi(mut self, dpi: u32) -> Self {
+        self.dpi = dpi;
+        self
+    }
+
+    pub fn with_pages(mut self, pages: RangeInclusive<u32>) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+
+    /// Returns the pages to render for a document with `page_count` pages, clamped to the
+    /// document. Returns None if no page falls in the range.
+    pub fn page_range(&self, page_count: u32) -> Option<RangeInclusive<u32>> {
+        let (start, end) = match &self.pages {
+            Some(pages) => ((*pages.start()).max(1), (*pages.end()).min(page_count)),
+            None => (1, page_count),
+        };
+        (start <= end).then_some(start..=end)
+    }
+}
+
+/// Configures multimodal PDF embedding, where the raster images embedded in a PDF are embedded
+/// with a vision model and returned alongside the text chunks.
+///
+/// Text chunks and images of the same page share the "page_number" and file metadata. Every
+/// record also carries a "content_type" of "text", "figure" or "page".
+#[derive(Clone)]
+pub struct PdfImageConfig {
+    /// The vision embedder, such as CLIP or SigLIP, used for the images.
+    pub image_embedder: Arc<Embedder>,
+    /// Images whose width or height is below this many pixels are skipped. Defaults to 64.
+    pub min_image_size: u32,
+    /// When true, every page is also rendered with [TextEmbedConfig::pdf_render_config] and
+    /// embedded as a whole. Defaults to false.
+    pub include_page_images: bool,
+}
+
+impl PdfImageConfig {
+    pub fn new(image_embedder: Arc<Embedder>) -> Self {
+        Self {
+            image_embedder,
+            min_image_size: 64,
+            include_page_images: false,
+        }
+    }
+
+    pub fn with_min_image_size(mut self, min_image_size: u32) -> Self {
+        self.min_image_size = min_image_size;
+        self
+    }
+
+    pub fn with_page_images(mut self, include_page_images: bool) -> Self {
+        self.include_page_images = include_page_images;
+        self
+    }
+}
+
 #[derive(Clone)]
 pub enum SplittingStrategy {
     /// Splits text-based content by sentence, resulting in one embedding per sentence.
@@ -162,10 +352,28 @@ pub enum SplittingStrategy {
     },
 }
 
+/// Controls how an audio transcript is grouped into chunks before it is embedded.
+///
+/// Chunks are built from the timestamped phrases of the transcript, so enable timestamps on the
+/// audio decoder for fine-grained windows. Without them, whole decoder segments are grouped.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AudioChunking {
+    /// One chunk per decoder segment of up to 30 seconds.
+    Segment,
+    /// Groups consecutive phrases into windows of at most `seconds` seconds.
+    FixedDuration { seconds: f64 },
+    /// Groups consecutive phrases until a sentence ends, starting a new chunk early if the
+    /// current one would exceed `max_seconds` seconds.
+    Sentence { max_seconds: f64 },
+}
+
 #[derive(Clone)]
 pub struct ImageEmbedConfig {
     pub buffer_size: Option<usize>, // Required for adapter. Default is 100.
     pub batch_size: Option<usize>,
+    /// Compresses the multi-vector embeddings of images, such as those of ColPali. See
+    /// [MultiVectorCompression]. Defaults to None.
+    pub multi_vector_compression: Option<MultiVectorCompression>,
 }
 
 impl Default for ImageEmbedConfig {
@@ -173,6 +381,7 @@ impl Default for ImageEmbedConfig {
         Self {
             buffer_size: Some(100),
             batch_size: Some(32),
+            multi_vector_compression: None,
         }
     }
 }
@@ -182,6 +391,33 @@ impl ImageEmbedConfig {
         Self {
             buffer_size,
             batch_size,
+            multi_vector_compression: None,
         }
     }
+
+    /// Compress the multi-vector embeddings of images. See [MultiVectorCompression].
+    pub fn with_multi_vector_compression(mut self, compression: MultiVectorCompression) -> Self {
+        self.multi_vector_compression = Some(compression);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pdf_render_page_range() {
+        let config = PdfRenderConfig::default();
+        assert_eq!(config.page_range(4), Some(1..=4));
+        assert_eq!(config.page_range(0), None);
+
+        let config = config.with_pages(0..=10);
+        assert_eq!(config.page_range(4), Some(1..=4));
+
+        let config = PdfRenderConfig::new(Some(200), Some(3..=3));
+        assert_eq!(config.dpi, 200);
+        assert_eq!(config.page_range(4), Some(3..=3));
+        assert_eq!(config.page_range(2), None);
+    }
 }
@@ -1,12 +1,14 @@
 use std::{collections::HashMap, fs};
 
 use base64::Engine;
+use image::DynamicImage;
 use reqwest::Client;
 use serde::Deserialize;
 use serde_json::json;
 
 use crate::embeddings::embed::{EmbedData, EmbeddingResult};
 use crate::embeddings::local::colpali::get_images_from_pdf;
+use crate::embeddings::local::image_processor::open_image;
 
 /// Represents the response from the Cohere embedding API.
 #[derive(Deserialize, Debug, Default)]
@@ -68,7 +70,11 @@ impl CohereEmbedder {
     }
 
     fn load_image<T: AsRef<std::path::Path>>(&self, path: T) -> Result<String, anyhow::Error> {
-        let img = image::ImageReader::open(path)?.decode()?;
+        let img = open_image(path)?;
+        self.encode_image(&img)
+    }
+
+    fn encode_image(&self, img: &DynamicImage) -> Result<String, anyhow::Error> {
         let img = img.to_rgb8();
         let mut buffer = Vec::new();
         img.write_to(
@@ -167,30 +173,7 @@ impl CohereEmbedder {
         let mut embeddings = Vec::new();
         for image_path in image_paths.chunks(batch_size.unwrap_or(32)) {
             let imgs = self.load_image_batch(image_path)?;
-            let response = self
-                .client
-                .post(&self.url)
-                .header("Accept", "application/json")
-                .header("Content-Type", "application/json")
-                .header("Authorization", format!("Bearer {}", self.api_key))
-                .json(&json!({
-                    "images": imgs,
-                    "model": self.model,
-                    "input_type": "image"
-                }))
-                .send()
-                .await?;
-
-            let data = match response.error_for_status() {
-                Ok(resp) => resp.json::<CohereEmbedResponse>().await?,
-                Err(e) => {
-                    println!("❌ API Error: {}", e);
-                    return Err(anyhow::anyhow!("API request failed: {}", e));
-                }
-            };
-            let encodings = data.embeddings;
-            let embedding = encodings.float.iter().cloned();
-            embeddings.extend(embedding);
+            embeddings.extend(self.embed_encoded_images(&imgs).await?);
         }
         let embeddings = embeddings
             .iter()
@@ -216,6 +199,51 @@ impl CohereEmbedder {
         Ok(embeddings)
     }
 
+    /// Embeds decoded images, such as images received as bytes. The results carry no metadata.
+    pub async fn embed_dynamic_images(
+        &self,
+        images: &[DynamicImage],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbedData>, anyhow::Error> {
+        let mut embeddings = Vec::new();
+        for image_batch in images.chunks(batch_size.unwrap_or(32)) {
+            let imgs = image_batch
+                .iter()
+                .map(|img| self.encode_image(img))
+                .collect::<Result<Vec<_>, _>>()?;
+            embeddings.extend(
+                self.embed_encoded_images(&imgs)
+                    .await?
+                    .into_iter()
+                    .map(|data| EmbedData::new(EmbeddingResult::DenseVector(data), None, None)),
+            );
+        }
+        Ok(embeddings)
+    }
+
+    async fn embed_encoded_images(&self, imgs: &[String]) -> Result<Vec<Vec<f32>>, anyhow::Error> {
+        let response = self
+            .client
+            .post(&self.url)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .header("Authorization", format!("Bearer {}", self.api_key))
+            .json(&json!({
+                "images": imgs,
+                "model": self.model,
+                "input_type": "image"
+            }))
+            .send()
+            .await?;
+
+        let data = response
+            .error_for_status()
+            .map_err(|e| anyhow::anyhow!("API request failed: {}", e))?
+            .json::<CohereEmbedResponse>()
+            .await?;
+        Ok(data.embeddings.float)
+    }
+
     pub async fn embed_pdf(
         &self,
         file_path: impl AsRef<std::path::Path>,
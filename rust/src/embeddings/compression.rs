@@ -0,0 +1,672 @@
+//! Compression for multi-vector embeddings.
+//!
+//! Late-interaction models such as ColPali produce one vector per image patch or token, which
+//! quickly makes storage the bottleneck. The strategies in [MultiVectorCompression] trade a
+//! little retrieval quality for a much smaller index:
+//!
+//! - **Token pooling** clusters similar vectors hierarchically and averages each cluster, reducing
+//!   the number of vectors by a pool factor.
+//! - **Binarization** keeps only the sign of every dimension, packed into bits.
+//! - **Residual compression** stores each vector as the id of its nearest centroid plus a
+//!   low-bit quantized residual, as in PLAID. The centroids and residual buckets form a
+//!   [ResidualCodebook] that is fitted once on a sample of the corpus and shared by every
+//!   embedding compressed with it.
+//!
+//! Set a strategy with [TextEmbedConfig::with_multi_vector_compression] or
+//! [ImageEmbedConfig::with_multi_vector_compression] to compress the embeddings of documents and
+//! images as they are embedded, or call [MultiVectorCompression::compress] directly.
+//!
+//! [TextEmbedConfig::with_multi_vector_compression]: crate::config::TextEmbedConfig::with_multi_vector_compression
+//! [ImageEmbedConfig::with_multi_vector_compression]: crate::config::ImageEmbedConfig::with_multi_vector_compression
+//!
+//! # Example
+//!
+//! ```rust
+//! use embed_anything::embeddings::compression::MultiVectorCompression;
+//! use embed_anything::embeddings::embed::EmbeddingResult;
+//!
+//! let embedding = EmbeddingResult::MultiVector(vec![vec![0.6, 0.8], vec![0.8, 0.6], vec![-1.0, 0.0]]);
+//! let pooled = MultiVectorCompression::TokenPooling { pool_factor: 2 }
+//!     .compress(embedding)
+//!     .unwrap();
+//! assert_eq!(pooled.to_multi_vector().unwrap().len(), 2);
+//! ```
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use super::embed::{EmbedData, EmbeddingResult};
+use crate::scoring::dot_product;
+
+/// Number of k-means iterations used to fit the centroids for residual compression.
+const KMEANS_ITERATIONS: usize = 10;
+
+/// Compression strategy applied to multi-vector embeddings.
+#[derive(Debug, Clone)]
+pub enum MultiVectorCompression {
+    /// Hierarchically merges the most similar vectors until at most `n / pool_factor` clusters
+    /// remain, and replaces each cluster by the mean of its vectors.
+    TokenPooling { pool_factor: usize },
+    /// Keeps the sign of every dimension, packed into 1 bit per dimension.
+    Binary,
+    /// Assigns every vector to the nearest centroid of the codebook and quantizes the residual
+    /// with its buckets. See [ResidualCodebook::fit].
+    Residual(Arc<ResidualCodebook>),
+}
+
+/// Vectors binarized by [MultiVectorCompression::Binary].
+#[derive(Deserialize, Debug, Clone)]
+pub struct BinaryMultiVector {
+    pub dim: usize,
+    /// One packed bit per dimension, set when the value is positive.
+    pub codes: Vec<Vec<u8>>,
+}
+
+/// Centroids and residual buckets shared by the embeddings of a corpus for
+/// [MultiVectorCompression::Residual].
+///
+/// # Example
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use embed_anything::embeddings::compression::{MultiVectorCompression, ResidualCodebook};
+/// use embed_anything::embeddings::embed::EmbeddingResult;
+///
+/// let sample = vec![
+///     vec![vec![1.0, 0.0], vec![0.9, 0.1]],
+///     vec![vec![0.0, 1.0], vec![0.1, 0.9]],
+/// ];
+/// let codebook = ResidualCodebook::fit(&sample, 2, 2).unwrap();
+/// let compression = MultiVectorCompression::Residual(Arc::new(codebook));
+/// let embedding = EmbeddingResult::MultiVector(vec![vec![0.8, 0.2]]);
+/// let compressed = compression.compress(embedding).unwrap();
+/// assert_eq!(compressed.to_multi_vector().unwrap().len(), 1);
+/// ```
+#[derive(Deserialize, Debug, Clone)]
+pub struct ResidualCodebook {
+    pub dim: usize,
+    pub nbits: u8,
+    pub centroids: Vec<Vec<f32>>,
+    /// Smallest residual of each bucket after the first.
+    pub cutoffs: Vec<f32>,
+    /// Value that each of the `2^nbits` residual buckets decodes to.
+    pub bucket_weights: Vec<f32>,
+}
+
+/// Vectors compressed by [MultiVectorCompression::Residual].
+#[derive(Deserialize, Debug, Clone)]
+pub struct ResidualMultiVector {
+    pub codebook: Arc<ResidualCodebook>,
+    /// Index of the nearest centroid for each vector.
+    pub codes: Vec<u32>,
+    /// Bucket ids of each residual dimension, packed `nbits` per dimension.
+    pub residuals: Vec<Vec<u8>>,
+}
+
+/// A compressed multi-vector embedding.
+#[derive(Deserialize, Debug, Clone)]
+pub enum CompressedMultiVector {
+    Binary(BinaryMultiVector),
+    Residual(ResidualMultiVector),
+}
+
+impl CompressedMultiVector {
+    /// Reconstructs approximate vectors from the compressed representation.
+    ///
+    /// Binary vectors decode to `±1/sqrt(dim)` so that they keep unit norm.
+    pub fn decompress(&self) -> Vec<Vec<f32>> {
+        match self {
+            CompressedMultiVector::Binary(binary) => {
+                let value = 1.0 / (binary.dim as f32).sqrt();
+                binary
+                    .codes
+                    .iter()
+                    .map(|code| {
+                        unpack_bits(code, 1, binary.dim)
+                            .into_iter()
+                            .map(|bit| if bit == 1 { value } else { -value })
+                            .collect()
+                    })
+                    .collect()
+            }
+            CompressedMultiVector::Residual(residual) => {
+                let codebook = &residual.codebook;
+                residual
+                    .codes
+                    .iter()
+                    .zip(&residual.residuals)
+                    .map(|(&code, packed)| {
+                        codebook.centroids[code as usize]
+                            .iter()
+                            .zip(unpack_bits(packed, codebook.nbits, codebook.dim))
+                            .map(|(c, bucket)| c + codebook.bucket_weights[bucket as usize])
+                            .collect()
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns the number of vectors in the embedding.
+    pub fn len(&self) -> usize {
+        match self {
+            CompressedMultiVector::Binary(binary) => binary.codes.len(),
+            CompressedMultiVector::Residual(residual) => residual.codes.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl MultiVectorCompression {
+    /// Compresses a multi-vector embedding. Dense embeddings are rejected.
+    pub fn compress(&self, embedding: EmbeddingResult) -> anyhow::Result<EmbeddingResult> {
+        let vectors = match embedding {
+            EmbeddingResult::MultiVector(vectors) => vectors,
+            EmbeddingResult::DenseVector(_) | EmbeddingResult::CompressedMultiVector(_) => {
+                return Err(anyhow!(
+                    "Only uncompressed multi-vector embeddings can be compressed"
+                ))
+            }
+        };
+
+        match self {
+            MultiVectorCompression::TokenPooling { pool_factor } => {
+                if *pool_factor == 0 {
+                    return Err(anyhow!("pool_factor must be greater than 0"));
+                }
+                Ok(EmbeddingResult::MultiVector(pool_tokens(
+                    vectors,
+                    *pool_factor,
+                )))
+            }
+            MultiVectorCompression::Binary => Ok(EmbeddingResult::CompressedMultiVector(
+                CompressedMultiVector::Binary(binarize(&vectors)),
+            )),
+            MultiVectorCompression::Residual(codebook) => {
+                Ok(EmbeddingResult::CompressedMultiVector(
+                    CompressedMultiVector::Residual(codebook.compress(vectors)?),
+                ))
+            }
+        }
+    }
+
+    /// Compresses the multi-vector embedding of every `EmbedData`, keeping dense and already
+    /// compressed embeddings, text and metadata untouched.
+    pub fn compress_embed_data(&self, data: Vec<EmbedData>) -> anyhow::Result<Vec<EmbedData>> {
+        data.into_par_iter()
+            .map(|data| match data.embedding {
+                EmbeddingResult::MultiVector(_) => Ok(EmbedData::new(
+                    self.compress(data.embedding)?,
+                    data.text,
+                    data.metadata,
+                )),
+                _ => Ok(data),
+            })
+            .collect()
+    }
+}
+
+/// Hierarchical (agglomerative) token pooling.
+///
+/// Clusters are represented by the sum of their vectors, so the similarity between two clusters
+/// is the cosine similarity of their means. Candidate pairs are kept in a max-heap; merging two
+/// clusters bumps the version of the survivor, which invalidates its old pairs lazily, and
+/// pushes its pairs with the remaining clusters. This takes O(n² log n) time for n vectors.
+fn pool_tokens(vectors: Vec<Vec<f32>>, pool_factor: usize) -> Vec<Vec<f32>> {
+    let n = vectors.len();
+    let target = n.div_ceil(pool_factor).max(1);
+    if n <= target {
+        return vectors;
+    }
+
+    let normalized = |v: &[f32]| {
+        let norm = dot_product(v, v).sqrt().max(1e-10);
+        v.iter().map(|x| x / norm).collect::<Vec<_>>()
+    };
+    let mut sums = vectors;
+    let mut counts = vec![1usize; n];
+    let mut alive = vec![true; n];
+    let mut versions = vec![0usize; n];
+    let mut units = sums.iter().map(|v| normalized(v)).collect::<Vec<_>>();
+
+    let mut heap = (0..n)
+        .into_par_iter()
+        .flat_map_iter(|i| {
+            let units = &units;
+            (i + 1..n).map(move |j| MergeCandidate {
+                similarity: dot_product(&units[i], &units[j]),
+                clusters: (i, j),
+                versions: (0, 0),
+            })
+        })
+        .collect::<BinaryHeap<_>>();
+
+    let mut remaining = n;
+    while remaining > target {
+        let Some(candidate) = heap.pop() else {
+            break;
+        };
+        let (i, j) = candidate.clusters;
+        if !alive[i] || !alive[j] || candidate.versions != (versions[i], versions[j]) {
+            continue;
+        }
+
+        // Merge cluster j into cluster i
+        let merged = std::mem::take(&mut sums[j]);
+        for (a, b) in sums[i].iter_mut().zip(merged) {
+            *a += b;
+        }
+        counts[i] += counts[j];
+        alive[j] = false;
+        versions[i] += 1;
+        units[i] = normalized(&sums[i]);
+        remaining -= 1;
+
+        for k in (0..n).filter(|&k| alive[k] && k != i) {
+            let (a, b) = (i.min(k), i.max(k));
+            heap.push(MergeCandidate {
+                similarity: dot_product(&units[i], &units[k]),
+                clusters: (a, b),
+                versions: (versions[a], versions[b]),
+            });
+        }
+    }
+
+    sums.into_iter()
+        .zip(counts)
+        .zip(alive)
+        .filter(|(_, alive)| *alive)
+        .map(|((sum, count), _)| sum.into_iter().map(|x| x / count as f32).collect())
+        .collect()
+}
+
+/// A pair of clusters that token pooling may merge, valid while both keep the given versions.
+struct MergeCandidate {
+    similarity: f32,
+    clusters: (usize, usize),
+    versions: (usize, usize),
+}
+
+impl Ord for MergeCandidate {
+    /// Orders by similarity, then prefers the pair with the lowest indices, so that pooling is
+    /// deterministic.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.similarity
+            .total_cmp(&other.similarity)
+            .then_with(|| other.clusters.cmp(&self.clusters))
+    }
+}
+
+impl PartialOrd for MergeCandidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for MergeCandidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MergeCandidate {}
+
+fn binarize(vectors: &[Vec<f32>]) -> BinaryMultiVector {
+    let dim = vectors.first().map(|v| v.len()).unwrap_or(0);
+    let codes = vectors
+        .iter()
+        .map(|v| {
+            let bits = v.iter().map(|&x| (x > 0.0) as u8).collect::<Vec<_>>();
+            pack_bits(&bits, 1)
+        })
+        .collect();
+    BinaryMultiVector { dim, codes }
+}
+
+impl ResidualCodebook {
+    /// Fits `num_centroids` centroids to the vectors of `embeddings` with k-means, and
+    /// `2^nbits` residual buckets to the quantiles of their residuals. `nbits` must be 1, 2, 4 or
+    /// 8, and there must be more vectors than centroids.
+    ///
+    /// Every k-means iteration compares each vector with every centroid, so fit the codebook on
+    /// a sample of the corpus, e.g. a few thousand vectors per centroid at most, and reuse it for
+    /// all the embeddings.
+    pub fn fit(
+        embeddings: &[Vec<Vec<f32>>],
+        num_centroids: usize,
+        nbits: u8,
+    ) -> anyhow::Result<Self> {
+        if ![1, 2, 4, 8].contains(&nbits) {
+            return Err(anyhow!("nbits must be 1, 2, 4 or 8, got {}", nbits));
+        }
+        if num_centroids == 0 {
+            return Err(anyhow!("num_centroids must be greater than 0"));
+        }
+        let vectors = embeddings.iter().flatten().cloned().collect::<Vec<_>>();
+        if num_centroids >= vectors.len() {
+            return Err(anyhow!(
+                "num_centroids must be smaller than the number of vectors, got {} centroids for {} vectors",
+                num_centroids,
+                vectors.len()
+            ));
+        }
+        let dim = vectors[0].len();
+        if vectors.iter().any(|v| v.len() != dim) {
+            return Err(anyhow!("All vectors must have the same length"));
+        }
+
+        let centroids = kmeans(&vectors, num_centroids);
+        // Bucket cutoffs and weights are quantiles of all residual values
+        let mut sorted = vectors
+            .par_iter()
+            .flat_map_iter(|v| {
+                let centroid = &centroids[nearest_centroid(v, &centroids)];
+                v.iter().zip(centroid).map(|(x, c)| x - c)
+            })
+            .collect::<Vec<_>>();
+        sorted.par_sort_unstable_by(f32::total_cmp);
+        let num_buckets = 1usize << nbits;
+        let quantile = |q: f32| sorted[((q * sorted.len() as f32) as usize).min(sorted.len() - 1)];
+        let cutoffs = (1..num_buckets)
+            .map(|i| quantile(i as f32 / num_buckets as f32))
+            .collect();
+        let bucket_weights = (0..num_buckets)
+            .map(|i| quantile((i as f32 + 0.5) / num_buckets as f32))
+            .collect();
+
+        Ok(Self {
+            dim,
+            nbits,
+            centroids,
+            cutoffs,
+            bucket_weights,
+        })
+    }
+
+    /// Compresses the vectors of one embedding with the centroids and buckets of the codebook.
+    fn compress(self: &Arc<Self>, vectors: Vec<Vec<f32>>) -> anyhow::Result<ResidualMultiVector> {
+        if let Some(v) = vectors.iter().find(|v| v.len() != self.dim) {
+            return Err(anyhow!(
+                "Vector length {} does not match the codebook dimension {}",
+                v.len(),
+                self.dim
+            ));
+        }
+        let (codes, residuals) = vectors
+            .par_iter()
+            .map(|v| {
+                let code = nearest_centroid(v, &self.centroids);
+                let buckets = v
+                    .iter()
+                    .zip(&self.centroids[code])
+                    .map(|(x, c)| {
+                        let residual = x - c;
+                        self.cutoffs.iter().filter(|c| **c <= residual).count() as u8
+                    })
+                    .collect::<Vec<_>>();
+                (code as u32, pack_bits(&buckets, self.nbits))
+            })
+            .unzip();
+
+        Ok(ResidualMultiVector {
+            codebook: self.clone(),
+            codes,
+            residuals,
+        })
+    }
+}
+
+fn nearest_centroid(vector: &[f32], centroids: &[Vec<f32>]) -> usize {
+    centroids
+        .iter()
+        .map(|c| {
+            vector
+                .iter()
+                .zip(c)
+                .map(|(x, y)| (x - y) * (x - y))
+                .sum::<f32>()
+        })
+        .enumerate()
+        .fold(
+            (0, f32::INFINITY),
+            |best, (i, d)| if d < best.1 { (i, d) } else { best },
+        )
+        .0
+}
+
+/// Lloyd's k-means, initialized with evenly spaced vectors so that results are deterministic.
+fn kmeans(vectors: &[Vec<f32>], k: usize) -> Vec<Vec<f32>> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let dim = vectors[0].len();
+    let step = vectors.len() as f32 / k as f32;
+    let mut centroids = (0..k)
+        .map(|i| vectors[(i as f32 * step) as usize].clone())
+        .collect::<Vec<_>>();
+
+    for _ in 0..KMEANS_ITERATIONS {
+        let assignments = vectors
+            .par_iter()
+            .map(|v| nearest_centroid(v, &centroids))
+            .collect::<Vec<_>>();
+
+        let mut sums = vec![vec![0.0f32; dim]; k];
+        let mut counts = vec![0usize; k];
+        for (v, &a) in vectors.iter().zip(&assignments) {
+            for (s, x) in sums[a].iter_mut().zip(v) {
+                *s += x;
+            }
+            counts[a] += 1;
+        }
+        for ((centroid, sum), count) in centroids.iter_mut().zip(sums).zip(counts) {
+            // Keep the previous centroid for empty clusters
+            if count > 0 {
+                *centroid = sum.into_iter().map(|x| x / count as f32).collect();
+            }
+        }
+    }
+    centroids
+}
+
+/// Packs values of `nbits` bits each (1, 2, 4 or 8) into bytes, least significant bits first.
+fn pack_bits(values: &[u8], nbits: u8) -> Vec<u8> {
+    let nbits = nbits as usize;
+    let mut packed = vec![0u8; (values.len() * nbits).div_ceil(8)];
+    for (i, &value) in values.iter().enumerate() {
+        let bit = i * nbits;
+        packed[bit / 8] |= value << (bit % 8);
+    }
+    packed
+}
+
+fn unpack_bits(packed: &[u8], nbits: u8, len: usize) -> Vec<u8> {
+    let nbits = nbits as usize;
+    let mask = ((1u16 << nbits) - 1) as u8;
+    (0..len)
+        .map(|i| {
+            let bit = i * nbits;
+            (packed[bit / 8] >> (bit % 8)) & mask
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_vectors(n: usize, dim: usize) -> Vec<Vec<f32>> {
+        // Deterministic pseudo-random values in [-1, 1)
+        let mut state = 42u32;
+        (0..n)
+            .map(|_| {
+                (0..dim)
+                    .map(|_| {
+                        state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                        (state >> 8) as f32 / (1 << 23) as f32 - 1.0
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_token_pooling() {
+        let vectors = vec![
+            vec![1.0, 0.0],
+            vec![0.9, 0.1],
+            vec![0.0, 1.0],
+            vec![0.1, 0.9],
+        ];
+        let pooled = MultiVectorCompression::TokenPooling { pool_factor: 2 }
+            .compress(EmbeddingResult::MultiVector(vectors))
+            .unwrap()
+            .to_multi_vector()
+            .unwrap();
+        assert_eq!(pooled.len(), 2);
+        assert!(pooled.contains(&vec![0.95, 0.05]));
+        assert!(pooled.contains(&vec![0.05, 0.95]));
+
+        // Dense embeddings are left as they are.
+        let data = MultiVectorCompression::TokenPooling { pool_factor: 2 }
+            .compress_embed_data(vec![EmbedData::new(
+                EmbeddingResult::DenseVector(vec![1.0, 0.0]),
+                None,
+                None,
+            )])
+            .unwrap();
+        assert_eq!(data[0].embedding.to_dense().unwrap(), vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_token_pooling_matches_exhaustive_search() {
+        // Merges the most similar pair of clusters by comparing every pair at every step.
+        fn exhaustive(vectors: Vec<Vec<f32>>, target: usize) -> Vec<Vec<f32>> {
+            let mut clusters = vectors.into_iter().map(|v| (v, 1)).collect::<Vec<_>>();
+            let cosine = |a: &[f32], b: &[f32]| {
+                dot_product(a, b) / (dot_product(a, a).sqrt() * dot_product(b, b).sqrt())
+            };
+            while clusters.len() > target {
+                let mut best = (0, 1, f32::NEG_INFINITY);
+                for i in 0..clusters.len() {
+                    for j in i + 1..clusters.len() {
+                        let similarity = cosine(&clusters[i].0, &clusters[j].0);
+                        if similarity > best.2 {
+                            best = (i, j, similarity);
+                        }
+                    }
+                }
+                let (sum, count) = clusters.remove(best.1);
+                for (a, b) in clusters[best.0].0.iter_mut().zip(sum) {
+                    *a += b;
+                }
+                clusters[best.0].1 += count;
+            }
+            clusters
+                .into_iter()
+                .map(|(sum, count)| sum.into_iter().map(|x| x / count as f32).collect())
+                .collect()
+        }
+
+        let vectors = random_vectors(40, 8);
+        let pooled = pool_tokens(vectors.clone(), 3);
+        let expected = exhaustive(vectors, 14);
+        assert_eq!(pooled.len(), expected.len());
+        for (a, b) in pooled.iter().flatten().zip(expected.iter().flatten()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+
+        // A ColPali page has about a thousand patch embeddings.
+        assert_eq!(pool_tokens(random_vectors(1030, 128), 3).len(), 344);
+    }
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let vectors = random_vectors(5, 13);
+        let compressed = MultiVectorCompression::Binary
+            .compress(EmbeddingResult::MultiVector(vectors.clone()))
+            .unwrap();
+        let EmbeddingResult::CompressedMultiVector(CompressedMultiVector::Binary(binary)) =
+            &compressed
+        else {
+            panic!("expected binary embedding");
+        };
+        assert_eq!(binary.codes[0].len(), 2);
+
+        let decompressed = compressed.to_multi_vector().unwrap();
+        for (original, decoded) in vectors.iter().zip(decompressed) {
+            for (x, y) in original.iter().zip(decoded) {
+                assert_eq!(*x > 0.0, y > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_residual_roundtrip() {
+        let vectors = random_vectors(256, 16);
+        let sample = vectors.chunks(64).map(|c| c.to_vec()).collect::<Vec<_>>();
+        let codebook = Arc::new(ResidualCodebook::fit(&sample, 8, 2).unwrap());
+        assert_eq!(codebook.centroids.len(), 8);
+        assert_eq!(codebook.bucket_weights.len(), 4);
+
+        let compression = MultiVectorCompression::Residual(codebook.clone());
+        let data = compression
+            .compress_embed_data(
+                sample
+                    .iter()
+                    .map(|vectors| {
+                        EmbedData::new(EmbeddingResult::MultiVector(vectors.clone()), None, None)
+                    })
+                    .collect(),
+            )
+            .unwrap();
+        let mut decompressed = Vec::new();
+        for data in &data {
+            let EmbeddingResult::CompressedMultiVector(CompressedMultiVector::Residual(residual)) =
+                &data.embedding
+            else {
+                panic!("expected residual embedding");
+            };
+            // Every embedding refers to the same codebook instead of a copy.
+            assert!(Arc::ptr_eq(&residual.codebook, &codebook));
+            assert_eq!(residual.residuals[0].len(), 4);
+            decompressed.extend(data.embedding.to_multi_vector().unwrap());
+        }
+        assert_eq!(decompressed.len(), vectors.len());
+
+        let error = vectors
+            .iter()
+            .flatten()
+            .zip(decompressed.iter().flatten())
+            .map(|(x, y)| (x - y).abs())
+            .sum::<f32>()
+            / (256 * 16) as f32;
+        assert!(error < 0.25, "mean reconstruction error too high: {error}");
+
+        // Vectors of another length cannot be compressed with the codebook.
+        assert!(compression
+            .compress(EmbeddingResult::MultiVector(random_vectors(2, 8)))
+            .is_err());
+    }
+
+    #[test]
+    fn test_residual_codebook_validation() {
+        let sample = vec![random_vectors(8, 4)];
+        assert!(ResidualCodebook::fit(&sample, 4, 3).is_err());
+        assert!(ResidualCodebook::fit(&sample, 0, 2).is_err());
+        // A centroid per vector would leave nothing to quantize.
+        assert!(ResidualCodebook::fit(&sample, 8, 2).is_err());
+        assert!(ResidualCodebook::fit(&[], 1, 2).is_err());
+        assert!(ResidualCodebook::fit(&[vec![vec![1.0, 0.0], vec![1.0]]], 1, 2).is_err());
+        assert!(ResidualCodebook::fit(&sample, 7, 2).is_ok());
+    }
+}
@@ -1,4 +1,5 @@
-use crate::config::{ImageEmbedConfig, TextEmbedConfig};
+use crate::config::{ImageEmbedConfig, PdfRenderConfig, TextEmbedConfig};
+use crate::embeddings::compression::CompressedMultiVector;
 use crate::embeddings::local::vision_encoder::VisionEncoderEmbedder;
 use crate::file_processor::audio::audio_processor::Segment;
 use crate::Dtype;
@@ -7,9 +8,12 @@ use super::cloud::cohere::CohereEmbedder;
 use super::cloud::gemini::GeminiEmbedder;
 use super::cloud::openai::OpenAIEmbedder;
 use super::local::bert::{BertEmbed, BertEmbedder, SparseBertEmbedder};
+use super::local::clap::ClapEmbedder;
 
 use super::local::clip::ClipEmbedder;
 use super::local::colpali::{ColPaliEmbed, ColPaliEmbedder};
+use super::local::colsmol::ColSmolEmbedder;
+use super::local::image_processor::load_image_from_memory;
 use super::local::jina::{JinaEmbed, JinaEmbedder};
 use super::local::model2vec::Model2VecEmbedder;
 use super::local::modernbert::ModernBertEmbedder;
@@ -18,6 +22,7 @@ use super::local::text_embedding::ONNXModel;
 use anyhow::anyhow;
 use anyhow::Result;
 use hf_hub::Repo;
+use image::DynamicImage;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::future::Future;
@@ -31,10 +36,14 @@ use {
     super::local::ort_jina::OrtJinaEmbedder,
 };
 
+/// An embedding produced by an embedder. New kinds of embeddings may be added, so matches
+/// outside this crate need a wildcard arm.
 #[derive(Deserialize, Debug, Clone)]
+#[non_exhaustive]
 pub enum EmbeddingResult {
     DenseVector(Vec<f32>),
     MultiVector(Vec<Vec<f32>>),
+    CompressedMultiVector(CompressedMultiVector),
 }
 
 impl From<Vec<f32>> for EmbeddingResult {
@@ -53,15 +62,16 @@ impl EmbeddingResult {
     pub fn to_dense(&self) -> Result<Vec<f32>, anyhow::Error> {
         match self {
             EmbeddingResult::DenseVector(x) => Ok(x.to_vec()),
-            EmbeddingResult::MultiVector(_) => Err(anyhow!(
-                "Multi-vector Embedding are not supported for this operation"
-            )),
+            EmbeddingResult::MultiVector(_) | EmbeddingResult::CompressedMultiVector(_) => Err(
+                anyhow!("Multi-vector Embedding are not supported for this operation"),
+            ),
         }
     }
 
     pub fn to_multi_vector(&self) -> Result<Vec<Vec<f32>>, anyhow::Error> {
         match self {
             EmbeddingResult::MultiVector(x) => Ok(x.to_vec()),
+            EmbeddingResult::CompressedMultiVector(x) => Ok(x.decompress()),
             EmbeddingResult::DenseVector(_) => Err(anyhow!(
                 "Dense Embedding are not supported for this operation"
             )),
@@ -331,6 +341,9 @@ impl VisionEmbedder {
             "ColPali" => Ok(Self::ColPali(Box::new(ColPaliEmbedder::new(
                 model_id, revision,
             )?))),
+            "ColIdefics3" | "Idefics3ForConditionalGeneration" => Ok(Self::ColPali(Box::new(
+                ColSmolEmbedder::new(model_id, revision)?,
+            ))),
    
             _ => Err(anyhow::anyhow!("Model not supported")),
         }
@@ -351,6 +364,69 @@ impl VisionEmbedder {
     }
 }
 
+/// Embedders of sounds, sharing an embedding space with texts that describe them.
+#[derive(Clone)]
+pub enum AudioEmbedder {
+    Clap(Box<ClapEmbedder>),
+}
+
+impl From<AudioEmbedder> for Embedder {
+    fn from(value: AudioEmbedder) -> Self {
+        Embedder::Audio(Box::new(value))
+    }
+}
+
+impl AudioEmbedder {
+    pub fn from_pretrained_hf(
+        architecture: &str,
+        model_id: &str,
+        revision: Option<&str>,
+        token: Option<&str>,
+    ) -> Result<Self, anyhow::Error> {
+        match architecture {
+            "ClapModel" => Ok(Self::Clap(Box::new(ClapEmbedder::new(
+                model_id, revision, token,
+            )?))),
+            _ => Err(anyhow::anyhow!("Model not supported")),
+        }
+    }
+
+    /// Embeds texts into the space of the audio embeddings.
+    pub fn embed(
+        &self,
+        text_batch: &[&str],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, anyhow::Error> {
+        match self {
+            Self::Clap(embedder) => embedder.embed(text_batch, batch_size),
+        }
+    }
+
+    /// Embeds a mono recording sampled at `sample_rate` Hz, one embedding per window of the model.
+    pub fn embed_pcm(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbedData>, anyhow::Error> {
+        match self {
+            Self::Clap(embedder) => embedder.embed_pcm(samples, sample_rate, batch_size),
+        }
+    }
+
+    /// Decodes and embeds an audio file, one embedding per window of the model.
+    #[cfg(feature = "audio")]
+    pub fn embed_audio_file<T: AsRef<std::path::Path>>(
+        &self,
+        audio_file: T,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbedData>, anyhow::Error> {
+        match self {
+            Self::Clap(embedder) => embedder.embed_audio_file(audio_file, batch_size),
+        }
+    }
+}
+
 /// This is a builder for the Embedder. You can use it to build an Embedder from either HF or ONNX models.
 /// You need to provide atleast the `model_id` or the `onnx_model_id`.
 /// ## Example
@@ -524,6 +600,7 @@ impl EmbedderBuilder {
 pub enum Embedder {
     Text(TextEmbedder),
     Vision(Box<VisionEmbedder>),
+    Audio(Box<AudioEmbedder>),
 }
 
 impl Embedder {
@@ -536,6 +613,7 @@ impl Embedder {
         match self {
             Self::Text(embedder) => embedder.embed(text_batch, batch_size, late_chunking).await,
             Self::Vision(embedder) => embedder.embed(text_batch, batch_size).await,
+            Self::Audio(embedder) => embedder.embed(text_batch, batch_size),
         }
     }
 
@@ -571,12 +649,23 @@ impl Embedder {
             "CLIPModel" | "SiglipModel"  => Ok(Self::Vision(Box::new(
                 VisionEmbedder::from_pretrained_hf(architecture, model_id, revision, token)?,
             ))),
-            "ColPali" => Ok(Self::Vision(Box::new(
-                VisionEmbedder::from_pretrained_hf(architecture, model_id, revision, token)?,
-            ))),
+            "ColPali" | "ColIdefics3" | "Idefics3ForConditionalGeneration" => {
+                Ok(Self::Vision(Box::new(VisionEmbedder::from_pretrained_hf(
+                    architecture,
+                    model_id,
+                    revision,
+                    token,
+                )?)))
+            }
             "Dinov2Model" => Ok(Self::Vision(Box::new(
                 VisionEmbedder::from_pretrained_hf(architecture, model_id, revision, token)?,
             ))),
+            "ClapModel" => Ok(Self::Audio(Box::new(AudioEmbedder::from_pretrained_hf(
+                architecture,
+                model_id,
+                revision,
+                token,
+            )?))),
             "BertModel" => Ok(Self::Text(TextEmbedder::from_pretrained_hf(
                 architecture,
                 model_id,
@@ -706,6 +795,15 @@ impl Embedder {
         crate::embed_file(file_path, self, config, adapter).await
     }
 
+    pub async fn embed_bytes(
+        &self,
+        bytes: &[u8],
+        filename_hint: Option<&str>,
+        config: Option<&TextEmbedConfig>,
+    ) -> Result<Vec<EmbedData>> {
+        crate::embed_bytes(bytes, filename_hint, self, config).await
+    }
+
     pub async fn embed_webpage(
         &self,
         url: String,
@@ -793,6 +891,17 @@ impl EmbedImage for Embedder {
         }
     }
 
+    async fn embed_dynamic_images(
+        &self,
+        images: &[DynamicImage],
+        batch_size: Option<usize>,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        match self {
+            Self::Vision(embedder) => embedder.embed_dynamic_images(images, batch_size).await,
+            _ => Err(anyhow::anyhow!("Model not supported for vision embedding")),
+        }
+    }
+
     async fn embed_pdf<T: AsRef<std::path::Path>>(
         &self,
         pdf_path: T,
@@ -803,6 +912,22 @@ impl EmbedImage for Embedder {
             _ => Err(anyhow::anyhow!("Model not supported for PDF embedding")),
         }
     }
+
+    async fn embed_pdf_with_render_config<T: AsRef<std::path::Path>>(
+        &self,
+        pdf_path: T,
+        batch_size: Option<usize>,
+        render_config: &PdfRenderConfig,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        match self {
+            Self::Vision(embedder) => {
+                embedder
+                    .embed_pdf_with_render_config(pdf_path, batch_size, render_config)
+                    .await
+            }
+            _ => Err(anyhow::anyhow!("Model not supported for PDF embedding")),
+        }
+    }
 }
 
 pub trait TextEmbed {
@@ -839,11 +964,54 @@ pub trait EmbedImage {
         image_paths: &[T],
         batch_size: Option<usize>,
     ) -> impl Future<Output = anyhow::Result<Vec<EmbedData>>>;
+    /// Embeds images that are already decoded, e.g. received over the network. The results carry
+    /// no metadata. Embedders that only embed image files keep the default, which returns an
+    /// error.
+    fn embed_dynamic_images(
+        &self,
+        _images: &[DynamicImage],
+        _batch_size: Option<usize>,
+    ) -> impl Future<Output = anyhow::Result<Vec<EmbedData>>> {
+        async move {
+            Err(anyhow!(
+                "embedding in-memory images is not supported by this embedder"
+            ))
+        }
+    }
+    /// Decodes an encoded image (PNG, JPEG, ...) from memory and embeds it.
+    fn embed_image_bytes(
+        &self,
+        bytes: &[u8],
+        metadata: Option<HashMap<String, String>>,
+    ) -> impl Future<Output = anyhow::Result<EmbedData>> {
+        async move {
+            let image = load_image_from_memory(bytes)?;
+            let mut embed_data = self
+                .embed_dynamic_images(&[image], Some(1))
+                .await?
+                .pop()
+                .ok_or_else(|| anyhow!("No embedding returned for image"))?;
+            embed_data.metadata = metadata;
+            Ok(embed_data)
+        }
+    }
     fn embed_pdf<T: AsRef<std::path::Path>>(
         &self,
         pdf_path: T,
         batch_size: Option<usize>,
     ) -> impl Future<Output = anyhow::Result<Vec<EmbedData>>>;
+    /// Embeds the pages of a PDF rendered as set by `render_config`. [EmbedImage::embed_pdf]
+    /// renders them with [PdfRenderConfig::default()]. Embedders that do not render pages
+    /// themselves ignore `render_config`.
+    fn embed_pdf_with_render_config<T: AsRef<std::path::Path>>(
+        &self,
+        pdf_path: T,
+        batch_size: Option<usize>,
+        render_config: &PdfRenderConfig,
+    ) -> impl Future<Output = anyhow::Result<Vec<EmbedData>>> {
+        let _ = render_config;
+        self.embed_pdf(pdf_path, batch_size)
+    }
 }
 
 impl EmbedImage for VisionEmbedder {
@@ -869,23 +1037,65 @@ impl EmbedImage for VisionEmbedder {
     ) -> anyhow::Result<Vec<EmbedData>> {
         match self {
             Self::Clip(embedder) => embedder.embed_image_batch(image_paths, batch_size).await,
-            Self::ColPali(embedder) => embedder.embed_image_batch(
-                &image_paths
-                    .iter()
-                    .map(|p| PathBuf::from(p.as_ref()))
-                    .collect::<Vec<_>>(),
-            ),
+            Self::ColPali(embedder) => {
+                let mut embeddings = Vec::new();
+                for image_batch in image_paths.chunks(batch_size.unwrap_or(1).max(1)) {
+                    embeddings.extend(
+                        embedder.embed_image_batch(
+                            &image_batch
+                                .iter()
+                                .map(|p| PathBuf::from(p.as_ref()))
+                                .collect::<Vec<_>>(),
+                        )?,
+                    );
+                }
+                Ok(embeddings)
+            }
             Self::Cohere(embedder) => embedder.embed_image_batch(image_paths, batch_size).await,
             Self::VisionEncoder(embedder) => embedder.embed_image_batch(image_paths, batch_size).await,
         }
     }
 
+    async fn embed_dynamic_images(
+        &self,
+        images: &[DynamicImage],
+        batch_size: Option<usize>,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        match self {
+            Self::Clip(embedder) => embedder.embed_dynamic_images(images, batch_size).await,
+            Self::ColPali(embedder) => {
+                let mut embeddings = Vec::new();
+                for image_batch in images.chunks(batch_size.unwrap_or(1).max(1)) {
+                    embeddings.extend(embedder.embed_dynamic_images(image_batch)?);
+                }
+                Ok(embeddings)
+            }
+            Self::Cohere(embedder) => embedder.embed_dynamic_images(images, batch_size).await,
+            Self::VisionEncoder(embedder) => embedder.embed_dynamic_images(images, batch_size).await,
+        }
+    }
+
     async fn embed_pdf<T: AsRef<std::path::Path>>(
         &self,
         pdf_path: T,
         batch_size: Option<usize>,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        self.embed_pdf_with_render_config(pdf_path, batch_size, &PdfRenderConfig::default())
+            .await
+    }
+
+    async fn embed_pdf_with_render_config<T: AsRef<std::path::Path>>(
+        &self,
+        pdf_path: T,
+        batch_size: Option<usize>,
+        render_config: &PdfRenderConfig,
     ) -> anyhow::Result<Vec<EmbedData>> {
         match self {
+            Self::ColPali(embedder) => embedder.embed_pdf(
+                PathBuf::from(pdf_path.as_ref()),
+                batch_size.unwrap_or(1).max(1),
+                render_config,
+            ),
             Self::Cohere(embedder) => embedder.embed_pdf(pdf_path, batch_size).await,
             _ => Err(anyhow::anyhow!("Model not supported for PDF embedding")),
         }
@@ -0,0 +1,266 @@
+//! CLAP embeddings for sounds and their descriptions.
+//!
+//! Unlike transcription, which only captures speech, CLAP embeds the sound itself: music, alarms
+//! or machinery get embeddings that are close to texts describing them. Recordings are resampled
+//! to the rate of the model and embedded in windows of 10 seconds.
+
+#[cfg(feature = "mkl")]
+extern crate intel_mkl_src;
+
+#[cfg(feature = "accelerate")]
+extern crate accelerate_src;
+
+use std::collections::HashMap;
+
+use anyhow::Error as E;
+use candle_core::{DType, Device, Tensor};
+use candle_nn::VarBuilder;
+use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
+
+use crate::{
+    embeddings::{
+        embed::{EmbedData, EmbeddingResult},
+        select_device,
+    },
+    file_processor::audio::resample::resample,
+    models::{
+        clap::{
+            feature_extractor::{ClapFeatureExtractor, ClapFeatureExtractorConfig},
+            ClapConfig, ClapModel,
+        },
+        clip::div_l2_norm,
+    },
+};
+
+#[derive(Clone)]
+pub struct ClapEmbedder {
+    pub model: ClapModel,
+    pub tokenizer: Tokenizer,
+    pub feature_extractor: ClapFeatureExtractor,
+    pub device: Device,
+}
+
+impl Default for ClapEmbedder {
+    fn default() -> Self {
+        Self::new("laion/clap-htsat-unfused", None, None).unwrap()
+    }
+}
+
+impl ClapEmbedder {
+    pub fn new(model_id: &str, revision: Option<&str>, token: Option<&str>) -> Result<Self, E> {
+        let api = hf_hub::api::sync::ApiBuilder::from_env()
+            .with_token(token.map(|s| s.to_string()))
+            .build()?;
+
+        let api = match revision {
+            Some(rev) => api.repo(hf_hub::Repo::with_revision(
+                model_id.to_string(),
+                hf_hub::RepoType::Model,
+                rev.to_string(),
+            )),
+            None => api.repo(hf_hub::Repo::new(
+                model_id.to_string(),
+                hf_hub::RepoType::Model,
+            )),
+        };
+
+        let device = select_device();
+
+        let vb = match api.get("model.safetensors") {
+            Ok(safetensors) => unsafe {
+                VarBuilder::from_mmaped_safetensors(&[safetensors], DType::F32, &device)?
+            },
+            Err(_) => match api.get("pytorch_model.bin") {
+                Ok(pytorch_model) => VarBuilder::from_pth(pytorch_model, DType::F32, &device)?,
+                Err(e) => {
+                    return Err(anyhow::Error::msg(format!(
+                        "Model weights not found. The weights should either be a `model.safetensors` or `pytorch_model.bin` file.  Error: {}",
+                        e
+                    )));
+                }
+            },
+        };
+        let config: ClapConfig =
+            serde_json::from_str(&std::fs::read_to_string(api.get("config.json")?)?)?;
+        let feature_extractor_config: ClapFeatureExtractorConfig =
+            match api.get("preprocessor_config.json") {
+                Ok(path) => serde_json::from_str(&std::fs::read_to_string(path)?)?,
+                Err(_) => ClapFeatureExtractorConfig::default(),
+            };
+
+        let mut tokenizer = Tokenizer::from_file(api.get("tokenizer.json")?).map_err(E::msg)?;
+        tokenizer.with_padding(Some(PaddingParams {
+            strategy: tokenizers::PaddingStrategy::BatchLongest,
+            pad_id: config.text_config.pad_token_id,
+            ..Default::default()
+        }));
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config.text_config.max_position_embeddings - 2,
+                ..Default::default()
+            }))
+            .map_err(E::msg)?;
+
+        let model = ClapModel::new(vb, &config)?;
+
+        Ok(Self {
+            model,
+            tokenizer,
+            feature_extractor: ClapFeatureExtractor::new(feature_extractor_config),
+            device,
+        })
+    }
+
+    /// Embeds texts into the joint space, for instance to search clips by a description.
+    pub fn embed(
+        &self,
+        text_batch: &[&str],
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbeddingResult>, E> {
+        let mut encodings = Vec::new();
+        for mini_text_batch in text_batch.chunks(batch_size.unwrap_or(32)) {
+            let tokens = self
+                .tokenizer
+                .encode_batch(mini_text_batch.to_vec(), true)
+                .map_err(E::msg)?;
+            let input_ids = tokens
+                .iter()
+                .map(|tokens| Tensor::new(tokens.get_ids(), &self.device))
+                .collect::<candle_core::Result<Vec<_>>>()?;
+            let attention_mask = tokens
+                .iter()
+                .map(|tokens| Tensor::new(tokens.get_attention_mask(), &self.device))
+                .collect::<candle_core::Result<Vec<_>>>()?;
+            let input_ids = Tensor::stack(&input_ids, 0)?;
+            let attention_mask = Tensor::stack(&attention_mask, 0)?;
+
+            let batch_encodings = self.model.get_text_features(&input_ids, &attention_mask)?;
+            let normalized_encodings = div_l2_norm(&batch_encodings)?.to_vec2::<f32>()?;
+            encodings.extend(
+                normalized_encodings
+                    .into_iter()
+                    .map(EmbeddingResult::DenseVector),
+            );
+        }
+        Ok(encodings)
+    }
+
+    /// Embeds a mono recording in windows of 10 seconds.
+    ///
+    /// The recording is resampled to the rate of the model. Every embedding carries the `start`
+    /// and `end` of its window in seconds as metadata. A final window shorter than 10 seconds is
+    /// repeated to fill the window, as done during training.
+    pub fn embed_pcm(
+        &self,
+        samples: &[f32],
+        sample_rate: u32,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbedData>, E> {
+        let rate = self.feature_extractor.config.sampling_rate;
+        let samples = resample(samples, sample_rate, rate);
+        let windows = samples
+            .chunks(self.feature_extractor.window_samples())
+            .collect::<Vec<_>>();
+        let frames = self.feature_extractor.frames();
+        let mel_bins = self.feature_extractor.config.feature_size;
+
+        let mut embeddings = Vec::with_capacity(windows.len());
+        for (batch_index, batch) in windows.chunks(batch_size.unwrap_or(32)).enumerate() {
+            let features = batch
+                .iter()
+                .flat_map(|window| self.feature_extractor.log_mel(window))
+                .collect::<Vec<_>>();
+            let features =
+                Tensor::from_vec(features, (batch.len(), frames, mel_bins), &self.device)?;
+            let batch_encodings = self.model.get_audio_features(&features)?;
+            let normalized_encodings = div_l2_norm(&batch_encodings)?.to_vec2::<f32>()?;
+
+            let first_window = batch_index * batch_size.unwrap_or(32);
+            for (i, (embedding, window)) in normalized_encodings.into_iter().zip(batch).enumerate()
+            {
+                let start = ((first_window + i) * self.feature_extractor.window_samples()) as f64
+                    / rate as f64;
+                let mut metadata = HashMap::new();
+                metadata.insert("start".to_string(), start.to_string());
+                metadata.insert(
+                    "end".to_string(),
+                    (start + window.len() as f64 / rate as f64).to_string(),
+                );
+                embeddings.push(EmbedData::new(
+                    EmbeddingResult::DenseVector(embedding),
+                    None,
+                    Some(metadata),
+                ));
+            }
+        }
+        Ok(embeddings)
+    }
+
+    /// Decodes an audio file and embeds it with [Self::embed_pcm]. The embeddings also carry the
+    /// `file_name` of the recording.
+    #[cfg(feature = "audio")]
+    pub fn embed_audio_file<T: AsRef<std::path::Path>>(
+        &self,
+        audio_file: T,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbedData>, E> {
+        use crate::file_processor::audio::pcm_decode::audio_processing::pcm_decode;
+
+        let (samples, sample_rate) = pcm_decode(&audio_file)?;
+        let mut embeddings = self.embed_pcm(&samples, sample_rate, batch_size)?;
+        let file_name = audio_file.as_ref().to_str().unwrap_or_default().to_string();
+        for embedding in embeddings.iter_mut() {
+            if let Some(metadata) = embedding.metadata.as_mut() {
+                metadata.insert("file_name".to_string(), file_name.clone());
+            }
+        }
+        Ok(embeddings)
+    }
+}
+
+#[cfg(all(test, feature = "audio"))]
+mod tests {
+    use super::*;
+    use crate::file_processor::audio::pcm_decode::audio_processing::pcm_decode;
+
+    fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+        let dot = a.iter().zip(b).map(|(a, b)| a * b).sum::<f32>();
+        let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm(a) * norm(b))
+    }
+
+    /// Compares the embeddings with those of the Hugging Face `ClapModel`, which
+    /// `test_files/audio/clap_reference.py` writes to `clap_reference.json`.
+    #[test]
+    #[ignore = "downloads laion/clap-htsat-unfused and needs test_files/audio/clap_reference.json"]
+    fn test_parity_with_transformers() {
+        let reference: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string("../test_files/audio/clap_reference.json")
+                .expect("run test_files/audio/clap_reference.py to write the reference"),
+        )
+        .unwrap();
+        let embedder =
+            ClapEmbedder::new(reference["model_id"].as_str().unwrap(), None, None).unwrap();
+
+        let (samples, sample_rate) = pcm_decode("../test_files/audio/samples_jfk.wav").unwrap();
+        let embeddings = embedder.embed_pcm(&samples, sample_rate, None).unwrap();
+        let audio = embeddings[0].embedding.to_dense().unwrap();
+        let expected: Vec<f32> = serde_json::from_value(reference["audio"].clone()).unwrap();
+        assert_eq!(audio.len(), expected.len());
+        // The recording is resampled differently than by librosa, so the mel features and the
+        // embedding differ slightly.
+        assert!(cosine_similarity(&audio, &expected) > 0.99);
+
+        let texts: Vec<String> = serde_json::from_value(reference["texts"].clone()).unwrap();
+        let texts = texts.iter().map(String::as_str).collect::<Vec<_>>();
+        let expected: Vec<Vec<f32>> = serde_json::from_value(reference["text"].clone()).unwrap();
+        for (embedding, expected) in embedder.embed(&texts, None).unwrap().iter().zip(expected) {
+            let embedding = embedding.to_dense().unwrap();
+            assert_eq!(embedding.len(), expected.len());
+            assert!(embedding
+                .iter()
+                .zip(&expected)
+                .all(|(a, b)| (a - b).abs() < 1e-4));
+        }
+    }
+}
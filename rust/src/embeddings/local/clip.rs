@@ -9,7 +9,11 @@ use std::{collections::HashMap, fs};
 use anyhow::Error as E;
 
 use crate::{
-    embeddings::{embed::EmbeddingResult, select_device},
+    embeddings::{
+        embed::EmbeddingResult,
+        local::image_processor::{open_image, ImagePreprocessorConfig},
+        select_device,
+    },
     models::{
         clip::div_l2_norm,
         clip::{self, ClipConfig},
@@ -17,6 +21,7 @@ use crate::{
     },
 };
 use candle_core::{DType, Device, Tensor};
+use image::DynamicImage;
 use candle_nn::{Module, VarBuilder};
 use candle_transformers::models::dinov2::DinoVisionTransformer;
 use tokenizers::{PaddingParams, Tokenizer};
@@ -63,6 +68,7 @@ pub struct ClipEmbedder {
     pub device: Device,
     pub max_len: usize,
     pub pad_id: u32,
+    pub preprocessor: ImagePreprocessorConfig,
 }
 impl Default for ClipEmbedder {
     fn default() -> Self {
@@ -187,12 +193,16 @@ impl ClipEmbedder {
             )
         };
 
+        let preprocessor =
+            ImagePreprocessorConfig::from_hub_or(&api, ImagePreprocessorConfig::resize_to_fill(224))?;
+
         Ok(ClipEmbedder {
             model,
             tokenizer,
             device,
             max_len,
             pad_id,
+            preprocessor,
         })
     }
 
@@ -255,39 +265,16 @@ impl ClipEmbedder {
         Ok((input_ids, vec_seq.iter().map(|s| s.to_string()).collect()))
     }
 
-    fn load_image<T: AsRef<std::path::Path>>(
-        &self,
-        path: T,
-        image_size: usize,
-    ) -> anyhow::Result<Tensor> {
-        let img = image::ImageReader::open(path)?.decode()?;
-        let (height, width) = (image_size, image_size);
-        let img = img.resize_to_fill(
-            width as u32,
-            height as u32,
-            image::imageops::FilterType::Triangle,
-        );
-
-        let img = img.to_rgb8();
-
-        let img = img.into_raw();
-        let img = Tensor::from_vec(img, (height, width, 3), &self.device)?
-            .permute((2, 0, 1))?
-            .to_dtype(DType::F32)?
-            .affine(2. / 255., -1.)?;
-        // .unsqueeze(0)?;
-        Ok(img)
+    fn load_image<T: AsRef<std::path::Path>>(&self, path: T) -> anyhow::Result<Tensor> {
+        let img = open_image(path)?;
+        self.preprocessor.to_tensor(&img, &self.device)
     }
 
-    fn load_images<T: AsRef<std::path::Path>>(
-        &self,
-        paths: &[T],
-        image_size: usize,
-    ) -> anyhow::Result<Tensor> {
+    fn load_images<T: AsRef<std::path::Path>>(&self, paths: &[T]) -> anyhow::Result<Tensor> {
         let mut images = vec![];
 
         for path in paths {
-            let tensor = self.load_image(path, image_size)?;
+            let tensor = self.load_image(path)?;
             images.push(tensor);
         }
 
@@ -333,13 +320,9 @@ impl EmbedImage for ClipEmbedder {
         image_paths: &[T],
         batch_size: Option<usize>,
     ) -> anyhow::Result<Vec<EmbedData>> {
-        let config = clip::ClipConfig::vit_base_patch32();
-
         let mut encodings = Vec::new();
         for image_batch in image_paths.chunks(batch_size.unwrap_or(32)) {
-            let images = self
-                .load_images(image_batch, config.vision_config.image_size)
-                .unwrap();
+            let images = self.load_images(image_batch)?;
             let batch_encodings = self.model.get_image_features(&images)?;
             let normalized_encodings = div_l2_norm(&batch_encodings)?.to_vec2::<f32>()?;
             encodings.extend(normalized_encodings);
@@ -374,12 +357,7 @@ impl EmbedImage for ClipEmbedder {
         image_path: T,
         metadata: Option<HashMap<String, String>>,
     ) -> anyhow::Result<EmbedData> {
-        let config = clip::ClipConfig::vit_base_patch32();
-        let image = self
-            .load_image(&image_path, config.vision_config.image_size)
-            .unwrap()
-            .unsqueeze(0)
-            .unwrap();
+        let image = self.load_image(&image_path)?.unsqueeze(0)?;
         let encoding = &self.model.get_image_features(&image)?;
         let normalized_encoding = &div_l2_norm(encoding)?.to_vec2::<f32>()?[0];
         Ok(EmbedData::new(
@@ -389,6 +367,23 @@ impl EmbedImage for ClipEmbedder {
         ))
     }
 
+    async fn embed_dynamic_images(
+        &self,
+        images: &[DynamicImage],
+        batch_size: Option<usize>,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        let mut embeddings = Vec::new();
+        for image_batch in images.chunks(batch_size.unwrap_or(32)) {
+            let images = self.preprocessor.to_batch_tensor(image_batch, &self.device)?;
+            let batch_encodings = self.model.get_image_features(&images)?;
+            let normalized_encodings = div_l2_norm(&batch_encodings)?.to_vec2::<f32>()?;
+            embeddings.extend(normalized_encodings.into_iter().map(|data| {
+                EmbedData::new(EmbeddingResult::DenseVector(data), None, None)
+            }));
+        }
+        Ok(embeddings)
+    }
+
     async fn embed_pdf<T: AsRef<std::path::Path>>(
         &self,
         _pdf_path: T,
@@ -427,7 +422,7 @@ mod tests {
     fn test_load_image() {
         let clip_embedder = ClipEmbedder::default();
         let image = clip_embedder
-            .load_image("../test_files/clip/cat1.jpg", 224)
+            .load_image("../test_files/clip/cat1.jpg")
             .unwrap();
         assert_eq!(image.shape().clone().into_dims(), &[3, 224, 224]);
     }
@@ -442,7 +437,6 @@ mod tests {
                     "../test_files/clip/cat1.jpg",
                     "../test_files/clip/cat2.jpeg",
                 ],
-                224,
             )
             .unwrap();
         assert_eq!(images.shape().clone().into_dims(), &[2, 3, 224, 224]);
@@ -2,7 +2,9 @@ use std::path::PathBuf;
 use std::sync::RwLock;
 use std::{collections::HashMap, path::Path};
 
+use crate::config::PdfRenderConfig;
 use crate::embeddings::embed::{EmbedData, EmbeddingResult};
+use crate::embeddings::local::image_processor::{open_image, ImagePreprocessorConfig};
 use crate::embeddings::select_device;
 use crate::models::{colpali::Model, paligemma};
 use anyhow::Error as E;
@@ -11,7 +13,7 @@ use candle_core::{DType, Device, Tensor};
 use candle_nn::VarBuilder;
 use image::{DynamicImage, ImageFormat};
 
-use pdf2image::{Pages, RenderOptionsBuilder, PDF};
+use pdf2image::{Pages, RenderOptionsBuilder, DPI, PDF};
 use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 
 pub trait ColPaliEmbed {
@@ -22,7 +24,20 @@ pub trait ColPaliEmbed {
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error>;
 
     fn embed_query(&self, query: &str) -> anyhow::Result<Vec<EmbedData>>;
-    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>>;
+
+    /// Renders the pages of a PDF selected by `render_config` and embeds them. Each result carries
+    /// the "page_number", "file_path" and base64 encoded "image" of its page as metadata.
+    fn embed_pdf(
+        &self,
+        file_path: PathBuf,
+        batch_size: usize,
+        render_config: &PdfRenderConfig,
+    ) -> anyhow::Result<Vec<EmbedData>>;
+
+    /// Embeds every page of a PDF rendered with the default [PdfRenderConfig].
+    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>> {
+        self.embed_pdf(file_path, batch_size, &PdfRenderConfig::default())
+    }
     fn embed_image(
         &self,
         image_path: PathBuf,
@@ -30,6 +45,9 @@ pub trait ColPaliEmbed {
     ) -> anyhow::Result<EmbedData>;
 
     fn embed_image_batch(&self, image_paths: &[PathBuf]) -> anyhow::Result<Vec<EmbedData>>;
+
+    /// Embeds images that are already decoded. The results carry no metadata.
+    fn embed_dynamic_images(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>>;
 }
 
 pub struct ColPaliEmbedder {
@@ -39,6 +57,7 @@ pub struct ColPaliEmbedder {
     pub device: Device,
     dtype: DType,
     dummy_input: Tensor,
+    preprocessor: ImagePreprocessorConfig,
 }
 
 impl ColPaliEmbedder {
@@ -101,6 +120,10 @@ impl ColPaliEmbedder {
         let dummy_prompt: &str = "Describe the image.";
 
         let dummy_input: Tensor = tokenize_batch(&tokenizer, vec![dummy_prompt], &device)?;
+        let preprocessor = ImagePreprocessorConfig::from_hub_or(
+            &repo,
+            ImagePreprocessorConfig::resize_to_fill(config.vision_config.image_size as u32),
+        )?;
 
         Ok(Self {
             model: RwLock::new(model),
@@ -109,31 +132,15 @@ impl ColPaliEmbedder {
             device,
             dtype,
             dummy_input,
+            preprocessor,
         })
     }
 
-    fn images_to_tensor(
-        &self,
-        pages: &[DynamicImage],
-        image_size: usize,
-    ) -> anyhow::Result<Tensor> {
-        let mut images = vec![];
-        for page in pages.iter() {
-            let img = page.resize_to_fill(
-                image_size as u32,
-                image_size as u32,
-                image::imageops::FilterType::Triangle,
-            );
-            let img = img.to_rgb8();
-            let img = img.into_raw();
-            let img = Tensor::from_vec(img, (image_size, image_size, 3), &self.device)?
-                .permute((2, 0, 1))?
-                .to_dtype(self.dtype)?
-                .affine(2. / 255., -1.)?;
-            images.push(img);
-        }
-        let images = Tensor::stack(&images, 0)?;
-        Ok(images)
+    fn images_to_tensor(&self, images: &[DynamicImage]) -> anyhow::Result<Tensor> {
+        Ok(self
+            .preprocessor
+            .to_batch_tensor(images, &self.device)?
+            .to_dtype(self.dtype)?)
     }
 }
 
@@ -187,13 +194,7 @@ impl ColPaliEmbed for ColPaliEmbedder {
         image_path: PathBuf,
         metadata: Option<HashMap<String, String>>,
     ) -> anyhow::Result<EmbedData> {
-        let pixel_values = load_image(
-            image_path,
-            self.config.vision_config.image_size,
-            &self.device,
-        )?
-        .unsqueeze(0)?
-        .to_dtype(self.dtype)?;
+        let pixel_values = self.images_to_tensor(&[open_image(image_path)?])?;
         let encoding = self
             .model
             .write()
@@ -209,12 +210,11 @@ impl ColPaliEmbed for ColPaliEmbedder {
     }
 
     fn embed_image_batch(&self, image_paths: &[PathBuf]) -> anyhow::Result<Vec<EmbedData>> {
-        let pixel_values = load_images(
-            image_paths,
-            self.config.vision_config.image_size,
-            &self.device,
-        )?
-        .to_dtype(self.dtype)?;
+        let images = image_paths
+            .iter()
+            .map(open_image)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let pixel_values = self.images_to_tensor(&images)?;
         let encodings = self
             .model
             .write()
@@ -228,49 +228,53 @@ impl ColPaliEmbed for ColPaliEmbedder {
             .map(|x| EmbedData::new(EmbeddingResult::MultiVector(x), None, None))
             .collect::<Vec<_>>())
     }
-    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>> {
-        let dtype = self.dtype;
-        let pages = get_images_from_pdf(&file_path)?;
+
+    fn embed_dynamic_images(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        let pixel_values = self.images_to_tensor(images)?;
+        let dummy_input = self.dummy_input.repeat((pixel_values.dims()[0], 0))?;
+        let encodings = self
+            .model
+            .write()
+            .unwrap()
+            .forward_images(&pixel_values, &dummy_input)?
+            .to_dtype(DType::F32)?
+            .to_vec3::<f32>()?;
+
+        Ok(encodings
+            .into_iter()
+            .map(|x| EmbedData::new(EmbeddingResult::MultiVector(x), None, None))
+            .collect::<Vec<_>>())
+    }
+
+    fn embed_pdf(
+        &self,
+        file_path: PathBuf,
+        batch_size: usize,
+        render_config: &PdfRenderConfig,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        let pages = render_pdf_pages(&file_path, render_config)?;
         let mut embed_data = Vec::new();
-        for (index, batch) in pages.chunks(batch_size).enumerate() {
-            let start_page = index * batch_size + 1;
-            let end_page = start_page + batch.len();
-            let page_numbers = (start_page..=end_page).collect::<Vec<_>>();
-            let page_images = self
-                .images_to_tensor(batch, self.config.vision_config.image_size)?
-                .to_device(&self.device)?
-                .to_dtype(dtype)?;
-            let dummy_input = self.dummy_input.repeat((page_images.dims()[0], 0))?;
+        for batch in pages.chunks(batch_size.max(1)) {
+            let (page_numbers, page_images): (Vec<_>, Vec<_>) = batch.iter().cloned().unzip();
+            let page_tensor = self.images_to_tensor(&page_images)?;
+            let dummy_input = self.dummy_input.repeat((page_tensor.dims()[0], 0))?;
 
             let image_embeddings = self
                 .model
                 .write()
                 .unwrap()
-                .forward_images(&page_images, &dummy_input)?
+                .forward_images(&page_tensor, &dummy_input)?
                 .to_dtype(DType::F32)?
                 .to_vec3::<f32>()?
                 .into_iter()
-                .map(|x| EmbeddingResult::MultiVector(x.to_vec()));
+                .map(EmbeddingResult::MultiVector);
 
             // zip the embeddings with the page numbers
             let embed_data_batch = image_embeddings
-                .zip(page_numbers.into_iter())
-                .zip(batch.iter())
+                .zip(page_numbers)
+                .zip(page_images.iter())
                 .map(|((embedding, page_number), page_image)| {
-                    let mut metadata = HashMap::new();
-
-                    let mut buf = Vec::new();
-                    let mut cursor = std::io::Cursor::new(&mut buf);
-                    page_image.write_to(&mut cursor, ImageFormat::Png).unwrap();
-                    let engine = base64::engine::general_purpose::STANDARD;
-                    let base64_image = engine.encode(&buf);
-
-                    metadata.insert("page_number".to_string(), page_number.to_string());
-                    metadata.insert(
-                        "file_path".to_string(),
-                        file_path.to_str().unwrap_or("").to_string(),
-                    );
-                    metadata.insert("image".to_string(), base64_image);
+                    let metadata = pdf_page_metadata(&file_path, page_number, page_image);
                     EmbedData::new(embedding, None, Some(metadata))
                 });
             embed_data.extend(embed_data_batch);
@@ -322,52 +326,59 @@ pub fn hub_load_safetensors(
     Ok(safetensors_files)
 }
 
-pub fn load_image<T: AsRef<std::path::Path>>(
-    path: T,
-    image_size: usize,
-    device: &Device,
-) -> anyhow::Result<Tensor> {
-    let img = image::ImageReader::open(path)?.decode()?;
-    let (height, width) = (image_size, image_size);
-    let img = img.resize_to_fill(
-        width as u32,
-        height as u32,
-        image::imageops::FilterType::Triangle,
-    );
-
-    let img = img.to_rgb8();
-
-    let img = img.into_raw();
-    let img = Tensor::from_vec(img, (height, width, 3), device)?
-        .permute((2, 0, 1))?
-        .to_dtype(DType::F32)?
-        .affine(2. / 255., -1.)?;
-    Ok(img)
+pub fn get_images_from_pdf<T: AsRef<Path>>(file_path: &T) -> Result<Vec<DynamicImage>, E> {
+    let pages = render_pdf_pages(file_path, &PdfRenderConfig::default())?;
+    Ok(pages.into_iter().map(|(_, image)| image).collect())
 }
 
-fn load_images<T: AsRef<std::path::Path>>(
-    paths: &[T],
-    image_size: usize,
-    device: &Device,
-) -> anyhow::Result<Tensor> {
-    let mut images = vec![];
-
-    for path in paths {
-        let tensor = load_image(path, image_size, device)?;
-        images.push(tensor);
-    }
-
-    let images = Tensor::stack(&images, 0)?;
+/// Renders the pages of a PDF selected by `render_config`, returning each image with its 1-based
+/// page number.
+pub fn render_pdf_pages<T: AsRef<Path>>(
+    file_path: &T,
+    render_config: &PdfRenderConfig,
+) -> Result<Vec<(u32, DynamicImage)>, E> {
+    render_pdf(PDF::from_file(file_path)?, render_config)
+}
 
-    Ok(images)
+/// Same as [render_pdf_pages], for a PDF held in memory.
+pub fn render_pdf_pages_from_bytes(
+    bytes: &[u8],
+    render_config: &PdfRenderConfig,
+) -> Result<Vec<(u32, DynamicImage)>, E> {
+    render_pdf(PDF::from_bytes(bytes.to_vec())?, render_config)
 }
 
-pub fn get_images_from_pdf<T: AsRef<Path>>(file_path: &T) -> Result<Vec<DynamicImage>, E> {
-    let pdf = PDF::from_file(file_path)?;
-    let page_count = pdf.page_count();
+fn render_pdf(pdf: PDF, render_config: &PdfRenderConfig) -> Result<Vec<(u32, DynamicImage)>, E> {
+    let Some(page_range) = render_config.page_range(pdf.page_count()) else {
+        return Ok(Vec::new());
+    };
     let pages = pdf.render(
-        Pages::Range(1..=page_count),
-        RenderOptionsBuilder::default().build()?,
+        Pages::Range(page_range.clone()),
+        RenderOptionsBuilder::default()
+            .resolution(DPI::Uniform(render_config.dpi))
+            .build()?,
     )?;
-    Ok(pages)
+    Ok(page_range.zip(pages).collect())
+}
+
+/// Builds the metadata attached to the embedding of a rendered PDF page.
+pub(crate) fn pdf_page_metadata(
+    file_path: &Path,
+    page_number: u32,
+    page_image: &DynamicImage,
+) -> HashMap<String, String> {
+    let mut buf = Vec::new();
+    let mut cursor = std::io::Cursor::new(&mut buf);
+    page_image.write_to(&mut cursor, ImageFormat::Png).unwrap();
+    let engine = base64::engine::general_purpose::STANDARD;
+    let base64_image = engine.encode(&buf);
+
+    let mut metadata = HashMap::new();
+    metadata.insert("page_number".to_string(), page_number.to_string());
+    metadata.insert(
+        "file_path".to_string(),
+        file_path.to_str().unwrap_or("").to_string(),
+    );
+    metadata.insert("image".to_string(), base64_image);
+    metadata
 }
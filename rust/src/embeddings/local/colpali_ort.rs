@@ -3,9 +3,8 @@ use std::{collections::HashMap, path::PathBuf};
 
 use crate::models::paligemma;
 use anyhow::Error as E;
-use base64::Engine;
 use half::f16;
-use image::{DynamicImage, ImageFormat};
+use image::DynamicImage;
 use ndarray::prelude::*;
 use ort::execution_providers::{CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProvider};
 use ort::session::builder::GraphOptimizationLevel;
@@ -13,9 +12,10 @@ use ort::session::Session;
 use rayon::prelude::*;
 use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 
+use crate::config::PdfRenderConfig;
 use crate::embeddings::embed::{EmbedData, EmbeddingResult};
 
-use super::colpali::{get_images_from_pdf, ColPaliEmbed};
+use super::colpali::{pdf_page_metadata, render_pdf_pages, ColPaliEmbed};
 
 pub struct OrtColPaliEmbedder {
     pub model: RwLock<Session>,
@@ -214,6 +214,32 @@ impl OrtColPaliEmbedder {
 
         Ok(e)
     }
+
+    /// Runs the model on a batch of preprocessed images, prefixing the dummy prompt with the
+    /// image tokens.
+    fn run_image_model(&self, pixel_values: Array4<f32>) -> Result<Vec<EmbeddingResult>, E> {
+        let num_images = pixel_values.shape()[0];
+        let mut dummy_input_batches = vec![];
+        for _ in 0..num_images {
+            dummy_input_batches.push(self.dummy_input.clone());
+        }
+        let dummy_input_batches = Array2::<i64>::from_shape_vec(
+            (num_images, self.dummy_input.shape()[1]),
+            dummy_input_batches
+                .into_iter()
+                .flatten()
+                .collect::<Vec<i64>>(),
+        )?;
+
+        let image_input_ids = Array2::<i64>::from_elem((num_images, 1024), 257152);
+
+        let input_ids = ndarray::concatenate![Axis(1), image_input_ids, dummy_input_batches];
+
+        let attention_mask =
+            Array2::<i64>::ones((num_images, 1024 + self.dummy_input.shape()[1]));
+
+        self.run_model(input_ids, attention_mask, pixel_values)
+    }
 }
 
 impl ColPaliEmbed for OrtColPaliEmbedder {
@@ -257,55 +283,25 @@ impl ColPaliEmbed for OrtColPaliEmbedder {
         Ok(e)
     }
 
-    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>> {
-        let pages = get_images_from_pdf(&file_path)?;
+    fn embed_pdf(
+        &self,
+        file_path: PathBuf,
+        batch_size: usize,
+        render_config: &PdfRenderConfig,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        let pages = render_pdf_pages(&file_path, render_config)?;
         let mut embed_data = Vec::new();
-        for (index, batch) in pages.chunks(batch_size).enumerate() {
-            let start_page = index * batch_size + 1;
-            let end_page = start_page + batch.len();
-            let page_numbers = (start_page..=end_page).collect::<Vec<_>>();
-            let page_images = pages_to_array(batch, self.num_channels, self.image_size)?;
-
-            let mut dummy_input_batches = vec![];
-            for _ in 0..page_images.shape()[0] {
-                dummy_input_batches.push(self.dummy_input.clone());
-            }
-            let dummy_input_batches = Array2::<i64>::from_shape_vec(
-                (page_images.shape()[0], self.dummy_input.shape()[1]),
-                dummy_input_batches
-                    .into_iter()
-                    .flatten()
-                    .collect::<Vec<i64>>(),
-            )?;
-
-            let image_input_ids = Array2::<i64>::from_elem((page_images.shape()[0], 1024), 257152);
-
-            let input_ids = ndarray::concatenate![Axis(1), image_input_ids, dummy_input_batches];
-
-            let attention_mask =
-                Array2::<i64>::ones((page_images.shape()[0], 1024 + self.dummy_input.shape()[1]));
-
-            let image_embeddings = self.run_model(input_ids, attention_mask, page_images)?;
+        for batch in pages.chunks(batch_size.max(1)) {
+            let (page_numbers, batch_images): (Vec<_>, Vec<_>) = batch.iter().cloned().unzip();
+            let page_images = pages_to_array(&batch_images, self.num_channels, self.image_size)?;
+            let image_embeddings = self.run_image_model(page_images)?;
             // zip the embeddings with the page numbers
             let embed_data_batch = image_embeddings
                 .into_iter()
-                .zip(page_numbers.into_iter())
-                .zip(batch.iter())
+                .zip(page_numbers)
+                .zip(batch_images.iter())
                 .map(|((embedding, page_number), page_image)| {
-                    let mut metadata = HashMap::new();
-
-                    let mut buf = Vec::new();
-                    let mut cursor = std::io::Cursor::new(&mut buf);
-                    page_image.write_to(&mut cursor, ImageFormat::Png).unwrap();
-                    let engine = base64::engine::general_purpose::STANDARD;
-                    let base64_image = engine.encode(&buf);
-
-                    metadata.insert("page_number".to_string(), page_number.to_string());
-                    metadata.insert(
-                        "file_path".to_string(),
-                        file_path.to_str().unwrap_or("").to_string(),
-                    );
-                    metadata.insert("image".to_string(), base64_image);
+                    let metadata = pdf_page_metadata(&file_path, page_number, page_image);
                     EmbedData::new(embedding, None, Some(metadata))
                 });
             embed_data.extend(embed_data_batch);
@@ -334,6 +330,15 @@ impl ColPaliEmbed for OrtColPaliEmbedder {
         Ok(e[0].clone())
     }
 
+    fn embed_dynamic_images(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        let image_array = pages_to_array(images, self.num_channels, self.image_size)?;
+        Ok(self
+            .run_image_model(image_array)?
+            .into_iter()
+            .map(|x| EmbedData::new(x, None, None))
+            .collect::<Vec<_>>())
+    }
+
     fn embed_image_batch(&self, image_paths: &[PathBuf]) -> anyhow::Result<Vec<EmbedData>> {
         let image_array = load_images_as_array(image_paths, self.num_channels, self.image_size)?;
 
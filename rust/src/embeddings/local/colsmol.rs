@@ -2,13 +2,13 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::RwLock;
 
-use base64::Engine;
 use candle_core::{DType, Device};
 use candle_nn::VarBuilder;
-use image::ImageFormat;
+use image::DynamicImage;
 
+use crate::config::PdfRenderConfig;
 use crate::embeddings::embed::{EmbedData, EmbeddingResult};
-use crate::embeddings::local::colpali::{get_images_from_pdf, ColPaliEmbed};
+use crate::embeddings::local::colpali::{pdf_page_metadata, render_pdf_pages, ColPaliEmbed};
 use crate::embeddings::select_device;
 use crate::models::idefics3::model::{ColIdefics3Model, Idefics3Config};
 use crate::models::idefics3::tensor_processing::Idefics3Processor;
@@ -157,15 +157,41 @@ impl ColPaliEmbed for ColSmolEmbedder {
             .map(|x| EmbedData::new(EmbeddingResult::MultiVector(x), None, None))
             .collect::<Vec<_>>())
     }
-    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>> {
-        let pages = get_images_from_pdf(&file_path)?;
+
+    fn embed_dynamic_images(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        let (input_ids, attention_mask, pixel_values, pixel_attention_mask) =
+            self.processor.preprocess(images, &self.device)?;
+        let encodings = self
+            .model
+            .write()
+            .unwrap()
+            .forward(
+                &input_ids,
+                &attention_mask,
+                &Some(pixel_values),
+                &pixel_attention_mask,
+            )?
+            .to_dtype(DType::F32)?
+            .to_vec3::<f32>()?;
+
+        Ok(encodings
+            .into_iter()
+            .map(|x| EmbedData::new(EmbeddingResult::MultiVector(x), None, None))
+            .collect::<Vec<_>>())
+    }
+
+    fn embed_pdf(
+        &self,
+        file_path: PathBuf,
+        batch_size: usize,
+        render_config: &PdfRenderConfig,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        let pages = render_pdf_pages(&file_path, render_config)?;
         let mut embed_data = Vec::new();
-        for (index, batch) in pages.chunks(batch_size).enumerate() {
-            let start_page = index * batch_size + 1;
-            let end_page = start_page + batch.len();
-            let page_numbers = (start_page..=end_page).collect::<Vec<_>>();
+        for batch in pages.chunks(batch_size.max(1)) {
+            let (page_numbers, batch_images): (Vec<_>, Vec<_>) = batch.iter().cloned().unzip();
             let (input_ids, attention_mask, pixel_values, pixel_attention_mask) =
-                self.processor.preprocess(batch, &self.device)?;
+                self.processor.preprocess(&batch_images, &self.device)?;
 
             let image_embeddings = self
                 .model
@@ -184,23 +210,10 @@ impl ColPaliEmbed for ColSmolEmbedder {
 
             // zip the embeddings with the page numbers
             let embed_data_batch = image_embeddings
-                .zip(page_numbers.into_iter())
-                .zip(batch.iter())
+                .zip(page_numbers)
+                .zip(batch_images.iter())
                 .map(|((embedding, page_number), page_image)| {
-                    let mut metadata = HashMap::new();
-
-                    let mut buf = Vec::new();
-                    let mut cursor = std::io::Cursor::new(&mut buf);
-                    page_image.write_to(&mut cursor, ImageFormat::Png).unwrap();
-                    let engine = base64::engine::general_purpose::STANDARD;
-                    let base64_image = engine.encode(&buf);
-
-                    metadata.insert("page_number".to_string(), page_number.to_string());
-                    metadata.insert(
-                        "file_path".to_string(),
-                        file_path.to_str().unwrap_or("").to_string(),
-                    );
-                    metadata.insert("image".to_string(), base64_image);
+                    let metadata = pdf_page_metadata(&file_path, page_number, page_image);
                     EmbedData::new(embedding, None, Some(metadata))
                 });
             embed_data.extend(embed_data_batch);
@@ -0,0 +1,475 @@
+//! Image preprocessing driven by a model's `preprocessor_config.json`.
+//!
+//! Hugging Face vision models ship a `preprocessor_config.json` describing how images are resized,
+//! cropped, rescaled and normalized before they reach the model. [ImagePreprocessorConfig] mirrors
+//! the fields used by the CLIP, SigLIP, DINOv2 and PaliGemma image processors and turns a
+//! [DynamicImage] into the pixel values the model expects.
+//!
+//! # Example
+//!
+//! ```rust
+//! use embed_anything::embeddings::local::image_processor::ImagePreprocessorConfig;
+//!
+//! let config: ImagePreprocessorConfig = serde_json::from_str(
+//!     r#"{"size": {"shortest_edge": 256}, "do_center_crop": true, "crop_size": 224}"#,
+//! )?;
+//! let image = image::DynamicImage::new_rgb8(640, 480);
+//! let (pixels, (height, width)) = config.preprocess(&image);
+//! assert_eq!((height, width), (224, 224));
+//! assert_eq!(pixels.len(), 3 * 224 * 224);
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use std::io::Cursor;
+use std::path::Path;
+
+use candle_core::{Device, Tensor};
+use hf_hub::api::sync::{ApiError, ApiRepo};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageDecoder, ImageReader, RgbImage};
+use serde::{Deserialize, Deserializer};
+
+/// Mean used by models trained with ImageNet normalization, such as DINOv2.
+pub const IMAGENET_MEAN: [f32; 3] = [0.485, 0.456, 0.406];
+/// Standard deviation used by models trained with ImageNet normalization, such as DINOv2.
+pub const IMAGENET_STD: [f32; 3] = [0.229, 0.224, 0.225];
+
+/// A `size` or `crop_size` entry of `preprocessor_config.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum SizeConfig {
+    /// A single number. As a `size`, this is the shortest edge when center cropping and a square
+    /// otherwise.
+    Square(u32),
+    /// An exact output size.
+    HeightWidth { height: u32, width: u32 },
+    /// Resize so that the shortest edge has this length, keeping the aspect ratio.
+    ShortestEdge { shortest_edge: u32 },
+}
+
+/// Preprocessing options read from a model's `preprocessor_config.json`.
+///
+/// Missing fields fall back to the defaults of the Hugging Face base image processor.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ImagePreprocessorConfig {
+    pub do_resize: bool,
+    pub size: Option<SizeConfig>,
+    /// The resampling filter, using PIL's numbering: 0 nearest, 1 lanczos, 2 bilinear,
+    /// 3 bicubic, 4 box and 5 hamming.
+    pub resample: u8,
+    pub do_center_crop: bool,
+    pub crop_size: Option<SizeConfig>,
+    pub do_rescale: bool,
+    pub rescale_factor: f32,
+    pub do_normalize: bool,
+    /// The mean of every channel. A single number applies to all three channels.
+    #[serde(deserialize_with = "channel_values")]
+    pub image_mean: Vec<f32>,
+    /// The standard deviation of every channel. A single number applies to all three channels.
+    #[serde(deserialize_with = "channel_values")]
+    pub image_std: Vec<f32>,
+}
+
+/// Reads a per-channel value given either as a list or as a single number.
+fn channel_values<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum ChannelValues {
+        Scalar(f32),
+        List(Vec<f32>),
+    }
+    Ok(match ChannelValues::deserialize(deserializer)? {
+        ChannelValues::Scalar(value) => vec![value; 3],
+        ChannelValues::List(values) => values,
+    })
+}
+
+impl Default for ImagePreprocessorConfig {
+    fn default() -> Self {
+        Self {
+            do_resize: true,
+            size: None,
+            resample: 2,
+            do_center_crop: false,
+            crop_size: None,
+            do_rescale: true,
+            rescale_factor: 1.0 / 255.0,
+            do_normalize: true,
+            image_mean: vec![0.5; 3],
+            image_std: vec![0.5; 3],
+        }
+    }
+}
+
+impl ImagePreprocessorConfig {
+    /// Reads the configuration from a `preprocessor_config.json` file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let config = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&config)?)
+    }
+
+    /// Downloads `preprocessor_config.json` from a model repository, falling back to `default`
+    /// when the repository does not have one. Other errors, such as network failures, are
+    /// returned instead of preprocessing images with the wrong configuration.
+    pub fn from_hub_or(repo: &ApiRepo, default: Self) -> anyhow::Result<Self> {
+        match repo.get("preprocessor_config.json") {
+            Ok(path) => Self::from_file(path),
+            Err(error) if is_not_found(&error) => Ok(default),
+            Err(error) => Err(anyhow::anyhow!(
+                "Failed to download preprocessor_config.json: {}",
+                error
+            )),
+        }
+    }
+
+    /// Scales the image to cover a `size` x `size` square and crops the center, normalizing
+    /// pixels to [-1, 1].
+    pub fn resize_to_fill(size: u32) -> Self {
+        Self {
+            size: Some(SizeConfig::ShortestEdge {
+                shortest_edge: size,
+            }),
+            do_center_crop: true,
+            crop_size: Some(SizeConfig::Square(size)),
+            ..Default::default()
+        }
+    }
+
+    fn filter(&self) -> FilterType {
+        match self.resample {
+            0 => FilterType::Nearest,
+            1 => FilterType::Lanczos3,
+            3 => FilterType::CatmullRom,
+            _ => FilterType::Triangle,
+        }
+    }
+
+    fn resize(&self, image: &DynamicImage) -> DynamicImage {
+        let (width, height) = image.dimensions();
+        let (new_width, new_height) = match self.size {
+            Some(SizeConfig::HeightWidth { height, width }) => (width, height),
+            Some(SizeConfig::Square(size)) if !self.do_center_crop => (size, size),
+            Some(SizeConfig::Square(size))
+            | Some(SizeConfig::ShortestEdge {
+                shortest_edge: size,
+            }) => shortest_edge_size(width, height, size),
+            None => return image.clone(),
+        };
+        if (new_width, new_height) == (width, height) {
+            return image.clone();
+        }
+        image.resize_exact(new_width, new_height, self.filter())
+    }
+
+    fn center_crop(&self, image: RgbImage) -> RgbImage {
+        let (crop_width, crop_height) = match self.crop_size {
+            Some(SizeConfig::Square(size)) => (size, size),
+            Some(SizeConfig::HeightWidth { height, width }) => (width, height),
+            Some(SizeConfig::ShortestEdge { shortest_edge }) => (shortest_edge, shortest_edge),
+            None => return image,
+        };
+        let (width, height) = image.dimensions();
+        if (width, height) == (crop_width, crop_height) {
+            return image;
+        }
+        // Images smaller than the crop are centered on a black canvas.
+        let offset = |size: u32, crop: u32| -> i64 {
+            if size >= crop {
+                -(((size - crop) / 2) as i64)
+            } else {
+                ((crop - size) / 2) as i64
+            }
+        };
+        let mut cropped = RgbImage::new(crop_width, crop_height);
+        image::imageops::overlay(
+            &mut cropped,
+            &image,
+            offset(width, crop_width),
+            offset(height, crop_height),
+        );
+        cropped
+    }
+
+    /// Resizes, crops, rescales and normalizes an image. Returns the pixel values in
+    /// channel-first (CHW) order together with the output `(height, width)`.
+    pub fn preprocess(&self, image: &DynamicImage) -> (Vec<f32>, (usize, usize)) {
+        let image = if self.do_resize {
+            self.resize(image)
+        } else {
+            image.clone()
+        };
+        let image = image.to_rgb8();
+        let image = if self.do_center_crop {
+            self.center_crop(image)
+        } else {
+            image
+        };
+
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut pixels = vec![0f32; 3 * height * width];
+        for (index, pixel) in image.pixels().enumerate() {
+            for channel in 0..3 {
+                let mut value = pixel[channel] as f32;
+                if self.do_rescale {
+                    value *= self.rescale_factor;
+                }
+                if self.do_normalize {
+                    value = (value - self.image_mean[channel]) / self.image_std[channel];
+                }
+                pixels[channel * height * width + index] = value;
+            }
+        }
+        (pixels, (height, width))
+    }
+
+    /// Preprocesses an image into a `(3, height, width)` tensor.
+    pub fn to_tensor(&self, image: &DynamicImage, device: &Device) -> anyhow::Result<Tensor> {
+        let (pixels, (height, width)) = self.preprocess(image);
+        Ok(Tensor::from_vec(pixels, (3, height, width), device)?)
+    }
+
+    /// Preprocesses a batch of images into a `(batch, 3, height, width)` tensor.
+    pub fn to_batch_tensor(
+        &self,
+        images: &[DynamicImage],
+        device: &Device,
+    ) -> anyhow::Result<Tensor> {
+        let images = images
+            .iter()
+            .map(|image| self.to_tensor(image, device))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Tensor::stack(&images, 0)?)
+    }
+}
+
+/// Whether the hub answered that the file does not exist.
+fn is_not_found(error: &ApiError) -> bool {
+    match error {
+        ApiError::RequestError(error) => matches!(**error, ureq::Error::Status(404, _)),
+        ApiError::TooManyRetries(error) => is_not_found(error),
+        _ => false,
+    }
+}
+
+fn shortest_edge_size(width: u32, height: u32, shortest_edge: u32) -> (u32, u32) {
+    let scale = |long: u32, short: u32| (shortest_edge as u64 * long as u64 / short as u64) as u32;
+    if width <= height {
+        (shortest_edge, scale(height, width))
+    } else {
+        (scale(width, height), shortest_edge)
+    }
+}
+
+/// Opens an image file, applying the rotation or flip recorded in its EXIF orientation.
+pub fn open_image<P: AsRef<Path>>(path: P) -> anyhow::Result<DynamicImage> {
+    decode_oriented(ImageReader::open(path)?.with_guessed_format()?)
+}
+
+/// Decodes an encoded image held in memory, applying its EXIF orientation.
+pub fn load_image_from_memory(bytes: &[u8]) -> anyhow::Result<DynamicImage> {
+    decode_oriented(ImageReader::new(Cursor::new(bytes)).with_guessed_format()?)
+}
+
+fn decode_oriented<R: std::io::BufRead + std::io::Seek>(
+    reader: ImageReader<R>,
+) -> anyhow::Result<DynamicImage> {
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    fn gradient(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            Rgb([(10 * x) as u8, (10 * y) as u8, 100])
+        }))
+    }
+
+    #[test]
+    fn test_parse_preprocessor_configs() {
+        let dinov2: ImagePreprocessorConfig = serde_json::from_str(
+            r#"{
+                "crop_size": {"height": 224, "width": 224},
+                "do_center_crop": true,
+                "do_normalize": true,
+                "do_rescale": true,
+                "do_resize": true,
+                "image_mean": [0.485, 0.456, 0.406],
+                "image_processor_type": "BitImageProcessor",
+                "image_std": [0.229, 0.224, 0.225],
+                "resample": 3,
+                "rescale_factor": 0.00392156862745098,
+                "size": {"shortest_edge": 256}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            dinov2.size,
+            Some(SizeConfig::ShortestEdge { shortest_edge: 256 })
+        );
+        assert_eq!(
+            dinov2.crop_size,
+            Some(SizeConfig::HeightWidth {
+                height: 224,
+                width: 224
+            })
+        );
+        assert_eq!(dinov2.filter(), FilterType::CatmullRom);
+        assert_eq!(dinov2.image_mean, IMAGENET_MEAN);
+
+        let clip: ImagePreprocessorConfig =
+            serde_json::from_str(r#"{"size": 224, "crop_size": 224, "do_center_crop": true}"#)
+                .unwrap();
+        assert_eq!(clip.size, Some(SizeConfig::Square(224)));
+        let (_, size) = clip.preprocess(&gradient(300, 200));
+        assert_eq!(size, (224, 224));
+
+        let siglip: ImagePreprocessorConfig = serde_json::from_str(
+            r#"{"size": {"height": 8, "width": 6}, "image_mean": 0.5, "image_std": [0.5, 0.5, 0.5]}"#,
+        )
+        .unwrap();
+        assert_eq!(siglip.image_mean, vec![0.5; 3]);
+        let (_, size) = siglip.preprocess(&gradient(30, 20));
+        assert_eq!(size, (8, 6));
+    }
+
+    #[test]
+    fn test_missing_preprocessor_config() {
+        let status = |code: u16| {
+            let response = ureq::Response::new(code, "", "").unwrap();
+            ApiError::RequestError(Box::new(ureq::Error::Status(code, response)))
+        };
+        assert!(is_not_found(&status(404)));
+        assert!(is_not_found(&ApiError::TooManyRetries(Box::new(status(
+            404
+        )))));
+        assert!(!is_not_found(&status(401)));
+        assert!(!is_not_found(&status(503)));
+        let io = std::io::Error::new(std::io::ErrorKind::InvalidData, "corrupt cache");
+        assert!(!is_not_found(&ApiError::IoError(io)));
+    }
+
+    #[test]
+    fn test_shortest_edge_resize() {
+        assert_eq!(shortest_edge_size(640, 480, 256), (341, 256));
+        assert_eq!(shortest_edge_size(480, 640, 256), (256, 341));
+        assert_eq!(shortest_edge_size(100, 100, 50), (50, 50));
+    }
+
+    #[test]
+    fn test_center_crop_and_normalize_parity() {
+        // Reference values computed by hand for a 4x3 gradient: crop the central 2x2 region
+        // (x in 1..3, y in 0..2), rescale by 1/255 and normalize with ImageNet statistics.
+        let config = ImagePreprocessorConfig {
+            size: None,
+            do_center_crop: true,
+            crop_size: Some(SizeConfig::Square(2)),
+            image_mean: IMAGENET_MEAN.to_vec(),
+            image_std: IMAGENET_STD.to_vec(),
+            ..Default::default()
+        };
+        let (pixels, size) = config.preprocess(&gradient(4, 3));
+        assert_eq!(size, (2, 2));
+
+        let normalize = |value: f32, channel: usize| {
+            (value / 255.0 - IMAGENET_MEAN[channel]) / IMAGENET_STD[channel]
+        };
+        let expected = [
+            [10.0, 20.0, 10.0, 20.0],
+            [0.0, 0.0, 10.0, 10.0],
+            [100.0, 100.0, 100.0, 100.0],
+        ]
+        .iter()
+        .enumerate()
+        .flat_map(|(channel, values)| values.map(|value| normalize(value, channel)))
+        .collect::<Vec<_>>();
+        for (actual, expected) in pixels.iter().zip(&expected) {
+            assert!((actual - expected).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_crop_pads_small_images() {
+        let config = ImagePreprocessorConfig {
+            do_center_crop: true,
+            crop_size: Some(SizeConfig::Square(4)),
+            do_normalize: false,
+            do_rescale: false,
+            ..Default::default()
+        };
+        let (pixels, size) = config.preprocess(&gradient(2, 2));
+        assert_eq!(size, (4, 4));
+        // The 2x2 image sits in the middle of a black 4x4 canvas.
+        let blue = &pixels[2 * 16..];
+        assert_eq!(blue[0], 0.0);
+        assert_eq!(blue[5], 100.0);
+        assert_eq!(blue[10], 100.0);
+        assert_eq!(blue[15], 0.0);
+    }
+
+    /// Compares the pixel values with those of the Hugging Face image processors of CLIP,
+    /// DINOv2 and ColPali, which `test_files/clip/pixel_values.py` writes to `pixel_values.json`.
+    #[test]
+    fn test_parity_with_transformers() {
+        let fixture: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string("../test_files/clip/pixel_values.json").unwrap(),
+        )
+        .unwrap();
+        let image = open_image(format!(
+            "../test_files/clip/{}",
+            fixture["image"].as_str().unwrap()
+        ))
+        .unwrap();
+
+        for (model, reference) in fixture["models"].as_object().unwrap() {
+            let config: ImagePreprocessorConfig =
+                serde_json::from_value(reference["preprocessor_config"].clone()).unwrap();
+            let (pixels, (height, width)) = config.preprocess(&image);
+
+            let shape: Vec<usize> = serde_json::from_value(reference["shape"].clone()).unwrap();
+            assert_eq!(shape, vec![3, height, width], "{model}");
+            let expected: Vec<f32> =
+                serde_json::from_value(reference["pixel_values"].clone()).unwrap();
+            assert_eq!(pixels.len(), expected.len(), "{model}");
+            // PIL rounds to 8 bits between its two resampling passes and the image crate does
+            // not, so single pixels may differ by a level, about 0.017 after normalization.
+            let differences = pixels
+                .iter()
+                .zip(&expected)
+                .map(|(actual, expected)| (actual - expected).abs())
+                .collect::<Vec<_>>();
+            let mean = differences.iter().sum::<f32>() / differences.len() as f32;
+            let max = differences.iter().cloned().fold(0.0, f32::max);
+            assert!(mean < 0.005, "{model}: mean difference {mean}");
+            assert!(max < 0.05, "{model}: max difference {max}");
+        }
+    }
+
+    #[test]
+    fn test_exif_orientation() {
+        // A 2x1 JPEG rotated by 90 degrees through its EXIF orientation tag decodes as 1x2.
+        let mut jpeg = Vec::new();
+        gradient(2, 1)
+            .write_to(&mut Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+        let exif: &[u8] = &[
+            0xFF, 0xE1, 0x00, 0x22, b'E', b'x', b'i', b'f', 0x00, 0x00, b'M', b'M', 0x00, 0x2A,
+            0x00, 0x00, 0x00, 0x08, 0x00, 0x01, 0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01,
+            0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let with_exif = [&jpeg[..2], exif, &jpeg[2..]].concat();
+
+        assert_eq!(load_image_from_memory(&jpeg).unwrap().dimensions(), (2, 1));
+        assert_eq!(
+            load_image_from_memory(&with_exif).unwrap().dimensions(),
+            (1, 2)
+        );
+    }
+}
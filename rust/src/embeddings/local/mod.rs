@@ -4,6 +4,7 @@
 //! Models use either Candle backend or ONNX Runtime for inference.
 
 pub mod bert;
+pub mod clap;
 pub mod clip;
 #[cfg(feature = "ort")]
 pub mod colbert;
@@ -11,6 +12,7 @@ pub mod colpali;
 #[cfg(feature = "ort")]
 pub mod colpali_ort;
 pub mod colsmol;
+pub mod image_processor;
 pub mod jina;
 pub mod model2vec;
 pub mod model_info;
@@ -1,14 +1,14 @@
 use std::sync::RwLock;
 use std::{collections::HashMap, path::PathBuf};
 
+use crate::config::PdfRenderConfig;
 use crate::embeddings::embed::{EmbedData, EmbeddingResult};
 use crate::embeddings::local::colpali::ColPaliEmbed;
 use crate::models::idefics3::array_processing::Idefics3Processor;
 use crate::models::paligemma;
 use anyhow::Error as E;
-use base64::Engine;
 use half::f16;
-use image::ImageFormat;
+use image::DynamicImage;
 use ndarray::prelude::*;
 use ort::execution_providers::{CUDAExecutionProvider, CoreMLExecutionProvider, ExecutionProvider};
 use ort::session::builder::GraphOptimizationLevel;
@@ -16,7 +16,7 @@ use ort::session::Session;
 use rayon::prelude::*;
 use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 
-use super::colpali::get_images_from_pdf;
+use super::colpali::{pdf_page_metadata, render_pdf_pages};
 
 
 pub struct OrtColSmolEmbedder {
@@ -286,14 +286,17 @@ impl ColPaliEmbed for OrtColSmolEmbedder {
         Ok(e)
     }
 
-    fn embed_file(&self, file_path: PathBuf, batch_size: usize) -> anyhow::Result<Vec<EmbedData>> {
-        let pages = get_images_from_pdf(&file_path)?;
+    fn embed_pdf(
+        &self,
+        file_path: PathBuf,
+        _batch_size: usize,
+        render_config: &PdfRenderConfig,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        let pages = render_pdf_pages(&file_path, render_config)?;
         let mut embed_data = Vec::new();
-        for (index, batch) in pages.chunks(1).enumerate() {
-            let start_page = index * batch_size + 1;
-            let end_page = start_page + batch.len();
-            let page_numbers = (start_page..=end_page).collect::<Vec<_>>();
-            let (input_ids, attention_mask, page_images, pixel_attention_mask) = self.processor.preprocess(&batch)?;
+        for batch in pages.chunks(1) {
+            let (page_numbers, batch_images): (Vec<_>, Vec<_>) = batch.iter().cloned().unzip();
+            let (input_ids, attention_mask, page_images, pixel_attention_mask) = self.processor.preprocess(&batch_images)?;
 
             let image_embeddings = self.run_model(
                 input_ids,
@@ -307,23 +310,10 @@ impl ColPaliEmbed for OrtColSmolEmbedder {
             // zip the embeddings with the page numbers
             let embed_data_batch = image_embeddings
                 .into_iter()
-                .zip(page_numbers.into_iter())
-                .zip(batch.iter())
+                .zip(page_numbers)
+                .zip(batch_images.iter())
                 .map(|((embedding, page_number), page_image)| {
-                    let mut metadata = HashMap::new();
-
-                    let mut buf = Vec::new();
-                    let mut cursor = std::io::Cursor::new(&mut buf);
-                    page_image.write_to(&mut cursor, ImageFormat::Png).unwrap();
-                    let engine = base64::engine::general_purpose::STANDARD;
-                    let base64_image = engine.encode(&buf);
-
-                    metadata.insert("page_number".to_string(), page_number.to_string());
-                    metadata.insert(
-                        "file_path".to_string(),
-                        file_path.to_str().unwrap_or("").to_string(),
-                    );
-                    metadata.insert("image".to_string(), base64_image);
+                    let metadata = pdf_page_metadata(&file_path, page_number, page_image);
                     EmbedData::new(embedding, None, Some(metadata))
                 });
             embed_data.extend(embed_data_batch);
@@ -357,6 +347,25 @@ impl ColPaliEmbed for OrtColSmolEmbedder {
         Ok(e[0].clone())
     }
 
+    fn embed_dynamic_images(&self, images: &[DynamicImage]) -> anyhow::Result<Vec<EmbedData>> {
+        let mut embed_data = Vec::new();
+        for image in images {
+            let (input_ids, attention_mask, image_array, pixel_attention_mask) =
+                self.processor.preprocess(std::slice::from_ref(image))?;
+            let embeddings = self.run_model(
+                input_ids,
+                attention_mask,
+                image_array.insert_axis(Axis(0)),
+                pixel_attention_mask
+                    .unwrap()
+                    .insert_axis(Axis(0))
+                    .mapv(|x| x as i64),
+            )?;
+            embed_data.extend(embeddings.into_iter().map(|x| EmbedData::new(x, None, None)));
+        }
+        Ok(embed_data)
+    }
+
     fn embed_image_batch(&self, image_paths: &[PathBuf]) -> anyhow::Result<Vec<EmbedData>> {
         let images = image_paths
             .iter()
@@ -9,10 +9,15 @@ use std::{collections::HashMap, fs};
 use anyhow::Error as E;
 
 use crate::{
-    embeddings::{embed::EmbeddingResult, select_device},
+    embeddings::{
+        embed::EmbeddingResult,
+        local::image_processor::{open_image, ImagePreprocessorConfig},
+        select_device,
+    },
     models::clip::div_l2_norm,
 };
 use candle_core::{DType, Device, Tensor};
+use image::DynamicImage;
 use candle_nn::{Module, VarBuilder};
 
 use crate::embeddings::embed::{EmbedData, EmbedImage};
@@ -33,7 +38,7 @@ impl VisionEncoderModel {
 pub struct VisionEncoderEmbedder {
     pub model: VisionEncoderModel,
     pub device: Device,
-    pub crop_size: usize,
+    pub preprocessor: ImagePreprocessorConfig,
 }
 impl Default for VisionEncoderEmbedder {
     fn default() -> Self {
@@ -79,16 +84,7 @@ impl VisionEncoderEmbedder {
         let config_str = std::fs::read_to_string(config_filename)?;
         let config_json: serde_json::Value = serde_json::from_str(&config_str)?;
 
-        let preprocessor_config_filename = api.get("preprocessor_config.json")?;
-        let preprocessor_config_str = std::fs::read_to_string(preprocessor_config_filename)?;
-        let preprocessor_config_json: serde_json::Value =
-            serde_json::from_str(&preprocessor_config_str)?;
-        let crop_size = preprocessor_config_json
-            .get("crop_size").expect("crop_size not found")
-            .get("height")
-            .expect("height not found")
-            .as_u64()
-            .expect("height not a number") as usize;
+        let preprocessor = ImagePreprocessorConfig::from_file(api.get("preprocessor_config.json")?)?;
 
         let model = if let Some(architectures) = config_json.get("architectures") {
             if let Some(arch) = architectures.get(0) {
@@ -147,7 +143,7 @@ impl VisionEncoderEmbedder {
         Ok(VisionEncoderEmbedder {
             model,
             device,
-            crop_size,
+            preprocessor,
         })
     }
 
@@ -155,32 +151,8 @@ impl VisionEncoderEmbedder {
         &self,
         path: T,
     ) -> anyhow::Result<Tensor> {
-        let img = image::ImageReader::open(path)?.decode()?;
-        let (height, width) = (self.crop_size, self.crop_size);
-        let img = img.resize_exact(
-            width as u32,
-            height as u32,
-            image::imageops::FilterType::CatmullRom,
-        );
-
-        let img = img.to_rgb8();
-
-        let img = img.into_raw();
-        let img = Tensor::from_vec(img, (height, width, 3), &self.device)?
-            .permute((2, 0, 1))?
-            .to_dtype(DType::F32)?
-            .affine(0.00392156862745098, 0.)?; // Rescale factor: 1/255
-                                               // ImageNet normalization: (image - mean) / std
-        let mean = Tensor::new(&[0.485f32, 0.456f32, 0.406f32], &self.device)?
-            .unsqueeze(1)?
-            .unsqueeze(2)?; // Shape: [3, 1, 1]
-        let std = Tensor::new(&[0.229f32, 0.224f32, 0.225f32], &self.device)?
-            .unsqueeze(1)?
-            .unsqueeze(2)?; // Shape: [3, 1, 1]
-
-        let img = img.broadcast_sub(&mean)?.broadcast_div(&std)?;
-        // .unsqueeze(0)?;
-        Ok(img)
+        let img = open_image(path)?;
+        self.preprocessor.to_tensor(&img, &self.device)
     }
 
     fn load_images<T: AsRef<std::path::Path>>(
@@ -257,6 +229,23 @@ impl EmbedImage for VisionEncoderEmbedder {
         ))
     }
 
+    async fn embed_dynamic_images(
+        &self,
+        images: &[DynamicImage],
+        batch_size: Option<usize>,
+    ) -> anyhow::Result<Vec<EmbedData>> {
+        let mut embeddings = Vec::new();
+        for image_batch in images.chunks(batch_size.unwrap_or(32)) {
+            let images = self.preprocessor.to_batch_tensor(image_batch, &self.device)?;
+            let batch_encodings = self.model.get_image_features(&images)?;
+            let normalized_encodings = div_l2_norm(&batch_encodings)?.to_vec2::<f32>()?;
+            embeddings.extend(normalized_encodings.into_iter().map(|data| {
+                EmbedData::new(EmbeddingResult::DenseVector(data), None, None)
+            }));
+        }
+        Ok(embeddings)
+    }
+
     async fn embed_pdf<T: AsRef<std::path::Path>>(
         &self,
         _pdf_path: T,
@@ -11,6 +11,7 @@ use embed::{EmbedData, Embedder, EmbeddingResult};
 use crate::file_processor::audio::audio_processor::Segment;
 
 pub mod cloud;
+pub mod compression;
 pub mod embed;
 pub mod local;
 pub mod utils;
@@ -49,13 +50,22 @@ pub fn get_audio_metadata<T: AsRef<std::path::Path>>(
                 audio_file.as_ref().to_str().unwrap().to_string(),
             );
             metadata.insert("text".to_string(), segments[i].dr.text.clone());
-            EmbedData::new(
+            if let Some(language) = &segments[i].language {
+                metadata.insert("language".to_string(), language.clone());
+            }
+            if !segments[i].timestamps.is_empty() {
+                metadata.insert(
+                    "timestamps".to_string(),
+                    serde_json::to_string(&segments[i].timestamps)?,
+                );
+            }
+            Ok(EmbedData::new(
                 data.clone(),
                 Some(segments[i].dr.text.clone()),
                 Some(metadata),
-            )
+            ))
         })
-        .collect::<Vec<_>>();
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
     Ok(final_embeddings)
 }
 
@@ -77,6 +87,27 @@ pub async fn embed_audio<T: AsRef<std::path::Path>>(
     get_audio_metadata(encodings, segments, audio_file)
 }
 
+/// Embeds segments transcribed from several audio files, given with the file they come from.
+pub async fn embed_audio_segments(
+    embedder: &Embedder,
+    segments: Vec<(Segment, String)>,
+    batch_size: Option<usize>,
+) -> Result<Vec<EmbedData>, anyhow::Error> {
+    let text_batch = segments
+        .iter()
+        .map(|(segment, _)| segment.dr.text.as_str())
+        .collect::<Vec<_>>();
+    let encodings = embedder.embed(&text_batch, batch_size, None).await?;
+    let embeddings = encodings
+        .into_iter()
+        .zip(segments)
+        .map(|(encoding, (segment, audio_file))| {
+            get_audio_metadata(vec![encoding], vec![segment], audio_file)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(embeddings.into_iter().flatten().collect())
+}
+
 pub fn normalize_l2(v: &Tensor) -> candle_core::Result<Tensor> {
     v.broadcast_div(&v.sqr()?.sum_keepdim(1)?.sqrt()?)
 }
@@ -3,14 +3,16 @@
 //! Provides functionality for discovering and filtering files in directories
 //! based on patterns and file types for embedding processing.
 
-use std::{collections::HashSet, io::Error, path::PathBuf};
+use std::{collections::HashSet, io::Error, path::PathBuf, sync::Arc};
 
+use processors_rs::registry::ProcessorRegistry;
 use regex::Regex;
 use walkdir::WalkDir;
 // use tokio::fs;
 
 pub struct FileParser {
     pub files: Vec<String>,
+    registry: Arc<ProcessorRegistry>,
 }
 
 impl Default for FileParser {
@@ -21,24 +23,38 @@ impl Default for FileParser {
 
 impl FileParser {
     pub fn new() -> Self {
-        Self { files: Vec::new() }
+        Self {
+            files: Vec::new(),
+            registry: Arc::new(ProcessorRegistry::new()),
+        }
     }
 
+    /// Sets the registry that decides which files [FileParser::get_text_files] picks up when no
+    /// extensions are given.
+    pub fn with_registry(mut self, registry: Arc<ProcessorRegistry>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Finds the files in a directory with one of the extensions, or, without extensions, the
+    /// files of a format the registry detects.
     pub fn get_text_files(
         &mut self,
         directory_path: &PathBuf,
         extensions: Option<Vec<String>>,
     ) -> Result<Vec<String>, Error> {
-        let extension_regex = match extensions {
-            Some(exts) => Regex::new(&format!(r"\.({})$", exts.join("|"))).unwrap(),
-            None => Regex::new(r"\.(pdf|md|txt|docx)$").unwrap(),
-        };
+        let extension_regex =
+            extensions.map(|exts| Regex::new(&format!(r"\.({})$", exts.join("|"))).unwrap());
+        let registry = &self.registry;
 
         let files: Vec<String> = WalkDir::new(directory_path)
             .into_iter()
             .filter_map(|entry| entry.ok())
             .filter(|entry| entry.file_type().is_file())
-            .filter(|entry| extension_regex.is_match(entry.file_name().to_str().unwrap_or("")))
+            .filter(|entry| match &extension_regex {
+                Some(regex) => regex.is_match(entry.file_name().to_str().unwrap_or("")),
+                None => matches!(registry.detect(entry.path()), Ok(Some(_))),
+            })
             .map(|entry| {
                 let absolute_path = entry
                     .path()
@@ -73,8 +89,11 @@ impl FileParser {
         Ok(self.files.clone())
     }
 
+    /// Finds the audio files in a directory, including the audio tracks of MP4, MKV and WebM
+    /// videos.
     pub fn get_audio_files(&mut self, directory_path: &PathBuf) -> Result<Vec<String>, Error> {
-        let audio_regex = Regex::new(r".*\.(wav)$").unwrap();
+        let audio_regex =
+            Regex::new(r"(?i).*\.(wav|mp3|flac|ogg|oga|m4a|aac|mp4|m4v|mkv|mka|webm)$").unwrap();
 
         let audio_paths: Vec<String> = WalkDir::new(directory_path)
             .into_iter()
@@ -177,6 +196,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_text_files_default() {
+        let temp_dir = TempDir::new("example").unwrap();
+        for file in ["a.pdf", "b.rs", "c.py", "d.ts", "e.jpg", "f.lock"] {
+            File::create(temp_dir.path().join(file)).unwrap();
+        }
+        std::fs::write(temp_dir.path().join("g"), "%PDF-1.7\n").unwrap();
+        std::fs::write(temp_dir.path().join("h.txt"), [0x89, b'P', b'N', b'G', 0]).unwrap();
+
+        let mut file_parser = FileParser::new();
+        let mut files = file_parser
+            .get_text_files(&PathBuf::from(temp_dir.path()), None)
+            .unwrap();
+        files.sort();
+
+        let names = files
+            .iter()
+            .map(|file| {
+                PathBuf::from(file)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a.pdf", "b.rs", "c.py", "d.ts", "g"]);
+    }
+
     #[test]
     fn test_get_image_paths() {
         let temp_dir = TempDir::new("example").unwrap();
@@ -201,12 +248,20 @@ mod tests {
 
     #[test]
     fn test_get_audio_paths() {
+        let temp_dir = TempDir::new("example").unwrap();
+        for file in [
+            "a.wav", "b.MP3", "c.flac", "d.ogg", "e.m4a", "f.mp4", "g.mkv", "h.txt",
+        ] {
+            File::create(temp_dir.path().join(file)).unwrap();
+        }
+
         let mut file_parser = FileParser::new();
         let audio_files = file_parser
-            .get_audio_files(&PathBuf::from("../test_files"))
+            .get_audio_files(&PathBuf::from(temp_dir.path()))
             .unwrap();
 
-        assert_eq!(audio_files.len(), 2);
+        assert_eq!(audio_files.len(), 7);
+        assert!(audio_files.iter().all(|file| !file.ends_with(".txt")));
     }
 
     #[test]
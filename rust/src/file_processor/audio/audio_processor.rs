@@ -5,6 +5,7 @@ extern crate intel_mkl_src;
 extern crate accelerate_src;
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::{Error as E, Result};
 use candle_core::{Device, IndexOp, Tensor};
@@ -14,15 +15,20 @@ use rand::{
     distr::{weighted::WeightedIndex, Distribution},
     SeedableRng,
 };
+use serde::Serialize;
 use tokenizers::Tokenizer;
 
 use candle_transformers::models::whisper::{self as m, Config};
 
 use crate::embeddings::select_device;
 
+use super::language::{detect_language, language_code, language_token};
+use super::vad::VadConfig;
+
 #[cfg(feature = "audio")]
-use {crate::embeddings::embed::AudioDecoder, candle_transformers::models::whisper::audio};
+use crate::embeddings::embed::AudioDecoder;
 
+#[derive(Clone)]
 pub enum WhichAudioDecoderModel {
     Normal(m::model::Whisper),
     Quantized(m::quantized_model::Whisper),
@@ -63,11 +69,34 @@ impl WhichAudioDecoderModel {
     }
 }
 
+/// Called with the number of seconds of a recording that have been transcribed and its total
+/// duration in seconds.
+pub type AudioProgressCallback = Arc<dyn Fn(f64, f64) + Send + Sync>;
+
+/// Cloning the model shares its weights.
+#[derive(Clone)]
 pub struct AudioDecoderModel {
     pub model: WhichAudioDecoderModel,
     pub tokenizer: Tokenizer,
     pub config: Config,
     pub device: Device,
+    /// Whether to predict timestamp tokens and return timestamped phrases in
+    /// [Segment::timestamps]. Defaults to false.
+    pub timestamps: bool,
+    /// Whether the model can transcribe languages other than English.
+    pub multilingual: bool,
+    /// The Whisper code of the spoken language, such as "fr". Defaults to None, which detects the
+    /// language of every recording with multilingual models.
+    pub language: Option<String>,
+    /// Whether to transcribe the speech or translate it to English. Defaults to
+    /// [Task::Transcribe].
+    pub task: Task,
+    /// When set, recordings are split into speech regions with
+    /// [detect_speech](super::vad::detect_speech) and only the speech is decoded. Defaults to
+    /// None, which decodes fixed 30 second windows.
+    pub vad: Option<VadConfig>,
+    /// Reports the transcription progress of every recording. Defaults to None.
+    pub progress_callback: Option<AudioProgressCallback>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -86,13 +115,87 @@ pub struct Segment {
     pub start: f64,
     pub duration: f64,
     pub dr: DecodingResult,
+    /// Timestamped phrases of the segment, in seconds from the start of the recording. Only
+    /// filled when the decoder runs with timestamps enabled.
+    pub timestamps: Vec<TimestampedText>,
+    /// The code of the spoken language, either as configured or as detected.
+    pub language: Option<String>,
 }
 
-#[allow(dead_code)]
+impl Segment {
+    /// Moves a segment decoded from audio starting `offset` seconds into the recording to the
+    /// time of the recording. The decoder pads the audio with silence, so the segment and its
+    /// phrases are clamped to `end`, the end of the decoded audio in seconds.
+    pub(crate) fn offset(&mut self, offset: f64, end: f64) {
+        self.start += offset;
+        self.duration = self.duration.min(end - self.start);
+        for phrase in &mut self.timestamps {
+            phrase.start += offset;
+            phrase.end = (phrase.end + offset).min(end);
+        }
+    }
+}
+
+/// A piece of transcribed text with its start and end time in seconds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimestampedText {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+impl TimestampedText {
+    /// Splits the text into words with estimated timestamps. Whisper only predicts timestamps
+    /// at phrase boundaries, so the duration of the phrase is divided among its words in
+    /// proportion to their length.
+    pub fn words(&self) -> Vec<TimestampedText> {
+        let words = self.text.split_whitespace().collect::<Vec<_>>();
+        let total_chars = words.iter().map(|word| word.chars().count()).sum::<usize>();
+        if total_chars == 0 {
+            return Vec::new();
+        }
+        let seconds_per_char = (self.end - self.start) / total_chars as f64;
+        let last = words.len() - 1;
+        let mut start = self.start;
+        words
+            .into_iter()
+            .enumerate()
+            .map(|(i, word)| {
+                let end = match i == last {
+                    true => self.end,
+                    false => start + word.chars().count() as f64 * seconds_per_char,
+                };
+                let word = TimestampedText {
+                    start,
+                    end,
+                    text: word.to_string(),
+                };
+                start = end;
+                word
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum Task {
+    #[default]
     Transcribe,
+    /// Translates the speech to English.
     Translate,
 }
+
+impl TryFrom<&str> for Task {
+    type Error = E;
+
+    fn try_from(task: &str) -> Result<Self> {
+        match task {
+            "transcribe" => Ok(Self::Transcribe),
+            "translate" => Ok(Self::Translate),
+            _ => anyhow::bail!("unknown task {task}, expected \"transcribe\" or \"translate\""),
+        }
+    }
+}
 pub struct Decoder<'a> {
     pub model: &'a mut AudioDecoderModel,
     pub rng: rand::rngs::StdRng,
@@ -107,6 +210,9 @@ pub struct Decoder<'a> {
     pub no_speech_token: u32,
     pub no_timestamps_token: u32,
     pub language_token: Option<u32>,
+    /// Called by [Decoder::run] with the number of seconds of the mel spectrogram decoded so
+    /// far.
+    pub on_progress: Option<Box<dyn FnMut(f64) + 'a>>,
 }
 
 impl<'a> Decoder<'a> {
@@ -158,6 +264,7 @@ impl<'a> Decoder<'a> {
             no_speech_token,
             language_token,
             no_timestamps_token,
+            on_progress: None,
         })
     }
 
@@ -165,7 +272,7 @@ impl<'a> Decoder<'a> {
         let model = &mut self.model;
         let audio_features = model.model.encoder_forward(mel, true)?;
         if self.verbose {
-            println!("audio features: {:?}", audio_features.dims());
+            tracing::debug!("audio features: {:?}", audio_features.dims());
         }
         let sample_len = model.model.config().max_target_positions / 2;
         let mut sum_logprob = 0f64;
@@ -268,7 +375,7 @@ impl<'a> Decoder<'a> {
                     }
                 }
                 Err(err) => {
-                    println!("Error running at {t}: {err}")
+                    tracing::warn!("Error running at {t}: {err}")
                 }
             }
         }
@@ -285,23 +392,30 @@ impl<'a> Decoder<'a> {
             let segment_size = usize::min(content_frames - seek, m::N_FRAMES);
             let mel_segment = mel.narrow(2, seek, segment_size)?;
             let segment_duration = (segment_size * m::HOP_LENGTH) as f64 / m::SAMPLE_RATE as f64;
+            let _span =
+                tracing::debug_span!("segment", start = time_offset, duration = segment_duration)
+                    .entered();
             let dr = self.decode_with_fallback(&mel_segment)?;
             seek += segment_size;
+            if let Some(on_progress) = self.on_progress.as_mut() {
+                on_progress(time_offset + segment_duration);
+            }
             if dr.no_speech_prob > m::NO_SPEECH_THRESHOLD && dr.avg_logprob < m::LOGPROB_THRESHOLD {
-                println!("no speech detected, skipping {seek} {dr:?}");
+                tracing::debug!(
+                    no_speech_prob = dr.no_speech_prob,
+                    avg_logprob = dr.avg_logprob,
+                    "no speech detected, skipping"
+                );
                 continue;
             }
-            let segment = Segment {
+            let mut segment = Segment {
                 start: time_offset,
                 duration: segment_duration,
                 dr,
+                timestamps: vec![],
+                language: None,
             };
             if self.timestamps {
-                println!(
-                    "{:.1}s -- {:.1}s",
-                    segment.start,
-                    segment.start + segment.duration,
-                );
                 let mut tokens_to_decode = vec![];
                 let mut prev_timestamp_s = 0f32;
                 for &token in segment.dr.tokens.iter() {
@@ -317,7 +431,17 @@ impl<'a> Decoder<'a> {
                                 .tokenizer
                                 .decode(&tokens_to_decode, true)
                                 .map_err(E::msg)?;
-                            println!("  {:.1}s-{:.1}s: {}", prev_timestamp_s, timestamp_s, text);
+                            tracing::debug!(
+                                "{:.1}s-{:.1}s: {}",
+                                prev_timestamp_s,
+                                timestamp_s,
+                                text
+                            );
+                            segment.timestamps.push(TimestampedText {
+                                start: segment.start + prev_timestamp_s as f64,
+                                end: segment.start + timestamp_s as f64,
+                                text: text.trim().to_string(),
+                            });
                             tokens_to_decode.clear()
                         }
                         prev_timestamp_s = timestamp_s;
@@ -332,20 +456,37 @@ impl<'a> Decoder<'a> {
                         .decode(&tokens_to_decode, true)
                         .map_err(E::msg)?;
                     if !text.is_empty() {
-                        println!("  {:.1}s-...: {}", prev_timestamp_s, text);
+                        tracing::debug!("{:.1}s-...: {}", prev_timestamp_s, text);
+                        segment.timestamps.push(TimestampedText {
+                            start: segment.start + prev_timestamp_s as f64,
+                            end: segment.start + segment.duration,
+                            text: text.trim().to_string(),
+                        });
                     }
                     tokens_to_decode.clear()
                 }
-            } else {
-                println!(
-                    "{:.1}s -- {:.1}s: {}",
-                    segment.start,
-                    segment.start + segment.duration,
-                    segment.dr.text,
-                )
+                // Keep the timestamp tokens out of the segment text.
+                let text_tokens = segment
+                    .dr
+                    .tokens
+                    .iter()
+                    .copied()
+                    .filter(|&token| token <= self.no_timestamps_token)
+                    .collect::<Vec<_>>();
+                segment.dr.text = self
+                    .model
+                    .tokenizer
+                    .decode(&text_tokens, true)
+                    .map_err(E::msg)?;
             }
+            tracing::debug!(
+                avg_logprob = segment.dr.avg_logprob,
+                no_speech_prob = segment.dr.no_speech_prob,
+                "{}",
+                segment.dr.text
+            );
             if self.verbose {
-                println!("{seek}: {segment:?}, in {:?}", start.elapsed());
+                tracing::debug!("{seek}: {segment:?}, in {:?}", start.elapsed());
             }
             segments.push(segment)
         }
@@ -506,6 +647,7 @@ impl AudioDecoderModel {
         quantized: bool,
     ) -> Result<Self> {
         let device = select_device();
+        let multilingual = WhichModel::from(model_type).is_multilingual();
 
         match quantized {
             false => {
@@ -529,6 +671,12 @@ impl AudioDecoderModel {
                     tokenizer,
                     config,
                     device,
+                    timestamps: false,
+                    multilingual,
+                    language: None,
+                    task: Task::Transcribe,
+                    vad: None,
+                    progress_callback: None,
                 })
             }
             true => {
@@ -556,63 +704,179 @@ impl AudioDecoderModel {
                     tokenizer,
                     config,
                     device,
+                    timestamps: false,
+                    multilingual,
+                    language: None,
+                    task: Task::Transcribe,
+                    vad: None,
+                    progress_callback: None,
                 })
             }
         }
     }
+
+    /// Enables timestamp prediction, so that [Segment::timestamps] holds the timestamped
+    /// phrases of every segment.
+    pub fn with_timestamps(mut self, timestamps: bool) -> Self {
+        self.timestamps = timestamps;
+        self
+    }
+
+    /// Sets the spoken language from its code, such as "fr", or its English name, such as
+    /// "French". Without a language, multilingual models detect it.
+    pub fn with_language(mut self, language: &str) -> Result<Self> {
+        let code = language_code(language)
+            .ok_or_else(|| anyhow::anyhow!("unsupported language {language}"))?;
+        if !self.multilingual && code != "en" {
+            anyhow::bail!("the language cannot be set to {code} for an English-only model");
+        }
+        self.language = Some(code.to_string());
+        Ok(self)
+    }
+
+    /// Splits recordings at pauses with voice activity detection before decoding them, skipping
+    /// silence. Segments then follow the detected speech regions.
+    pub fn with_vad(mut self, vad: VadConfig) -> Self {
+        self.vad = Some(vad);
+        self
+    }
+
+    /// Reports the transcription progress of every recording to `callback`, with the number of
+    /// seconds transcribed and the total duration in seconds.
+    pub fn with_progress_callback(
+        mut self,
+        callback: impl Fn(f64, f64) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Sets whether to transcribe the speech or translate it to English.
+    pub fn with_task(mut self, task: Task) -> Result<Self> {
+        if !self.multilingual && task == Task::Translate {
+            anyhow::bail!("translation requires a multilingual model");
+        }
+        self.task = task;
+        Ok(self)
+    }
+
+    /// Returns the mel filters matching the number of mel bins of the model.
+    pub(crate) fn mel_filters(&self) -> Result<Vec<f32>> {
+        let mel_bytes = match self.config.num_mel_bins {
+            80 => include_bytes!("melfilters.bytes").as_slice(),
+            128 => include_bytes!("melfilters128.bytes").as_slice(),
+            nmel => anyhow::bail!("unexpected num_mel_bins {nmel}"),
+        };
+        let mut mel_filters = vec![0f32; mel_bytes.len() / 4];
+        <byteorder::LittleEndian as byteorder::ByteOrder>::read_f32_into(
+            mel_bytes,
+            &mut mel_filters,
+        );
+        Ok(mel_filters)
+    }
+
+    /// Computes the `(1, num_mel_bins, frames)` mel spectrogram of 16 kHz PCM.
+    pub(crate) fn mel(&self, pcm_data: &[f32], mel_filters: &[f32]) -> Result<Tensor> {
+        let mel = m::audio::pcm_to_mel(&self.config, pcm_data, mel_filters);
+        let mel_len = mel.len();
+        let mel = Tensor::from_vec(
+            mel,
+            (
+                1,
+                self.config.num_mel_bins,
+                mel_len / self.config.num_mel_bins,
+            ),
+            &self.device,
+        )?;
+        tracing::debug!("loaded mel: {:?}", mel.dims());
+        Ok(mel)
+    }
+
+    /// Returns the language token to prompt the decoder with and the code of the language,
+    /// detecting the language from `mel` if none is set.
+    pub(crate) fn resolve_language(&mut self, mel: &Tensor) -> Result<(Option<u32>, String)> {
+        if !self.multilingual {
+            return Ok((None, "en".to_string()));
+        }
+        let code = match &self.language {
+            Some(code) => code.clone(),
+            None => {
+                let (code, probability) = detect_language(&mut self.model, &self.tokenizer, mel)?;
+                tracing::info!(language = code, probability, "detected language");
+                code.to_string()
+            }
+        };
+        Ok((Some(language_token(&self.tokenizer, &code)?), code))
+    }
 }
 
 #[cfg(feature = "audio")]
 mod audio_processing {
     use super::*;
-    use crate::file_processor::audio::pcm_decode;
+    use crate::file_processor::audio::{pcm_decode, resample::resample, vad::detect_speech};
 
     impl AudioDecoderModel {
         pub fn process_audio<T: AsRef<std::path::Path>>(
             &mut self,
             audio_path: T,
         ) -> Result<Vec<Segment>> {
-            let mel_bytes = match self.config.num_mel_bins {
-                80 => include_bytes!("melfilters.bytes").as_slice(),
-                128 => include_bytes!("melfilters128.bytes").as_slice(),
-                nmel => anyhow::bail!("unexpected num_mel_bins {nmel}"),
-            };
-            let mut mel_filters = vec![0f32; mel_bytes.len() / 4];
-            <byteorder::LittleEndian as byteorder::ByteOrder>::read_f32_into(
-                mel_bytes,
-                &mut mel_filters,
-            );
-
+            let mel_filters = self.mel_filters()?;
             let (pcm_data, sample_rate) = pcm_decode::audio_processing::pcm_decode(audio_path)?;
-            if sample_rate != m::SAMPLE_RATE as u32 {
-                anyhow::bail!("input file must have a {} sampling rate", m::SAMPLE_RATE)
+            if sample_rate == 0 {
+                anyhow::bail!("input file does not specify its sampling rate")
             }
-            println!("pcm data loaded {}", pcm_data.len());
-            let mel = audio::pcm_to_mel(&self.config, &pcm_data, &mel_filters);
-            let mel_len = mel.len();
-            let mel = Tensor::from_vec(
-                mel,
-                (
-                    1,
-                    self.config.num_mel_bins,
-                    mel_len / self.config.num_mel_bins,
-                ),
-                &self.device,
-            )?;
-            println!("loaded mel: {:?}", mel.dims());
+            let pcm_data = resample(&pcm_data, sample_rate, m::SAMPLE_RATE as u32);
+            let total_duration = pcm_data.len() as f64 / m::SAMPLE_RATE as f64;
+            let _span = tracing::info_span!("process_audio", duration = total_duration).entered();
+
+            // Speech regions in samples, or the whole recording without voice activity detection.
+            let regions = match &self.vad {
+                Some(vad) => detect_speech(&pcm_data, m::SAMPLE_RATE as u32, vad),
+                None => {
+                    let recording = 0..pcm_data.len();
+                    vec![recording]
+                }
+            };
+            let mels = regions
+                .iter()
+                .map(|region| self.mel(&pcm_data[region.clone()], &mel_filters))
+                .collect::<Result<Vec<_>>>()?;
+            let Some(first_mel) = mels.first() else {
+                return Ok(Vec::new());
+            };
 
-            let language_token = None;
+            let (language_token, language) = self.resolve_language(first_mel)?;
+            let (task, timestamps) = (self.task, self.timestamps);
+            let progress_callback = self.progress_callback.clone();
 
             let mut dc = Decoder::new(
                 self,
                 299792458,
                 &self.device.clone(),
                 language_token,
-                Some(Task::Transcribe),
-                false,
+                Some(task),
+                timestamps,
                 false,
             )?;
-            let segments = dc.run(&mel)?;
+            let mut segments = Vec::new();
+            for (region, mel) in regions.iter().zip(&mels) {
+                let offset = region.start as f64 / m::SAMPLE_RATE as f64;
+                let end = region.end as f64 / m::SAMPLE_RATE as f64;
+                dc.on_progress = progress_callback.clone().map(|callback| {
+                    Box::new(move |seconds: f64| {
+                        callback((offset + seconds).min(end), total_duration)
+                    }) as Box<dyn FnMut(f64)>
+                });
+                for mut segment in dc.run(mel)? {
+                    segment.offset(offset, end);
+                    segment.language = Some(language.clone());
+                    segments.push(segment);
+                }
+            }
+            // Trailing silence skipped by voice activity detection is never decoded.
+            if let Some(callback) = progress_callback {
+                callback(total_duration, total_duration);
+            }
 
             Ok(segments)
         }
@@ -0,0 +1,284 @@
+//! Transcript export to subtitle and JSON files.
+//!
+//! Converts the segments returned by `AudioDecoderModel::process_audio` into SubRip (SRT) and
+//! WebVTT subtitles or a JSON transcript, and writes them next to the audio file with
+//! [save_transcripts].
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use super::audio_processor::{Segment, TimestampedText};
+
+/// A transcript file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptFormat {
+    /// SubRip subtitles.
+    Srt,
+    /// WebVTT subtitles.
+    Vtt,
+    /// A JSON transcript with the timing and confidence of every segment.
+    Json,
+}
+
+impl TranscriptFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+            Self::Json => "json",
+        }
+    }
+
+    /// Renders the transcript of `segments` in this format.
+    pub fn render(&self, segments: &[Segment]) -> Result<String> {
+        match self {
+            Self::Srt => Ok(to_srt(segments)),
+            Self::Vtt => Ok(to_vtt(segments)),
+            Self::Json => to_json(segments),
+        }
+    }
+}
+
+impl TryFrom<&str> for TranscriptFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(format: &str) -> Result<Self> {
+        match format.to_lowercase().as_str() {
+            "srt" => Ok(Self::Srt),
+            "vtt" | "webvtt" => Ok(Self::Vtt),
+            "json" => Ok(Self::Json),
+            _ => anyhow::bail!("unknown transcript format {format}"),
+        }
+    }
+}
+
+/// Renders SubRip subtitles. Segments with timestamps get one cue per phrase.
+pub fn to_srt(segments: &[Segment]) -> String {
+    cues(segments)
+        .iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n\n",
+                i + 1,
+                format_time(cue.start, ','),
+                format_time(cue.end, ','),
+                cue.text
+            )
+        })
+        .collect()
+}
+
+/// Renders WebVTT subtitles. Segments with timestamps get one cue per phrase.
+pub fn to_vtt(segments: &[Segment]) -> String {
+    let cues = cues(segments)
+        .iter()
+        .map(|cue| {
+            format!(
+                "{} --> {}\n{}\n\n",
+                format_time(cue.start, '.'),
+                format_time(cue.end, '.'),
+                cue.text
+            )
+        })
+        .collect::<String>();
+    format!("WEBVTT\n\n{cues}")
+}
+
+#[derive(Serialize)]
+struct JsonTranscript<'a> {
+    language: Option<&'a str>,
+    text: String,
+    segments: Vec<JsonSegment<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonSegment<'a> {
+    start: f64,
+    end: f64,
+    text: &'a str,
+    avg_logprob: f64,
+    no_speech_prob: f64,
+    /// The mean token probability, `exp(avg_logprob)`.
+    confidence: f64,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    timestamps: &'a [TimestampedText],
+}
+
+/// Renders a JSON transcript with the text, timing and confidence of every segment.
+pub fn to_json(segments: &[Segment]) -> Result<String> {
+    let transcript = JsonTranscript {
+        language: segments.iter().find_map(|s| s.language.as_deref()),
+        text: segments
+            .iter()
+            .map(|segment| segment.dr.text.trim())
+            .collect::<Vec<_>>()
+            .join(" "),
+        segments: segments
+            .iter()
+            .map(|segment| JsonSegment {
+                start: segment.start,
+                end: segment.start + segment.duration,
+                text: segment.dr.text.trim(),
+                avg_logprob: segment.dr.avg_logprob,
+                no_speech_prob: segment.dr.no_speech_prob,
+                confidence: segment.dr.avg_logprob.exp(),
+                timestamps: &segment.timestamps,
+            })
+            .collect(),
+    };
+    Ok(serde_json::to_string_pretty(&transcript)?)
+}
+
+/// Writes the transcript of `segments` next to `audio_file` in every format and returns the paths
+/// of the written files. The extension of the format is appended to the name of the audio file,
+/// as in `talk.mp3.srt`, so that transcripts never replace other files named after the recording.
+pub fn save_transcripts<T: AsRef<Path>>(
+    segments: &[Segment],
+    audio_file: T,
+    formats: &[TranscriptFormat],
+) -> Result<Vec<PathBuf>> {
+    formats
+        .iter()
+        .map(|format| {
+            let mut path = audio_file.as_ref().as_os_str().to_owned();
+            path.push(".");
+            path.push(format.extension());
+            let path = PathBuf::from(path);
+            std::fs::write(&path, format.render(segments)?)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+/// The subtitle cues of a transcript: the timestamped phrases of every segment, or the segment
+/// itself when it has no timestamps.
+fn cues(segments: &[Segment]) -> Vec<TimestampedText> {
+    segments
+        .iter()
+        .flat_map(|segment| match segment.timestamps.is_empty() {
+            true => vec![TimestampedText {
+                start: segment.start,
+                end: segment.start + segment.duration,
+                text: segment.dr.text.trim().to_string(),
+            }],
+            false => segment.timestamps.clone(),
+        })
+        .filter(|cue| !cue.text.is_empty())
+        .collect()
+}
+
+/// Formats seconds as `HH:MM:SS<separator>mmm`.
+fn format_time(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{separator}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processor::audio::audio_processor::DecodingResult;
+    use tempdir::TempDir;
+
+    fn segments() -> Vec<Segment> {
+        vec![
+            Segment {
+                start: 0.0,
+                duration: 4.5,
+                dr: DecodingResult {
+                    text: " Hello there.".to_string(),
+                    avg_logprob: -0.1,
+                    ..Default::default()
+                },
+                timestamps: vec![],
+                language: Some("en".to_string()),
+            },
+            Segment {
+                start: 3661.25,
+                duration: 2.0,
+                dr: DecodingResult {
+                    text: "First. Second.".to_string(),
+                    ..Default::default()
+                },
+                timestamps: vec![
+                    TimestampedText {
+                        start: 3661.25,
+                        end: 3662.0,
+                        text: "First.".to_string(),
+                    },
+                    TimestampedText {
+                        start: 3662.0,
+                        end: 3663.25,
+                        text: "Second.".to_string(),
+                    },
+                ],
+                language: Some("en".to_string()),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_to_srt() {
+        assert_eq!(
+            to_srt(&segments()),
+            "1\n00:00:00,000 --> 00:00:04,500\nHello there.\n\n\
+             2\n01:01:01,250 --> 01:01:02,000\nFirst.\n\n\
+             3\n01:01:02,000 --> 01:01:03,250\nSecond.\n\n"
+        );
+    }
+
+    #[test]
+    fn test_to_vtt() {
+        let vtt = to_vtt(&segments());
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:04.500\nHello there.\n\n"));
+        assert!(vtt.ends_with("01:01:02.000 --> 01:01:03.250\nSecond.\n\n"));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json: serde_json::Value = serde_json::from_str(&to_json(&segments()).unwrap()).unwrap();
+        assert_eq!(json["language"], "en");
+        assert_eq!(json["text"], "Hello there. First. Second.");
+        assert_eq!(json["segments"][0]["end"], 4.5);
+        assert!(
+            (json["segments"][0]["confidence"].as_f64().unwrap() - (-0.1f64).exp()).abs() < 1e-9
+        );
+        assert!(json["segments"][0].get("timestamps").is_none());
+        assert_eq!(json["segments"][1]["timestamps"][1]["text"], "Second.");
+    }
+
+    #[test]
+    fn test_save_transcripts() {
+        let temp_dir = TempDir::new("transcripts").unwrap();
+        let audio_file = temp_dir.path().join("call.mp3");
+        std::fs::write(temp_dir.path().join("call.srt"), "1\n").unwrap();
+        let paths = save_transcripts(
+            &segments(),
+            &audio_file,
+            &[TranscriptFormat::Srt, TranscriptFormat::Vtt],
+        )
+        .unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                temp_dir.path().join("call.mp3.srt"),
+                temp_dir.path().join("call.mp3.vtt")
+            ]
+        );
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("call.srt")).unwrap(),
+            "1\n"
+        );
+        assert!(std::fs::read_to_string(&paths[1])
+            .unwrap()
+            .starts_with("WEBVTT"));
+    }
+}
@@ -0,0 +1,178 @@
+//! Spoken language selection and detection for Whisper.
+//!
+//! Multilingual Whisper models are prompted with a language token such as `<|fr|>`. [LANGUAGES]
+//! lists the languages known to Whisper, [language_code] resolves a user supplied code or name,
+//! and [detect_language] predicts the language of a recording from its first 30 seconds.
+
+use anyhow::Result;
+use candle_core::{IndexOp, Tensor, D};
+use candle_transformers::models::whisper as m;
+use tokenizers::Tokenizer;
+
+use super::audio_processor::{token_id, WhichAudioDecoderModel};
+
+/// The languages supported by Whisper as `(code, name)` pairs, in token order. Cantonese is
+/// only known to large-v3 and later models.
+pub const LANGUAGES: [(&str, &str); 100] = [
+    ("en", "english"),
+    ("zh", "chinese"),
+    ("de", "german"),
+    ("es", "spanish"),
+    ("ru", "russian"),
+    ("ko", "korean"),
+    ("fr", "french"),
+    ("ja", "japanese"),
+    ("pt", "portuguese"),
+    ("tr", "turkish"),
+    ("pl", "polish"),
+    ("ca", "catalan"),
+    ("nl", "dutch"),
+    ("ar", "arabic"),
+    ("sv", "swedish"),
+    ("it", "italian"),
+    ("id", "indonesian"),
+    ("hi", "hindi"),
+    ("fi", "finnish"),
+    ("vi", "vietnamese"),
+    ("he", "hebrew"),
+    ("uk", "ukrainian"),
+    ("el", "greek"),
+    ("ms", "malay"),
+    ("cs", "czech"),
+    ("ro", "romanian"),
+    ("da", "danish"),
+    ("hu", "hungarian"),
+    ("ta", "tamil"),
+    ("no", "norwegian"),
+    ("th", "thai"),
+    ("ur", "urdu"),
+    ("hr", "croatian"),
+    ("bg", "bulgarian"),
+    ("lt", "lithuanian"),
+    ("la", "latin"),
+    ("mi", "maori"),
+    ("ml", "malayalam"),
+    ("cy", "welsh"),
+    ("sk", "slovak"),
+    ("te", "telugu"),
+    ("fa", "persian"),
+    ("lv", "latvian"),
+    ("bn", "bengali"),
+    ("sr", "serbian"),
+    ("az", "azerbaijani"),
+    ("sl", "slovenian"),
+    ("kn", "kannada"),
+    ("et", "estonian"),
+    ("mk", "macedonian"),
+    ("br", "breton"),
+    ("eu", "basque"),
+    ("is", "icelandic"),
+    ("hy", "armenian"),
+    ("ne", "nepali"),
+    ("mn", "mongolian"),
+    ("bs", "bosnian"),
+    ("kk", "kazakh"),
+    ("sq", "albanian"),
+    ("sw", "swahili"),
+    ("gl", "galician"),
+    ("mr", "marathi"),
+    ("pa", "punjabi"),
+    ("si", "sinhala"),
+    ("km", "khmer"),
+    ("sn", "shona"),
+    ("yo", "yoruba"),
+    ("so", "somali"),
+    ("af", "afrikaans"),
+    ("oc", "occitan"),
+    ("ka", "georgian"),
+    ("be", "belarusian"),
+    ("tg", "tajik"),
+    ("sd", "sindhi"),
+    ("gu", "gujarati"),
+    ("am", "amharic"),
+    ("yi", "yiddish"),
+    ("lo", "lao"),
+    ("uz", "uzbek"),
+    ("fo", "faroese"),
+    ("ht", "haitian creole"),
+    ("ps", "pashto"),
+    ("tk", "turkmen"),
+    ("nn", "nynorsk"),
+    ("mt", "maltese"),
+    ("sa", "sanskrit"),
+    ("lb", "luxembourgish"),
+    ("my", "myanmar"),
+    ("bo", "tibetan"),
+    ("tl", "tagalog"),
+    ("mg", "malagasy"),
+    ("as", "assamese"),
+    ("tt", "tatar"),
+    ("haw", "hawaiian"),
+    ("ln", "lingala"),
+    ("ha", "hausa"),
+    ("ba", "bashkir"),
+    ("jw", "javanese"),
+    ("su", "sundanese"),
+    ("yue", "cantonese"),
+];
+
+/// Resolves a language code such as "fr" or an English language name such as "French" to its
+/// Whisper language code. Returns None for unknown languages.
+pub fn language_code(language: &str) -> Option<&'static str> {
+    let language = language.trim().to_lowercase();
+    LANGUAGES
+        .iter()
+        .find(|(code, name)| *code == language || *name == language)
+        .map(|(code, _)| *code)
+}
+
+/// Returns the id of the `<|code|>` token of a language.
+pub fn language_token(tokenizer: &Tokenizer, code: &str) -> Result<u32> {
+    Ok(token_id(tokenizer, &format!("<|{code}|>"))?)
+}
+
+/// Detects the spoken language of a recording from the first 30 seconds of its mel spectrogram.
+///
+/// Returns the language code and its probability. Only the languages known to the tokenizer of
+/// the model are considered.
+pub fn detect_language(
+    model: &mut WhichAudioDecoderModel,
+    tokenizer: &Tokenizer,
+    mel: &Tensor,
+) -> Result<(&'static str, f32)> {
+    let (_, _, frames) = mel.dims3()?;
+    let mel = mel.narrow(2, 0, frames.min(m::N_FRAMES))?;
+    let device = mel.device();
+
+    let (codes, token_ids): (Vec<_>, Vec<_>) = LANGUAGES
+        .iter()
+        .filter_map(|(code, _)| Some((*code, language_token(tokenizer, code).ok()?)))
+        .unzip();
+    let sot_token = token_id(tokenizer, m::SOT_TOKEN)?;
+
+    let audio_features = model.encoder_forward(&mel, true)?;
+    let tokens = Tensor::new(&[[sot_token]], device)?;
+    let ys = model.decoder_forward(&tokens, &audio_features, true)?;
+    let logits = model.decoder_final_linear(&ys.i(..1)?)?.i(0)?.i(0)?;
+    let logits = logits.index_select(&Tensor::new(token_ids.as_slice(), device)?, 0)?;
+    let probs = candle_nn::ops::softmax(&logits, D::Minus1)?.to_vec1::<f32>()?;
+
+    codes
+        .into_iter()
+        .zip(probs)
+        .max_by(|(_, p1), (_, p2)| p1.total_cmp(p2))
+        .ok_or_else(|| anyhow::anyhow!("the tokenizer has no language tokens"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_language_code() {
+        assert_eq!(language_code("fr"), Some("fr"));
+        assert_eq!(language_code(" French "), Some("fr"));
+        assert_eq!(language_code("Haitian Creole"), Some("ht"));
+        assert_eq!(language_code("klingon"), None);
+    }
+}
@@ -6,7 +6,21 @@
 //! # Modules
 //!
 //! - [`audio_processor`] - Main audio processing and transcription pipeline
+//! - [`export`] - Transcript export to SRT, WebVTT and JSON
+//! - [`language`] - Spoken language selection and detection
 //! - [`pcm_decode`] - PCM audio format decoding utilities
+//! - [`pipeline`] - Embedding of transcripts while the next recordings are decoded
+//! - [`resample`] - Sample-rate conversion and channel downmixing
+//! - [`stream`] - Streaming transcription of live PCM audio
+//! - [`transcript`] - Regrouping of transcripts into chunks for embedding
+//! - [`vad`] - Voice activity detection to split recordings at pauses
 
 pub mod audio_processor;
+pub mod export;
+pub mod language;
 pub mod pcm_decode;
+pub mod pipeline;
+pub mod resample;
+pub mod stream;
+pub mod transcript;
+pub mod vad;
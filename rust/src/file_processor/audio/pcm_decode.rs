@@ -4,6 +4,9 @@ pub mod audio_processing {
     use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
     use symphonia::core::conv::FromSample;
 
+    use crate::file_processor::audio::resample::downmix;
+
+    /// Converts a decoded buffer to f32 samples and downmixes all of its channels to mono.
     fn conv<T>(
         samples: &mut Vec<f32>,
         data: std::borrow::Cow<symphonia::core::audio::AudioBuffer<T>>,
@@ -11,20 +14,32 @@ pub mod audio_processing {
         T: symphonia::core::sample::Sample,
         f32: symphonia::core::conv::FromSample<T>,
     {
-        samples.extend(data.chan(0).iter().map(|v| f32::from_sample(*v)))
+        let channels = (0..data.spec().channels.count())
+            .map(|channel| {
+                data.chan(channel)
+                    .iter()
+                    .map(|v| f32::from_sample(*v))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        samples.extend(downmix(&channels))
     }
 
     pub(crate) fn pcm_decode<P: AsRef<std::path::Path>>(
         path: P,
     ) -> anyhow::Result<(Vec<f32>, u32)> {
         // Open the media source.
+        let path = path.as_ref();
         let src = std::fs::File::open(path)?;
 
         // Create the media source stream.
         let mss = symphonia::core::io::MediaSourceStream::new(Box::new(src), Default::default());
 
-        // Create a probe hint using the file's extension. [Optional]
-        let hint = symphonia::core::probe::Hint::new();
+        // Create a probe hint using the file's extension.
+        let mut hint = symphonia::core::probe::Hint::new();
+        if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+            hint.with_extension(extension);
+        }
 
         // Use the default options for metadata and format readers.
         let meta_opts: symphonia::core::meta::MetadataOptions = Default::default();
@@ -35,20 +50,21 @@ pub mod audio_processing {
         // Get the instantiated format reader.
         let mut format = probed.format;
 
-        // Find the first audio track with a known (decodeable) codec.
+        // Find the first audio track with a known (decodeable) codec. Video tracks have no
+        // sample rate.
         let track = format
             .tracks()
             .iter()
-            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-            .expect("no supported audio tracks");
+            .find(|t| {
+                t.codec_params.codec != CODEC_TYPE_NULL && t.codec_params.sample_rate.is_some()
+            })
+            .ok_or_else(|| anyhow::anyhow!("no supported audio tracks in {}", path.display()))?;
 
         // Use the default options for the decoder.
         let dec_opts: DecoderOptions = Default::default();
 
         // Create a decoder for the track.
-        let mut decoder = symphonia::default::get_codecs()
-            .make(&track.codec_params, &dec_opts)
-            .expect("unsupported codec");
+        let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &dec_opts)?;
         let track_id = track.id;
         let sample_rate = track.codec_params.sample_rate.unwrap_or(0);
         let mut pcm_data = Vec::new();
@@ -64,7 +80,7 @@ pub mod audio_processing {
                 continue;
             }
             match decoder.decode(&packet)? {
-                AudioBufferRef::F32(buf) => pcm_data.extend(buf.chan(0)),
+                AudioBufferRef::F32(data) => conv(&mut pcm_data, data),
                 AudioBufferRef::U8(data) => conv(&mut pcm_data, data),
                 AudioBufferRef::U16(data) => conv(&mut pcm_data, data),
                 AudioBufferRef::U24(data) => conv(&mut pcm_data, data),
@@ -0,0 +1,116 @@
+//! Embedding of transcripts while the next recordings are decoded.
+//!
+//! Decoding a recording with Whisper is long and synchronous. [decode_and_embed] runs it on a
+//! blocking thread, so that the segments of the recordings already decoded are embedded in the
+//! meantime instead of waiting for the whole directory.
+
+use std::future::Future;
+
+use anyhow::Result;
+use tokio::sync::mpsc;
+
+use crate::embeddings::embed::EmbedData;
+
+/// Decodes `files` one after the other with `decode` on a blocking thread and embeds their
+/// segments with `embed`, in buffers of `buffer_size` segments given with the file they come
+/// from, while the next files are decoded. The embeddings of every buffer are passed to
+/// `collect` as soon as they are ready. Buffers that fail to embed are reported and skipped.
+pub async fn decode_and_embed<T, D, E, F>(
+    files: Vec<String>,
+    mut decode: D,
+    buffer_size: usize,
+    mut embed: E,
+    mut collect: impl FnMut(Vec<EmbedData>),
+) -> Result<()>
+where
+    T: Send + 'static,
+    D: FnMut(&str) -> Vec<T> + Send + 'static,
+    E: FnMut(Vec<(T, String)>) -> F,
+    F: Future<Output = Result<Vec<EmbedData>>>,
+{
+    let buffer_size = buffer_size.max(1);
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let decoder = tokio::task::spawn_blocking(move || {
+        for file in files {
+            for segment in decode(&file) {
+                // The receiver is only dropped when embedding stops early.
+                if tx.send((segment, file.clone())).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+
+    let mut buffer = Vec::with_capacity(buffer_size);
+    loop {
+        let segment = rx.recv().await;
+        let done = segment.is_none();
+        buffer.extend(segment);
+        if buffer.len() == buffer_size || (done && !buffer.is_empty()) {
+            let segments = std::mem::replace(&mut buffer, Vec::with_capacity(buffer_size));
+            match embed(segments).await {
+                Ok(embeddings) => collect(embeddings),
+                Err(e) => eprintln!("Error processing audio segments: {:?}", e),
+            }
+        }
+        if done {
+            break;
+        }
+    }
+
+    decoder.await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_decode_and_embed_overlap() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let files = (0..3).map(|i| format!("{}.wav", i)).collect();
+
+        let decode_events = events.clone();
+        let decode = move |file: &str| {
+            std::thread::sleep(Duration::from_millis(200));
+            decode_events
+                .lock()
+                .unwrap()
+                .push(format!("decoded {}", file));
+            vec![file.to_string()]
+        };
+        let embed = |segments: Vec<(String, String)>| {
+            let events = events.clone();
+            async move {
+                events
+                    .lock()
+                    .unwrap()
+                    .push(format!("embedded {}", segments[0].1));
+                Ok(vec![EmbedData::new(
+                    crate::embeddings::embed::EmbeddingResult::DenseVector(vec![0.0]),
+                    Some(segments[0].0.clone()),
+                    None,
+                )])
+            }
+        };
+        let mut texts = Vec::new();
+        decode_and_embed(files, decode, 1, embed, |embeddings| {
+            texts.extend(
+                embeddings
+                    .into_iter()
+                    .filter_map(|embedding| embedding.text),
+            )
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(texts, vec!["0.wav", "1.wav", "2.wav"]);
+        let events = events.lock().unwrap();
+        let position = |event: &str| events.iter().position(|e| e == event).unwrap();
+        // The first file is embedded while the last one is still being decoded.
+        assert!(position("embedded 0.wav") < position("decoded 2.wav"));
+    }
+}
@@ -0,0 +1,278 @@
+//! Sample-rate conversion and channel downmixing for decoded PCM audio.
+//!
+//! Whisper expects mono audio sampled at 16 kHz. [resample] converts between arbitrary sample
+//! rates with a polyphase windowed-sinc filter, [Resampler] applies the same filter to a signal
+//! that arrives in pieces, and [downmix] averages the channels of a multi-channel recording into
+//! a single channel.
+
+/// Number of zero crossings of the sinc kernel on each side of its center, at the output rate.
+/// Higher values sharpen the anti-aliasing filter at the cost of speed.
+const ZERO_CROSSINGS: usize = 16;
+/// Fraction of the Nyquist frequency kept by the low-pass filter, leaving room for its roll-off.
+const ROLLOFF: f64 = 0.95;
+/// Shape parameter of the Kaiser window, trading main-lobe width for stop-band attenuation.
+const KAISER_BETA: f64 = 8.6;
+
+/// Averages the channels of a planar multi-channel signal into a single channel.
+///
+/// All channels are weighted equally. If the channels differ in length, the output is as long as
+/// the shortest one.
+pub fn downmix<C: AsRef<[f32]>>(channels: &[C]) -> Vec<f32> {
+    let Some(frames) = channels.iter().map(|c| c.as_ref().len()).min() else {
+        return Vec::new();
+    };
+    let scale = 1.0 / channels.len() as f32;
+    let mut mono = vec![0f32; frames];
+    for channel in channels {
+        for (out, sample) in mono.iter_mut().zip(channel.as_ref()) {
+            *out += sample * scale;
+        }
+    }
+    mono
+}
+
+/// Resamples a mono signal from `from_rate` to `to_rate` Hz.
+///
+/// Uses a polyphase windowed-sinc (Kaiser) filter. When downsampling, the cutoff is lowered to the
+/// output Nyquist frequency so that the result is free of aliasing. Returns the input unchanged
+/// if both rates are equal.
+pub fn resample(samples: &[f32], from_rate: u32, to_rate: u32) -> Vec<f32> {
+    let mut resampler = Resampler::new(from_rate, to_rate);
+    let mut output = resampler.push(samples);
+    output.extend(resampler.flush());
+    output
+}
+
+/// Resamples a mono signal that arrives in pieces, such as a live stream.
+///
+/// The filter reaches past the end of every piece, so each output sample is only returned once
+/// the input samples it depends on have been pushed. The output is the same as that of
+/// [resample] on the whole signal, without artifacts where the pieces meet.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    up: usize,
+    down: usize,
+    half_width: usize,
+    /// One filter per output phase. The filter of phase `p` is centered `p / up` input samples
+    /// after the integer input position.
+    filters: Vec<Vec<f32>>,
+    /// The input samples that the next output samples depend on.
+    input: Vec<f32>,
+    /// Index in the whole signal of the first sample of `input`.
+    input_start: usize,
+    /// Number of input samples pushed so far.
+    input_len: usize,
+    /// Number of output samples returned so far.
+    output_len: usize,
+}
+
+impl Resampler {
+    /// Creates a resampler from `from_rate` to `to_rate` Hz.
+    pub fn new(from_rate: u32, to_rate: u32) -> Self {
+        let divisor = gcd(from_rate, to_rate).max(1);
+        let (up, down) = ((to_rate / divisor) as usize, (from_rate / divisor) as usize);
+        let cutoff = (up as f64 / down as f64).min(1.0) * ROLLOFF;
+        let half_width = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+        let taps = 2 * half_width + 1;
+        let filters = match up == down {
+            true => Vec::new(),
+            false => (0..up)
+                .map(|phase| {
+                    let offset = phase as f64 / up as f64;
+                    (0..taps)
+                        .map(|tap| {
+                            let t = tap as f64 - half_width as f64 - offset;
+                            (cutoff * sinc(cutoff * t) * kaiser(t / (half_width as f64 + 1.0)))
+                                as f32
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>(),
+        };
+        Self {
+            up,
+            down,
+            half_width,
+            filters,
+            input: Vec::new(),
+            input_start: 0,
+            input_len: 0,
+            output_len: 0,
+        }
+    }
+
+    /// Appends input samples and returns the output samples that they complete.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<f32> {
+        if self.up == self.down {
+            self.input_len += samples.len();
+            self.output_len += samples.len();
+            return samples.to_vec();
+        }
+        self.input.extend_from_slice(samples);
+        self.input_len += samples.len();
+        // The last output sample whose filter lies within the input pushed so far.
+        let available = match self.input_len.checked_sub(self.half_width) {
+            Some(complete) => (complete * self.up).div_ceil(self.down),
+            None => 0,
+        };
+        self.resample_until(available)
+    }
+
+    /// Returns the output samples left at the end of the signal, treating the samples after it
+    /// as silence, and starts over.
+    pub fn flush(&mut self) -> Vec<f32> {
+        let output_len = (self.input_len * self.up).div_ceil(self.down);
+        let output = match self.up == self.down {
+            true => Vec::new(),
+            false => self.resample_until(output_len),
+        };
+        self.input.clear();
+        self.input_start = 0;
+        self.input_len = 0;
+        self.output_len = 0;
+        output
+    }
+
+    /// Computes the output samples up to `end`, excluded, and drops the input samples that the
+    /// following ones do not depend on.
+    fn resample_until(&mut self, end: usize) -> Vec<f32> {
+        let half_width = self.half_width;
+        let output = (self.output_len..end.max(self.output_len))
+            .map(|n| {
+                let position = n * self.down;
+                let (index, phase) = (position / self.up, position % self.up);
+                let filter = &self.filters[phase];
+                // Tap `k` multiplies the input sample at `index + k - half_width`. Samples
+                // outside the signal are treated as silence.
+                let first = (index + 1).saturating_sub(half_width + 1);
+                let last = (index + half_width).min(self.input_len - 1);
+                self.input[first - self.input_start..=last - self.input_start]
+                    .iter()
+                    .zip(&filter[first + half_width - index..])
+                    .map(|(sample, weight)| sample * weight)
+                    .sum()
+            })
+            .collect::<Vec<f32>>();
+        self.output_len += output.len();
+
+        let next_index = self.output_len * self.down / self.up;
+        let first = next_index.saturating_sub(half_width).min(self.input_len);
+        if first > self.input_start {
+            self.input.drain(..first - self.input_start);
+            self.input_start = first;
+        }
+        output
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        let x = std::f64::consts::PI * x;
+        x.sin() / x
+    }
+}
+
+/// The Kaiser window evaluated at `x` in [-1, 1].
+fn kaiser(x: f64) -> f64 {
+    if x.abs() >= 1.0 {
+        return 0.0;
+    }
+    bessel_i0(KAISER_BETA * (1.0 - x * x).sqrt()) / bessel_i0(KAISER_BETA)
+}
+
+/// The zeroth-order modified Bessel function of the first kind, computed from its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..50 {
+        term *= half_x / k as f64;
+        sum += term * term;
+        if term * term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f64, rate: u32, seconds: f64) -> Vec<f32> {
+        let len = (rate as f64 * seconds) as usize;
+        (0..len)
+            .map(|i| (2.0 * std::f64::consts::PI * frequency * i as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_downmix() {
+        let left = vec![1.0, 0.5, -1.0];
+        let right = vec![0.0, 0.5, 1.0, 2.0];
+        assert_eq!(downmix(&[left, right]), vec![0.5, 0.5, 0.0]);
+        assert!(downmix::<Vec<f32>>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_resample_preserves_in_band_tone() {
+        for from_rate in [44_100, 48_000, 8_000] {
+            let input = sine(440.0, from_rate, 1.0);
+            let output = resample(&input, from_rate, 16_000);
+            assert_eq!(output.len(), 16_000);
+
+            // Compare against the ideal tone away from the edges.
+            let expected = sine(440.0, 16_000, 1.0);
+            let error = output[1000..15_000]
+                .iter()
+                .zip(&expected[1000..15_000])
+                .map(|(a, b)| (a - b).abs())
+                .fold(0f32, f32::max);
+            assert!(error < 1e-2, "max error {error} at {from_rate} Hz");
+        }
+    }
+
+    #[test]
+    fn test_resample_removes_aliasing() {
+        // A 12 kHz tone is above the 8 kHz Nyquist frequency of the output and must be filtered
+        // out rather than folded back into the audible band.
+        let input = sine(12_000.0, 48_000, 0.5);
+        let output = resample(&input, 48_000, 16_000);
+        assert!(rms(&output[500..7500]) < 1e-2);
+    }
+
+    #[test]
+    fn test_resampler_matches_whole_signal() {
+        for from_rate in [44_100, 8_000, 16_000] {
+            let input = sine(440.0, from_rate, 0.5);
+            let mut resampler = Resampler::new(from_rate, 16_000);
+            let mut output = input
+                .chunks(997)
+                .flat_map(|piece| resampler.push(piece))
+                .collect::<Vec<_>>();
+            output.extend(resampler.flush());
+            assert_eq!(output, resample(&input, from_rate, 16_000));
+            assert!(resampler.flush().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_resample_identity() {
+        let input = sine(440.0, 16_000, 0.1);
+        assert_eq!(resample(&input, 16_000, 16_000), input);
+    }
+}
@@ -0,0 +1,384 @@
+//! Streaming transcription of live PCM audio.
+//!
+//! [AudioDecoderModel::stream] starts an [AudioStream] that accepts PCM samples as they arrive,
+//! for instance from a call, instead of a file. Samples are resampled to 16 kHz as they arrive
+//! and collected by a [PcmBuffer] into windows of 30 seconds, and every complete window is
+//! decoded right away with a decoder that is kept for the whole stream. The remaining audio is decoded by [AudioStream::finish].
+
+use anyhow::Result;
+use candle_transformers::models::whisper as m;
+
+use crate::{
+    config::TextEmbedConfig,
+    embeddings::{
+        embed::{EmbedData, Embedder},
+        embed_audio,
+    },
+};
+
+use super::{
+    audio_processor::{AudioDecoderModel, Decoder, Segment},
+    resample::Resampler,
+    transcript::rechunk_segments,
+};
+
+/// A window of audio resampled to 16 kHz, with its start and end in seconds from the start of
+/// the stream.
+#[derive(Debug, Clone)]
+pub struct PcmWindow {
+    pub start: f64,
+    pub end: f64,
+    pub samples: Vec<f32>,
+}
+
+/// Collects mono PCM pushed in pieces of any size into the 30 second windows of Whisper.
+///
+/// The stream is resampled as a whole before it is cut into windows, so that the filter of the
+/// resampler runs across the boundaries of the windows.
+#[derive(Debug, Clone)]
+pub struct PcmBuffer {
+    sample_rate: u32,
+    resampler: Resampler,
+    /// Samples resampled to 16 kHz that are not yet returned in windows.
+    buffer: Vec<f32>,
+    /// Number of samples pushed so far, at `sample_rate`.
+    pushed: usize,
+    /// Number of samples already returned in windows, at 16 kHz.
+    consumed: usize,
+}
+
+impl PcmBuffer {
+    /// Creates a buffer for samples at `sample_rate` Hz.
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            resampler: Resampler::new(sample_rate, m::SAMPLE_RATE as u32),
+            buffer: Vec::new(),
+            pushed: 0,
+            consumed: 0,
+        }
+    }
+
+    /// Appends samples and returns the windows they complete, resampled to 16 kHz.
+    pub fn push(&mut self, samples: &[f32]) -> Vec<PcmWindow> {
+        self.pushed += samples.len();
+        let resampled = self.resampler.push(samples);
+        self.windows(resampled)
+    }
+
+    /// Returns the windows left at the end of the stream: the samples held back by the
+    /// resampler may still complete a window before the last, incomplete one.
+    pub fn flush(&mut self) -> Vec<PcmWindow> {
+        let resampled = self.resampler.flush();
+        let mut windows = self.windows(resampled);
+        if !self.buffer.is_empty() {
+            let window = std::mem::take(&mut self.buffer);
+            windows.push(self.window(window));
+        }
+        windows
+    }
+
+    /// Returns the duration of the audio pushed so far in seconds.
+    pub fn duration(&self) -> f64 {
+        self.pushed as f64 / self.sample_rate as f64
+    }
+
+    fn windows(&mut self, resampled: Vec<f32>) -> Vec<PcmWindow> {
+        self.buffer.extend(resampled);
+        let mut windows = Vec::new();
+        while self.buffer.len() >= m::N_SAMPLES {
+            let rest = self.buffer.split_off(m::N_SAMPLES);
+            let window = std::mem::replace(&mut self.buffer, rest);
+            windows.push(self.window(window));
+        }
+        windows
+    }
+
+    fn window(&mut self, samples: Vec<f32>) -> PcmWindow {
+        let start = self.consumed as f64 / m::SAMPLE_RATE as f64;
+        self.consumed += samples.len();
+        PcmWindow {
+            start,
+            end: self.consumed as f64 / m::SAMPLE_RATE as f64,
+            samples,
+        }
+    }
+}
+
+/// Transcribes a live stream of mono PCM samples.
+///
+/// Segments are returned as soon as the 30 second window they belong to is complete, with times
+/// in seconds from the start of the stream. The language is resolved from the first window and
+/// kept for the rest of the stream. Voice activity detection is not applied to streams.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::file_processor::audio::audio_processor::AudioDecoderModel;
+///
+/// # fn example(chunks: Vec<Vec<f32>>) -> anyhow::Result<()> {
+/// let mut model = AudioDecoderModel::from_pretrained(None, None, "tiny-en", false)?;
+/// let mut stream = model.stream(8_000)?;
+/// for chunk in chunks {
+///     for segment in stream.push(&chunk)? {
+///         println!("{:.1}s: {}", segment.start, segment.dr.text);
+///     }
+/// }
+/// let last_segments = stream.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AudioStream<'a> {
+    decoder: Decoder<'a>,
+    buffer: PcmBuffer,
+    mel_filters: Vec<f32>,
+    language: Option<String>,
+    name: String,
+}
+
+impl AudioDecoderModel {
+    /// Starts transcribing a stream of mono PCM samples at `sample_rate` Hz.
+    pub fn stream(&mut self, sample_rate: u32) -> Result<AudioStream<'_>> {
+        if sample_rate == 0 {
+            anyhow::bail!("the sample rate of a stream must not be zero");
+        }
+        let mel_filters = self.mel_filters()?;
+        let device = self.device.clone();
+        let (task, timestamps) = (self.task, self.timestamps);
+        let decoder = Decoder::new(
+            self,
+            299792458,
+            &device,
+            None,
+            Some(task),
+            timestamps,
+            false,
+        )?;
+        Ok(AudioStream {
+            decoder,
+            buffer: PcmBuffer::new(sample_rate),
+            mel_filters,
+            language: None,
+            name: "stream".to_string(),
+        })
+    }
+}
+
+impl AudioStream<'_> {
+    /// Sets the name reported as the `file_name` of the embeddings of the stream. Defaults to
+    /// "stream".
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Returns the duration of the audio pushed so far in seconds.
+    pub fn duration(&self) -> f64 {
+        self.buffer.duration()
+    }
+
+    /// Appends samples and returns the segments of the windows they complete.
+    pub fn push(&mut self, samples: &[f32]) -> Result<Vec<Segment>> {
+        let mut segments = Vec::new();
+        for window in self.buffer.push(samples) {
+            segments.extend(self.decode(window)?);
+        }
+        Ok(segments)
+    }
+
+    /// Decodes the audio left after the last complete window and ends the stream.
+    pub fn finish(mut self) -> Result<Vec<Segment>> {
+        let mut segments = Vec::new();
+        for window in self.buffer.flush() {
+            segments.extend(self.decode(window)?);
+        }
+        Ok(segments)
+    }
+
+    /// Appends samples and embeds the segments of the windows they complete with `embedder`.
+    ///
+    /// The segments are regrouped by the `audio_chunking` of `config` within the windows that
+    /// complete.
+    pub async fn push_and_embed(
+        &mut self,
+        samples: &[f32],
+        embedder: &Embedder,
+        config: Option<&TextEmbedConfig>,
+    ) -> Result<Vec<EmbedData>> {
+        let segments = self.push(samples)?;
+        embed_segments(segments, &self.name, embedder, config).await
+    }
+
+    /// Decodes the audio left after the last complete window, embeds its segments with
+    /// `embedder` and ends the stream.
+    pub async fn finish_and_embed(
+        self,
+        embedder: &Embedder,
+        config: Option<&TextEmbedConfig>,
+    ) -> Result<Vec<EmbedData>> {
+        let name = self.name.clone();
+        let segments = self.finish()?;
+        embed_segments(segments, &name, embedder, config).await
+    }
+
+    fn decode(&mut self, window: PcmWindow) -> Result<Vec<Segment>> {
+        let _span =
+            tracing::debug_span!("stream_window", start = window.start, end = window.end).entered();
+        let model = &mut *self.decoder.model;
+        let mel = model.mel(&window.samples, &self.mel_filters)?;
+        // The mel spectrogram is padded with silence beyond the window, which is not decoded.
+        let mel = mel.narrow(2, 0, mel.dim(2)?.min(m::N_FRAMES))?;
+
+        let language = match &self.language {
+            Some(language) => language.clone(),
+            None => {
+                let (language_token, language) = model.resolve_language(&mel)?;
+                self.decoder.language_token = language_token;
+                self.language = Some(language.clone());
+                language
+            }
+        };
+
+        let mut segments = self.decoder.run(&mel)?;
+        for segment in &mut segments {
+            segment.offset(window.start, window.end);
+            segment.language = Some(language.clone());
+        }
+        Ok(segments)
+    }
+}
+
+async fn embed_segments(
+    segments: Vec<Segment>,
+    name: &str,
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+) -> Result<Vec<EmbedData>> {
+    let binding = TextEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let segments = rechunk_segments(segments, &config.audio_chunking);
+    if segments.is_empty() {
+        return Ok(Vec::new());
+    }
+    embed_audio(embedder, segments, name, config.batch_size).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_processor::audio::resample::resample;
+
+    fn ramp(len: usize) -> Vec<f32> {
+        (0..len).map(|i| i as f32 / len as f32).collect()
+    }
+
+    #[test]
+    fn test_pcm_buffer() {
+        let samples = ramp(70 * 16_000);
+        let mut buffer = PcmBuffer::new(16_000);
+        let windows = samples
+            .chunks(7_919)
+            .flat_map(|chunk| buffer.push(chunk))
+            .collect::<Vec<_>>();
+        assert_eq!(buffer.duration(), 70.0);
+
+        assert_eq!(windows.len(), 2);
+        assert_eq!((windows[1].start, windows[1].end), (30.0, 60.0));
+        assert_eq!(windows[1].samples, samples[480_000..960_000]);
+        let last = buffer.flush();
+        assert_eq!(last.len(), 1);
+        assert_eq!((last[0].start, last[0].end), (60.0, 70.0));
+        assert_eq!(last[0].samples, samples[960_000..]);
+        assert!(buffer.flush().is_empty());
+    }
+
+    #[test]
+    fn test_pcm_buffer_resamples() {
+        let mut buffer = PcmBuffer::new(8_000);
+        let samples = ramp(45 * 8_000);
+        let windows = buffer.push(&samples);
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].samples.len(), m::N_SAMPLES);
+        let last = buffer.flush();
+        assert_eq!((last[0].start, last[0].end), (30.0, 45.0));
+        assert_eq!(last[0].samples.len(), 15 * 16_000);
+
+        // The windows are cut from the stream resampled as a whole, without artifacts at the
+        // boundary of the windows.
+        let expected = resample(&samples, 8_000, m::SAMPLE_RATE as u32);
+        assert_eq!(
+            [&windows[0].samples[..], &last[0].samples].concat(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_pcm_buffer_flush_completes_window() {
+        // The samples held back by the resampler complete the first window when flushed.
+        let mut buffer = PcmBuffer::new(8_000);
+        assert!(buffer.push(&ramp(30 * 8_000)).is_empty());
+        let windows = buffer.flush();
+        assert_eq!(windows.len(), 1);
+        assert_eq!((windows[0].start, windows[0].end), (0.0, 30.0));
+    }
+
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_pcm_buffer_with_recording() {
+        use crate::file_processor::audio::pcm_decode::audio_processing::pcm_decode;
+
+        let (samples, sample_rate) = pcm_decode("../test_files/audio/samples_jfk.wav").unwrap();
+        // Three passes of the recording fill one window and a part of the next.
+        let recording = [samples.as_slice(); 3].concat();
+        let mut buffer = PcmBuffer::new(sample_rate);
+        let windows = recording
+            .chunks(sample_rate as usize / 50)
+            .flat_map(|frame| buffer.push(frame))
+            .collect::<Vec<_>>();
+
+        assert_eq!(sample_rate, m::SAMPLE_RATE as u32);
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].samples, recording[..m::N_SAMPLES]);
+        let last = buffer.flush();
+        assert_eq!(last[0].start, 30.0);
+        assert_eq!(last[0].samples, recording[m::N_SAMPLES..]);
+        assert!((last[0].end - 3.0 * samples.len() as f64 / sample_rate as f64).abs() < 1e-9);
+    }
+
+    /// Streams the recording at the rate of a call, in frames of 20 ms, and transcribes it.
+    #[cfg(feature = "audio")]
+    #[test]
+    #[ignore = "downloads openai/whisper-tiny.en"]
+    fn test_stream_recording() {
+        use crate::file_processor::audio::pcm_decode::audio_processing::pcm_decode;
+
+        let (samples, sample_rate) = pcm_decode("../test_files/audio/samples_jfk.wav").unwrap();
+        let call = resample(&samples, sample_rate, 8_000);
+        // Three passes of the recording, so that a sentence crosses the end of the first window.
+        let call = [call.as_slice(); 3].concat();
+
+        let mut model = AudioDecoderModel::from_pretrained(None, None, "tiny-en", false).unwrap();
+        let mut stream = model.stream(8_000).unwrap();
+        let mut segments = Vec::new();
+        for frame in call.chunks(160) {
+            segments.extend(stream.push(frame).unwrap());
+        }
+        assert!(!segments.is_empty());
+        assert!(segments.iter().all(|segment| segment.start < 30.0));
+        segments.extend(stream.finish().unwrap());
+
+        let text = segments
+            .iter()
+            .map(|segment| segment.dr.text.to_lowercase())
+            .collect::<String>();
+        // Twice in every pass, except where a window cuts a word.
+        assert!(text.matches("country").count() >= 4, "{}", text);
+        assert!(segments
+            .windows(2)
+            .all(|pair| pair[0].start <= pair[1].start));
+        let end = segments
+            .last()
+            .map(|segment| segment.start + segment.duration);
+        assert!(end.unwrap() <= call.len() as f64 / 8_000.0 + 1e-6);
+    }
+}
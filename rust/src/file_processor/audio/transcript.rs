@@ -0,0 +1,209 @@
+//! Regrouping of decoded transcripts into chunks for embedding.
+//!
+//! Whisper decodes audio in windows of 30 seconds, which rarely line up with the units that are
+//! useful to embed. [rechunk_segments] regroups the timestamped phrases of a transcript into
+//! fixed-duration or sentence-aligned chunks according to an [AudioChunking].
+
+use crate::config::AudioChunking;
+
+use super::audio_processor::{DecodingResult, Segment, TimestampedText};
+
+/// Regroups decoded segments into chunks according to `chunking`.
+///
+/// Every returned segment spans its phrases, carries them in [Segment::timestamps] and averages
+/// the decoding statistics of the segments it was built from. Segments without timestamps are
+/// treated as a single phrase.
+pub fn rechunk_segments(segments: Vec<Segment>, chunking: &AudioChunking) -> Vec<Segment> {
+    let (max_seconds, split_on_sentences) = match chunking {
+        AudioChunking::Segment => return segments,
+        AudioChunking::FixedDuration { seconds } => (*seconds, false),
+        AudioChunking::Sentence { max_seconds } => (*max_seconds, true),
+    };
+
+    // Pair every phrase with the index of the segment it comes from.
+    let phrases = segments
+        .iter()
+        .enumerate()
+        .flat_map(|(index, segment)| {
+            let phrases = if segment.timestamps.is_empty() {
+                vec![TimestampedText {
+                    start: segment.start,
+                    end: segment.start + segment.duration,
+                    text: segment.dr.text.trim().to_string(),
+                }]
+            } else {
+                segment.timestamps.clone()
+            };
+            phrases.into_iter().map(move |phrase| (index, phrase))
+        })
+        .filter(|(_, phrase)| !phrase.text.is_empty());
+
+    let mut chunks: Vec<Vec<(usize, TimestampedText)>> = Vec::new();
+    let mut current: Vec<(usize, TimestampedText)> = Vec::new();
+    for (index, phrase) in phrases {
+        if let Some((_, first)) = current.first() {
+            if phrase.end - first.start > max_seconds {
+                chunks.push(std::mem::take(&mut current));
+            }
+        }
+        let ends_sentence = split_on_sentences && ends_with_sentence(&phrase.text);
+        current.push((index, phrase));
+        if ends_sentence {
+            chunks.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+        .into_iter()
+        .map(|chunk| merge_phrases(&segments, chunk))
+        .collect()
+}
+
+fn ends_with_sentence(text: &str) -> bool {
+    text.trim_end_matches(['"', '\'', ')', ']'])
+        .ends_with(['.', '?', '!', '。', '？', '！'])
+}
+
+fn merge_phrases(segments: &[Segment], chunk: Vec<(usize, TimestampedText)>) -> Segment {
+    let mut sources = chunk.iter().map(|(index, _)| *index).collect::<Vec<_>>();
+    sources.dedup();
+    let mean = |value: fn(&DecodingResult) -> f64| {
+        sources.iter().map(|&i| value(&segments[i].dr)).sum::<f64>() / sources.len() as f64
+    };
+    let dr = DecodingResult {
+        tokens: vec![],
+        text: chunk
+            .iter()
+            .map(|(_, phrase)| phrase.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+        avg_logprob: mean(|dr| dr.avg_logprob),
+        no_speech_prob: mean(|dr| dr.no_speech_prob),
+        temperature: mean(|dr| dr.temperature),
+        compression_ratio: mean(|dr| dr.compression_ratio),
+    };
+    let start = chunk[0].1.start;
+    let end = chunk[chunk.len() - 1].1.end;
+    Segment {
+        start,
+        duration: end - start,
+        dr,
+        timestamps: chunk.into_iter().map(|(_, phrase)| phrase).collect(),
+        language: segments[sources[0]].language.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrase(start: f64, end: f64, text: &str) -> TimestampedText {
+        TimestampedText {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    fn segment(start: f64, duration: f64, timestamps: Vec<TimestampedText>) -> Segment {
+        let text = timestamps
+            .iter()
+            .map(|phrase| phrase.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        Segment {
+            start,
+            duration,
+            dr: DecodingResult {
+                text,
+                avg_logprob: -0.5,
+                ..Default::default()
+            },
+            timestamps,
+            language: None,
+        }
+    }
+
+    fn transcript() -> Vec<Segment> {
+        vec![
+            segment(
+                0.0,
+                30.0,
+                vec![
+                    phrase(0.0, 4.0, "Hello there."),
+                    phrase(4.0, 12.0, "This is a long"),
+                    phrase(12.0, 18.0, "sentence that goes on."),
+                    phrase(18.0, 30.0, "And another"),
+                ],
+            ),
+            segment(30.0, 10.0, vec![phrase(30.0, 36.0, "one ends here!")]),
+        ]
+    }
+
+    fn texts(segments: &[Segment]) -> Vec<&str> {
+        segments.iter().map(|s| s.dr.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_rechunk_fixed_duration() {
+        let chunks = rechunk_segments(
+            transcript(),
+            &AudioChunking::FixedDuration { seconds: 15.0 },
+        );
+        assert_eq!(
+            texts(&chunks),
+            vec![
+                "Hello there. This is a long",
+                "sentence that goes on.",
+                "And another",
+                "one ends here!"
+            ]
+        );
+        assert_eq!((chunks[0].start, chunks[0].duration), (0.0, 12.0));
+        assert_eq!(chunks[0].timestamps.len(), 2);
+        assert_eq!(chunks[0].dr.avg_logprob, -0.5);
+    }
+
+    #[test]
+    fn test_rechunk_sentence() {
+        let chunks = rechunk_segments(transcript(), &AudioChunking::Sentence { max_seconds: 60.0 });
+        assert_eq!(
+            texts(&chunks),
+            vec![
+                "Hello there.",
+                "This is a long sentence that goes on.",
+                "And another one ends here!"
+            ]
+        );
+        // The last sentence spans both decoder segments.
+        assert_eq!((chunks[2].start, chunks[2].duration), (18.0, 18.0));
+
+        let chunks = rechunk_segments(transcript(), &AudioChunking::Sentence { max_seconds: 10.0 });
+        assert_eq!(
+            texts(&chunks)[1..3],
+            ["This is a long", "sentence that goes on."]
+        );
+    }
+
+    #[test]
+    fn test_rechunk_without_timestamps() {
+        let mut segments = transcript();
+        segments.iter_mut().for_each(|s| s.timestamps.clear());
+        let chunks = rechunk_segments(segments, &AudioChunking::FixedDuration { seconds: 60.0 });
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].start, chunks[0].duration), (0.0, 40.0));
+        assert_eq!(chunks[0].timestamps.len(), 2);
+    }
+
+    #[test]
+    fn test_words() {
+        let words = phrase(1.0, 2.0, "ab abc").words();
+        assert_eq!(words.len(), 2);
+        assert_eq!(words[0].start, 1.0);
+        assert!((words[0].end - 1.4).abs() < 1e-9);
+        assert_eq!((words[1].text.as_str(), words[1].end), ("abc", 2.0));
+    }
+}
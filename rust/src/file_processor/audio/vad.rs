@@ -0,0 +1,227 @@
+//! Energy-based voice activity detection.
+//!
+//! [detect_speech] splits a recording into speech regions at its pauses before it is decoded, so
+//! that decoder windows do not cut through sentences and long silences are not decoded at all.
+//! Frames are classified by their energy relative to the noise floor of the recording, and the
+//! resulting regions are smoothed, padded and limited to the 30 second window of Whisper.
+
+use std::ops::Range;
+
+/// Length of the analysis frames in seconds.
+const FRAME_SECONDS: f64 = 0.03;
+
+/// Configures [detect_speech]. All durations are in seconds.
+///
+/// # Example
+///
+/// ```rust
+/// use embed_anything::file_processor::audio::vad::VadConfig;
+/// let config = VadConfig::default().with_min_silence(1.0).with_threshold_db(12.0);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct VadConfig {
+    /// How many decibels above the noise floor a frame must be to count as speech. The noise
+    /// floor is the 10th percentile of the frame energies. Recordings without pauses have no
+    /// noise floor, so the threshold is also kept this far below the loudest frame. Defaults to
+    /// 10.
+    pub threshold_db: f32,
+    /// Frames below this energy in dBFS are always silence. Defaults to -50.
+    pub min_energy_db: f32,
+    /// Pauses shorter than this do not split a region. Defaults to 0.5.
+    pub min_silence: f64,
+    /// Regions with less speech than this are dropped. Defaults to 0.25.
+    pub min_speech: f64,
+    /// Silence kept before and after every region. Defaults to 0.2.
+    pub padding: f64,
+    /// Longer regions are split at their quietest frame. Defaults to 30, the window of Whisper.
+    pub max_region: f64,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            threshold_db: 10.0,
+            min_energy_db: -50.0,
+            min_silence: 0.5,
+            min_speech: 0.25,
+            padding: 0.2,
+            max_region: 30.0,
+        }
+    }
+}
+
+impl VadConfig {
+    pub fn with_threshold_db(mut self, threshold_db: f32) -> Self {
+        self.threshold_db = threshold_db;
+        self
+    }
+
+    pub fn with_min_energy_db(mut self, min_energy_db: f32) -> Self {
+        self.min_energy_db = min_energy_db;
+        self
+    }
+
+    pub fn with_min_silence(mut self, min_silence: f64) -> Self {
+        self.min_silence = min_silence;
+        self
+    }
+
+    pub fn with_min_speech(mut self, min_speech: f64) -> Self {
+        self.min_speech = min_speech;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: f64) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn with_max_region(mut self, max_region: f64) -> Self {
+        self.max_region = max_region;
+        self
+    }
+}
+
+/// Returns the speech regions of a mono signal as ranges of sample indices, in order.
+pub fn detect_speech(samples: &[f32], sample_rate: u32, config: &VadConfig) -> Vec<Range<usize>> {
+    let frame_len = ((sample_rate as f64 * FRAME_SECONDS) as usize).max(1);
+    let frame_seconds = frame_len as f64 / sample_rate as f64;
+    let to_frames = |seconds: f64| (seconds / frame_seconds).round() as usize;
+
+    let energies = samples
+        .chunks(frame_len)
+        .map(|frame| {
+            let power = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+            10.0 * (power + 1e-10).log10()
+        })
+        .collect::<Vec<_>>();
+    if energies.is_empty() {
+        return Vec::new();
+    }
+    let mut sorted = energies.clone();
+    sorted.sort_by(f32::total_cmp);
+    let (noise_floor, peak) = (sorted[sorted.len() / 10], sorted[sorted.len() - 1]);
+    let threshold = (noise_floor + config.threshold_db)
+        .min(peak - config.threshold_db)
+        .max(config.min_energy_db);
+
+    // Runs of consecutive speech frames, bridging pauses shorter than `min_silence`.
+    let mut runs: Vec<Range<usize>> = Vec::new();
+    for (frame, _) in energies.iter().enumerate().filter(|(_, &e)| e > threshold) {
+        match runs.last_mut() {
+            Some(run) if frame - run.end < to_frames(config.min_silence) => run.end = frame + 1,
+            _ => runs.push(frame..frame + 1),
+        }
+    }
+
+    let padding = to_frames(config.padding);
+    let mut regions: Vec<Range<usize>> = Vec::new();
+    for run in runs
+        .into_iter()
+        .filter(|run| run.len() >= to_frames(config.min_speech))
+    {
+        let run = run.start.saturating_sub(padding)..(run.end + padding).min(energies.len());
+        match regions.last_mut() {
+            Some(region) if run.start <= region.end => region.end = run.end,
+            _ => regions.push(run),
+        }
+    }
+
+    let max_frames = to_frames(config.max_region).max(2);
+    regions
+        .into_iter()
+        .flat_map(|region| split_region(region, &energies, max_frames))
+        .map(|region| region.start * frame_len..(region.end * frame_len).min(samples.len()))
+        .collect()
+}
+
+/// Splits a region longer than `max_frames` at the quietest frame of the second half of each
+/// window.
+fn split_region(region: Range<usize>, energies: &[f32], max_frames: usize) -> Vec<Range<usize>> {
+    let mut parts = Vec::new();
+    let mut start = region.start;
+    while region.end - start > max_frames {
+        let window = start + max_frames / 2..start + max_frames;
+        let split = window
+            .min_by(|&a, &b| energies[a].total_cmp(&energies[b]))
+            .unwrap_or(start + max_frames);
+        parts.push(start..split);
+        start = split;
+    }
+    parts.push(start..region.end);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    /// Low-level noise or a tone of the given length, from a fixed pseudo-random sequence.
+    fn signal(seconds: f64, tone: bool, seed: &mut u32) -> Vec<f32> {
+        (0..(seconds * RATE as f64) as usize)
+            .map(|i| {
+                *seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = (*seed >> 8) as f32 / (1 << 24) as f32 - 0.5;
+                let tone = match tone {
+                    true => 0.3 * (i as f32 * 440.0 * std::f32::consts::TAU / RATE as f32).sin(),
+                    false => 0.0,
+                };
+                tone + 0.002 * noise
+            })
+            .collect()
+    }
+
+    fn seconds(regions: &[Range<usize>]) -> Vec<(f64, f64)> {
+        regions
+            .iter()
+            .map(|r| (r.start as f64 / RATE as f64, r.end as f64 / RATE as f64))
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_speech() {
+        let mut seed = 7;
+        let samples = [
+            (1.0, false),
+            (2.0, true),
+            (2.0, false),
+            (1.5, true),
+            (0.2, false),
+            (1.0, true),
+            (1.0, false),
+        ]
+        .iter()
+        .flat_map(|&(length, tone)| signal(length, tone, &mut seed))
+        .collect::<Vec<_>>();
+
+        let regions = seconds(&detect_speech(&samples, RATE, &VadConfig::default()));
+        // The short pause is bridged, and every region is padded by 0.2 seconds.
+        let expected = [(0.8, 3.2), (4.8, 7.9)];
+        assert_eq!(regions.len(), expected.len());
+        for ((start, end), (expected_start, expected_end)) in regions.iter().zip(expected) {
+            assert!((start - expected_start).abs() < 0.05, "{regions:?}");
+            assert!((end - expected_end).abs() < 0.05, "{regions:?}");
+        }
+    }
+
+    #[test]
+    fn test_detect_speech_splits_long_regions() {
+        let samples = signal(70.0, true, &mut 7);
+        let regions = seconds(&detect_speech(&samples, RATE, &VadConfig::default()));
+        // A constant tone has no quiet frame, so only the length of the regions is checked.
+        assert!(regions.len() >= 3);
+        assert_eq!(regions[0].0, 0.0);
+        assert_eq!(regions[regions.len() - 1].1, 70.0);
+        assert!(regions.iter().all(|(start, end)| end - start <= 30.0));
+        assert!(regions.windows(2).all(|pair| pair[0].1 == pair[1].0));
+    }
+
+    #[test]
+    fn test_detect_speech_silence() {
+        let samples = signal(5.0, false, &mut 7);
+        assert!(detect_speech(&samples, RATE, &VadConfig::default()).is_empty());
+        assert!(detect_speech(&[], RATE, &VadConfig::default()).is_empty());
+    }
+}
@@ -76,13 +76,16 @@ pub mod file_processor;
 pub mod models;
 #[cfg(feature = "ort")]
 pub mod reranker;
+pub mod scoring;
 pub mod text_loader;
 
 use anyhow::{Error, Result};
-use config::{ImageEmbedConfig, TextEmbedConfig};
+use config::{ImageEmbedConfig, PdfImageConfig, TextEmbedConfig};
 use embeddings::{
-    embed::{EmbedData, EmbedImage, Embedder, TextEmbedder, VisionEmbedder},
+    compression::MultiVectorCompression,
+    embed::{AudioEmbedder, EmbedData, EmbedImage, Embedder, TextEmbedder, VisionEmbedder},
     get_text_metadata,
+    local::colpali::{pdf_page_metadata, render_pdf_pages, render_pdf_pages_from_bytes},
 };
 use file_loader::FileParser;
 use file_processor::audio::audio_processor::AudioDecoderModel;
@@ -94,13 +97,24 @@ use text_loader::TextLoader;
 use tokio::sync::mpsc; // Add this at the top of your file
 
 #[cfg(feature = "audio")]
-use embeddings::embed_audio;
+use embeddings::{embed_audio, embed_audio_segments};
 use processors_rs::{
+    code_processor::{CodeProcessor, Language},
     docx_processor::DocxProcessor,
+    email_processor::EmailProcessor,
+    epub_processor::EpubProcessor,
     html_processor::HtmlProcessor,
+    ipynb_processor::IpynbProcessor,
     markdown_processor::MarkdownProcessor,
+    odt_processor::OdtProcessor,
+    pdf::pdf_images::{extract_images_from_pdf, extract_images_from_pdf_bytes},
     pdf::pdf_processor::{OcrConfig, PdfBackend, PdfProcessor},
-    processor::{Document, FileProcessor, UrlProcessor},
+    pptx_processor::PptxProcessor,
+    processor::{BytesProcessor, Document, FileProcessor, UrlProcessor},
+    rtf_processor::RtfProcessor,
+    structured_processor::StructuredProcessor,
+    tabular_processor::TabularProcessor,
+    tex_processor::TexProcessor,
     txt_processor::TxtProcessor,
 };
 
@@ -217,7 +231,7 @@ pub async fn embed_file<T: AsRef<std::path::Path>>(
     match embedder {
         Embedder::Text(embedder) => emb_text(file_name, embedder, config, adapter).await,
         Embedder::Vision(embedder) => match file_name.as_ref().extension() {
-            Some(extension) if extension == "pdf" => {
+            Some(extension) if extension.eq_ignore_ascii_case("pdf") => {
                 let binding = TextEmbedConfig::default();
                 let config = config.unwrap_or(&binding);
                 let batch_size = config.batch_size.unwrap_or(32);
@@ -225,13 +239,173 @@ pub async fn embed_file<T: AsRef<std::path::Path>>(
                     "Embedding PDF file: {:?}",
                     file_name.as_ref().to_str().unwrap()
                 );
-                Ok(Some(embedder.embed_pdf(file_name, Some(batch_size)).await?))
+                let embeddings = embedder
+                    .embed_pdf_with_render_config(
+                        file_name,
+                        Some(batch_size),
+                        &config.pdf_render_config,
+                    )
+                    .await?;
+                Ok(Some(compress_embeddings(
+                    embeddings,
+                    config.multi_vector_compression.as_ref(),
+                )?))
+            }
+            _ => {
+                let embeddings = vec![emb_image(file_name, embedder).await?];
+                let compression =
+                    config.and_then(|config| config.multi_vector_compression.as_ref());
+                Ok(Some(compress_embeddings(embeddings, compression)?))
             }
-            _ => Ok(Some(vec![emb_image(file_name, embedder).await?])),
         },
+        Embedder::Audio(embedder) => {
+            // Embedding a recording is long and synchronous, so it runs on a blocking thread
+            // with a copy of the embedder that shares its weights.
+            let embedder = embedder.clone();
+            let audio_file = file_name.as_ref().to_path_buf();
+            let config = config.cloned();
+            tokio::task::spawn_blocking(move || {
+                emb_sound(audio_file, &embedder, config.as_ref(), adapter)
+            })
+            .await?
+        }
+    }
+}
+
+/// Embeds a document held in memory, such as an upload or an object read from a bucket, without
+/// writing it to a file.
+///
+/// The format of the document is detected from its content, or from the extension of
+/// `filename_hint` when the content has no signature, as for Markdown or CSV. The hint is also
+/// recorded as the "file_name" metadata. Text embedders embed the chunks of the document, with
+/// the images of a PDF too when [TextEmbedConfig::pdf_image_config] is set. Vision embedders
+/// embed the rendered pages of a PDF and the bytes of anything else as an image. Audio is only
+/// embedded from files.
+///
+/// # Example
+///
+/// ```rust
+/// use embed_anything::{embed_bytes, embeddings::embed::EmbedderBuilder};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let embedder = EmbedderBuilder::new()
+///     .model_architecture("bert")
+///     .model_id(Some("sentence-transformers/all-MiniLM-L12-v2"))
+///     .from_pretrained_hf()?;
+/// let upload = std::fs::read("report.docx")?;
+/// let embeddings = embed_bytes(&upload, Some("report.docx"), &embedder, None).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn embed_bytes(
+    bytes: &[u8],
+    filename_hint: Option<&str>,
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+) -> Result<Vec<EmbedData>> {
+    let binding = TextEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let metadata = filename_hint
+        .map(|file_name| HashMap::from([("file_name".to_string(), file_name.to_string())]));
+    let is_pdf = config
+        .registry
+        .detect_bytes(bytes, filename_hint)
+        .is_some_and(|format| format.name == "pdf");
+    match embedder {
+        Embedder::Text(embedder) => {
+            if let (true, Some(image_config)) = (is_pdf, &config.pdf_image_config) {
+                let source = Source::Bytes(bytes, filename_hint);
+                return emb_multimodal_pdf(source, embedder, config, image_config, None)
+                    .await
+                    .map(Option::unwrap_or_default);
+            }
+            let chunk_size = config.chunk_size.unwrap_or(1000);
+            let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
+            let document = extract_bytes(
+                bytes,
+                filename_hint,
+                chunk_size,
+                (chunk_size as f32 * overlap_ratio) as usize,
+                OcrConfig {
+                    use_ocr: config.use_ocr.unwrap_or(false),
+                    tesseract_path: config.tesseract_path.clone(),
+                },
+                Some(config.pdf_backend),
+                Some(config),
+            )?;
+            let chunk_refs: Vec<&str> = document.chunks.iter().map(|s| s.as_str()).collect();
+            let encodings = embedder
+                .embed(&chunk_refs, config.batch_size, config.late_chunking)
+                .await?;
+            let mut embeddings = get_text_metadata(&Rc::new(encodings), &chunk_refs, &metadata)?;
+            add_chunk_metadata(&mut embeddings, &document);
+            compress_embeddings(embeddings, config.multi_vector_compression.as_ref())
+        }
+        Embedder::Vision(embedder) if is_pdf => {
+            // Pages are rendered and embedded as images, as embed_file does for PDF files.
+            let (page_numbers, images): (Vec<_>, Vec<_>) =
+                render_pdf_pages_from_bytes(bytes, &config.pdf_render_config)?
+                    .into_iter()
+                    .unzip();
+            let batch_size = config.batch_size.unwrap_or(32);
+            let embeddings = embedder
+                .embed_dynamic_images(&images, Some(batch_size))
+                .await?;
+            let file_path = std::path::Path::new(filename_hint.unwrap_or_default());
+            let embeddings = embeddings
+                .into_iter()
+                .zip(page_numbers.into_iter().zip(&images))
+                .map(|(mut embed_data, (page_number, image))| {
+                    let mut page_metadata = pdf_page_metadata(file_path, page_number, image);
+                    page_metadata.extend(metadata.clone().unwrap_or_default());
+                    embed_data.metadata = Some(page_metadata);
+                    embed_data
+                })
+                .collect();
+            compress_embeddings(embeddings, config.multi_vector_compression.as_ref())
+        }
+        Embedder::Vision(embedder) => {
+            let embeddings = vec![embedder.embed_image_bytes(bytes, metadata).await?];
+            compress_embeddings(embeddings, config.multi_vector_compression.as_ref())
+        }
+        Embedder::Audio(_) => Err(Error::msg(
+            "Audio is only embedded from files, see embed_file",
+        )),
     }
 }
 
+/// Embeds the sound of an audio file with an audio embedder, one embedding per window of the
+/// model.
+#[cfg(feature = "audio")]
+fn emb_sound<T: AsRef<std::path::Path>>(
+    audio_file: T,
+    embedder: &AudioEmbedder,
+    config: Option<&TextEmbedConfig>,
+    adapter: Option<Box<dyn FnOnce(Vec<EmbedData>) + Send + Sync>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    let batch_size = config.and_then(|config| config.batch_size);
+    let embeddings = embedder.embed_audio_file(audio_file, batch_size)?;
+    match adapter {
+        Some(adapter) => {
+            adapter(embeddings);
+            Ok(None)
+        }
+        None => Ok(Some(embeddings)),
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+fn emb_sound<T: AsRef<std::path::Path>>(
+    _audio_file: T,
+    _embedder: &AudioEmbedder,
+    _config: Option<&TextEmbedConfig>,
+    _adapter: Option<Box<dyn FnOnce(Vec<EmbedData>) + Send + Sync>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    Err(anyhow::anyhow!(
+        "The 'audio' feature is not enabled. Please enable it to embed audio files."
+    ))
+}
+
 /// Embeddings of a webpage using the specified embedding model.
 ///
 /// # Arguments
@@ -290,6 +464,7 @@ pub async fn embed_webpage(
     let mut metadata = HashMap::new();
     metadata.insert("url".into(), url);
     let embeddings = get_text_metadata(&Rc::new(encodings), &chunks, &Some(metadata))?;
+    let embeddings = compress_embeddings(embeddings, config.multi_vector_compression.as_ref())?;
 
     // Send embeddings to vector database
     if let Some(adapter) = adapter {
@@ -309,6 +484,18 @@ async fn emb_text<T: AsRef<std::path::Path>>(
 ) -> Result<Option<Vec<EmbedData>>> {
     let binding = TextEmbedConfig::default();
     let config = config.unwrap_or(&binding);
+    if let Some(image_config) = &config.pdf_image_config {
+        if is_pdf(&file, config) {
+            return emb_multimodal_pdf(
+                Source::File(file.as_ref()),
+                embedding_model,
+                config,
+                image_config,
+                adapter,
+            )
+            .await;
+        }
+    }
     let chunk_size = config.chunk_size.unwrap_or(1000);
     let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
     let batch_size = config.batch_size;
@@ -325,6 +512,7 @@ async fn emb_text<T: AsRef<std::path::Path>>(
             tesseract_path,
         },
         Some(backend),
+        Some(config),
     )?;
 
     let metadata = TextLoader::get_metadata(file).ok();
@@ -336,19 +524,180 @@ async fn emb_text<T: AsRef<std::path::Path>>(
         let encodings = embedding_model
             .embed(&chunk_refs, batch_size, late_chunking)
             .await?;
-        let embeddings = get_text_metadata(&Rc::new(encodings), &chunk_refs, &metadata)?;
-        adapter(embeddings);
+        let mut embeddings = get_text_metadata(&Rc::new(encodings), &chunk_refs, &metadata)?;
+        add_chunk_metadata(&mut embeddings, &text);
+        adapter(compress_embeddings(
+            embeddings,
+            config.multi_vector_compression.as_ref(),
+        )?);
         Ok(None)
     } else {
         let encodings = embedding_model
             .embed(&chunk_refs, batch_size, late_chunking)
             .await?;
-        let embeddings = get_text_metadata(&Rc::new(encodings), &chunk_refs, &metadata)?;
+        let mut embeddings = get_text_metadata(&Rc::new(encodings), &chunk_refs, &metadata)?;
+        add_chunk_metadata(&mut embeddings, &text);
+
+        Ok(Some(compress_embeddings(
+            embeddings,
+            config.multi_vector_compression.as_ref(),
+        )?))
+    }
+}
+
+/// Embeds the text of a PDF page by page with `embedding_model` and the images inside it with the
+/// vision embedder of `image_config`. All records carry the file metadata, the "page_number" and a
+/// "content_type" of "text", "figure" or "page".
+async fn emb_multimodal_pdf(
+    source: Source<'_>,
+    embedding_model: &TextEmbedder,
+    config: &TextEmbedConfig,
+    image_config: &PdfImageConfig,
+    adapter: Option<Box<dyn FnOnce(Vec<EmbedData>) + Send + Sync>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    let chunk_size = config.chunk_size.unwrap_or(1000);
+    let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
+    let pdf_processor = PdfProcessor::new(
+        chunk_size,
+        (chunk_size as f32 * overlap_ratio) as usize,
+        OcrConfig {
+            use_ocr: config.use_ocr.unwrap_or(false),
+            tesseract_path: config.tesseract_path.clone(),
+        },
+        config.pdf_backend,
+    )?;
+    let (pages, file_metadata) = match source {
+        Source::File(file) => {
+            if !file.exists() {
+                return Err(
+                    FileLoadingError::FileNotFound(file.to_str().unwrap().to_string()).into(),
+                );
+            }
+            (
+                pdf_processor.process_pages(file)?,
+                TextLoader::get_metadata(file)?,
+            )
+        }
+        Source::Bytes(bytes, file_name) => (
+            pdf_processor.process_pages_from_bytes(bytes)?,
+            file_name
+                .map(|file_name| HashMap::from([("file_name".to_string(), file_name.to_string())]))
+                .unwrap_or_default(),
+        ),
+    };
+    let page_metadata = |page_number: u32, content_type: &str| {
+        let mut metadata = file_metadata.clone();
+        metadata.insert("page_number".to_string(), page_number.to_string());
+        metadata.insert("content_type".to_string(), content_type.to_string());
+        metadata
+    };
+
+    let (chunks, chunk_pages): (Vec<&str>, Vec<u32>) = pages
+        .iter()
+        .zip(1..)
+        .flat_map(|(page, page_number)| {
+            page.chunks
+                .iter()
+                .map(move |chunk| (chunk.as_str(), page_number))
+        })
+        .unzip();
+    let encodings = embedding_model
+        .embed(&chunks, config.batch_size, config.late_chunking)
+        .await?;
+    let mut embeddings = encodings
+        .into_iter()
+        .zip(chunks)
+        .zip(chunk_pages)
+        .map(|((encoding, chunk), page_number)| {
+            EmbedData::new(
+                encoding,
+                Some(chunk.to_string()),
+                Some(page_metadata(page_number, "text")),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let mut images = Vec::new();
+    let mut image_metadata = Vec::new();
+    let figures = match source {
+        Source::File(file) => extract_images_from_pdf(&file, image_config.min_image_size)?,
+        Source::Bytes(bytes, _) => {
+            extract_images_from_pdf_bytes(bytes, image_config.min_image_size)?
+        }
+    };
+    for figure in figures {
+        let mut metadata = page_metadata(figure.page_number, "figure");
+        metadata.insert("image_index".to_string(), figure.image_index.to_string());
+        images.push(figure.image);
+        image_metadata.push(metadata);
+    }
+    if image_config.include_page_images {
+        let rendered_pages = match source {
+            Source::File(file) => render_pdf_pages(&file, &config.pdf_render_config)?,
+            Source::Bytes(bytes, _) => {
+                render_pdf_pages_from_bytes(bytes, &config.pdf_render_config)?
+            }
+        };
+        for (page_number, image) in rendered_pages {
+            images.push(image);
+            image_metadata.push(page_metadata(page_number, "page"));
+        }
+    }
+    if !images.is_empty() {
+        let image_embeddings = image_config
+            .image_embedder
+            .embed_dynamic_images(&images, config.batch_size)
+            .await?;
+        embeddings.extend(image_embeddings.into_iter().zip(image_metadata).map(
+            |(mut embed_data, metadata)| {
+                embed_data.metadata = Some(metadata);
+                embed_data
+            },
+        ));
+    }
+    let embeddings = compress_embeddings(embeddings, config.multi_vector_compression.as_ref())?;
 
+    if let Some(adapter) = adapter {
+        adapter(embeddings);
+        Ok(None)
+    } else {
         Ok(Some(embeddings))
     }
 }
 
+/// Embeds a PDF with the images inside it, as [embed_file] does, when
+/// [TextEmbedConfig::pdf_image_config] is set and `embedder` embeds text. Returns None for the
+/// other files, whose chunks are embedded in buffers by the directory and batch functions.
+async fn emb_pdf_with_images(
+    file: &std::path::Path,
+    embedder: &Embedder,
+    config: &TextEmbedConfig,
+) -> Option<Result<Vec<EmbedData>>> {
+    let (Embedder::Text(embedder), Some(image_config)) = (embedder, &config.pdf_image_config)
+    else {
+        return None;
+    };
+    if !is_pdf(file, config) {
+        return None;
+    }
+    let source = Source::File(file);
+    Some(
+        emb_multimodal_pdf(source, embedder, config, image_config, None)
+            .await
+            .map(Option::unwrap_or_default),
+    )
+}
+
+/// Whether the registry of `config` detects `file` as a PDF, whatever its extension.
+fn is_pdf(file: impl AsRef<std::path::Path>, config: &TextEmbedConfig) -> bool {
+    config
+        .registry
+        .detect(file)
+        .ok()
+        .flatten()
+        .is_some_and(|format| format.name == "pdf")
+}
+
 async fn emb_image<T: AsRef<std::path::Path>>(
     image_path: T,
     embedding_model: &VisionEmbedder,
@@ -389,18 +738,22 @@ pub async fn emb_audio<T: AsRef<std::path::Path>>(
     embedder: &Arc<Embedder>,
     text_embed_config: Option<&TextEmbedConfig>,
 ) -> Result<Option<Vec<EmbedData>>> {
-    use file_processor::audio::audio_processor;
-
-    let segments: Vec<audio_processor::Segment> = audio_decoder.process_audio(&audio_file).unwrap();
-    let embeddings = embed_audio(
-        embedder,
-        segments,
-        audio_file,
-        text_embed_config
-            .unwrap_or(&TextEmbedConfig::default())
-            .batch_size,
-    )
-    .await?;
+    use file_processor::audio::{
+        audio_processor, export::save_transcripts, transcript::rechunk_segments,
+    };
+
+    let binding = TextEmbedConfig::default();
+    let config = text_embed_config.unwrap_or(&binding);
+    let segments: Vec<audio_processor::Segment> = audio_decoder.process_audio(&audio_file)?;
+    if let Err(e) = save_transcripts(&segments, &audio_file, &config.transcript_formats) {
+        tracing::warn!(
+            file = %audio_file.as_ref().display(),
+            error = ?e,
+            "Error saving the transcript"
+        );
+    }
+    let segments = rechunk_segments(segments, &config.audio_chunking);
+    let embeddings = embed_audio(embedder, segments, audio_file, config.batch_size).await?;
 
     Ok(Some(embeddings))
 }
@@ -417,6 +770,129 @@ pub async fn emb_audio<T: AsRef<std::path::Path>>(
     ))
 }
 
+/// Transcribes and embeds the audio files in a directory.
+///
+/// WAV, MP3, FLAC, Ogg, M4A and AAC files are embedded, as well as the audio tracks of MP4, MKV
+/// and WebM videos. Files are transcribed one after the other while the transcribed segments
+/// are embedded in buffers of `buffer_size` segments, and the embeddings of every buffer are
+/// passed to the adapter as soon as they are ready. Files that cannot be decoded are skipped.
+///
+/// # Arguments
+///
+/// * `directory` - A `PathBuf` representing the directory containing the audio files to embed.
+/// * `audio_decoder` - Audio decoder model for transcription
+/// * `embedder` - Embedding model for the transcribed segments
+/// * `config` - An optional `TextEmbedConfig` object specifying the batch size, buffer size and
+///   audio chunking. Default buffer size is 100.
+/// * `adapter` - An optional callback function to handle the embeddings.
+///
+/// # Returns
+/// An `Option` containing a vector of `EmbedData` objects representing the embeddings of the audio
+/// segments, or `None` if an adapter is used.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::embed_audio_directory;
+/// use embed_anything::embeddings::embed::Embedder;
+/// use embed_anything::file_processor::audio::audio_processor::AudioDecoderModel;
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// async fn embed_recordings() {
+///     let mut audio_decoder =
+///         AudioDecoderModel::from_pretrained(None, None, "tiny-en", false).unwrap();
+///     let embedder = Arc::new(
+///         Embedder::from_pretrained_hf("sentence-transformers/all-MiniLM-L6-v2", None, None, None)
+///             .unwrap(),
+///     );
+///     let directory = PathBuf::from("/path/to/recordings");
+///     let embeddings = embed_audio_directory(directory, &mut audio_decoder, &embedder, None, None)
+///         .await
+///         .unwrap();
+/// }
+/// ```
+#[cfg(feature = "audio")]
+#[cfg_attr(docsrs, doc(cfg(feature = "audio")))]
+pub async fn embed_audio_directory(
+    directory: PathBuf,
+    audio_decoder: &mut AudioDecoderModel,
+    embedder: &Arc<Embedder>,
+    config: Option<&TextEmbedConfig>,
+    adapter: Option<Box<dyn FnMut(Vec<EmbedData>) + Send + Sync>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    use file_processor::audio::{
+        export::save_transcripts, pipeline::decode_and_embed, transcript::rechunk_segments,
+    };
+
+    let binding = TextEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let buffer_size = config.buffer_size.unwrap_or(binding.buffer_size.unwrap());
+    let batch_size = config.batch_size;
+    let mut file_parser = FileParser::new();
+    file_parser.get_audio_files(&directory)?;
+
+    let pb = indicatif::ProgressBar::new(file_parser.files.len() as u64);
+    pb.set_style(indicatif::ProgressStyle::with_template(
+        "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+    )?);
+
+    // The decoder runs on a blocking thread, with a copy of the model that shares its weights.
+    let mut audio_decoder = audio_decoder.clone();
+    let transcript_formats = config.transcript_formats.clone();
+    let audio_chunking = config.audio_chunking.clone();
+    let decode = move |file: &str| {
+        let segments = match audio_decoder.process_audio(file) {
+            Ok(segments) => {
+                if let Err(e) = save_transcripts(&segments, file, &transcript_formats) {
+                    tracing::warn!(file = %file, error = ?e, "Error saving the transcript");
+                }
+                rechunk_segments(segments, &audio_chunking)
+            }
+            Err(e) => {
+                tracing::warn!(file = %file, error = ?e, "Error decoding the audio file");
+                Vec::new()
+            }
+        };
+        pb.inc(1);
+        segments
+    };
+
+    let embedder: &Embedder = embedder;
+    let mut all_embeddings = Vec::new();
+    let mut adapter = adapter;
+    decode_and_embed(
+        file_parser.files,
+        decode,
+        buffer_size,
+        |segments| embed_audio_segments(embedder, segments, batch_size),
+        |embeddings| match adapter.as_mut() {
+            Some(adapter) => adapter(embeddings),
+            None => all_embeddings.extend(embeddings),
+        },
+    )
+    .await?;
+
+    if adapter.is_some() {
+        Ok(None)
+    } else {
+        Ok(Some(all_embeddings))
+    }
+}
+
+#[cfg(not(feature = "audio"))]
+pub async fn embed_audio_directory(
+    _directory: PathBuf,
+    _audio_decoder: &mut AudioDecoderModel,
+    _embedder: &Arc<Embedder>,
+    _config: Option<&TextEmbedConfig>,
+    _adapter: Option<Box<dyn FnMut(Vec<EmbedData>) + Send + Sync>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    Err(anyhow::anyhow!(
+        "The 'audio' feature is not enabled. Please enable it to use the embed_audio_directory function."
+    ))
+}
+
 /// Embeds images in a directory using the specified embedding model.
 ///
 /// # Arguments
@@ -467,6 +943,8 @@ pub async fn embed_image_directory(
         .batch_size
         .unwrap_or(32);
 
+    let compression = config.and_then(|config| config.multi_vector_compression.clone());
+
     let (tx, mut rx) = mpsc::unbounded_channel();
     let (collector_tx, mut collector_rx) = mpsc::unbounded_channel();
 
@@ -488,7 +966,14 @@ pub async fn embed_image_directory(
 
                 if image_buffer.len() == buffer_size {
                     // Ensure embedder is mutable and not wrapped in Arc
-                    match process_images(&image_buffer, embedder.clone(), Some(batch_size)).await {
+                    match process_images(
+                        &image_buffer,
+                        embedder.clone(),
+                        Some(batch_size),
+                        compression.as_ref(),
+                    )
+                    .await
+                    {
                         Ok(embeddings) => {
                             let files = embeddings
                                 .iter()
@@ -516,7 +1001,14 @@ pub async fn embed_image_directory(
 
             // Process any remaining images
             if !image_buffer.is_empty() {
-                match process_images(&image_buffer, embedder, Some(batch_size)).await {
+                match process_images(
+                    &image_buffer,
+                    embedder,
+                    Some(batch_size),
+                    compression.as_ref(),
+                )
+                .await
+                {
                     Ok(embeddings) => {
                         let files = embeddings
                             .iter()
@@ -572,9 +1064,10 @@ async fn process_images<E: EmbedImage + Send + Sync + 'static>(
     image_buffer: &[String],
     embedder: Arc<E>,
     batch_size: Option<usize>,
+    compression: Option<&MultiVectorCompression>,
 ) -> Result<Arc<Vec<EmbedData>>> {
     let embeddings = embedder.embed_image_batch(image_buffer, batch_size).await?;
-    Ok(Arc::new(embeddings))
+    Ok(Arc::new(compress_embeddings(embeddings, compression)?))
 }
 
 /// Embeds text from files in a directory using the specified embedding model.
@@ -629,7 +1122,8 @@ pub async fn embed_directory_stream(
     let tesseract_path = config.tesseract_path.clone();
     let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
     let late_chunking = config.late_chunking;
-    let mut file_parser = FileParser::new();
+    let compression = config.multi_vector_compression.clone();
+    let mut file_parser = FileParser::new().with_registry(config.registry.clone());
     file_parser.get_text_files(&directory, extensions)?;
     let files = file_parser.files.clone();
     let (tx, mut rx) = mpsc::unbounded_channel();
@@ -641,6 +1135,9 @@ pub async fn embed_directory_stream(
     pb.set_style(indicatif::ProgressStyle::with_template(
         "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
     )?);
+    // PDFs embedded with their images skip the chunk buffers.
+    let (pdf_embedder, pdf_progress, pdf_collector_tx) =
+        (embedder.clone(), pb.clone(), collector_tx.clone());
 
     let processing_task = tokio::spawn({
         async move {
@@ -654,12 +1151,13 @@ pub async fn embed_directory_stream(
                 metadata_buffer.push(metadata);
 
                 if chunk_buffer.len() == buffer_size {
-                    match process_chunks(
+                    match embed_chunks(
                         &chunk_buffer,
                         &metadata_buffer,
                         &embedder,
                         batch_size,
                         late_chunking,
+                        compression.as_ref(),
                     )
                     .await
                     {
@@ -691,12 +1189,13 @@ pub async fn embed_directory_stream(
 
             // Process any remaining chunks
             if !chunk_buffer.is_empty() {
-                match process_chunks(
+                match embed_chunks(
                     &chunk_buffer,
                     &metadata_buffer,
                     &embedder,
                     batch_size,
                     late_chunking,
+                    compression.as_ref(),
                 )
                 .await
                 {
@@ -723,7 +1222,19 @@ pub async fn embed_directory_stream(
         }
     });
 
-    files.into_iter().for_each(|file| {
+    for file in files {
+        if let Some(embeddings) = emb_pdf_with_images(file.as_ref(), &pdf_embedder, config).await {
+            match embeddings {
+                Ok(embeddings) => {
+                    pdf_progress.inc(1);
+                    if let Err(e) = pdf_collector_tx.send(Arc::new(embeddings)) {
+                        eprintln!("Error sending embeddings to collector: {:?}", e);
+                    }
+                }
+                Err(e) => eprintln!("Error processing PDF: {:?}", e),
+            }
+            continue;
+        }
         let text = match extract_document(
             &file,
             chunk_size,
@@ -733,22 +1244,28 @@ pub async fn embed_directory_stream(
                 tesseract_path: tesseract_path.clone(),
             },
             Some(config.pdf_backend),
+            Some(config),
         ) {
             Ok(text) => text,
             Err(_) => {
-                return;
+                continue;
             }
         };
         let metadata = TextLoader::get_metadata(file).unwrap();
 
-        for chunk in text.chunks {
-            if let Err(e) = tx.send((chunk, Some(metadata.clone()))) {
+        for (i, chunk) in text.chunks.into_iter().enumerate() {
+            let mut metadata = metadata.clone();
+            if let Some(chunk_metadata) = text.chunk_metadata.get(i) {
+                metadata.extend(chunk_metadata.clone());
+            }
+            if let Err(e) = tx.send((chunk, Some(metadata))) {
                 eprintln!("Error sending chunk: {:?}", e);
             }
         }
-    });
+    }
 
     drop(tx);
+    drop(pdf_collector_tx);
 
     let mut all_embeddings = Vec::new();
     let mut adapter = adapter;
@@ -813,6 +1330,7 @@ pub async fn embed_files_batch(
     let buffer_size = config.buffer_size.unwrap_or(binding.buffer_size.unwrap());
     let batch_size = config.batch_size;
     let late_chunking = config.late_chunking;
+    let compression = config.multi_vector_compression.clone();
     let use_ocr = config.use_ocr.unwrap_or(false);
     let tesseract_path = config.tesseract_path.clone();
     let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
@@ -827,6 +1345,9 @@ pub async fn embed_files_batch(
     pb.set_style(indicatif::ProgressStyle::with_template(
         "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
     )?);
+    // PDFs embedded with their images skip the chunk buffers.
+    let (pdf_embedder, pdf_progress, pdf_collector_tx) =
+        (embedder.clone(), pb.clone(), collector_tx.clone());
 
     let processing_task = tokio::spawn({
         async move {
@@ -840,12 +1361,13 @@ pub async fn embed_files_batch(
                 metadata_buffer.push(metadata);
 
                 if chunk_buffer.len() == buffer_size {
-                    match process_chunks(
+                    match embed_chunks(
                         &chunk_buffer,
                         &metadata_buffer,
                         &embedder,
                         batch_size,
                         late_chunking,
+                        compression.as_ref(),
                     )
                     .await
                     {
@@ -877,12 +1399,13 @@ pub async fn embed_files_batch(
 
             // Process any remaining chunks
             if !chunk_buffer.is_empty() {
-                match process_chunks(
+                match embed_chunks(
                     &chunk_buffer,
                     &metadata_buffer,
                     &embedder,
                     batch_size,
                     late_chunking,
+                    compression.as_ref(),
                 )
                 .await
                 {
@@ -909,7 +1432,19 @@ pub async fn embed_files_batch(
         }
     });
 
-    files.into_iter().for_each(|file| {
+    for file in files {
+        if let Some(embeddings) = emb_pdf_with_images(file.as_ref(), &pdf_embedder, config).await {
+            match embeddings {
+                Ok(embeddings) => {
+                    pdf_progress.inc(1);
+                    if let Err(e) = pdf_collector_tx.send(Arc::new(embeddings)) {
+                        eprintln!("Error sending embeddings to collector: {:?}", e);
+                    }
+                }
+                Err(e) => eprintln!("Error processing PDF: {:?}", e),
+            }
+            continue;
+        }
         let text = match extract_document(
             &file,
             chunk_size,
@@ -919,22 +1454,28 @@ pub async fn embed_files_batch(
                 tesseract_path: tesseract_path.clone(),
             },
             Some(backend),
+            Some(config),
         ) {
             Ok(text) => text,
             Err(_) => {
-                return;
+                continue;
             }
         };
         let metadata = TextLoader::get_metadata(file).unwrap();
 
-        for chunk in text.chunks {
-            if let Err(e) = tx.send((chunk, Some(metadata.clone()))) {
+        for (i, chunk) in text.chunks.into_iter().enumerate() {
+            let mut metadata = metadata.clone();
+            if let Some(chunk_metadata) = text.chunk_metadata.get(i) {
+                metadata.extend(chunk_metadata.clone());
+            }
+            if let Err(e) = tx.send((chunk, Some(metadata))) {
                 eprintln!("Error sending chunk: {:?}", e);
             }
         }
-    });
+    }
 
     drop(tx);
+    drop(pdf_collector_tx);
 
     let mut all_embeddings = Vec::new();
     let mut adapter = adapter;
@@ -995,40 +1536,209 @@ pub async fn process_chunks(
     Ok(Arc::new(embeddings))
 }
 
+/// Embeds chunks with [process_chunks] and compresses their multi-vector embeddings.
+async fn embed_chunks(
+    chunks: &[String],
+    metadata: &[Option<HashMap<String, String>>],
+    embedding_model: &Arc<Embedder>,
+    batch_size: Option<usize>,
+    late_chunking: Option<bool>,
+    compression: Option<&MultiVectorCompression>,
+) -> Result<Arc<Vec<EmbedData>>> {
+    let embeddings =
+        process_chunks(chunks, metadata, embedding_model, batch_size, late_chunking).await?;
+    Ok(Arc::new(compress_embeddings(
+        Arc::unwrap_or_clone(embeddings),
+        compression,
+    )?))
+}
+
+/// Compresses the multi-vector embeddings with `compression`, if set. See
+/// [TextEmbedConfig::multi_vector_compression].
+fn compress_embeddings(
+    embeddings: Vec<EmbedData>,
+    compression: Option<&MultiVectorCompression>,
+) -> Result<Vec<EmbedData>> {
+    match compression {
+        Some(compression) => compression.compress_embed_data(embeddings),
+        None => Ok(embeddings),
+    }
+}
+
+/// Adds the metadata the processor recorded for each chunk of `document`, such as its slide
+/// number, to the embeddings of the chunks.
+fn add_chunk_metadata(embeddings: &mut [EmbedData], document: &Document) {
+    for (embedding, chunk_metadata) in embeddings.iter_mut().zip(&document.chunk_metadata) {
+        embedding
+            .metadata
+            .get_or_insert_with(HashMap::new)
+            .extend(chunk_metadata.clone());
+    }
+}
+
 fn extract_document(
     file: impl AsRef<std::path::Path>,
     chunk_size: usize,
     overlap: usize,
     ocr_config: OcrConfig,
     backend: Option<PdfBackend>,
+    config: Option<&TextEmbedConfig>,
 ) -> Result<Document> {
     if !file.as_ref().exists() {
         return Err(
             FileLoadingError::FileNotFound(file.as_ref().to_str().unwrap().to_string()).into(),
         );
     }
-    let file_extension = file.as_ref().extension().unwrap();
-    match file_extension.to_str().unwrap() {
-        "pdf" => PdfProcessor::new(
+    let binding = TextEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let Some(format) = config.registry.detect(&file)? else {
+        return Err(unsupported_file_type(
+            &file.as_ref().display().to_string(),
+            config,
+        ));
+    };
+    process_source(
+        Source::File(file.as_ref()),
+        &format.name,
+        chunk_size,
+        overlap,
+        ocr_config,
+        backend,
+        config,
+    )
+}
+
+/// Extracts the chunks of a document held in memory. `file_name` is used to detect the format of
+/// content without a signature and the language of source code.
+fn extract_bytes(
+    bytes: &[u8],
+    file_name: Option<&str>,
+    chunk_size: usize,
+    overlap: usize,
+    ocr_config: OcrConfig,
+    backend: Option<PdfBackend>,
+    config: Option<&TextEmbedConfig>,
+) -> Result<Document> {
+    let binding = TextEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let Some(format) = config.registry.detect_bytes(bytes, file_name) else {
+        return Err(unsupported_file_type(
+            file_name.unwrap_or("<bytes>"),
+            config,
+        ));
+    };
+    process_source(
+        Source::Bytes(bytes, file_name),
+        &format.name,
+        chunk_size,
+        overlap,
+        ocr_config,
+        backend,
+        config,
+    )
+}
+
+fn unsupported_file_type(file: &str, config: &TextEmbedConfig) -> Error {
+    Error::msg(format!(
+        "{}. Supported file types are: {}",
+        FileLoadingError::UnsupportedFileType(file.to_string()),
+        config.registry.extensions().join(", ")
+    ))
+}
+
+/// A document to extract: a file, or the bytes of one with its file name if known.
+#[derive(Clone, Copy)]
+enum Source<'a> {
+    File(&'a std::path::Path),
+    Bytes(&'a [u8], Option<&'a str>),
+}
+
+impl Source<'_> {
+    fn name(&self) -> Option<&std::path::Path> {
+        match self {
+            Source::File(path) => Some(path),
+            Source::Bytes(_, file_name) => file_name.map(std::path::Path::new),
+        }
+    }
+
+    fn process(self, processor: &(impl FileProcessor + BytesProcessor)) -> Result<Document> {
+        match self {
+            Source::File(path) => processor.process_file(path),
+            Source::Bytes(bytes, _) => processor.process_bytes(bytes),
+        }
+    }
+}
+
+/// Extracts the chunks of a document with the processor of its format.
+fn process_source(
+    source: Source,
+    format: &str,
+    chunk_size: usize,
+    overlap: usize,
+    ocr_config: OcrConfig,
+    backend: Option<PdfBackend>,
+    config: &TextEmbedConfig,
+) -> Result<Document> {
+    if let Some(processor) = config.registry.processor(format) {
+        return source.process(processor);
+    }
+    match format {
+        "pdf" => source.process(&PdfProcessor::new(
             chunk_size,
             overlap,
             ocr_config,
             backend.unwrap_or(PdfBackend::LoPdf),
-        )?
-        .process_file(file),
-        "md" => MarkdownProcessor::new(chunk_size, overlap)?.process_file(file),
-        "txt" => TxtProcessor::new(chunk_size, overlap)?.process_file(file),
-        "docx" => DocxProcessor::new(chunk_size, overlap)?.process_file(file),
-        "html" => HtmlProcessor::new(chunk_size, overlap)?.process_file(file),
-        _ => Err(FileLoadingError::UnsupportedFileType(
-            file.as_ref()
-                .extension()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-        )
-        .into()),
+        )?),
+        "md" => source.process(&MarkdownProcessor::new(chunk_size, overlap)?),
+        "txt" => source.process(&TxtProcessor::new(chunk_size, overlap)?),
+        "docx" => source.process(&DocxProcessor::new(chunk_size, overlap)?),
+        "pptx" => source.process(&PptxProcessor::new(chunk_size, overlap)?),
+        "epub" => source.process(&EpubProcessor::new(chunk_size, overlap)?),
+        "odt" => source.process(&OdtProcessor::new(chunk_size, overlap)?),
+        "rtf" => source.process(&RtfProcessor::new(chunk_size, overlap)?),
+        "tex" => source.process(&TexProcessor::new(chunk_size, overlap)?),
+        "ipynb" => source.process(
+            &IpynbProcessor::new(chunk_size, overlap)?.with_outputs(config.notebook_outputs),
+        ),
+        "eml" | "mbox" => {
+            // Attachments are routed through this function by their own format.
+            let config = config.clone();
+            let processor = EmailProcessor::new(chunk_size, overlap)?.with_attachment_processor(
+                move |attachment, file_name| {
+                    extract_bytes(
+                        attachment,
+                        Some(file_name),
+                        chunk_size,
+                        overlap,
+                        OcrConfig {
+                            use_ocr: ocr_config.use_ocr,
+                            tesseract_path: ocr_config.tesseract_path.clone(),
+                        },
+                        backend,
+                        Some(&config),
+                    )
+                },
+            );
+            source.process(&processor)
+        }
+        "csv" | "tsv" | "xlsx" => {
+            source.process(&TabularProcessor::new(config.tabular_config.clone()))
+        }
+        "json" | "jsonl" | "xml" => {
+            source.process(&StructuredProcessor::new(config.structured_config.clone()))
+        }
+        "html" => source.process(&HtmlProcessor::new(chunk_size, overlap)?),
+        "code" => {
+            let language = source
+                .name()
+                .and_then(Language::from_path)
+                .ok_or_else(|| Error::msg("The language of the code cannot be detected"))?;
+            source.process(&CodeProcessor::new(chunk_size, overlap)?.with_language(language))
+        }
+        name => Err(Error::msg(format!(
+            "No processor is registered for {} files",
+            name
+        ))),
     }
 }
 
@@ -1089,10 +1799,9 @@ impl From<FileLoadingError> for Error {
             FileLoadingError::FileNotFound(file) => {
                 Error::msg(format!("File not found: {:?}", file))
             }
-            FileLoadingError::UnsupportedFileType(file) => Error::msg(format!(
-                "Unsupported file type: {:?}. Currently supported file types are: pdf, md, txt, docx",
-                file
-            )),
+            FileLoadingError::UnsupportedFileType(file) => {
+                Error::msg(format!("Unsupported file type: {:?}", file))
+            }
         }
     }
 }
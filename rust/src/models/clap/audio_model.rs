@@ -0,0 +1,558 @@
+//! The HTSAT audio encoder of CLAP.
+//!
+//! HTSAT is a Swin Transformer over the log-mel spectrogram. The spectrogram is normalized per
+//! mel band, stretched to 1024 frames and folded into a 256x256 image, which goes through four
+//! stages of shifted-window attention. The pooled output is the mean of the final tokens.
+
+use candle_core::{DType, Device, Module, Result, Tensor, D};
+use candle_nn::{
+    conv2d, layer_norm, linear, linear_no_bias, Conv2dConfig, LayerNorm, Linear, VarBuilder,
+};
+use serde::Deserialize;
+
+fn default_window_size() -> usize {
+    8
+}
+
+fn default_num_mel_bins() -> usize {
+    64
+}
+
+fn default_spec_size() -> usize {
+    256
+}
+
+fn default_patch_size() -> usize {
+    4
+}
+
+fn default_patch_embeds_hidden_size() -> usize {
+    96
+}
+
+fn default_depths() -> Vec<usize> {
+    vec![2, 2, 6, 2]
+}
+
+fn default_num_attention_heads() -> Vec<usize> {
+    vec![4, 8, 16, 32]
+}
+
+fn default_hidden_size() -> usize {
+    768
+}
+
+fn default_mlp_ratio() -> f64 {
+    4.0
+}
+
+fn default_layer_norm_eps() -> f64 {
+    1e-5
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClapAudioConfig {
+    #[serde(default = "default_window_size")]
+    pub window_size: usize,
+    #[serde(default = "default_num_mel_bins")]
+    pub num_mel_bins: usize,
+    #[serde(default = "default_spec_size")]
+    pub spec_size: usize,
+    #[serde(default = "default_patch_size")]
+    pub patch_size: usize,
+    #[serde(default = "default_patch_embeds_hidden_size")]
+    pub patch_embeds_hidden_size: usize,
+    #[serde(default = "default_depths")]
+    pub depths: Vec<usize>,
+    #[serde(default = "default_num_attention_heads")]
+    pub num_attention_heads: Vec<usize>,
+    #[serde(default = "default_hidden_size")]
+    pub hidden_size: usize,
+    #[serde(default = "default_mlp_ratio")]
+    pub mlp_ratio: f64,
+    #[serde(default = "default_layer_norm_eps")]
+    pub layer_norm_eps: f64,
+}
+
+impl Default for ClapAudioConfig {
+    fn default() -> Self {
+        serde_json::from_str("{}").unwrap()
+    }
+}
+
+/// Returns the `(out_len, in_len)` matrix of a bicubic resize with aligned corners, as done by
+/// `torch.nn.functional.interpolate(mode="bicubic", align_corners=True)` along one axis.
+pub fn bicubic_matrix(in_len: usize, out_len: usize) -> Vec<f32> {
+    const A: f64 = -0.75;
+    let near = |x: f64| ((A + 2.0) * x - (A + 3.0)) * x * x + 1.0;
+    let far = |x: f64| ((A * x - 5.0 * A) * x + 8.0 * A) * x - 4.0 * A;
+    let scale = match out_len > 1 {
+        true => (in_len - 1) as f64 / (out_len - 1) as f64,
+        false => 0.0,
+    };
+
+    let mut matrix = vec![0f32; out_len * in_len];
+    for i in 0..out_len {
+        let source = i as f64 * scale;
+        let index = source.floor();
+        let t = source - index;
+        let weights = [far(t + 1.0), near(t), near(1.0 - t), far(2.0 - t)];
+        for (offset, weight) in weights.iter().enumerate() {
+            let j = (index as isize + offset as isize - 1).clamp(0, in_len as isize - 1) as usize;
+            matrix[i * in_len + j] += *weight as f32;
+        }
+    }
+    matrix
+}
+
+/// Splits `(B, H, W, C)` into `(B * H/ws * W/ws, ws * ws, C)` windows.
+pub fn window_partition(xs: &Tensor, window_size: usize) -> Result<Tensor> {
+    let (b, h, w, c) = xs.dims4()?;
+    xs.reshape((
+        b,
+        h / window_size,
+        window_size,
+        w / window_size,
+        window_size,
+        c,
+    ))?
+    .permute((0, 1, 3, 2, 4, 5))?
+    .reshape((
+        b * (h / window_size) * (w / window_size),
+        window_size * window_size,
+        c,
+    ))
+}
+
+/// Reverses [window_partition] into `(B, H, W, C)`.
+pub fn window_reverse(windows: &Tensor, window_size: usize, h: usize, w: usize) -> Result<Tensor> {
+    let c = windows.dim(D::Minus1)?;
+    let b = windows.dim(0)? / (h / window_size * w / window_size);
+    windows
+        .reshape((
+            b,
+            h / window_size,
+            w / window_size,
+            window_size,
+            window_size,
+            c,
+        ))?
+        .permute((0, 1, 3, 2, 4, 5))?
+        .reshape((b, h, w, c))
+}
+
+/// Rolls `(B, H, W, C)` by `shift` along H and W.
+fn roll(xs: &Tensor, shift: isize) -> Result<Tensor> {
+    let mut xs = xs.clone();
+    for dim in [1, 2] {
+        let len = xs.dim(dim)?;
+        let shift = shift.rem_euclid(len as isize) as usize;
+        if shift > 0 {
+            xs = Tensor::cat(
+                &[
+                    xs.narrow(dim, len - shift, shift)?,
+                    xs.narrow(dim, 0, len - shift)?,
+                ],
+                dim,
+            )?;
+        }
+    }
+    Ok(xs)
+}
+
+/// The `(nW, N, N)` attention mask that keeps shifted windows from attending across the regions
+/// that were rolled together.
+fn shifted_window_mask(
+    resolution: usize,
+    window_size: usize,
+    shift_size: usize,
+    device: &Device,
+) -> Result<Tensor> {
+    let region = |i: usize| match i {
+        i if i < resolution - window_size => 0,
+        i if i < resolution - shift_size => 1,
+        _ => 2,
+    };
+    let regions = (0..resolution)
+        .flat_map(|y| (0..resolution).map(move |x| (3 * region(y) + region(x)) as f32))
+        .collect::<Vec<_>>();
+    let regions = Tensor::from_vec(regions, (1, resolution, resolution, 1), device)?;
+    let windows = window_partition(&regions, window_size)?.squeeze(D::Minus1)?;
+    let difference = windows
+        .unsqueeze(1)?
+        .broadcast_sub(&windows.unsqueeze(2)?)?;
+    difference.ne(0f32)?.to_dtype(DType::F32)? * -100.0
+}
+
+#[derive(Clone)]
+struct WindowAttention {
+    query: Linear,
+    key: Linear,
+    value: Linear,
+    output: Linear,
+    relative_position_bias: Tensor,
+    num_heads: usize,
+}
+
+impl WindowAttention {
+    fn new(vb: VarBuilder, dim: usize, num_heads: usize, window_size: usize) -> Result<Self> {
+        let table_size = (2 * window_size - 1) * (2 * window_size - 1);
+        let table = vb.get((table_size, num_heads), "self.relative_position_bias_table")?;
+
+        let n = window_size * window_size;
+        let index = (0..n)
+            .flat_map(|i| {
+                (0..n).map(move |j| {
+                    let dy = i / window_size + window_size - 1 - j / window_size;
+                    let dx = i % window_size + window_size - 1 - j % window_size;
+                    (dy * (2 * window_size - 1) + dx) as u32
+                })
+            })
+            .collect::<Vec<_>>();
+        let index = Tensor::from_vec(index, n * n, vb.device())?;
+        let relative_position_bias = table
+            .index_select(&index, 0)?
+            .reshape((n, n, num_heads))?
+            .permute((2, 0, 1))?
+            .contiguous()?
+            .unsqueeze(0)?;
+
+        Ok(Self {
+            query: linear(dim, dim, vb.pp("self.query"))?,
+            key: linear(dim, dim, vb.pp("self.key"))?,
+            value: linear(dim, dim, vb.pp("self.value"))?,
+            output: linear(dim, dim, vb.pp("output.dense"))?,
+            relative_position_bias,
+            num_heads,
+        })
+    }
+
+    fn forward(&self, xs: &Tensor, mask: Option<&Tensor>) -> Result<Tensor> {
+        let (b, n, c) = xs.dims3()?;
+        let head_dim = c / self.num_heads;
+        let heads = |xs: Tensor| {
+            xs.reshape((b, n, self.num_heads, head_dim))?
+                .transpose(1, 2)?
+                .contiguous()
+        };
+        let q = (heads(self.query.forward(xs)?)? / (head_dim as f64).sqrt())?;
+        let k = heads(self.key.forward(xs)?)?;
+        let v = heads(self.value.forward(xs)?)?;
+
+        let mut scores = q
+            .matmul(&k.t()?)?
+            .broadcast_add(&self.relative_position_bias)?;
+        if let Some(mask) = mask {
+            let windows = mask.dim(0)?;
+            scores = scores
+                .reshape((b / windows, windows, self.num_heads, n, n))?
+                .broadcast_add(&mask.unsqueeze(1)?.unsqueeze(0)?)?
+                .reshape((b, self.num_heads, n, n))?;
+        }
+        let probs = candle_nn::ops::softmax_last_dim(&scores)?;
+        let context = probs.matmul(&v)?.transpose(1, 2)?.reshape((b, n, c))?;
+        self.output.forward(&context)
+    }
+}
+
+#[derive(Clone)]
+struct SwinBlock {
+    layernorm_before: LayerNorm,
+    attention: WindowAttention,
+    layernorm_after: LayerNorm,
+    intermediate: Linear,
+    output: Linear,
+    resolution: usize,
+    window_size: usize,
+    shift_size: usize,
+    mask: Option<Tensor>,
+}
+
+impl SwinBlock {
+    fn new(
+        vb: VarBuilder,
+        config: &ClapAudioConfig,
+        dim: usize,
+        num_heads: usize,
+        resolution: usize,
+        shift: bool,
+    ) -> Result<Self> {
+        // Windows never exceed the resolution, and windows covering everything are not shifted.
+        let (window_size, shift_size) = match resolution <= config.window_size {
+            true => (resolution, 0),
+            false => (
+                config.window_size,
+                if shift { config.window_size / 2 } else { 0 },
+            ),
+        };
+        let mask = match shift_size > 0 {
+            true => Some(shifted_window_mask(
+                resolution,
+                window_size,
+                shift_size,
+                vb.device(),
+            )?),
+            false => None,
+        };
+        let hidden = (dim as f64 * config.mlp_ratio) as usize;
+        Ok(Self {
+            layernorm_before: layer_norm(dim, config.layer_norm_eps, vb.pp("layernorm_before"))?,
+            attention: WindowAttention::new(vb.pp("attention"), dim, num_heads, window_size)?,
+            layernorm_after: layer_norm(dim, config.layer_norm_eps, vb.pp("layernorm_after"))?,
+            intermediate: linear(dim, hidden, vb.pp("intermediate.dense"))?,
+            output: linear(hidden, dim, vb.pp("output.dense"))?,
+            resolution,
+            window_size,
+            shift_size,
+            mask,
+        })
+    }
+
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        let (b, _, c) = xs.dims3()?;
+        let (resolution, shift) = (self.resolution, self.shift_size as isize);
+
+        let hidden = self
+            .layernorm_before
+            .forward(xs)?
+            .reshape((b, resolution, resolution, c))?;
+        let hidden = roll(&hidden, -shift)?;
+        let windows = window_partition(&hidden, self.window_size)?;
+        let windows = self.attention.forward(&windows, self.mask.as_ref())?;
+        let hidden = window_reverse(&windows, self.window_size, resolution, resolution)?;
+        let hidden = roll(&hidden, shift)?.reshape((b, resolution * resolution, c))?;
+
+        let xs = (xs + hidden)?;
+        let hidden = self.layernorm_after.forward(&xs)?;
+        let hidden = self.intermediate.forward(&hidden)?.gelu_erf()?;
+        xs + self.output.forward(&hidden)?
+    }
+}
+
+/// Halves the resolution and doubles the channels of `(B, H * W, C)` tokens.
+#[derive(Clone)]
+struct PatchMerging {
+    norm: LayerNorm,
+    reduction: Linear,
+    resolution: usize,
+}
+
+impl PatchMerging {
+    fn new(
+        vb: VarBuilder,
+        config: &ClapAudioConfig,
+        dim: usize,
+        resolution: usize,
+    ) -> Result<Self> {
+        Ok(Self {
+            norm: layer_norm(4 * dim, config.layer_norm_eps, vb.pp("norm"))?,
+            reduction: linear_no_bias(4 * dim, 2 * dim, vb.pp("reduction"))?,
+            resolution,
+        })
+    }
+
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        let (b, _, c) = xs.dims3()?;
+        let half = self.resolution / 2;
+        // The merged channels are ordered (even row, even column), (odd, even), (even, odd),
+        // (odd, odd).
+        let xs = xs
+            .reshape((b, half, 2, half, 2, c))?
+            .permute((0, 1, 3, 4, 2, 5))?
+            .reshape((b, half * half, 4 * c))?;
+        self.reduction.forward(&self.norm.forward(&xs)?)
+    }
+}
+
+#[derive(Clone)]
+struct Stage {
+    blocks: Vec<SwinBlock>,
+    downsample: Option<PatchMerging>,
+}
+
+#[derive(Clone)]
+pub struct ClapAudioModel {
+    batch_norm: (Tensor, Tensor),
+    patch_embed: candle_nn::Conv2d,
+    patch_norm: LayerNorm,
+    stages: Vec<Stage>,
+    norm: LayerNorm,
+    freq_ratio: usize,
+}
+
+impl ClapAudioModel {
+    pub fn new(vb: VarBuilder, config: &ClapAudioConfig) -> Result<Self> {
+        let mel_bins = config.num_mel_bins;
+        let batch_norm = {
+            let vb = vb.pp("batch_norm");
+            let weight = vb.get(mel_bins, "weight")?;
+            let bias = vb.get(mel_bins, "bias")?;
+            let mean = vb.get(mel_bins, "running_mean")?;
+            let var = vb.get(mel_bins, "running_var")?;
+            let scale = (weight / (var + 1e-5)?.sqrt()?)?;
+            let shift = (bias - (mean * &scale)?)?;
+            (scale, shift)
+        };
+
+        let patch_embed = conv2d(
+            1,
+            config.patch_embeds_hidden_size,
+            config.patch_size,
+            Conv2dConfig {
+                stride: config.patch_size,
+                ..Default::default()
+            },
+            vb.pp("patch_embed.proj"),
+        )?;
+        let patch_norm = layer_norm(
+            config.patch_embeds_hidden_size,
+            config.layer_norm_eps,
+            vb.pp("patch_embed.norm"),
+        )?;
+
+        let mut stages = Vec::with_capacity(config.depths.len());
+        for (i, (&depth, &num_heads)) in config
+            .depths
+            .iter()
+            .zip(&config.num_attention_heads)
+            .enumerate()
+        {
+            let vb = vb.pp(format!("layers.{i}"));
+            let dim = config.patch_embeds_hidden_size << i;
+            let resolution = (config.spec_size / config.patch_size) >> i;
+            let blocks = (0..depth)
+                .map(|j| {
+                    SwinBlock::new(
+                        vb.pp(format!("blocks.{j}")),
+                        config,
+                        dim,
+                        num_heads,
+                        resolution,
+                        j % 2 == 1,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let downsample = match i + 1 < config.depths.len() {
+                true => Some(PatchMerging::new(
+                    vb.pp("downsample"),
+                    config,
+                    dim,
+                    resolution,
+                )?),
+                false => None,
+            };
+            stages.push(Stage { blocks, downsample });
+        }
+
+        let num_features = config.patch_embeds_hidden_size << (config.depths.len() - 1);
+        let norm = layer_norm(num_features, config.layer_norm_eps, vb.pp("norm"))?;
+
+        Ok(Self {
+            batch_norm,
+            patch_embed,
+            patch_norm,
+            stages,
+            norm,
+            freq_ratio: config.spec_size / mel_bins,
+        })
+    }
+
+    /// Normalizes `(B, frames, mel_bins)` log-mel features, stretches them to
+    /// `spec_size * freq_ratio` frames and folds them into a `(B, 1, spec_size, spec_size)` image.
+    fn mel_to_image(&self, features: &Tensor) -> Result<Tensor> {
+        let (b, in_frames, mel_bins) = features.dims3()?;
+        let (scale, shift) = &self.batch_norm;
+        let features = features.broadcast_mul(scale)?.broadcast_add(shift)?;
+        let frames = mel_bins * self.freq_ratio * self.freq_ratio;
+        let time_resize = Tensor::from_vec(
+            bicubic_matrix(in_frames, frames),
+            (frames, in_frames),
+            features.device(),
+        )?;
+        let features = time_resize
+            .broadcast_left(b)?
+            .matmul(&features.contiguous()?)?;
+        features
+            .reshape((b, self.freq_ratio, frames / self.freq_ratio, mel_bins))?
+            .transpose(2, 3)?
+            .reshape((b, 1, mel_bins * self.freq_ratio, frames / self.freq_ratio))
+    }
+
+    /// Returns the pooled output of a batch of `(B, frames, mel_bins)` log-mel features.
+    pub fn forward(&self, features: &Tensor) -> Result<Tensor> {
+        let image = self.mel_to_image(features)?;
+        let xs = self
+            .patch_embed
+            .forward(&image)?
+            .flatten_from(2)?
+            .transpose(1, 2)?;
+        let mut xs = self.patch_norm.forward(&xs)?;
+        for stage in &self.stages {
+            for block in &stage.blocks {
+                xs = block.forward(&xs)?;
+            }
+            if let Some(downsample) = &stage.downsample {
+                xs = downsample.forward(&xs)?;
+            }
+        }
+        self.norm.forward(&xs)?.mean(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::IndexOp;
+
+    #[test]
+    fn test_bicubic_matrix() {
+        let (in_len, out_len) = (5, 9);
+        let matrix = bicubic_matrix(in_len, out_len);
+        // Every row sums to one, and the corners and every other output hit the inputs exactly.
+        for row in matrix.chunks(in_len) {
+            assert!((row.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        }
+        for i in (0..out_len).step_by(2) {
+            let row = &matrix[i * in_len..(i + 1) * in_len];
+            assert!((row[i / 2] - 1.0).abs() < 1e-6, "{row:?}");
+        }
+        // A linear ramp stays linear in the interior.
+        let ramp = (0..in_len).map(|x| x as f32).collect::<Vec<_>>();
+        let value = matrix[3 * in_len..4 * in_len]
+            .iter()
+            .zip(&ramp)
+            .map(|(w, x)| w * x)
+            .sum::<f32>();
+        assert!((value - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_window_partition_roundtrip() -> Result<()> {
+        let xs = Tensor::arange(0f32, 2. * 8. * 8. * 3., &Device::Cpu)?.reshape((2, 8, 8, 3))?;
+        let windows = window_partition(&xs, 4)?;
+        assert_eq!(windows.dims(), &[8, 16, 3]);
+        // The second window of the first image starts at column 4.
+        assert_eq!(
+            windows.i((1, 0))?.to_vec1::<f32>()?,
+            xs.i((0, 0, 4))?.to_vec1::<f32>()?
+        );
+        let restored = window_reverse(&windows, 4, 8, 8)?;
+        assert_eq!(
+            restored.flatten_all()?.to_vec1::<f32>()?,
+            xs.flatten_all()?.to_vec1::<f32>()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_shifted_window_mask() -> Result<()> {
+        let mask = shifted_window_mask(8, 4, 2, &Device::Cpu)?;
+        assert_eq!(mask.dims(), &[4, 16, 16]);
+        // The first window lies in a single region, the last one spans four.
+        assert_eq!(mask.i(0)?.abs()?.sum_all()?.to_scalar::<f32>()?, 0.0);
+        let last = mask.i(3)?.to_vec2::<f32>()?;
+        assert_eq!(last[0][0], 0.0);
+        assert_eq!(last[0][3], -100.0);
+        Ok(())
+    }
+}
@@ -0,0 +1,363 @@
+//! Log-mel feature extraction for CLAP.
+//!
+//! Mirrors the `ClapFeatureExtractor` of transformers: 48 kHz audio is cut into windows of 10
+//! seconds, shorter windows are repeated and zero padded, and every window is turned into a
+//! power spectrogram with a periodic Hann window and mapped to decibel-scaled mel bands.
+
+use serde::Deserialize;
+
+fn default_feature_size() -> usize {
+    64
+}
+
+fn default_sampling_rate() -> u32 {
+    48_000
+}
+
+fn default_hop_length() -> usize {
+    480
+}
+
+fn default_max_length_s() -> usize {
+    10
+}
+
+fn default_fft_window_size() -> usize {
+    1024
+}
+
+fn default_frequency_max() -> f64 {
+    14_000.0
+}
+
+fn default_truncation() -> String {
+    "fusion".to_string()
+}
+
+/// The `preprocessor_config.json` of a CLAP checkpoint.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClapFeatureExtractorConfig {
+    /// Number of mel bands.
+    #[serde(default = "default_feature_size")]
+    pub feature_size: usize,
+    #[serde(default = "default_sampling_rate")]
+    pub sampling_rate: u32,
+    #[serde(default = "default_hop_length")]
+    pub hop_length: usize,
+    /// Length of the windows in seconds.
+    #[serde(default = "default_max_length_s")]
+    pub max_length_s: usize,
+    #[serde(default = "default_fft_window_size")]
+    pub fft_window_size: usize,
+    #[serde(default)]
+    pub frequency_min: f64,
+    #[serde(default = "default_frequency_max")]
+    pub frequency_max: f64,
+    /// Fused checkpoints use unnormalized HTK mel filters, the others Slaney filters.
+    #[serde(default = "default_truncation")]
+    pub truncation: String,
+}
+
+impl Default for ClapFeatureExtractorConfig {
+    fn default() -> Self {
+        serde_json::from_str("{}").unwrap()
+    }
+}
+
+/// Computes the log-mel input of the CLAP audio encoder.
+#[derive(Debug, Clone)]
+pub struct ClapFeatureExtractor {
+    pub config: ClapFeatureExtractorConfig,
+    /// Mel filters, `fft_window_size / 2 + 1` rows of `feature_size` weights.
+    filters: Vec<Vec<f32>>,
+    window: Vec<f32>,
+}
+
+impl ClapFeatureExtractor {
+    pub fn new(config: ClapFeatureExtractorConfig) -> Self {
+        let htk = config.truncation == "fusion";
+        let filters = mel_filter_bank(
+            config.fft_window_size / 2 + 1,
+            config.feature_size,
+            config.frequency_min,
+            config.frequency_max,
+            config.sampling_rate,
+            htk,
+        );
+        let n = config.fft_window_size;
+        let window = (0..n)
+            .map(|i| (0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / n as f64).cos()) as f32)
+            .collect();
+        Self {
+            config,
+            filters,
+            window,
+        }
+    }
+
+    /// Number of samples in a window.
+    pub fn window_samples(&self) -> usize {
+        self.config.sampling_rate as usize * self.config.max_length_s
+    }
+
+    /// Number of spectrogram frames of a window.
+    pub fn frames(&self) -> usize {
+        self.window_samples() / self.config.hop_length + 1
+    }
+
+    /// Returns the log-mel spectrogram of a window of at most [Self::window_samples] samples as
+    /// `frames` rows of `feature_size` bands. Shorter windows are repeated and then zero padded.
+    pub fn log_mel(&self, samples: &[f32]) -> Vec<f32> {
+        let length = self.window_samples();
+        let mut waveform = Vec::with_capacity(length);
+        if !samples.is_empty() {
+            let repeats = (length / samples.len()).max(1);
+            for _ in 0..repeats {
+                waveform.extend_from_slice(samples);
+            }
+        }
+        waveform.resize(length, 0.0);
+
+        let n_fft = self.config.fft_window_size;
+        let padded = reflect_pad(&waveform, n_fft / 2);
+        let mut mel = Vec::with_capacity(self.frames() * self.config.feature_size);
+        for start in (0..=padded.len() - n_fft).step_by(self.config.hop_length) {
+            let frame = padded[start..start + n_fft]
+                .iter()
+                .zip(&self.window)
+                .map(|(sample, weight)| sample * weight)
+                .collect::<Vec<_>>();
+            let power = power_spectrum(&frame);
+            for band in 0..self.config.feature_size {
+                let energy = power
+                    .iter()
+                    .zip(&self.filters)
+                    .map(|(p, filters)| p * filters[band])
+                    .sum::<f32>();
+                mel.push(10.0 * energy.max(1e-10).log10());
+            }
+        }
+        mel
+    }
+}
+
+/// Pads a signal by mirroring `pad` samples at both ends, excluding the edge samples.
+fn reflect_pad(samples: &[f32], pad: usize) -> Vec<f32> {
+    let len = samples.len() as isize;
+    (-(pad as isize)..len + pad as isize)
+        .map(|i| {
+            let mut i = i.abs();
+            if i >= len {
+                i = 2 * (len - 1) - i;
+            }
+            samples[i.clamp(0, len - 1) as usize]
+        })
+        .collect()
+}
+
+/// Returns the power of the first `n / 2 + 1` frequency bins of a frame whose length is a power
+/// of two.
+fn power_spectrum(frame: &[f32]) -> Vec<f32> {
+    let n = frame.len();
+    let mut re = frame.iter().map(|&x| x as f64).collect::<Vec<_>>();
+    let mut im = vec![0f64; n];
+
+    // Iterative radix-2 Cooley-Tukey FFT.
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * std::f64::consts::PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+
+    (0..=n / 2)
+        .map(|k| (re[k] * re[k] + im[k] * im[k]) as f32)
+        .collect()
+}
+
+fn hertz_to_mel(freq: f64, htk: bool) -> f64 {
+    if htk {
+        return 2595.0 * (1.0 + freq / 700.0).log10();
+    }
+    let logstep = 27.0 / 6.4f64.ln();
+    match freq >= 1000.0 {
+        true => 15.0 + (freq / 1000.0).ln() * logstep,
+        false => 3.0 * freq / 200.0,
+    }
+}
+
+fn mel_to_hertz(mel: f64, htk: bool) -> f64 {
+    if htk {
+        return 700.0 * (10f64.powf(mel / 2595.0) - 1.0);
+    }
+    let logstep = 6.4f64.ln() / 27.0;
+    match mel >= 15.0 {
+        true => 1000.0 * (logstep * (mel - 15.0)).exp(),
+        false => 200.0 * mel / 3.0,
+    }
+}
+
+/// Builds triangular mel filters, one row of `num_mel_filters` weights per frequency bin. HTK
+/// filters are unnormalized, Slaney filters are normalized to constant energy per band.
+fn mel_filter_bank(
+    num_frequency_bins: usize,
+    num_mel_filters: usize,
+    min_frequency: f64,
+    max_frequency: f64,
+    sampling_rate: u32,
+    htk: bool,
+) -> Vec<Vec<f32>> {
+    let (mel_min, mel_max) = (
+        hertz_to_mel(min_frequency, htk),
+        hertz_to_mel(max_frequency, htk),
+    );
+    let filter_freqs = (0..num_mel_filters + 2)
+        .map(|i| {
+            let mel = mel_min + (mel_max - mel_min) * i as f64 / (num_mel_filters + 1) as f64;
+            mel_to_hertz(mel, htk)
+        })
+        .collect::<Vec<_>>();
+    let nyquist = (sampling_rate / 2) as f64;
+
+    (0..num_frequency_bins)
+        .map(|bin| {
+            let freq = nyquist * bin as f64 / (num_frequency_bins - 1) as f64;
+            (0..num_mel_filters)
+                .map(|m| {
+                    let (lower, center, upper) =
+                        (filter_freqs[m], filter_freqs[m + 1], filter_freqs[m + 2]);
+                    let down = (freq - lower) / (center - lower);
+                    let up = (upper - freq) / (upper - center);
+                    let weight = down.min(up).max(0.0);
+                    let norm = match htk {
+                        true => 1.0,
+                        false => 2.0 / (upper - lower),
+                    };
+                    (weight * norm) as f32
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mel_scale_roundtrip() {
+        for htk in [true, false] {
+            for freq in [0.0, 50.0, 999.0, 1000.0, 14_000.0] {
+                assert!((mel_to_hertz(hertz_to_mel(freq, htk), htk) - freq).abs() < 1e-6);
+            }
+        }
+        assert!((hertz_to_mel(1000.0, false) - 15.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_power_spectrum() {
+        // A cosine at bin 4 of a 64 point frame concentrates its power in that bin.
+        let frame = (0..64)
+            .map(|i| (2.0 * std::f32::consts::PI * 4.0 * i as f32 / 64.0).cos())
+            .collect::<Vec<_>>();
+        let power = power_spectrum(&frame);
+        assert_eq!(power.len(), 33);
+        assert!((power[4] - 32.0 * 32.0).abs() < 1e-2);
+        assert!(power.iter().enumerate().all(|(k, p)| k == 4 || *p < 1e-6));
+    }
+
+    #[test]
+    fn test_log_mel() {
+        let extractor = ClapFeatureExtractor::new(ClapFeatureExtractorConfig::default());
+        assert_eq!(extractor.frames(), 1001);
+
+        // A one second tone is repeated over the whole window.
+        let tone = (0..48_000)
+            .map(|i| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 48_000.0).sin())
+            .collect::<Vec<_>>();
+        let mel = extractor.log_mel(&tone);
+        assert_eq!(mel.len(), 1001 * 64);
+        let frame = &mel[500 * 64..501 * 64];
+        let loudest = (0..64)
+            .max_by(|&a, &b| frame[a].total_cmp(&frame[b]))
+            .unwrap();
+        let center = mel_to_hertz(
+            hertz_to_mel(0.0, true)
+                + (hertz_to_mel(14_000.0, true) - hertz_to_mel(0.0, true)) * (loudest + 1) as f64
+                    / 65.0,
+            true,
+        );
+        assert!((center - 1000.0).abs() < 150.0, "{center}");
+
+        // Silence is clamped to -100 dB.
+        assert!(extractor.log_mel(&[]).iter().all(|&db| db == -100.0));
+    }
+
+    /// Compares the features with those of the `ClapFeatureExtractor` of transformers, which
+    /// `test_files/audio/clap_log_mel.py` writes to `clap_log_mel.json`.
+    #[cfg(feature = "audio")]
+    #[test]
+    fn test_log_mel_parity_with_transformers() {
+        use crate::file_processor::audio::{
+            pcm_decode::audio_processing::pcm_decode, resample::resample,
+        };
+
+        let fixture: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string("../test_files/audio/clap_log_mel.json").unwrap(),
+        )
+        .unwrap();
+        let config: ClapFeatureExtractorConfig =
+            serde_json::from_value(fixture["preprocessor_config"].clone()).unwrap();
+        let extractor = ClapFeatureExtractor::new(config);
+        let bands = extractor.config.feature_size;
+
+        let audio = format!("../test_files/audio/{}", fixture["audio"].as_str().unwrap());
+        let (samples, sample_rate) = pcm_decode(audio).unwrap();
+        let seconds = fixture["seconds"].as_u64().unwrap() as usize;
+        let window = resample(
+            &samples[..sample_rate as usize * seconds],
+            sample_rate,
+            extractor.config.sampling_rate,
+        );
+        let mel = extractor.log_mel(&window);
+        assert_eq!(mel.len(), extractor.frames() * bands);
+
+        let frames: Vec<usize> = serde_json::from_value(fixture["frames"].clone()).unwrap();
+        let expected: Vec<Vec<f32>> = serde_json::from_value(fixture["log_mel"].clone()).unwrap();
+        for (frame, expected) in frames.iter().zip(&expected) {
+            let actual = &mel[frame * bands..(frame + 1) * bands];
+            for (band, (actual, expected)) in actual.iter().zip(expected).enumerate() {
+                // The features are computed in float32 rather than float64, which only shows in
+                // the bands close to silence.
+                let tolerance = if *expected < -80.0 { 0.05 } else { 5e-3 };
+                assert!(
+                    (actual - expected).abs() < tolerance,
+                    "frame {frame} band {band}: {actual} dB instead of {expected} dB"
+                );
+            }
+        }
+    }
+}
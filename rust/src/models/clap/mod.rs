@@ -0,0 +1,154 @@
+//! Contrastive Language-Audio Pretraining
+//!
+//! CLAP learns a joint embedding space for sounds and their descriptions, so that a text such as
+//! "glass breaking" retrieves matching clips. Audio is encoded by an HTSAT Swin Transformer over
+//! log-mel spectrograms, text by a RoBERTa encoder, and both are projected to the same space.
+//!
+//! <https://github.com/LAION-AI/CLAP>
+//! <https://github.com/huggingface/transformers/tree/main/src/transformers/models/clap>
+use self::{
+    audio_model::{ClapAudioConfig, ClapAudioModel},
+    text_model::{ClapTextConfig, ClapTextModel},
+};
+use candle_core::{Module, Result, Tensor};
+use candle_nn::{linear, Linear, VarBuilder};
+use serde::Deserialize;
+
+pub mod audio_model;
+pub mod feature_extractor;
+pub mod text_model;
+
+fn default_projection_dim() -> usize {
+    512
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClapConfig {
+    #[serde(default)]
+    pub text_config: ClapTextConfig,
+    #[serde(default)]
+    pub audio_config: ClapAudioConfig,
+    #[serde(default = "default_projection_dim")]
+    pub projection_dim: usize,
+}
+
+/// Two linear layers with a ReLU in between, mapping an encoder output to the joint space.
+#[derive(Clone)]
+struct ClapProjection {
+    linear1: Linear,
+    linear2: Linear,
+}
+
+impl ClapProjection {
+    fn new(vb: VarBuilder, in_dim: usize, projection_dim: usize) -> Result<Self> {
+        Ok(Self {
+            linear1: linear(in_dim, projection_dim, vb.pp("linear1"))?,
+            linear2: linear(projection_dim, projection_dim, vb.pp("linear2"))?,
+        })
+    }
+}
+
+impl Module for ClapProjection {
+    fn forward(&self, xs: &Tensor) -> Result<Tensor> {
+        self.linear2.forward(&self.linear1.forward(xs)?.relu()?)
+    }
+}
+
+/// Cloning the model shares its weights.
+#[derive(Clone)]
+pub struct ClapModel {
+    text_model: ClapTextModel,
+    audio_model: ClapAudioModel,
+    text_projection: ClapProjection,
+    audio_projection: ClapProjection,
+}
+
+impl ClapModel {
+    pub fn new(vb: VarBuilder, config: &ClapConfig) -> Result<Self> {
+        let text_model = ClapTextModel::new(vb.pp("text_model"), &config.text_config)?;
+        let audio_model =
+            ClapAudioModel::new(vb.pp("audio_model.audio_encoder"), &config.audio_config)?;
+        let text_projection = ClapProjection::new(
+            vb.pp("text_projection"),
+            config.text_config.hidden_size,
+            config.projection_dim,
+        )?;
+        let audio_projection = ClapProjection::new(
+            vb.pp("audio_projection"),
+            config.audio_config.hidden_size,
+            config.projection_dim,
+        )?;
+        Ok(Self {
+            text_model,
+            audio_model,
+            text_projection,
+            audio_projection,
+        })
+    }
+
+    /// Returns the unnormalized embeddings of a batch of token ids and their attention mask.
+    pub fn get_text_features(&self, input_ids: &Tensor, attention_mask: &Tensor) -> Result<Tensor> {
+        let pooled = self.text_model.forward(input_ids, attention_mask)?;
+        self.text_projection.forward(&pooled)
+    }
+
+    /// Returns the unnormalized embeddings of a batch of `(B, frames, mel_bins)` log-mel
+    /// features, as computed by [feature_extractor::ClapFeatureExtractor].
+    pub fn get_audio_features(&self, input_features: &Tensor) -> Result<Tensor> {
+        let pooled = self.audio_model.forward(input_features)?;
+        self.audio_projection.forward(&pooled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::{DType, Device};
+
+    #[test]
+    fn test_forward_shapes() {
+        // A small model with every weight set to zero, which runs both towers end to end.
+        let config: ClapConfig = serde_json::from_value(serde_json::json!({
+            "text_config": {
+                "vocab_size": 32,
+                "hidden_size": 16,
+                "num_hidden_layers": 1,
+                "num_attention_heads": 2,
+                "intermediate_size": 32,
+                "max_position_embeddings": 16,
+            },
+            "audio_config": {
+                "window_size": 2,
+                "num_mel_bins": 8,
+                "spec_size": 16,
+                "patch_size": 2,
+                "patch_embeds_hidden_size": 8,
+                "depths": [2, 1],
+                "num_attention_heads": [1, 2],
+                "hidden_size": 16,
+            },
+            "projection_dim": 4,
+        }))
+        .unwrap();
+        let device = Device::Cpu;
+        let model = ClapModel::new(VarBuilder::zeros(DType::F32, &device), &config).unwrap();
+
+        let input_ids = Tensor::new(&[[0u32, 5, 6, 2], [0, 7, 2, 1]], &device).unwrap();
+        let attention_mask = Tensor::new(&[[1u32, 1, 1, 1], [1, 1, 1, 0]], &device).unwrap();
+        let text = model
+            .get_text_features(&input_ids, &attention_mask)
+            .unwrap();
+        assert_eq!(text.dims(), &[2, 4]);
+
+        let features = Tensor::zeros((3, 21, 8), DType::F32, &device).unwrap();
+        let audio = model.get_audio_features(&features).unwrap();
+        assert_eq!(audio.dims(), &[3, 4]);
+        assert!(audio
+            .flatten_all()
+            .unwrap()
+            .to_vec1::<f32>()
+            .unwrap()
+            .iter()
+            .all(|x| x.is_finite()));
+    }
+}
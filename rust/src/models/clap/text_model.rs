@@ -0,0 +1,131 @@
+//! The RoBERTa text encoder of CLAP.
+//!
+//! The encoder is the XLM-RoBERTa model of candle-transformers, followed by the pooler of
+//! `ClapTextModel`, a dense layer with a tanh activation on the first token.
+
+use candle_core::{IndexOp, Module, Result, Tensor};
+use candle_nn::{linear, Activation, Linear, VarBuilder};
+use candle_transformers::models::xlm_roberta::{Config, XLMRobertaModel};
+use serde::Deserialize;
+use std::sync::Arc;
+
+fn default_vocab_size() -> usize {
+    50265
+}
+
+fn default_hidden_size() -> usize {
+    768
+}
+
+fn default_num_hidden_layers() -> usize {
+    12
+}
+
+fn default_num_attention_heads() -> usize {
+    12
+}
+
+fn default_intermediate_size() -> usize {
+    3072
+}
+
+fn default_hidden_act() -> Activation {
+    Activation::Gelu
+}
+
+fn default_max_position_embeddings() -> usize {
+    514
+}
+
+fn default_type_vocab_size() -> usize {
+    1
+}
+
+fn default_layer_norm_eps() -> f64 {
+    1e-12
+}
+
+fn default_pad_token_id() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClapTextConfig {
+    #[serde(default = "default_vocab_size")]
+    pub vocab_size: usize,
+    #[serde(default = "default_hidden_size")]
+    pub hidden_size: usize,
+    #[serde(default = "default_num_hidden_layers")]
+    pub num_hidden_layers: usize,
+    #[serde(default = "default_num_attention_heads")]
+    pub num_attention_heads: usize,
+    #[serde(default = "default_intermediate_size")]
+    pub intermediate_size: usize,
+    #[serde(default = "default_hidden_act")]
+    pub hidden_act: Activation,
+    #[serde(default = "default_max_position_embeddings")]
+    pub max_position_embeddings: usize,
+    #[serde(default = "default_type_vocab_size")]
+    pub type_vocab_size: usize,
+    #[serde(default = "default_layer_norm_eps")]
+    pub layer_norm_eps: f64,
+    #[serde(default = "default_pad_token_id")]
+    pub pad_token_id: u32,
+}
+
+impl Default for ClapTextConfig {
+    fn default() -> Self {
+        serde_json::from_str("{}").unwrap()
+    }
+}
+
+impl From<&ClapTextConfig> for Config {
+    fn from(config: &ClapTextConfig) -> Self {
+        Config {
+            hidden_size: config.hidden_size,
+            layer_norm_eps: config.layer_norm_eps,
+            attention_probs_dropout_prob: 0.0,
+            hidden_dropout_prob: 0.0,
+            num_attention_heads: config.num_attention_heads,
+            position_embedding_type: "absolute".to_string(),
+            intermediate_size: config.intermediate_size,
+            hidden_act: config.hidden_act,
+            num_hidden_layers: config.num_hidden_layers,
+            vocab_size: config.vocab_size,
+            max_position_embeddings: config.max_position_embeddings,
+            type_vocab_size: config.type_vocab_size,
+            pad_token_id: config.pad_token_id,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ClapTextModel {
+    // The encoder cannot be cloned, so it is shared.
+    encoder: Arc<XLMRobertaModel>,
+    pooler: Linear,
+}
+
+impl ClapTextModel {
+    pub fn new(vb: VarBuilder, config: &ClapTextConfig) -> Result<Self> {
+        let encoder = XLMRobertaModel::new(&config.into(), vb.clone())?;
+        let pooler = linear(
+            config.hidden_size,
+            config.hidden_size,
+            vb.pp("pooler.dense"),
+        )?;
+        Ok(Self {
+            encoder: Arc::new(encoder),
+            pooler,
+        })
+    }
+
+    /// Returns the pooled output of a batch of token ids and their attention mask.
+    pub fn forward(&self, input_ids: &Tensor, attention_mask: &Tensor) -> Result<Tensor> {
+        let token_type_ids = input_ids.zeros_like()?;
+        let hidden_states =
+            self.encoder
+                .forward(input_ids, attention_mask, &token_type_ids, None, None, None)?;
+        self.pooler.forward(&hidden_states.i((.., 0))?)?.tanh()
+    }
+}
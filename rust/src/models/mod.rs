@@ -17,6 +17,7 @@
 //! ```
 
 pub mod bert;
+pub mod clap;
 pub mod clip;
 pub mod colpali;
 pub mod dinov2;
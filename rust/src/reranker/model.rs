@@ -10,6 +10,7 @@ use ort::{
 };
 use tokenizers::{PaddingParams, Tokenizer, TruncationParams};
 
+use crate::embeddings::embed::EmbedData;
 use crate::Dtype;
 use crate::{embeddings::local::bert::TokenizerConfig, reranker::qwen3};
 use serde::Serialize;
@@ -27,6 +28,14 @@ pub struct DocumentRank {
     pub rank: usize,
 }
 
+/// A reranked `EmbedData` together with its relevance score and rank (starting at 1).
+#[derive(Debug, Clone)]
+pub struct EmbedDataRank {
+    pub embed_data: EmbedData,
+    pub relevance_score: f32,
+    pub rank: usize,
+}
+
 pub struct Reranker {
     model: RwLock<Session>,
     model_type: Option<String>,
@@ -151,6 +160,33 @@ impl Reranker {
         documents: Vec<&str>,
         batch_size: usize,
     ) -> Result<Vec<Vec<f32>>, E> {
+        if documents.is_empty() {
+            return Ok(vec![Vec::new(); queries.len()]);
+        }
+
+        let pairs = queries
+            .iter()
+            .flat_map(|query| documents.iter().map(move |doc| (*query, *doc)))
+            .collect::<Vec<_>>();
+
+        let scores = self.compute_pair_scores(&pairs, batch_size)?;
+
+        Ok(scores
+            .chunks(documents.len())
+            .map(|row| row.to_vec())
+            .collect::<Vec<_>>())
+    }
+
+    /// Scores each `(query, document)` pair independently and returns one relevance score per pair,
+    /// in the same order as `pairs`.
+    pub fn compute_pair_scores(
+        &self,
+        pairs: &[(&str, &str)],
+        batch_size: usize,
+    ) -> Result<Vec<f32>, E> {
+        if pairs.is_empty() {
+            return Ok(Vec::new());
+        }
 
         // Check model type once at the beginning
         let is_qwen3 = self.model_type.as_ref().is_some_and(|t| t == "qwen3");
@@ -158,11 +194,6 @@ impl Reranker {
         let prefix = "<|im_start|>system\nJudge whether the Document meets the requirements based on the Query and the Instruct provided. Note that the answer can only be \"yes\" or \"no\".<|im_end|>\n<|im_start|>user\n";
         let suffix = "<|im_end|>\n<|im_start|>assistant\n<think>\n\n</think>\n\n";
 
-        let pairs = queries
-            .iter()
-            .flat_map(|query| documents.iter().map(move |doc| (*query, *doc)))
-            .collect::<Vec<_>>();
-
         let pairs = if is_qwen3 {
             pairs.iter().map(|(query, doc)| (format!("{}{}", prefix, query), format!("{}{}", doc, suffix))).collect::<Vec<_>>()
         } else {
@@ -232,17 +263,13 @@ impl Reranker {
             }
         }
 
-        let scores_tensor = Tensor::from_vec(
-            scores.clone(),
-            (queries.len(), documents.len()),
-            &Device::Cpu,
-        )?;
+        let scores_tensor = Tensor::from_vec(scores, pairs.len(), &Device::Cpu)?;
 
         if is_qwen3 {
-            Ok(scores_tensor.to_vec2::<f32>()?)
+            Ok(scores_tensor.to_vec1::<f32>()?)
         } else {
             let sigmoid_scores = candle_nn::ops::sigmoid(&scores_tensor)?;
-            Ok(sigmoid_scores.to_vec2::<f32>()?)
+            Ok(sigmoid_scores.to_vec1::<f32>()?)
         }
     }
 
@@ -280,6 +307,61 @@ impl Reranker {
         Ok(reranker_results)
     }
 
+    /// Reranks retrieved `EmbedData` candidates for a single query.
+    ///
+    /// The candidates are scored against `query` using their `text` field and returned sorted by
+    /// relevance, with their original embedding and metadata preserved. Candidates scoring below
+    /// `min_score` are dropped and at most `top_k` results are returned.
+    pub fn rerank_embed_data(
+        &self,
+        query: &str,
+        documents: Vec<EmbedData>,
+        top_k: Option<usize>,
+        min_score: Option<f32>,
+        batch_size: usize,
+    ) -> Result<Vec<EmbedDataRank>, E> {
+        Ok(self
+            .rerank_embed_data_batch(vec![(query, documents)], top_k, min_score, batch_size)?
+            .pop()
+            .unwrap_or_default())
+    }
+
+    /// Reranks a separate candidate list for each query.
+    ///
+    /// Only the `(query, candidate)` pairs that are passed in are scored, so each query pays for
+    /// its own candidates rather than for every document in the batch. All pairs are scored
+    /// together in batches of `batch_size`. The results are returned in the same order as
+    /// `candidates`.
+    pub fn rerank_embed_data_batch(
+        &self,
+        candidates: Vec<(&str, Vec<EmbedData>)>,
+        top_k: Option<usize>,
+        min_score: Option<f32>,
+        batch_size: usize,
+    ) -> Result<Vec<Vec<EmbedDataRank>>, E> {
+        let mut pairs = Vec::new();
+        for (query, documents) in candidates.iter() {
+            for document in documents {
+                let text = document
+                    .text
+                    .as_deref()
+                    .ok_or(E::msg("EmbedData has no text to rerank"))?;
+                pairs.push((*query, text));
+            }
+        }
+
+        let scores = self.compute_pair_scores(&pairs, batch_size)?;
+
+        let mut offset = 0;
+        let mut results = Vec::with_capacity(candidates.len());
+        for (_, documents) in candidates {
+            let query_scores = &scores[offset..offset + documents.len()];
+            offset += documents.len();
+            results.push(rank_embed_data(documents, query_scores, top_k, min_score));
+        }
+        Ok(results)
+    }
+
     pub fn tokenize_batch_ndarray(&self, pairs: &[(String, String)]) -> anyhow::Result<Array2<i64>> {
         let token_ids = self
             .tokenizer
@@ -329,3 +411,76 @@ impl Reranker {
         Ok(attention_mask_array)
     }
 }
+
+/// Sorts `documents` by descending score, drops those below `min_score` and keeps at most `top_k`.
+fn rank_embed_data(
+    documents: Vec<EmbedData>,
+    scores: &[f32],
+    top_k: Option<usize>,
+    min_score: Option<f32>,
+) -> Vec<EmbedDataRank> {
+    let mut ranked = documents
+        .into_iter()
+        .zip(scores.iter().copied())
+        .collect::<Vec<_>>();
+    ranked.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+
+    ranked
+        .into_iter()
+        .filter(|(_, score)| min_score.is_none_or(|min_score| *score >= min_score))
+        .take(top_k.unwrap_or(usize::MAX))
+        .enumerate()
+        .map(|(i, (embed_data, relevance_score))| EmbedDataRank {
+            embed_data,
+            relevance_score,
+            rank: i + 1,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::embed::EmbeddingResult;
+    use std::collections::HashMap;
+
+    fn embed_data(text: &str) -> EmbedData {
+        let mut metadata = HashMap::new();
+        metadata.insert("file_name".to_string(), format!("{text}.txt"));
+        EmbedData::new(
+            EmbeddingResult::DenseVector(vec![0.0; 4]),
+            Some(text.to_string()),
+            Some(metadata),
+        )
+    }
+
+    #[test]
+    fn test_rank_embed_data() {
+        let documents = vec![embed_data("a"), embed_data("b"), embed_data("c")];
+        let ranked = rank_embed_data(documents, &[0.2, 0.9, 0.5], None, None);
+
+        let texts = ranked
+            .iter()
+            .map(|r| r.embed_data.text.clone().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["b", "c", "a"]);
+        assert_eq!(ranked[0].rank, 1);
+        assert_eq!(ranked[0].relevance_score, 0.9);
+        assert_eq!(
+            ranked[0].embed_data.metadata.as_ref().unwrap()["file_name"],
+            "b.txt"
+        );
+    }
+
+    #[test]
+    fn test_rank_embed_data_top_k_and_min_score() {
+        let documents = vec![embed_data("a"), embed_data("b"), embed_data("c")];
+        let ranked = rank_embed_data(documents.clone(), &[0.2, 0.9, 0.5], Some(1), None);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].embed_data.text.as_deref(), Some("b"));
+
+        let ranked = rank_embed_data(documents, &[0.2, 0.9, 0.5], None, Some(0.4));
+        assert_eq!(ranked.len(), 2);
+        assert!(ranked.iter().all(|r| r.relevance_score >= 0.4));
+    }
+}
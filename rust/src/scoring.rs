@@ -0,0 +1,236 @@
+//! Similarity scoring for dense and multi-vector embeddings.
+//!
+//! Provides cosine, dot-product and L2 helpers for dense vectors, and MaxSim late-interaction
+//! scoring for the multi-vector embeddings produced by ColBERT, ColPali and ColSmol models.
+//!
+//! # Example
+//!
+//! ```rust
+//! use embed_anything::scoring::maxsim;
+//!
+//! let query = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+//! let document = vec![vec![1.0, 0.0], vec![0.6, 0.8]];
+//! assert!((maxsim(&query, &document) - 1.8).abs() < 1e-6);
+//! ```
+
+use std::borrow::Cow;
+
+use anyhow::anyhow;
+use rayon::prelude::*;
+
+use crate::embeddings::embed::{EmbedData, EmbeddingResult};
+
+/// Number of independent accumulators used in [dot_product]. Splitting the sum lets the compiler
+/// vectorize the loop.
+const LANES: usize = 8;
+
+/// Computes the dot product of two vectors of equal length.
+///
+/// # Panics
+///
+/// Panics if the vectors differ in length.
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len(), "vectors of different lengths");
+    let mut acc = [0.0f32; LANES];
+    let a_chunks = a.chunks_exact(LANES);
+    let b_chunks = b.chunks_exact(LANES);
+    let tail = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(x, y)| x * y)
+        .sum::<f32>();
+    for (x, y) in a_chunks.zip(b_chunks) {
+        for ((acc, x), y) in acc.iter_mut().zip(x).zip(y) {
+            *acc += x * y;
+        }
+    }
+    acc.iter().sum::<f32>() + tail
+}
+
+/// Computes the cosine similarity of two vectors. Returns 0 if either vector has zero norm.
+///
+/// # Panics
+///
+/// Panics if the vectors differ in length.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let norm = (dot_product(a, a) * dot_product(b, b)).sqrt();
+    if norm == 0.0 {
+        0.0
+    } else {
+        dot_product(a, b) / norm
+    }
+}
+
+/// Computes the Euclidean (L2) distance between two vectors.
+///
+/// # Panics
+///
+/// Panics if the vectors differ in length.
+pub fn l2_distance(a: &[f32], b: &[f32]) -> f32 {
+    assert_eq!(a.len(), b.len(), "vectors of different lengths");
+    a.iter()
+        .zip(b)
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Computes the MaxSim late-interaction score between a query and a document.
+///
+/// For every query vector the highest dot product with any document vector is taken, and these
+/// maxima are summed. The vectors are expected to be normalized, as returned by the ColBERT and
+/// ColPali embedders.
+///
+/// # Panics
+///
+/// Panics if the query and document vectors differ in length.
+pub fn maxsim(query: &[Vec<f32>], document: &[Vec<f32>]) -> f32 {
+    query
+        .iter()
+        .map(|q| {
+            document
+                .iter()
+                .map(|d| dot_product(q, d))
+                .fold(f32::NEG_INFINITY, f32::max)
+        })
+        .filter(|score| score.is_finite())
+        .sum()
+}
+
+/// Scores a multi-vector query against every document with [maxsim] and returns the `top_k`
+/// best matches as `(document index, score)` pairs, sorted by descending score.
+///
+/// Documents are scored in parallel. Compressed documents are decompressed before scoring.
+/// Returns an error if any document does not hold a multi-vector embedding, or if its vectors
+/// differ in length from those of the query.
+pub fn maxsim_search(
+    query: &[Vec<f32>],
+    documents: &[EmbedData],
+    top_k: usize,
+) -> anyhow::Result<Vec<(usize, f32)>> {
+    let documents = multi_vectors(documents)?;
+    search(query, &documents, top_k)
+}
+
+/// Runs [maxsim_search] for a batch of queries. Results are returned in the order of `queries`.
+///
+/// Compressed documents are decompressed once for the whole batch.
+pub fn maxsim_search_batch(
+    queries: &[EmbedData],
+    documents: &[EmbedData],
+    top_k: usize,
+) -> anyhow::Result<Vec<Vec<(usize, f32)>>> {
+    let documents = multi_vectors(documents)?;
+    queries
+        .iter()
+        .map(|query| search(&multi_vector(&query.embedding)?, &documents, top_k))
+        .collect()
+}
+
+fn search(
+    query: &[Vec<f32>],
+    documents: &[Cow<'_, [Vec<f32>]>],
+    top_k: usize,
+) -> anyhow::Result<Vec<(usize, f32)>> {
+    if let Some(dimension) = query.first().map(Vec::len) {
+        let mismatch = query
+            .iter()
+            .chain(documents.iter().flat_map(|document| document.iter()))
+            .find(|vector| vector.len() != dimension);
+        if let Some(vector) = mismatch {
+            return Err(anyhow!(
+                "MaxSim scoring requires vectors of the same length, got {} and {}",
+                dimension,
+                vector.len()
+            ));
+        }
+    }
+
+    let mut scores = documents
+        .par_iter()
+        .enumerate()
+        .map(|(index, document)| (index, maxsim(query, document)))
+        .collect::<Vec<_>>();
+
+    scores.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    scores.truncate(top_k);
+    Ok(scores)
+}
+
+fn multi_vectors(documents: &[EmbedData]) -> anyhow::Result<Vec<Cow<'_, [Vec<f32>]>>> {
+    documents
+        .iter()
+        .map(|document| multi_vector(&document.embedding))
+        .collect()
+}
+
+fn multi_vector(embedding: &EmbeddingResult) -> anyhow::Result<Cow<'_, [Vec<f32>]>> {
+    match embedding {
+        EmbeddingResult::MultiVector(vectors) => Ok(Cow::Borrowed(vectors)),
+        EmbeddingResult::CompressedMultiVector(vectors) => Ok(Cow::Owned(vectors.decompress())),
+        EmbeddingResult::DenseVector(_) => Err(anyhow!(
+            "MaxSim scoring requires multi-vector embeddings"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dense_metrics() {
+        let a = (0..19).map(|i| i as f32).collect::<Vec<_>>();
+        let b = (0..19).map(|i| (19 - i) as f32).collect::<Vec<_>>();
+        let expected = a.iter().zip(&b).map(|(x, y)| x * y).sum::<f32>();
+        assert!((dot_product(&a, &b) - expected).abs() < 1e-3);
+
+        assert!((cosine_similarity(&a, &a) - 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&a, &[0.0; 19]), 0.0);
+        assert!((l2_distance(&[0.0, 3.0], &[4.0, 0.0]) - 5.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_maxsim_search() {
+        let query = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        let documents = vec![
+            EmbedData::new(vec![vec![1.0, 0.0]].into(), None, None),
+            EmbedData::new(vec![vec![1.0, 0.0], vec![0.0, 1.0]].into(), None, None),
+            EmbedData::new(vec![vec![-1.0, 0.0]].into(), None, None),
+        ];
+
+        let results = maxsim_search(&query, &documents, 2).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, 1);
+        assert!((results[0].1 - 2.0).abs() < 1e-6);
+        assert_eq!(results[1].0, 0);
+
+        let dense = vec![EmbedData::new(vec![1.0, 0.0].into(), None, None)];
+        assert!(maxsim_search(&query, &dense, 1).is_err());
+
+        let wider = vec![EmbedData::new(vec![vec![1.0, 0.0, 0.0]].into(), None, None)];
+        let error = maxsim_search(&query, &wider, 1).unwrap_err();
+        assert!(error.to_string().contains("same length"));
+    }
+
+    #[test]
+    #[should_panic(expected = "vectors of different lengths")]
+    fn test_dot_product_length_mismatch() {
+        dot_product(&[1.0, 2.0], &[1.0]);
+    }
+
+    #[test]
+    fn test_maxsim_search_batch() {
+        let documents = vec![
+            EmbedData::new(vec![vec![1.0, 0.0]].into(), None, None),
+            EmbedData::new(vec![vec![0.0, 1.0]].into(), None, None),
+        ];
+        let queries = vec![
+            EmbedData::new(vec![vec![0.0, 1.0]].into(), None, None),
+            EmbedData::new(vec![vec![1.0, 0.0]].into(), None, None),
+        ];
+        let results = maxsim_search_batch(&queries, &documents, 1).unwrap();
+        assert_eq!(results, [[(1, 1.0)], [(0, 1.0)]]);
+    }
+}
@@ -102,25 +102,32 @@ async fn create_embeddings(req: web::Json<OpenAIEmbedRequest>) -> HttpResponse {
     };
 
     // Convert to OpenAI format
-    let embedding_data: Vec<EmbeddingData> = embeddings
-        .into_iter()
-        .enumerate()
-        .map(|(index, embed_data)| {
-            let embedding_vector = match embed_data.embedding {
-                embed_anything::embeddings::embed::EmbeddingResult::DenseVector(vec) => vec,
-                embed_anything::embeddings::embed::EmbeddingResult::MultiVector(_) => {
-                    // For multi-vector embeddings, we'll flatten them (this might need adjustment based on requirements)
-                    vec![0.0] // Placeholder - you might want to handle this differently
-                }
-            };
-
-            EmbeddingData {
-                object: "embedding".to_string(),
-                index,
-                embedding: embedding_vector,
+    let mut embedding_data: Vec<EmbeddingData> = Vec::with_capacity(embeddings.len());
+    for (index, embed_data) in embeddings.into_iter().enumerate() {
+        let embedding_vector = match embed_data.embedding {
+            embed_anything::embeddings::embed::EmbeddingResult::DenseVector(vec) => vec,
+            embed_anything::embeddings::embed::EmbeddingResult::MultiVector(_) => {
+                // For multi-vector embeddings, we'll flatten them (this might need adjustment based on requirements)
+                vec![0.0] // Placeholder - you might want to handle this differently
             }
-        })
-        .collect();
+            // Compressed multi-vector embeddings have no OpenAI representation.
+            _ => {
+                return HttpResponse::BadRequest().json(ErrorResponse {
+                    error: ErrorDetail {
+                        message: "Compressed multi-vector embeddings are not supported".to_string(),
+                        error_type: "invalid_request_error".to_string(),
+                        code: Some("unsupported_embedding".to_string()),
+                    },
+                });
+            }
+        };
+
+        embedding_data.push(EmbeddingData {
+            object: "embedding".to_string(),
+            index,
+            embedding: embedding_vector,
+        });
+    }
 
     // Calculate usage (simplified - you might want to implement proper token counting)
     let total_tokens = req.input.iter().map(|s| s.split_whitespace().count()).sum();
@@ -0,0 +1,180 @@
+"""Writes clap_log_mel.json, the log-mel features that the `ClapFeatureExtractor` of
+transformers computes for the first 10 seconds of samples_jfk.wav. The feature extraction test
+in rust/src/models/clap/feature_extractor.rs compares against them.
+
+    python test_files/audio/clap_log_mel.py
+
+The script only needs the standard library. The recording is resampled from 16 to 48 kHz with
+the windowed-sinc filter of rust/src/file_processor/audio/resample.rs, so that the test compares
+the feature extraction alone. The features then follow `_np_extract_fbank_features` for the
+checkpoints without fusion: a centered, reflect-padded power spectrogram with a periodic Hann
+window, Slaney mel filters and decibels floored at 1e-10, computed in float64. Every 20th frame
+is kept to keep the fixture small.
+"""
+
+import cmath
+import json
+import math
+import struct
+import wave
+from pathlib import Path
+
+DIRECTORY = Path(__file__).parent
+AUDIO = "samples_jfk.wav"
+FRAME_STEP = 20
+
+# The preprocessor_config.json of laion/clap-htsat-unfused.
+CONFIG = {
+    "feature_size": 64,
+    "fft_window_size": 1024,
+    "frequency_max": 14000,
+    "frequency_min": 50,
+    "hop_length": 480,
+    "max_length_s": 10,
+    "sampling_rate": 48000,
+    "truncation": "rand_trunc",
+}
+
+# The constants of resample.rs.
+ZERO_CROSSINGS = 16
+ROLLOFF = 0.95
+KAISER_BETA = 8.6
+
+
+def f32(value):
+    return struct.unpack("f", struct.pack("f", value))[0]
+
+
+def bessel_i0(x):
+    total, term, half_x = 1.0, 1.0, x / 2.0
+    for k in range(1, 50):
+        term *= half_x / k
+        total += term * term
+        if term * term < total * 1e-12:
+            break
+    return total
+
+
+def kaiser(x):
+    if abs(x) >= 1.0:
+        return 0.0
+    return bessel_i0(KAISER_BETA * math.sqrt(1.0 - x * x)) / bessel_i0(KAISER_BETA)
+
+
+def sinc(x):
+    if abs(x) < 1e-9:
+        return 1.0
+    x = math.pi * x
+    return math.sin(x) / x
+
+
+def resample(samples, from_rate, to_rate):
+    divisor = math.gcd(from_rate, to_rate)
+    up, down = to_rate // divisor, from_rate // divisor
+    cutoff = min(up / down, 1.0) * ROLLOFF
+    half_width = math.ceil(ZERO_CROSSINGS / cutoff)
+    filters = [
+        [
+            f32(
+                cutoff
+                * sinc(cutoff * (tap - half_width - phase / up))
+                * kaiser((tap - half_width - phase / up) / (half_width + 1.0))
+            )
+            for tap in range(2 * half_width + 1)
+        ]
+        for phase in range(up)
+    ]
+    output = []
+    for n in range(-(-len(samples) * up // down)):
+        index, phase = divmod(n * down, up)
+        first = max(index - half_width, 0)
+        last = min(index + half_width, len(samples) - 1)
+        weights = filters[phase][first + half_width - index :]
+        output.append(sum(s * w for s, w in zip(samples[first : last + 1], weights)))
+    return output
+
+
+def fft(values):
+    n = len(values)
+    if n == 1:
+        return values
+    even, odd = fft(values[0::2]), fft(values[1::2])
+    twiddles = [cmath.exp(-2j * math.pi * k / n) * odd[k] for k in range(n // 2)]
+    return [even[k] + twiddles[k] for k in range(n // 2)] + [
+        even[k] - twiddles[k] for k in range(n // 2)
+    ]
+
+
+def hertz_to_mel(freq):
+    if freq >= 1000.0:
+        return 15.0 + math.log(freq / 1000.0) * (27.0 / math.log(6.4))
+    return 3.0 * freq / 200.0
+
+
+def mel_to_hertz(mel):
+    if mel >= 15.0:
+        return 1000.0 * math.exp(math.log(6.4) / 27.0 * (mel - 15.0))
+    return 200.0 * mel / 3.0
+
+
+def mel_filter_bank(bins, filters, min_frequency, max_frequency, sampling_rate):
+    """mel_filter_bank(norm="slaney", mel_scale="slaney"), one row of weights per filter."""
+    mel_min, mel_max = hertz_to_mel(min_frequency), hertz_to_mel(max_frequency)
+    freqs = [
+        mel_to_hertz(mel_min + (mel_max - mel_min) * i / (filters + 1)) for i in range(filters + 2)
+    ]
+    fft_freqs = [(sampling_rate // 2) * i / (bins - 1) for i in range(bins)]
+    bank = []
+    for m in range(filters):
+        lower, center, upper = freqs[m], freqs[m + 1], freqs[m + 2]
+        bank.append(
+            [
+                max(0.0, min((f - lower) / (center - lower), (upper - f) / (upper - center)))
+                * 2.0
+                / (upper - lower)
+                for f in fft_freqs
+            ]
+        )
+    return bank
+
+
+with wave.open(str(DIRECTORY / AUDIO)) as recording:
+    assert (recording.getnchannels(), recording.getsampwidth()) == (1, 2)
+    rate = recording.getframerate()
+    frames = recording.readframes(rate * CONFIG["max_length_s"])
+samples = [s / 32768.0 for s in struct.unpack("<%dh" % (len(frames) // 2), frames)]
+waveform = resample(samples, rate, CONFIG["sampling_rate"])
+assert len(waveform) == CONFIG["sampling_rate"] * CONFIG["max_length_s"]
+
+n_fft, hop = CONFIG["fft_window_size"], CONFIG["hop_length"]
+pad = n_fft // 2
+padded = waveform[pad:0:-1] + waveform + waveform[-2 : -pad - 2 : -1]
+window = [0.5 - 0.5 * math.cos(2.0 * math.pi * i / n_fft) for i in range(n_fft)]
+bank = mel_filter_bank(
+    n_fft // 2 + 1,
+    CONFIG["feature_size"],
+    CONFIG["frequency_min"],
+    CONFIG["frequency_max"],
+    CONFIG["sampling_rate"],
+)
+
+indices = list(range(0, len(waveform) // hop + 1, FRAME_STEP))
+log_mel = []
+for index in indices:
+    frame = [s * w for s, w in zip(padded[index * hop : index * hop + n_fft], window)]
+    power = [abs(value) ** 2 for value in fft(frame)[: n_fft // 2 + 1]]
+    log_mel.append(
+        [
+            float("%.7g" % (10.0 * math.log10(max(sum(w * p for w, p in zip(row, power)), 1e-10))))
+            for row in bank
+        ]
+    )
+
+fixture = {
+    "audio": AUDIO,
+    "seconds": CONFIG["max_length_s"],
+    "preprocessor_config": CONFIG,
+    "frames": indices,
+    "log_mel": log_mel,
+}
+(DIRECTORY / "clap_log_mel.json").write_text(json.dumps(fixture))
@@ -0,0 +1,38 @@
+"""Writes clap_reference.json, the embeddings of the Hugging Face `ClapModel` that the CLAP
+parity test in rust/src/embeddings/local/clap.rs compares against.
+
+    pip install transformers torch librosa
+    python test_files/audio/clap_reference.py
+"""
+
+import json
+from pathlib import Path
+
+import librosa
+import torch
+from transformers import ClapModel, ClapProcessor
+
+MODEL_ID = "laion/clap-htsat-unfused"
+TEXTS = ["a man giving a speech", "a dog barking"]
+DIRECTORY = Path(__file__).parent
+
+model = ClapModel.from_pretrained(MODEL_ID).eval()
+processor = ClapProcessor.from_pretrained(MODEL_ID)
+
+audio, _ = librosa.load(DIRECTORY / "samples_jfk.wav", sr=48_000, mono=True)
+# The first window of 10 seconds, which the processor would otherwise crop at random.
+audio = audio[: 10 * 48_000]
+
+with torch.no_grad():
+    inputs = processor(audios=audio, sampling_rate=48_000, return_tensors="pt")
+    audio_embedding = model.get_audio_features(**inputs)
+    inputs = processor(text=TEXTS, return_tensors="pt", padding=True)
+    text_embeddings = model.get_text_features(**inputs)
+
+reference = {
+    "model_id": MODEL_ID,
+    "texts": TEXTS,
+    "audio": torch.nn.functional.normalize(audio_embedding, dim=-1)[0].tolist(),
+    "text": torch.nn.functional.normalize(text_embeddings, dim=-1).tolist(),
+}
+(DIRECTORY / "clap_reference.json").write_text(json.dumps(reference))
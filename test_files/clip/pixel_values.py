@@ -0,0 +1,228 @@
+"""Writes pixel_values.json, the pixel values that the Hugging Face image processors of CLIP,
+DINOv2 and ColPali compute for cat1_small.png. The preprocessing parity test in
+rust/src/embeddings/local/image_processor.rs compares against them.
+
+    python test_files/clip/pixel_values.py
+
+The script only needs the standard library. It follows the slow, Pillow-based image processors
+of transformers step by step: the size computations of `get_resize_output_image_size`, the
+resampling of Pillow's `Image.resize` (Resample.c, with its 8-bit fixed-point passes), the
+`center_crop`, and the float32 `rescale` and `normalize`. The images are processed at 24 x 24
+instead of 224 or 448 to keep the fixture small.
+"""
+
+import json
+import struct
+import zlib
+from pathlib import Path
+
+DIRECTORY = Path(__file__).parent
+IMAGE = "cat1_small.png"
+
+CONFIGS = {
+    # openai/clip-vit-base-patch32: resize the shortest edge and crop the center.
+    "clip": {
+        "do_resize": True,
+        "size": 24,
+        "resample": 3,
+        "do_center_crop": True,
+        "crop_size": 24,
+        "do_rescale": True,
+        "rescale_factor": 1 / 255,
+        "do_normalize": True,
+        "image_mean": [0.48145466, 0.4578275, 0.40821073],
+        "image_std": [0.26862954, 0.26130258, 0.27577711],
+    },
+    # facebook/dinov2-small: resize the shortest edge past the crop, then crop the center with
+    # ImageNet statistics.
+    "dinov2": {
+        "do_resize": True,
+        "size": {"shortest_edge": 28},
+        "resample": 3,
+        "do_center_crop": True,
+        "crop_size": {"height": 24, "width": 24},
+        "do_rescale": True,
+        "rescale_factor": 1 / 255,
+        "do_normalize": True,
+        "image_mean": [0.485, 0.456, 0.406],
+        "image_std": [0.229, 0.224, 0.225],
+    },
+    # vidore/colpali-v1.2-merged: resize to a square without keeping the aspect ratio.
+    "colpali": {
+        "do_resize": True,
+        "size": {"height": 24, "width": 24},
+        "resample": 3,
+        "do_rescale": True,
+        "rescale_factor": 1 / 255,
+        "do_normalize": True,
+        "image_mean": [0.5, 0.5, 0.5],
+        "image_std": [0.5, 0.5, 0.5],
+    },
+}
+
+
+def read_png(path):
+    """Decodes a non-interlaced 8-bit RGB PNG into rows of (r, g, b) tuples."""
+    data = path.read_bytes()
+    assert data[:8] == b"\x89PNG\r\n\x1a\n"
+    position, idat = 8, b""
+    while position < len(data):
+        (length,) = struct.unpack(">I", data[position : position + 4])
+        kind = data[position + 4 : position + 8]
+        body = data[position + 8 : position + 8 + length]
+        if kind == b"IHDR":
+            width, height, depth, color, _, _, interlace = struct.unpack(">IIBBBBB", body)
+            assert (depth, color, interlace) == (8, 2, 0)
+        elif kind == b"IDAT":
+            idat += body
+        position += 12 + length
+
+    raw, stride, bpp = zlib.decompress(idat), width * 3, 3
+    rows, previous = [], bytearray(stride)
+    for y in range(height):
+        kind = raw[y * (stride + 1)]
+        line = bytearray(raw[y * (stride + 1) + 1 : (y + 1) * (stride + 1)])
+        for i in range(stride):
+            left = line[i - bpp] if i >= bpp else 0
+            up = previous[i]
+            up_left = previous[i - bpp] if i >= bpp else 0
+            if kind == 1:
+                line[i] = (line[i] + left) & 0xFF
+            elif kind == 2:
+                line[i] = (line[i] + up) & 0xFF
+            elif kind == 3:
+                line[i] = (line[i] + (left + up) // 2) & 0xFF
+            elif kind == 4:
+                p = left + up - up_left
+                pa, pb, pc = abs(p - left), abs(p - up), abs(p - up_left)
+                predictor = left if pa <= pb and pa <= pc else up if pb <= pc else up_left
+                line[i] = (line[i] + predictor) & 0xFF
+        rows.append([tuple(line[x * 3 : x * 3 + 3]) for x in range(width)])
+        previous = line
+    return rows
+
+
+def bicubic(x):
+    a = -0.5
+    x = abs(x)
+    if x < 1.0:
+        return ((a + 2.0) * x - (a + 3.0)) * x * x + 1
+    if x < 2.0:
+        return (((x - 5) * x + 8) * x - 4) * a
+    return 0.0
+
+
+def bilinear(x):
+    x = abs(x)
+    return 1.0 - x if x < 1.0 else 0.0
+
+
+FILTERS = {2: (bilinear, 1.0), 3: (bicubic, 2.0)}
+PRECISION_BITS = 32 - 8 - 2
+
+
+def coefficients(in_size, out_size, resample):
+    """precompute_coeffs and normalize_coeffs_8bpc of Pillow's Resample.c."""
+    kernel, kernel_support = FILTERS[resample]
+    scale = in_size / out_size
+    filter_scale = max(scale, 1.0)
+    support = kernel_support * filter_scale
+    result = []
+    for xx in range(out_size):
+        center = (xx + 0.5) * scale
+        xmin = max(int(center - support + 0.5), 0)
+        xmax = min(int(center + support + 0.5), in_size) - xmin
+        weights = [kernel((x + xmin - center + 0.5) * (1.0 / filter_scale)) for x in range(xmax)]
+        total = sum(weights)
+        if total != 0.0:
+            weights = [w / total for w in weights]
+        weights = [
+            int(-0.5 + w * (1 << PRECISION_BITS)) if w < 0 else int(0.5 + w * (1 << PRECISION_BITS))
+            for w in weights
+        ]
+        result.append((xmin, weights))
+    return result
+
+
+def clip8(value):
+    return min(max(value >> PRECISION_BITS, 0), 255)
+
+
+def resample_pass(line, coeffs):
+    output = []
+    for xmin, weights in coeffs:
+        pixel = []
+        for channel in range(3):
+            acc = 1 << (PRECISION_BITS - 1)
+            for x, weight in enumerate(weights):
+                acc += line[xmin + x][channel] * weight
+            pixel.append(clip8(acc))
+        output.append(tuple(pixel))
+    return output
+
+
+def pil_resize(rows, width, height, resample):
+    """Image.resize((width, height), resample, reducing_gap=None) of an RGB image."""
+    in_height, in_width = len(rows), len(rows[0])
+    if width != in_width:
+        horizontal = coefficients(in_width, width, resample)
+        rows = [resample_pass(row, horizontal) for row in rows]
+    if height != in_height:
+        vertical = coefficients(in_height, height, resample)
+        columns = [[row[x] for row in rows] for x in range(width)]
+        columns = [resample_pass(column, vertical) for column in columns]
+        rows = [[column[y] for column in columns] for y in range(height)]
+    return rows
+
+
+def output_size(config, height, width):
+    """The (height, width) after resizing, as get_size_dict and get_resize_output_image_size."""
+    size = config["size"]
+    if isinstance(size, int):
+        size = {"shortest_edge": size}
+    if "shortest_edge" in size:
+        short, long = (width, height) if width <= height else (height, width)
+        new_short, new_long = size["shortest_edge"], int(size["shortest_edge"] * long / short)
+        return (new_long, new_short) if width <= height else (new_short, new_long)
+    return size["height"], size["width"]
+
+
+def center_crop(rows, config):
+    size = config["crop_size"]
+    if isinstance(size, int):
+        size = {"height": size, "width": size}
+    crop_height, crop_width = size["height"], size["width"]
+    top = (len(rows) - crop_height) // 2
+    left = (len(rows[0]) - crop_width) // 2
+    return [row[left : left + crop_width] for row in rows[top : top + crop_height]]
+
+
+def f32(value):
+    return struct.unpack("f", struct.pack("f", value))[0]
+
+
+def preprocess(rows, config):
+    height, width = output_size(config, len(rows), len(rows[0]))
+    rows = pil_resize(rows, width, height, config["resample"])
+    if config.get("do_center_crop"):
+        rows = center_crop(rows, config)
+    pixels = []
+    for channel in range(3):
+        mean, std = f32(config["image_mean"][channel]), f32(config["image_std"][channel])
+        for row in rows:
+            for pixel in row:
+                value = f32(pixel[channel] * config["rescale_factor"])
+                pixels.append(f32(f32(value - mean) / std))
+    return [3, len(rows), len(rows[0])], pixels
+
+
+image = read_png(DIRECTORY / IMAGE)
+fixture = {"image": IMAGE, "models": {}}
+for name, config in CONFIGS.items():
+    shape, pixels = preprocess(image, config)
+    fixture["models"][name] = {
+        "preprocessor_config": config,
+        "shape": shape,
+        "pixel_values": [float("%.9g" % value) for value in pixels],
+    }
+(DIRECTORY / "pixel_values.json").write_text(json.dumps(fixture))